@@ -32,8 +32,8 @@ pub use fugit;
 /// More details in [`Dependencies`] documentation.
 ///
 /// # Safety
-/// `CanId::ADDRESS` points to the start of a valid HW register of a CAN
-/// peripheral
+/// `CanId::ADDRESS`, advanced by `CanId::REGISTER_BLOCK_OFFSET` bytes, points
+/// to the start of a valid HW register of a CAN peripheral
 ///
 /// # Examples
 /// ```no_run
@@ -49,11 +49,61 @@ pub use fugit;
 ///
 /// unsafe impl CanId for Can1 {
 ///     const ADDRESS: *const () = 0xBEEF0000 as *const _;
+///     // This instance has a smaller Message RAM configuration
+///     const MAX_STANDARD_FILTERS: usize = 28;
+///     const MAX_EXTENDED_FILTERS: usize = 8;
 /// }
 /// ```
 pub unsafe trait CanId {
     /// Static address of HW register controlling corresponding CAN peripheral
     const ADDRESS: *const ();
+    /// Number of standard ID filters supported by the peripheral
+    ///
+    /// The `MAX_*` constants default to the maxima of the MCAN specification
+    /// and only have to be overridden for integrations that support fewer
+    /// elements. Values above the defaults have no effect.
+    const MAX_STANDARD_FILTERS: usize = 128;
+    /// Number of extended ID filters supported by the peripheral
+    const MAX_EXTENDED_FILTERS: usize = 64;
+    /// Number of dedicated receive buffers supported by the peripheral
+    const MAX_DEDICATED_RX_BUFFERS: usize = 64;
+    /// Number of receive FIFO 0 elements supported by the peripheral
+    const MAX_RX_FIFO_0: usize = 64;
+    /// Number of receive FIFO 1 elements supported by the peripheral
+    const MAX_RX_FIFO_1: usize = 64;
+    /// Number of transmit buffers supported by the peripheral
+    const MAX_TX_BUFFERS: usize = 32;
+    /// Number of transmit event FIFO elements supported by the peripheral
+    const MAX_TX_EVENT_FIFO: usize = 32;
+    /// Revision of the M_CAN core integrated in the peripheral, as reported
+    /// by its CREL register
+    ///
+    /// Defaults to the register layout of 3.1.x, which later revisions share.
+    const REVISION: CoreRevision = CoreRevision::V3_1;
+    /// Offset in bytes of the M_CAN registers from `ADDRESS`
+    ///
+    /// Integrations that wrap the M_CAN core with vendor-specific registers
+    /// placed before it can point `ADDRESS` to the start of the wrapper and
+    /// express the position of the core here.
+    const REGISTER_BLOCK_OFFSET: usize = 0;
+}
+
+/// Revision of the M_CAN core
+///
+/// Revisions differ in a few register fields, most importantly the widths of
+/// the bit timing fields. Only the differences that matter to [`mcan`] are
+/// told apart, so a variant covers all revisions with the same layout of
+/// these fields.
+///
+/// [`mcan`]: <https://docs.rs/crate/mcan/>
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CoreRevision {
+    /// 3.0.x, with the bit timings in the BTP and FBTP registers
+    V3_0,
+    /// 3.1.x and later, including 3.2.x and 3.3.x, with the bit timings in the
+    /// wider NBTP and DBTP registers
+    V3_1,
 }
 
 /// Trait representing CAN peripheral dependencies
@@ -277,6 +327,19 @@ pub unsafe trait Dependencies<Id: CanId> {
     /// correctness of the `Message RAM` placement, the target HAL has to
     /// provide information about its absolute position in RAM.
     fn eligible_message_ram_start(&self) -> *const ();
+    /// Length in bytes of the memory starting at
+    /// [`Self::eligible_message_ram_start`] that the peripheral can address,
+    /// if known.
+    ///
+    /// Some integrations have less Message RAM than the 64K addressable by
+    /// MCAN and wrap addresses beyond it around, which corrupts the memory at
+    /// the start of the region. Target HALs for such integrations should
+    /// provide the length so that a `Message RAM` extending beyond it is
+    /// rejected. Defaults to `None`, which only limits the `Message RAM` to
+    /// the 64K addressable by MCAN.
+    fn eligible_message_ram_len(&self) -> Option<usize> {
+        None
+    }
     /// Frequency of the host / main / CPU clock.
     ///
     /// MCAN uses the host clock for most of its internal operations and its
@@ -0,0 +1,136 @@
+//! Host-side timing of hot paths against the memory-backed peripheral of
+//! `mcan::test`
+//!
+//! The absolute numbers say little about the target, but they move with the
+//! amount of work done per operation, which makes them useful for spotting
+//! regressions. Run through `cargo xtask bench`, or directly with
+//! `cargo bench -p mcan --features test-util --bench operations`. Every
+//! result is printed as a `bench: <name> <nanoseconds per iteration>` line.
+
+use fugit::RateExtU32;
+use mcan::bus::CanConfigurable;
+use mcan::generic_array::typenum::consts::*;
+use mcan::interrupt::{Interrupt, InterruptSet};
+use mcan::message::{rx, tx};
+use mcan::messageram::SharedMemory;
+use mcan::prelude::*;
+use mcan::test::{MockCan, MockDependencies};
+use std::hint::black_box;
+use std::time::Instant;
+
+const RXF0S: usize = 0xa4;
+const TXFQS: usize = 0xc4;
+const TXBAR: usize = 0xd0;
+const IR: usize = 0x50;
+
+type Can0 = MockCan<0>;
+
+struct Capacities;
+
+impl mcan::messageram::Capacities for Capacities {
+    type StandardFilters = U4;
+    type ExtendedFilters = U4;
+    type RxBufferMessage = rx::Message<8>;
+    type DedicatedRxBuffers = U0;
+    type RxFifo0Message = rx::Message<8>;
+    type RxFifo0 = U16;
+    type RxFifo1Message = rx::Message<8>;
+    type RxFifo1 = U4;
+    type TxMessage = tx::Message<8>;
+    type TxBuffers = U4;
+    type DedicatedTxBuffers = U0;
+    type TxEventFifo = U4;
+}
+
+/// Median time per iteration of `f` over several samples
+fn measure(name: &str, mut f: impl FnMut()) {
+    const SAMPLES: usize = 31;
+    const ITERATIONS: u32 = 10_000;
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let mut samples: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                f();
+            }
+            start.elapsed().as_nanos() as f64 / f64::from(ITERATIONS)
+        })
+        .collect();
+    samples.sort_by(f64::total_cmp);
+    println!("bench: {name} {:.1}", samples[SAMPLES / 2]);
+}
+
+fn main() {
+    let memory = Box::leak(Box::new(SharedMemory::<Capacities>::new()));
+    let dependencies = MockDependencies::new(memory);
+    let can = CanConfigurable::<Can0, _, _>::new(500.kHz(), dependencies, memory)
+        .expect("memory is addressable");
+    let mut can = can.finalize_recoverable().expect("configuration is valid");
+    let regs = Can0::registers();
+
+    // One frame at get index 3, which the mock never consumes
+    regs.write(RXF0S, 3 << 8 | 1);
+    measure("rx_single", || {
+        black_box(can.rx_fifo_0.receive().ok());
+    });
+    measure("rx_in_place", || {
+        black_box(can.rx_fifo_0.receive_with(|message| message.id()).ok());
+    });
+
+    // 16 frames starting at get index 8, copied out in one go
+    regs.write(RXF0S, 8 << 8 | 16);
+    let mut frames = [0_u8; 16 * std::mem::size_of::<rx::Message<8>>()];
+    measure("rx_batch_16", || {
+        let descriptor = can.rx_fifo_0.drain_descriptor().expect("FIFO is full");
+        let mut copied = 0;
+        for segment in descriptor.segments() {
+            let len = segment.count * descriptor.element_size();
+            // Safety: The segment lies within the Message RAM.
+            let source = unsafe { std::slice::from_raw_parts(segment.address, len) };
+            frames[copied..copied + len].copy_from_slice(source);
+            copied += len;
+        }
+        can.rx_fifo_0
+            .complete_drain(descriptor)
+            .expect("FIFO is unchanged");
+        black_box(&frames);
+    });
+
+    let message = tx::MessageBuilder {
+        id: embedded_can::StandardId::new(0x123).unwrap().into(),
+        frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(&[1, 2, 3, 4])),
+        store_tx_event: None,
+    }
+    .build()
+    .unwrap();
+    regs.write(TXFQS, 1 << 16);
+    measure("tx_single", || {
+        // Release the buffer, as the mock never transmits it
+        regs.write(TXBAR, 0);
+        black_box(can.tx.transmit_queued_indexed(message).ok());
+    });
+
+    let interrupts = can.interrupt_configuration.enable_line_0(can.interrupts);
+    let flagged: InterruptSet = [
+        Interrupt::RxFifo0NewMessage,
+        Interrupt::TransmissionCompleted,
+        Interrupt::TxEventFifoNewEntry,
+        Interrupt::BusOff,
+    ]
+    .into_iter()
+    .collect();
+    // The mock keeps the flags, as clearing writes them back
+    regs.write(
+        IR,
+        flagged
+            .iter()
+            .fold(0, |ir, interrupt| ir | 1 << interrupt as u32),
+    );
+    measure("isr_dispatch_4", || {
+        for interrupt in interrupts.iter_flagged() {
+            black_box(interrupt);
+        }
+    });
+}
@@ -0,0 +1,57 @@
+//! Post-mortem decoding of register dumps on the host
+//!
+//! Crash handlers can store raw register values in memory that survives a
+//! reset. This decodes them with the same types the firmware uses, e.g.
+//! `cargo run -p mcan --example decode -- psr 0x54a ecr 0x6460 ir 0x1000001`.
+
+use mcan::bus::{ErrorCounters, ProtocolStatus, ReservedBitsError};
+use mcan::interrupt::InterruptSet;
+use std::env;
+use std::process::ExitCode;
+
+fn parse(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn decode(register: &str, bits: u32) -> Result<(), ReservedBitsError> {
+    match register {
+        "psr" => {
+            let status = ProtocolStatus::from_raw(bits)?;
+            println!("{status:#?}");
+            println!("last error code: {:?}", status.last_error_code());
+            println!("data last error code: {:?}", status.data_last_error_code());
+            println!("activity: {:?}", status.activity());
+            println!("operation state: {:?}", status.operation_state());
+        }
+        "ecr" => println!("{:#?}", ErrorCounters::from_raw(bits)?),
+        "ir" | "ie" | "ils" => println!("{:#?}", InterruptSet::from_raw(bits)?),
+        _ => eprintln!("unknown register `{register}`, expected psr, ecr, ir, ie or ils"),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || !args.chunks_exact(2).remainder().is_empty() {
+        eprintln!("usage: decode <register> <value> [<register> <value> ...]");
+        return ExitCode::FAILURE;
+    }
+    let mut result = ExitCode::SUCCESS;
+    for pair in args.chunks_exact(2) {
+        let (register, value) = (pair[0].to_lowercase(), &pair[1]);
+        let Some(bits) = parse(value) else {
+            eprintln!("{register}: `{value}` is not a number");
+            result = ExitCode::FAILURE;
+            continue;
+        };
+        println!("{register} = {bits:#010x}");
+        if let Err(error) = decode(&register, bits) {
+            eprintln!("{register}: reserved bits {:#010x} are set", error.bits);
+            result = ExitCode::FAILURE;
+        }
+    }
+    result
+}
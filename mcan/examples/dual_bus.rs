@@ -0,0 +1,189 @@
+//! Canonical two-bus application used to track the code size of the crate
+//!
+//! Built as a static library so that it needs neither a runtime nor a linker
+//! script. `cargo xtask size` builds it for `thumbv7em-none-eabihf` and sums
+//! up the sizes of the `mcan` symbols it contains. The peripheral addresses
+//! and clocks are made up, the code is never run.
+
+#![cfg_attr(target_os = "none", no_std)]
+
+use fugit::{HertzU32, RateExtU32};
+use mcan::bus::{Can, CanConfigurable};
+use mcan::embedded_can::StandardId;
+use mcan::filter::{Action, Filter};
+use mcan::generic_array::typenum::consts::*;
+use mcan::interrupt::{state::EnabledLine0, Interrupt, OwnedInterruptSet};
+use mcan::message::{rx, tx};
+use mcan::messageram::{SharedMemoryCell, SharedMemoryGrant};
+use mcan::prelude::*;
+
+/// First bus
+pub enum Can0 {}
+
+// Safety: Stand-in address, the code is never run.
+unsafe impl mcan_core::CanId for Can0 {
+    const ADDRESS: *const () = 0x4003_0000 as *const ();
+}
+
+/// Second bus
+pub enum Can1 {}
+
+// Safety: Stand-in address, the code is never run.
+unsafe impl mcan_core::CanId for Can1 {
+    const ADDRESS: *const () = 0x4003_4000 as *const ();
+}
+
+/// Stand-in for the dependencies provided by a platform HAL
+pub struct Dependencies;
+
+// Safety: The code is never run.
+unsafe impl<Id: mcan_core::CanId> mcan_core::Dependencies<Id> for Dependencies {
+    fn eligible_message_ram_start(&self) -> *const () {
+        0x2040_0000 as *const ()
+    }
+
+    fn host_clock(&self) -> HertzU32 {
+        150.MHz()
+    }
+
+    fn can_clock(&self) -> HertzU32 {
+        48.MHz()
+    }
+}
+
+/// Message RAM layout shared by both buses
+pub struct Capacities;
+
+impl mcan::messageram::Capacities for Capacities {
+    type StandardFilters = U8;
+    type ExtendedFilters = U4;
+    type RxBufferMessage = rx::Message<64>;
+    type DedicatedRxBuffers = U0;
+    type RxFifo0Message = rx::Message<64>;
+    type RxFifo0 = U16;
+    type RxFifo1Message = rx::Message<64>;
+    type RxFifo1 = U8;
+    type TxMessage = tx::Message<64>;
+    type TxBuffers = U16;
+    type DedicatedTxBuffers = U0;
+    type TxEventFifo = U16;
+}
+
+static MEMORY_0: SharedMemoryCell<Capacities> = SharedMemoryCell::new();
+static MEMORY_1: SharedMemoryCell<Capacities> = SharedMemoryCell::new();
+// Safety: These are the only grants for their memory and are never stolen.
+static GRANT_0: SharedMemoryGrant<Capacities> = unsafe { SharedMemoryGrant::new(&MEMORY_0) };
+static GRANT_1: SharedMemoryGrant<Capacities> = unsafe { SharedMemoryGrant::new(&MEMORY_1) };
+
+type Bus<Id> = Can<'static, Id, Dependencies, Capacities>;
+
+/// A configured bus along with its enabled interrupts
+pub struct Node<Id> {
+    can: Bus<Id>,
+    interrupts: OwnedInterruptSet<Id, EnabledLine0>,
+}
+
+fn configure<Id: mcan_core::CanId>(
+    grant: &SharedMemoryGrant<Capacities>,
+    fd: bool,
+) -> Option<Node<Id>> {
+    let memory = grant.take()?;
+    let mut can = CanConfigurable::<Id, _, _>::new(500.kHz(), Dependencies, memory).ok()?;
+    if fd {
+        can.config().mode = mcan::config::Mode::Fd {
+            allow_bit_rate_switching: true,
+            data_phase_timing: mcan::config::BitTiming::new(2.MHz()),
+        };
+    }
+    can.filters_standard()
+        .push(Filter::Classic {
+            action: Action::StoreFifo0,
+            filter: StandardId::new(0x100).unwrap(),
+            mask: StandardId::new(0x700).unwrap(),
+        })
+        .ok()?;
+    let mut can = can.finalize_recoverable().ok()?;
+    let interrupts = can.interrupt_configuration.enable_line_0(
+        can.interrupts
+            .split(
+                [
+                    Interrupt::RxFifo0NewMessage,
+                    Interrupt::TxFifoEmpty,
+                    Interrupt::BusOff,
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .ok()?,
+    );
+    Some(Node { can, interrupts })
+}
+
+fn service<Id: mcan_core::CanId>(node: &mut Node<Id>) -> u32 {
+    let mut received = 0;
+    for interrupt in node.interrupts.iter_flagged() {
+        match interrupt {
+            Interrupt::RxFifo0NewMessage => {
+                for message in &mut node.can.rx_fifo_0 {
+                    received += u32::from(message.data().first().copied().unwrap_or(0));
+                }
+            }
+            Interrupt::BusOff => node.can.aux.operational_mode(),
+            _ => {}
+        }
+    }
+    received
+}
+
+fn send<Id: mcan_core::CanId>(node: &mut Node<Id>, id: u16, data: &[u8]) -> bool {
+    let Ok(message) = (tx::MessageBuilder {
+        id: StandardId::new(id).unwrap().into(),
+        frame_type: tx::FrameType::FlexibleDatarate {
+            payload: data,
+            bit_rate_switching: true,
+            force_error_state_indicator: false,
+        },
+        store_tx_event: None,
+    })
+    .build() else {
+        return false;
+    };
+    node.can.tx.transmit_queued_indexed(message).is_ok()
+}
+
+static mut NODES: Option<(Node<Can0>, Node<Can1>)> = None;
+
+/// Configures both buses
+#[no_mangle]
+pub extern "C" fn dual_bus_init() -> bool {
+    let (Some(node_0), Some(node_1)) = (
+        configure::<Can0>(&GRANT_0, false),
+        configure::<Can1>(&GRANT_1, true),
+    ) else {
+        return false;
+    };
+    // Safety: Called once before the interrupt handlers are enabled.
+    unsafe { *core::ptr::addr_of_mut!(NODES) = Some((node_0, node_1)) };
+    true
+}
+
+/// Interrupt handler, forwarding the frames received on the first bus to the
+/// second
+#[no_mangle]
+pub extern "C" fn dual_bus_isr() -> u32 {
+    // Safety: Not reentrant, the only user after `dual_bus_init`.
+    let Some((node_0, node_1)) = (unsafe { (*core::ptr::addr_of_mut!(NODES)).as_mut() }) else {
+        return 0;
+    };
+    let received = service(node_0) + service(node_1);
+    if received > 0 {
+        send(node_1, 0x200, &received.to_le_bytes());
+    }
+    received
+}
+
+#[cfg(target_os = "none")]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
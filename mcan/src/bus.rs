@@ -1,14 +1,27 @@
 //! Pad declarations for the CAN buses
 
-use crate::config::{BitTimingError, DATA_BIT_TIMING_RANGES, NOMINAL_BIT_TIMING_RANGES};
-use crate::filter::{FiltersExtended, FiltersStandard};
-use crate::interrupt::{state, InterruptConfiguration, OwnedInterruptSet};
-use crate::messageram::SharedMemoryInner;
-use crate::reg::{ecr::R as ECR, psr::R as PSR};
-use crate::rx_dedicated_buffers::RxDedicatedBuffer;
-use crate::rx_fifo::{Fifo0, Fifo1, RxFifo};
-use crate::tx_buffers::Tx;
+use crate::config::{
+    AppliedBitTiming, BitTiming, BitTimingError, BitTimingRanges, ComputedTiming, Loopback,
+    OperationMode, RxFifoConfig, RxFifoMode, TransmitterDelayCompensation,
+};
+use crate::filter::{
+    ExtFilter, Filter, FilterExtendedId, FilterStandardId, FiltersExtended, FiltersStandard,
+    SbMsgType,
+};
+use crate::interrupt::{
+    state, Interrupt, InterruptConfiguration, InterruptLine, InterruptSet, OwnedInterruptSet,
+};
+use crate::message::{rx, tx, Message, Raw as _};
+use crate::messageram::{
+    check_capacities, Capacity, CapacityExceededError, MemoryError, SharedMemoryInner,
+    TxBufferSplit,
+};
+use crate::reg::{ecr::R as ECR, psr::R as PSR, UnownedRef, UnownedRegister};
+use crate::rx_dedicated_buffers::{BindError, BoundRxBuffer, RxDedicatedBuffer, StoreBufferError};
+use crate::rx_fifo::{DynRxFifo as _, Fifo0, Fifo1, RxFifo};
+use crate::tx_buffers::{DynTx as _, Tx};
 use crate::tx_event_fifo::TxEventFifo;
+use core::cell::Cell;
 use core::convert::From;
 use core::fmt::{self, Debug};
 use core::ops::Deref;
@@ -16,10 +29,11 @@ use core::ops::Deref;
 use super::{
     config::{CanConfig, Mode},
     message::AnyMessage,
-    messageram::{Capacities, SharedMemory},
+    messageram::{Capacities, CapacitiesSummary, ElementSizeWarning, SharedMemory},
 };
 use fugit::HertzU32;
 use generic_array::typenum::Unsigned;
+use vcell::VolatileCell;
 
 /// Wrapper for the protocol status register
 pub struct ProtocolStatus(PSR);
@@ -38,6 +52,58 @@ impl From<PSR> for ProtocolStatus {
     }
 }
 
+impl ProtocolStatus {
+    /// Bits of the register that are not reserved
+    const VALID: u32 = 0x007f_7fff;
+
+    /// Decodes a raw value of the protocol status register, e.g. from a
+    /// register dump taken by a crash handler
+    pub fn from_raw(bits: u32) -> Result<Self, ReservedBitsError> {
+        ReservedBitsError::check(bits, Self::VALID)?;
+        Ok(Self(PSR::new(bits)))
+    }
+
+    /// Type of the last error that occurred in the arbitration phase, or in
+    /// the data phase of frames without bit rate switching
+    pub fn last_error_code(&self) -> LastErrorCode {
+        LastErrorCode::from_field(self.lec().bits())
+    }
+
+    /// Type of the last error that occurred in the data phase of a CAN FD
+    /// frame with bit rate switching
+    pub fn data_last_error_code(&self) -> LastErrorCode {
+        LastErrorCode::from_field(self.dlec().bits())
+    }
+
+    /// Transmitter delay measured at the start of the last bit rate switched
+    /// frame, TDCV, in CAN clock periods
+    ///
+    /// Only measured with [`CanConfig::transmitter_delay_compensation`]
+    /// enabled. The secondary sample point is at this delay plus the
+    /// configured offset.
+    pub fn transmitter_delay(&self) -> u8 {
+        self.tdcv().bits()
+    }
+
+    /// What the peripheral is doing on the bus
+    pub fn activity(&self) -> Activity {
+        Activity::from_field(self.act().bits())
+    }
+
+    /// Fault confinement state of the peripheral
+    pub fn operation_state(&self) -> OperationState {
+        if self.bo().bit_is_set() {
+            OperationState::BusOff
+        } else if self.ep().bit_is_set() {
+            OperationState::ErrorPassive
+        } else if self.ew().bit_is_set() {
+            OperationState::ErrorWarning
+        } else {
+            OperationState::ErrorActive
+        }
+    }
+}
+
 impl Debug for ProtocolStatus {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ProtocolStatus")
@@ -56,6 +122,67 @@ impl Debug for ProtocolStatus {
     }
 }
 
+/// Event fields of the protocol status register, which reading it clears
+const PSR_EVENTS: u32 = 0x0000_7f07;
+/// Event fields without any events, with LEC and DLEC reading `NoChange`
+const PSR_NO_EVENTS: u32 = 0x0000_0707;
+
+/// Accumulates the event fields of a protocol status register value `read`
+/// into the `accumulated` ones, as the register would have kept them without
+/// the read in between: the error codes are replaced unless `read` reports
+/// no change, and the flags are combined.
+fn accumulate_events(accumulated: u32, read: u32) -> u32 {
+    let code = |shift: u32| match read >> shift & 0x7 {
+        0x7 => accumulated >> shift & 0x7,
+        code => code,
+    } << shift;
+    code(0) | code(8) | (accumulated | read) & PSR_EVENTS & !PSR_NO_EVENTS
+}
+
+/// Events of the protocol status register, see
+/// [`DynAux::take_protocol_events`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolEvents {
+    /// See [`ProtocolStatus::last_error_code`]
+    pub last_error_code: LastErrorCode,
+    /// See [`ProtocolStatus::data_last_error_code`]
+    pub data_last_error_code: LastErrorCode,
+    /// A protocol exception event occurred, PXE
+    pub protocol_exception: bool,
+    /// A CAN FD frame was received, RFDF
+    pub received_fd: bool,
+    /// A CAN FD frame with its BRS flag set was received, RBRS
+    pub received_bit_rate_switching: bool,
+    /// A CAN FD frame with its ESI flag set was received, RESI
+    pub received_error_state_indicator: bool,
+}
+
+impl ProtocolEvents {
+    fn from_bits(bits: u32) -> Self {
+        let status = ProtocolStatus(PSR::new(bits));
+        Self {
+            last_error_code: status.last_error_code(),
+            data_last_error_code: status.data_last_error_code(),
+            protocol_exception: status.pxe().bit(),
+            received_fd: status.rfdf().bit(),
+            received_bit_rate_switching: status.rbrs().bit(),
+            received_error_state_indicator: status.resi().bit(),
+        }
+    }
+}
+
+/// Fields of the protocol status register that reflect the current state,
+/// see [`DynAux::protocol_state`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolState {
+    /// See [`ProtocolStatus::activity`]
+    pub activity: Activity,
+    /// See [`ProtocolStatus::operation_state`]
+    pub operation_state: OperationState,
+    /// See [`ProtocolStatus::transmitter_delay`]
+    pub transmitter_delay: u8,
+}
+
 /// Wrapper for the error counters register
 pub struct ErrorCounters(ECR);
 
@@ -73,6 +200,18 @@ impl From<ECR> for ErrorCounters {
     }
 }
 
+impl ErrorCounters {
+    /// Bits of the register that are not reserved
+    const VALID: u32 = 0x00ff_ffff;
+
+    /// Decodes a raw value of the error counter register, e.g. from a register
+    /// dump taken by a crash handler
+    pub fn from_raw(bits: u32) -> Result<Self, ReservedBitsError> {
+        ReservedBitsError::check(bits, Self::VALID)?;
+        Ok(Self(ECR::new(bits)))
+    }
+}
+
 impl Debug for ErrorCounters {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ErrorCounters")
@@ -84,6 +223,355 @@ impl Debug for ErrorCounters {
     }
 }
 
+/// A raw register or field value has reserved bits set
+///
+/// Reserved bits read as zero, so this points to a corrupted register dump.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReservedBitsError {
+    /// The reserved bits that are set
+    pub bits: u32,
+}
+
+impl ReservedBitsError {
+    pub(crate) fn check(bits: u32, valid: u32) -> Result<(), Self> {
+        match bits & !valid {
+            0 => Ok(()),
+            bits => Err(Self { bits }),
+        }
+    }
+}
+
+/// Type of the last error on the bus, see [`ProtocolStatus::last_error_code`]
+/// and [`ProtocolStatus::data_last_error_code`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LastErrorCode {
+    /// No error occurred since the code was last reset by a successful
+    /// transmission or reception
+    NoError,
+    /// More than 5 equal bits in a sequence occurred in a part of a frame
+    /// where this is not allowed
+    Stuff,
+    /// A fixed format part of a received frame has the wrong format
+    Form,
+    /// A transmitted message was not acknowledged by another node
+    Ack,
+    /// A recessive bit was sent but the bus was monitored dominant, outside
+    /// of the arbitration field
+    Bit1,
+    /// A dominant bit was sent but the bus was monitored recessive
+    Bit0,
+    /// The CRC of a received message does not match
+    Crc,
+    /// No bus event was detected since the code was last read
+    NoChange,
+}
+
+impl LastErrorCode {
+    /// Decodes the raw value of the 3 bit LEC or DLEC field
+    pub fn from_raw(bits: u8) -> Result<Self, ReservedBitsError> {
+        ReservedBitsError::check(bits.into(), 0b111)?;
+        Ok(Self::from_field(bits))
+    }
+
+    fn from_field(bits: u8) -> Self {
+        match bits {
+            0 => Self::NoError,
+            1 => Self::Stuff,
+            2 => Self::Form,
+            3 => Self::Ack,
+            4 => Self::Bit1,
+            5 => Self::Bit0,
+            6 => Self::Crc,
+            _ => Self::NoChange,
+        }
+    }
+}
+
+/// Occurrences of each type of protocol error, accumulated by the application
+///
+/// The peripheral only keeps the type of the last error, so counting them
+/// requires taking the events after every error, e.g. in the handler of
+/// [`Interrupt::ProtocolErrorArbitration`] and
+/// [`Interrupt::ProtocolErrorData`]:
+///
+/// ```no_run
+/// # use mcan::bus::{DynAux, ErrorStatistics};
+/// # fn handler(aux: &impl DynAux, statistics: &mut ErrorStatistics) {
+/// statistics.record(&aux.take_protocol_events());
+/// # }
+/// ```
+///
+/// Errors that occur between two takes overwrite each other and are counted
+/// once.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorStatistics {
+    /// Errors in the arbitration phase, or in the data phase of frames
+    /// without bit rate switching, see [`ProtocolEvents::last_error_code`]
+    pub arbitration: ErrorCounts,
+    /// Errors in the data phase of CAN FD frames with bit rate switching,
+    /// see [`ProtocolEvents::data_last_error_code`]
+    pub data: ErrorCounts,
+}
+
+impl ErrorStatistics {
+    /// Statistics without any errors
+    pub const fn new() -> Self {
+        Self {
+            arbitration: ErrorCounts::new(),
+            data: ErrorCounts::new(),
+        }
+    }
+
+    /// Counts the errors of both phases in `events`
+    pub fn record(&mut self, events: &ProtocolEvents) {
+        self.arbitration.record(events.last_error_code);
+        self.data.record(events.data_last_error_code);
+    }
+
+    /// Number of errors of all types in both phases
+    pub fn total(&self) -> u32 {
+        self.arbitration.total().saturating_add(self.data.total())
+    }
+
+    /// Resets all counters to zero
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Saturating counters of the error types of a [`LastErrorCode`], see
+/// [`ErrorStatistics`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorCounts {
+    /// See [`LastErrorCode::Stuff`]
+    pub stuff: u32,
+    /// See [`LastErrorCode::Form`]
+    pub form: u32,
+    /// See [`LastErrorCode::Ack`]
+    pub ack: u32,
+    /// See [`LastErrorCode::Bit1`]
+    pub bit1: u32,
+    /// See [`LastErrorCode::Bit0`]
+    pub bit0: u32,
+    /// See [`LastErrorCode::Crc`]
+    pub crc: u32,
+}
+
+impl ErrorCounts {
+    /// Counts without any errors
+    pub const fn new() -> Self {
+        Self {
+            stuff: 0,
+            form: 0,
+            ack: 0,
+            bit1: 0,
+            bit0: 0,
+            crc: 0,
+        }
+    }
+
+    /// Counts an error of type `code`
+    ///
+    /// [`LastErrorCode::NoError`] and [`LastErrorCode::NoChange`] are not
+    /// errors and leave the counters unchanged.
+    pub fn record(&mut self, code: LastErrorCode) {
+        if let Some(counter) = self.counter(code) {
+            *counter = counter.saturating_add(1);
+        }
+    }
+
+    /// Number of errors of type `code`, zero for [`LastErrorCode::NoError`]
+    /// and [`LastErrorCode::NoChange`]
+    pub fn count(&self, code: LastErrorCode) -> u32 {
+        let mut counts = *self;
+        counts.counter(code).map_or(0, |counter| *counter)
+    }
+
+    /// Number of errors of all types
+    pub fn total(&self) -> u32 {
+        [
+            self.stuff, self.form, self.ack, self.bit1, self.bit0, self.crc,
+        ]
+        .into_iter()
+        .fold(0, u32::saturating_add)
+    }
+
+    fn counter(&mut self, code: LastErrorCode) -> Option<&mut u32> {
+        match code {
+            LastErrorCode::NoError | LastErrorCode::NoChange => None,
+            LastErrorCode::Stuff => Some(&mut self.stuff),
+            LastErrorCode::Form => Some(&mut self.form),
+            LastErrorCode::Ack => Some(&mut self.ack),
+            LastErrorCode::Bit1 => Some(&mut self.bit1),
+            LastErrorCode::Bit0 => Some(&mut self.bit0),
+            LastErrorCode::Crc => Some(&mut self.crc),
+        }
+    }
+}
+
+/// What the peripheral is doing on the bus, see [`ProtocolStatus::activity`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Activity {
+    /// Synchronizing on CAN communication
+    Synchronizing,
+    /// Neither receiver nor transmitter
+    Idle,
+    /// Operating as receiver
+    Receiver,
+    /// Operating as transmitter
+    Transmitter,
+}
+
+impl Activity {
+    /// Decodes the raw value of the 2 bit ACT field
+    pub fn from_raw(bits: u8) -> Result<Self, ReservedBitsError> {
+        ReservedBitsError::check(bits.into(), 0b11)?;
+        Ok(Self::from_field(bits))
+    }
+
+    fn from_field(bits: u8) -> Self {
+        match bits {
+            0 => Self::Synchronizing,
+            1 => Self::Idle,
+            2 => Self::Receiver,
+            _ => Self::Transmitter,
+        }
+    }
+}
+
+/// Fault confinement state of the peripheral, see
+/// [`ProtocolStatus::operation_state`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OperationState {
+    /// Both error counters are below the warning limit of 96
+    ErrorActive,
+    /// At least one error counter reached the warning limit of 96
+    ErrorWarning,
+    /// At least one error counter exceeded the error passive limit of 127
+    ErrorPassive,
+    /// The transmit error counter exceeded 255 and the peripheral stopped
+    /// participating in bus communication
+    BusOff,
+}
+
+impl OperationState {
+    /// Decodes the fault confinement state from a raw value of the protocol
+    /// status register
+    pub fn from_raw(bits: u32) -> Result<Self, ReservedBitsError> {
+        Ok(ProtocolStatus::from_raw(bits)?.operation_state())
+    }
+
+    /// Returns the change to report when `current` is observed after `self`,
+    /// along with the state to compare the next observation against.
+    ///
+    /// Leaving bus off is reported as [`BusStateChange::BusOffRecovered`]
+    /// first. If the peripheral already went on to a state other than error
+    /// active, that state is reported by the next call.
+    fn transition(self, current: Self) -> (Option<BusStateChange>, Self) {
+        match (self, current) {
+            (previous, current) if previous == current => (None, previous),
+            (Self::BusOff, _) => (Some(BusStateChange::BusOffRecovered), Self::ErrorActive),
+            (_, Self::ErrorActive) => (Some(BusStateChange::ErrorActive), current),
+            (_, Self::ErrorWarning) => (Some(BusStateChange::ErrorWarning), current),
+            (_, Self::ErrorPassive) => (Some(BusStateChange::ErrorPassive), current),
+            (_, Self::BusOff) => (Some(BusStateChange::BusOff), current),
+        }
+    }
+}
+
+/// Status of the last high priority message, see
+/// [`DynAux::high_priority_message_status`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HighPriorityMessageStatus {
+    /// Where the message was stored, MSI
+    pub storage: MessageStorage,
+    /// Index of the FIFO element the message was stored in, BIDX
+    ///
+    /// Only valid if the message was stored in a FIFO.
+    pub buffer_index: usize,
+    /// Index of the filter that matched the message, FIDX
+    pub filter_index: usize,
+    /// `true` if the filter is in the extended ID filter list, FLST
+    pub extended_filter: bool,
+}
+
+impl HighPriorityMessageStatus {
+    /// Bits of the register that are not reserved
+    const VALID: u32 = 0x0000_ffff;
+
+    /// Decodes a raw value of the high priority message status register
+    pub fn from_raw(bits: u32) -> Result<Self, ReservedBitsError> {
+        ReservedBitsError::check(bits, Self::VALID)?;
+        Ok(Self::from_bits(bits))
+    }
+
+    fn from_bits(bits: u32) -> Self {
+        Self {
+            storage: MessageStorage::from_field(bits >> 6 & 0x3),
+            buffer_index: (bits & 0x3f) as usize,
+            filter_index: (bits >> 8 & 0x7f) as usize,
+            extended_filter: bits & 1 << 15 != 0,
+        }
+    }
+}
+
+impl From<crate::reg::hpms::R> for HighPriorityMessageStatus {
+    fn from(status: crate::reg::hpms::R) -> Self {
+        Self::from_bits(status.bits())
+    }
+}
+
+/// Storage of a high priority message, see [`HighPriorityMessageStatus`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MessageStorage {
+    /// No FIFO selected, e.g. because the filter only sets the priority and
+    /// the message was rejected, or no high priority message was received
+    /// yet
+    NoFifo,
+    /// The message was lost because its FIFO was full
+    Lost,
+    /// The message was stored in RX FIFO 0
+    Fifo0,
+    /// The message was stored in RX FIFO 1
+    Fifo1,
+}
+
+impl MessageStorage {
+    fn from_field(bits: u32) -> Self {
+        match bits {
+            0 => Self::NoFifo,
+            1 => Self::Lost,
+            2 => Self::Fifo0,
+            _ => Self::Fifo1,
+        }
+    }
+}
+
+/// Progress of the recovery from bus off, see [`DynAux::bus_off_recovery`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BusOffRecovery {
+    /// The peripheral is not bus off
+    NotBusOff,
+    /// The peripheral is bus off and halted in initialization mode, which the
+    /// hardware enters along with bus off. Recovery starts when leaving it,
+    /// see [`DynAux::initiate_bus_off_recovery`].
+    Halted,
+    /// The peripheral waits for 129 occurrences of 11 consecutive recessive
+    /// bits before it resumes normal operation with reset error counters
+    Recovering,
+}
+
+impl BusOffRecovery {
+    /// Decodes the recovery state from CCCR.INIT and PSR.BO
+    fn from_status(initializing: bool, bus_off: bool) -> Self {
+        match (bus_off, initializing) {
+            (false, _) => Self::NotBusOff,
+            (true, true) => Self::Halted,
+            (true, false) => Self::Recovering,
+        }
+    }
+}
+
 /// Errors that may occur during configuration
 #[derive(Debug)]
 pub enum ConfigurationError {
@@ -91,11 +579,126 @@ pub enum ConfigurationError {
     BitTiming(BitTimingError),
     /// Time stamp prescaler value is not in the range [1, 16]
     InvalidTimeStampPrescaler,
+    /// A FIFO watermark is larger than the depth of the FIFO, so the watermark
+    /// interrupt would never trigger
+    ///
+    /// Only reported with [`CanConfig::strict_validation`], otherwise the
+    /// watermark is clamped to the depth.
+    WatermarkExceedsDepth {
+        /// The FIFO the watermark is configured for
+        fifo: Capacity,
+        /// The configured watermark
+        watermark: u8,
+        /// Depth of the FIFO selected by [`Capacities`]
+        depth: usize,
+    },
+    /// A FIFO in overwrite mode has no
+    /// [`RxFifoConfig::overwrite_read_offset`], or one that is 0 or not less
+    /// than the depth of the FIFO
+    ///
+    /// [`RxFifoConfig::overwrite_read_offset`]: crate::config::RxFifoConfig::overwrite_read_offset
+    InvalidOverwriteReadOffset {
+        /// The FIFO the offset is configured for
+        fifo: Capacity,
+        /// The configured offset
+        offset: Option<u8>,
+        /// Depth of the FIFO selected by [`Capacities`]
+        depth: usize,
+    },
+    /// The peripheral is not in normal operation, e.g. because it is bus off,
+    /// so it cannot be reconfigured temporarily by [`Aux::with_loopback`] or
+    /// [`RxFifo::set_mode`](crate::rx_fifo::RxFifo::set_mode)
+    NotOperational,
+    /// Transmissions are requested or pending, which the peripheral would
+    /// cancel when temporarily entering configuration mode for
+    /// [`Aux::with_loopback`] or
+    /// [`RxFifo::set_mode`](crate::rx_fifo::RxFifo::set_mode)
+    TransmissionsPending,
+    /// [`CanConfig::transmitter_delay_compensation`] does not fit the
+    /// registers of the core revision
+    TransmitterDelayCompensationOutOfRange(TransmitterDelayCompensation),
+}
+
+/// Errors that may occur during construction
+#[derive(Debug)]
+pub enum ConstructionError {
+    /// The Message RAM cannot be addressed by the peripheral
+    Memory(MemoryError),
+    /// The Message RAM extends beyond
+    /// [`Dependencies::eligible_message_ram_len`], where the peripheral
+    /// wraps around
+    ///
+    /// [`Dependencies::eligible_message_ram_len`]: mcan_core::Dependencies::eligible_message_ram_len
+    MessageRamExceeded {
+        /// Bytes from the start of the eligible region to the end of the
+        /// Message RAM
+        required: usize,
+        /// Length of the eligible region
+        available: usize,
+    },
+    /// The [`Capacities`] exceed the limits of the peripheral
+    CapacityExceeded(CapacityExceededError),
+    /// The CAN clock of the [`Dependencies`] is zero or faster than their host
+    /// clock, which the peripheral does not support
+    ///
+    /// [`Dependencies`]: mcan_core::Dependencies
+    ClockConfiguration {
+        /// [`Dependencies::host_clock`](mcan_core::Dependencies::host_clock)
+        host: HertzU32,
+        /// [`Dependencies::can_clock`](mcan_core::Dependencies::can_clock)
+        can: HertzU32,
+    },
+    /// The nominal bitrate cannot be reached with the CAN clock, even with the
+    /// shortest bit time of [`BitTimingRanges::nominal`] and no prescaling
+    UnachievableBitrate {
+        /// The requested nominal bitrate
+        bitrate: HertzU32,
+        /// [`Dependencies::can_clock`](mcan_core::Dependencies::can_clock)
+        can: HertzU32,
+    },
+}
+
+impl From<MemoryError> for ConstructionError {
+    fn from(value: MemoryError) -> Self {
+        Self::Memory(value)
+    }
 }
 
-/// Error that may occur during construction
+impl From<CapacityExceededError> for ConstructionError {
+    fn from(value: CapacityExceededError) -> Self {
+        Self::CapacityExceeded(value)
+    }
+}
+
+/// The TX pin cannot be controlled directly while [`CanConfig::loopback`] is
+/// enabled, since loopback uses the test mode as well.
 #[derive(Debug)]
-pub struct MemoryNotAddressableError;
+pub struct LoopbackRequestedError;
+
+/// Level of the TX pin during a pin test, see
+/// [`CanConfigurable::pin_test_drive`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TxPinState {
+    /// The pin is driven by the CAN core, as in normal operation
+    ControlledByCore,
+    /// The pin outputs the sample point signal of the CAN core
+    SamplePoint,
+    /// The pin is held at the dominant (0) level
+    Dominant,
+    /// The pin is held at the recessive (1) level
+    Recessive,
+}
+
+impl From<TxPinState> for crate::reg::test::TXSELECT_A {
+    fn from(value: TxPinState) -> Self {
+        match value {
+            TxPinState::ControlledByCore => Self::CORE,
+            TxPinState::SamplePoint => Self::SAMPLE,
+            TxPinState::Dominant => Self::DOMINANT,
+            TxPinState::Recessive => Self::RECESSIVE,
+        }
+    }
+}
 
 impl From<BitTimingError> for ConfigurationError {
     fn from(value: BitTimingError) -> Self {
@@ -103,6 +706,104 @@ impl From<BitTimingError> for ConfigurationError {
     }
 }
 
+/// Account of the configuration window between [`Can::configure`] and
+/// [`CanConfigurable::finalize_with_report`], during which frames on the bus
+/// are missed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReconfigurationReport {
+    /// Timestamp ticks spent outside of normal operation, modulo the
+    /// counter period of 65536 ticks
+    ///
+    /// `None` if the timestamp counter is disabled, or if its source or
+    /// prescaler changed. The internal counter counts nominal bit times, so
+    /// it is also `None` if the nominal bit timing changed. Whether the
+    /// internal counter keeps counting while the peripheral is in
+    /// configuration mode depends on the integration of the core; if it is
+    /// stopped, this only covers the time spent outside of that mode. An
+    /// external counter keeps running regardless.
+    pub duration_ticks: Option<u16>,
+    /// `true` if a frame was being received or transmitted when
+    /// configuration mode was entered, according to the activity in PSR
+    ///
+    /// That frame was lost or has to be retransmitted. Frames that started
+    /// later are missed without a trace, so `false` does not rule out missed
+    /// traffic on a busy bus.
+    pub traffic_present: bool,
+    /// Messages in RX FIFO 0 and 1 that were discarded unread when
+    /// configuration mode was entered
+    pub discarded_rx_fifo_messages: [usize; 2],
+}
+
+/// State of the bus recorded by [`Can::configure`] for the
+/// [`ReconfigurationReport`]
+#[derive(Copy, Clone, Debug)]
+struct ReconfigurationGuard {
+    tscc: u32,
+    nbtp: u32,
+    timestamp: u16,
+    activity: Activity,
+    rx_fifo_levels: [usize; 2],
+}
+
+impl ReconfigurationGuard {
+    fn record<Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>(
+        can: &Can<'_, Id, D, C>,
+    ) -> Self {
+        let reg = &can.aux.reg;
+        Self {
+            tscc: reg.tscc.read().bits(),
+            nbtp: reg.nbtp.read().bits(),
+            timestamp: reg.tscv.read().tsc().bits(),
+            activity: can.aux.protocol_state().activity,
+            rx_fifo_levels: [can.rx_fifo_0.len(), can.rx_fifo_1.len()],
+        }
+    }
+
+    /// Assembles the report from the registers after the window
+    fn report(&self, tscc: u32, nbtp: u32, timestamp: u16) -> ReconfigurationReport {
+        /// TSS field of TSCC
+        const SELECT: u32 = 0b11;
+        const INTERNAL: u32 = 0b01;
+        const EXTERNAL: u32 = 0b10;
+        let unchanged = match self.tscc & SELECT {
+            INTERNAL => tscc == self.tscc && nbtp == self.nbtp,
+            EXTERNAL => tscc == self.tscc,
+            _ => false,
+        };
+        ReconfigurationReport {
+            duration_ticks: unchanged.then(|| timestamp.wrapping_sub(self.timestamp)),
+            traffic_present: matches!(self.activity, Activity::Receiver | Activity::Transmitter),
+            discarded_rx_fifo_messages: self.rx_fifo_levels,
+        }
+    }
+}
+
+/// The configuration was rejected by [`CanConfigurable::finalize_recoverable`]
+///
+/// The peripheral is still in configuration mode and can be finalized again
+/// after correcting the configuration.
+pub struct FinalizeError<'a, Id, D, C: Capacities> {
+    /// The bus in configuration mode, unchanged apart from registers that are
+    /// rewritten by every finalization
+    pub can: CanConfigurable<'a, Id, D, C>,
+    /// The reason the configuration was rejected
+    pub error: ConfigurationError,
+}
+
+impl<Id, D, C: Capacities> core::fmt::Debug for FinalizeError<'_, Id, D, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FinalizeError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Id, D, C: Capacities> From<FinalizeError<'_, Id, D, C>> for ConfigurationError {
+    fn from(value: FinalizeError<'_, Id, D, C>) -> Self {
+        value.error
+    }
+}
+
 /// A CAN bus that is not in configuration mode (CCE=0)
 ///
 /// Some errors (including Bus_Off) can asynchronously stop bus operation
@@ -127,6 +828,24 @@ pub struct Can<'a, Id, D, C: Capacities> {
     pub aux: Aux<'a, Id, D>,
 }
 
+/// Change of the fault confinement state of the peripheral, reported by
+/// [`DynAux::poll_bus_state`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BusStateChange {
+    /// Both error counters dropped below the warning limit of 96
+    ErrorActive,
+    /// At least one error counter reached the warning limit of 96
+    ErrorWarning,
+    /// At least one error counter exceeded the error passive limit of 127
+    ErrorPassive,
+    /// The transmit error counter exceeded 255 and the peripheral stopped
+    /// participating in bus communication
+    BusOff,
+    /// The peripheral left the bus off state. The error counters are reset
+    /// at this point, so the peripheral is error active.
+    BusOffRecovered,
+}
+
 /// Auxiliary struct
 ///
 /// Provides unsafe low-level register access as well as other common CAN APIs
@@ -143,10 +862,15 @@ pub struct Aux<'a, Id, D> {
     filters_standard: FiltersStandard<'a, Id>,
     /// Filters for messages with [`embedded_can::ExtendedId`]s
     filters_extended: FiltersExtended<'a, Id>,
+    /// Bus state last reported by [`DynAux::poll_bus_state`]
+    bus_state: OperationState,
+    /// Event fields of PSR accumulated since the last consuming read, see
+    /// [`DynAux::take_protocol_events`]
+    protocol_events: Cell<u32>,
 }
 
 /// Trait which erases generic parametrization for [`Aux`] type
-pub trait DynAux {
+pub trait DynAux: crate::sealed::Sealed {
     /// CAN identity type
     type Id;
 
@@ -158,25 +882,43 @@ pub trait DynAux {
     fn initialization_mode(&self);
 
     /// Requests the peripheral to enter "power down" mode.
+    #[deprecated(note = "use `request_clock_stop`")]
+    fn power_down_mode(&self) {
+        self.request_clock_stop();
+    }
+
+    /// Check if the transition to `Power Down (sleep mode)` is complete and
+    /// that it is safe to completely disable the peripheral.
+    #[deprecated(note = "use `poll_clock_stop_acknowledged`")]
+    fn is_ready_for_power_off(&self) -> bool {
+        self.poll_clock_stop_acknowledged().is_ok()
+    }
+
+    /// Requests the peripheral to enter "power down" mode by setting CSR,
+    /// e.g. before stopping its clocks for a low-power mode of the MCU.
     ///
     /// See `Power Down (sleep mode)`, in the peripheral docs, for user
-    /// consideration regarding clocking and message handling.
+    /// consideration regarding clocking and message handling. The peripheral
+    /// finishes pending transfers, then sets INIT and acknowledges, see
+    /// [`Self::poll_clock_stop_acknowledged`]. The configuration is kept.
     ///
     /// It is also worth noting that this mode should not be interpreted as the
     /// peripheral being "powered off", since it is still possible to configure
     /// the peripheral while in this mode.
-    fn power_down_mode(&self);
+    fn request_clock_stop(&self);
 
-    /// Check if the transition to `Power Down (sleep mode)` is complete and
-    /// that it is safe to completely disable the peripheral.
-    ///
-    /// Returns `false` if the peripheral is still handling outgoing or
-    /// incoming messages.
+    /// Returns [`nb::Error::WouldBlock`] until the peripheral acknowledged the
+    /// clock stop request (CSA), after which its clocks may be stopped.
     ///
     /// If the bus is heavily congested, the peripheral might never enter
     /// `Power Down (sleep mode)` on its own. In that case it can be forced by
     /// calling `initialization_mode`.
-    fn is_ready_for_power_off(&self) -> bool;
+    fn poll_clock_stop_acknowledged(&self) -> nb::Result<(), core::convert::Infallible>;
+
+    /// Withdraws the clock stop request once the clocks run again. The
+    /// peripheral stays in initialization mode until
+    /// [`Self::operational_mode`], which also withdraws the request.
+    fn clear_clock_stop_request(&self);
 
     /// Re-enters "Normal Operation" if in "Software Initialization" mode.
     /// In Software Initialization, messages are not received or transmitted.
@@ -187,26 +929,335 @@ pub trait DynAux {
     /// Returns `true` if the peripheral is in "Normal Operation" mode.
     fn is_operational(&self) -> bool;
 
+    /// Currently active [`OperationMode`]
+    ///
+    /// This is usually the configured one, except that the peripheral enters
+    /// [`OperationMode::Restricted`] on its own on a Message RAM access
+    /// failure, and that [`Loopback::Internal`] and [`Aux::with_loopback`]
+    /// monitor the bus.
+    fn operation_mode(&self) -> OperationMode;
+
     /// Access the error counters register value
     fn error_counters(&self) -> ErrorCounters;
 
     /// Access the protocol status register value
     ///
-    /// Reading the register clears fields: PXE, RFDF, RBRS, RESI, DLEC, LEC.
+    /// Reading the register clears the event fields PXE, RFDF, RBRS, RESI,
+    /// DLEC and LEC. Events recorded by other methods since the last
+    /// consuming read are included, and consumed along with the ones read,
+    /// like by [`Self::take_protocol_events`].
     fn protocol_status(&self) -> ProtocolStatus;
 
+    /// Takes the events of the protocol status register since the last
+    /// consuming read, see [`Self::protocol_status`]
+    ///
+    /// Every read of the register, e.g. by [`Self::is_bus_off`] or
+    /// [`Self::protocol_state`], records the events that the read clears, so
+    /// that none are lost between independent readers.
+    fn take_protocol_events(&self) -> ProtocolEvents;
+
+    /// Reads the fields of the protocol status register that reflect the
+    /// current state, without consuming events
+    fn protocol_state(&self) -> ProtocolState;
+
     /// Current value of the timestamp counter
     ///
     /// If timestamping is disabled, its value is zero.
     fn timestamp(&self) -> u16;
+
+    /// Status of the last message that matched a filter with one of the
+    /// `Priority*` [`Action`]s, to be read when
+    /// [`Interrupt::HighPriorityMessage`] is raised, e.g. to receive the
+    /// message from the right FIFO
+    ///
+    /// [`Action`]: crate::filter::Action
+    fn high_priority_message_status(&self) -> HighPriorityMessageStatus;
+
+    /// Returns `true` if CAN FD frames sent now would carry a recessive error
+    /// state indicator, that is if CAN FD is enabled and the peripheral is
+    /// error passive.
+    ///
+    /// Frames can additionally force a recessive ESI through
+    /// [`force_error_state_indicator`]. Whether a transmitted frame actually
+    /// carried a recessive ESI can be checked with [`TxEvent::esi`].
+    ///
+    /// [`force_error_state_indicator`]: crate::message::tx::FrameType::FlexibleDatarate::force_error_state_indicator
+    /// [`TxEvent::esi`]: crate::message::TxEvent::esi
+    fn will_transmit_esi_passive(&self) -> bool;
+
+    /// Returns `true` if the peripheral is bus off, the polled counterpart of
+    /// [`Interrupt::BusOff`]
+    ///
+    /// Recovery is started with [`Self::operational_mode`].
+    fn is_bus_off(&self) -> bool;
+
+    /// Progress of the recovery from bus off
+    fn bus_off_recovery(&self) -> BusOffRecovery;
+
+    /// Starts the recovery if the peripheral is bus off and halted, see
+    /// [`BusOffRecovery::Halted`]. Returns `true` if the recovery was
+    /// started.
+    ///
+    /// [`Interrupt::BusOff`] flags changes of the bus off state, so it is
+    /// raised both when the peripheral goes bus off and when the recovery
+    /// finishes. An interrupt driven recovery clears the flag and calls this
+    /// method in the handler; the flag raised next marks the end of the
+    /// recovery, which [`Self::poll_bus_off_recovery`] confirms.
+    ///
+    /// The recovery cannot be shortened by entering initialization mode
+    /// again, it only restarts the sequence.
+    fn initiate_bus_off_recovery(&self) -> bool;
+
+    /// Drives the recovery from bus off, completing once the peripheral is no
+    /// longer bus off
+    ///
+    /// Starts the recovery if the peripheral is halted, like
+    /// [`Self::initiate_bus_off_recovery`], and returns
+    /// [`nb::Error::WouldBlock`] while it is in progress. With a bus idle,
+    /// the recovery takes 1419 bit times. This completes immediately if the
+    /// peripheral is not bus off, also if it is in initialization mode for
+    /// another reason.
+    fn poll_bus_off_recovery(&self) -> nb::Result<(), core::convert::Infallible>;
+
+    /// Reports a change of the fault confinement state since the previous
+    /// call, e.g. to drive the NMT state machine of a CANopen stack.
+    ///
+    /// Each transition is reported exactly once; `None` is returned if the
+    /// state did not change. If the state changed several times between two
+    /// calls, only the current state is reported, except that leaving bus off
+    /// is always reported as [`BusStateChange::BusOffRecovered`] before the
+    /// state that followed it.
+    ///
+    /// The state is derived from the EW, EP and BO bits of the protocol
+    /// status register, see [`Self::protocol_state`].
+    fn poll_bus_state(&mut self) -> Option<BusStateChange>;
+
+    /// Host and CAN clock frequencies of the [`Dependencies`], e.g. for
+    /// logging the clock setup along with the computed bit timing
+    ///
+    /// [`Dependencies`]: mcan_core::Dependencies
+    fn clocks(&self) -> (HertzU32, HertzU32);
+
+    /// Nominal bit timing programmed in the peripheral
+    ///
+    /// Reads back NBTP, or BTP on M_CAN 3.0, e.g. to check which prescaler
+    /// and segments the [`TimingStrategy`] chose for the configured bitrate.
+    ///
+    /// [`TimingStrategy`]: crate::config::TimingStrategy
+    fn nominal_bit_timing(&self) -> AppliedBitTiming;
+
+    /// Data phase bit timing programmed in the peripheral, or `None` if CAN
+    /// FD is disabled
+    ///
+    /// Reads back DBTP, or FBTP on M_CAN 3.0, like
+    /// [`Self::nominal_bit_timing`].
+    fn data_bit_timing(&self) -> Option<AppliedBitTiming>;
+
+    /// Mask programmed in XIDAM, see [`CanConfig::extended_id_and_mask`]
+    fn extended_id_and_mask(&self) -> embedded_can::ExtendedId;
 }
 
 impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>> Aux<'a, Id, D> {
+    /// Interrupts concerning the peripheral as a whole that are not errors
+    pub const INTERRUPTS: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::HighPriorityMessage,
+        Interrupt::TimestampWraparound,
+        Interrupt::TimeoutOccured,
+    ]);
+
+    /// Interrupts signalling errors on the bus or in the peripheral, to be
+    /// handled along with [`DynAux::protocol_status`] and
+    /// [`DynAux::error_counters`]
+    pub const ERROR_INTERRUPTS: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::MessageRamAccessFailure,
+        Interrupt::BitErrorCorrected,
+        Interrupt::BitErrorUncorrected,
+        Interrupt::ErrorLoggingOverflow,
+        Interrupt::ErrorPassive,
+        Interrupt::WarningStatusChanged,
+        Interrupt::BusOff,
+        Interrupt::Watchdog,
+        Interrupt::ProtocolErrorArbitration,
+        Interrupt::ProtocolErrorData,
+        Interrupt::AccessToReservedAddress,
+    ]);
+
     fn configuration_mode(&self) {
         self.reg.configuration_mode()
     }
+
+    /// Reads the protocol status register, recording the events that the
+    /// read clears for [`DynAux::take_protocol_events`]
+    fn read_protocol_status(&self) -> ProtocolStatus {
+        let read = self.reg.psr.read().bits();
+        self.protocol_events
+            .set(accumulate_events(self.protocol_events.get(), read));
+        ProtocolStatus(PSR::new(read))
+    }
+
+    /// Borrows a register that no component of the crate claims, e.g. the
+    /// customer register for vendor extensions, see [`UnownedRegister`] for
+    /// the allow-list
+    ///
+    /// ```no_run
+    /// # fn example<Id: mcan::core::CanId, D: mcan::core::Dependencies<Id>>(
+    /// #     aux: &mcan::bus::Aux<'_, Id, D>,
+    /// # ) {
+    /// use mcan::reg::{crel::CREL_SPEC, cust::CUST_SPEC};
+    ///
+    /// let release = aux.borrow_register::<CREL_SPEC>().read().rel().bits();
+    /// aux.borrow_register::<CUST_SPEC>().write_bits(0x1);
+    /// # }
+    /// ```
+    pub fn borrow_register<R: UnownedRegister>(&self) -> UnownedRef<'_, R> {
+        UnownedRef::new(&self.reg)
+    }
+
+    /// Standard ID filters, e.g. for checking them with
+    /// [`Filters::verify`](crate::filter::Filters::verify) during normal
+    /// operation
+    pub fn filters_standard(&self) -> &FiltersStandard<'a, Id> {
+        &self.filters_standard
+    }
+
+    /// Extended ID filters, e.g. for checking them with
+    /// [`Filters::verify`](crate::filter::Filters::verify) during normal
+    /// operation
+    pub fn filters_extended(&self) -> &FiltersExtended<'a, Id> {
+        &self.filters_extended
+    }
+
+    /// Runs `f` with the peripheral in internal loopback mode, e.g. for a
+    /// self-test requested during normal operation.
+    ///
+    /// Transmitted frames are received back through the usual components,
+    /// subject to the filters, while the TX pin stays recessive and nothing
+    /// from the bus is received. Afterwards, the test mode and bus monitoring
+    /// settings are restored and normal operation resumes, also if `f`
+    /// panics.
+    ///
+    /// Loopback can only be enabled in configuration mode, so the peripheral
+    /// briefly enters it before and after `f`. Frames on the bus at these
+    /// points and while `f` runs are lost. Entering configuration mode also
+    /// resets the pending, transmission occurred and cancellation finished
+    /// flags of the TX buffers, the TX queue, the fill levels of the RX FIFOs
+    /// and the TX event FIFO, discarding the messages and events in them.
+    /// Transmissions requested by `f` that are still pending when it returns
+    /// are therefore cancelled without being reported, as are transmissions
+    /// requested from another context, e.g. an interrupt handler, after
+    /// checking for them and before entering configuration mode.
+    ///
+    /// Fails without changing anything with
+    /// [`ConfigurationError::NotOperational`] if the peripheral is not in
+    /// normal operation, or with [`ConfigurationError::TransmissionsPending`]
+    /// if transmissions are requested or pending, since they would be
+    /// dropped.
+    pub fn with_loopback<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> Result<R, ConfigurationError> {
+        self.check_reconfigurable()?;
+        let cccr = self.reg.cccr.read();
+        let guard = LoopbackGuard {
+            test_mode: cccr.test().bit(),
+            bus_monitoring: cccr.mon().bit(),
+            test: self.reg.test.read().bits(),
+            aux: self,
+        };
+        let reg = &guard.aux.reg;
+        reg.configuration_mode();
+        reg.cccr.modify(|_, w| w.test().set_bit().mon().set_bit());
+        reg.test.modify(|_, w| w.lbck().set_bit());
+        reg.operational_mode();
+        Ok(f(guard.aux))
+    }
+
+    /// Checks that the peripheral can briefly enter configuration mode
+    /// during operation without dropping pending transmissions
+    fn check_reconfigurable(&self) -> Result<(), ConfigurationError> {
+        if !self.reg.is_operational() {
+            return Err(ConfigurationError::NotOperational);
+        }
+        // A request is only added to TXBRP some cycles after being written
+        // to TXBAR, so TXBAR is read first to see it in either register
+        let requested = self.reg.txbar.read().bits();
+        if requested | self.reg.txbrp.read().bits() != 0 {
+            return Err(ConfigurationError::TransmissionsPending);
+        }
+        Ok(())
+    }
+
+    /// Switches the mode of RX FIFO `fifo` with `depth` elements during
+    /// operation, see [`RxFifo::set_mode`](crate::rx_fifo::RxFifo::set_mode),
+    /// and returns the read offset for its handle
+    pub(crate) fn set_rx_fifo_mode(
+        &mut self,
+        fifo: Capacity,
+        depth: usize,
+        mode: RxFifoMode,
+        overwrite_read_offset: Option<u8>,
+    ) -> Result<Option<u8>, ConfigurationError> {
+        self.check_reconfigurable()?;
+        let (regs, stored) = match fifo {
+            Capacity::RxFifo1 => (&self.reg.rxf1, &mut self.config.rx_fifo_1),
+            _ => (&self.reg.rxf0, &mut self.config.rx_fifo_0),
+        };
+        let config = RxFifoConfig {
+            mode,
+            overwrite_read_offset,
+            ..*stored
+        };
+        check_overwrite_read_offset(fifo, &config, depth)?;
+        // FOM is only writable in configuration mode
+        self.reg.configuration_mode();
+        regs.c.modify(|_, w| w.fom().bit(mode.into()));
+        self.reg.operational_mode();
+        *stored = config;
+        Ok(config.read_offset())
+    }
+}
+
+/// Checks that a FIFO with `depth` elements in overwrite mode has a read
+/// offset that leaves messages to read
+fn check_overwrite_read_offset(
+    fifo: Capacity,
+    config: &RxFifoConfig,
+    depth: usize,
+) -> Result<(), ConfigurationError> {
+    match config.read_offset() {
+        None if !bool::from(config.mode) => Ok(()),
+        Some(offset) if offset != 0 && usize::from(offset) < depth => Ok(()),
+        offset => Err(ConfigurationError::InvalidOverwriteReadOffset {
+            fifo,
+            offset,
+            depth,
+        }),
+    }
+}
+
+/// Restores the configuration changed by [`Aux::with_loopback`] when dropped
+struct LoopbackGuard<'g, 'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>> {
+    aux: &'g mut Aux<'a, Id, D>,
+    test_mode: bool,
+    bus_monitoring: bool,
+    test: u32,
+}
+
+impl<Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>> Drop for LoopbackGuard<'_, '_, Id, D> {
+    fn drop(&mut self) {
+        let reg = &self.aux.reg;
+        reg.configuration_mode();
+        // TEST is only writable while CCCR.TEST is set
+        // Safety: The value has been read from the register.
+        reg.test.write(|w| unsafe { w.bits(self.test) });
+        reg.cccr
+            .modify(|_, w| w.test().bit(self.test_mode).mon().bit(self.bus_monitoring));
+        reg.operational_mode();
+    }
 }
 
+impl<Id, D> crate::sealed::Sealed for Aux<'_, Id, D> {}
+
 impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>> DynAux for Aux<'a, Id, D> {
     type Id = Id;
     type Deps = D;
@@ -223,32 +1274,132 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>> DynAux for Aux<'a
         self.reg.is_operational()
     }
 
+    fn operation_mode(&self) -> OperationMode {
+        let cccr = self.reg.cccr.read();
+        OperationMode::from_bits(cccr.mon().bit(), cccr.asm().bit())
+    }
+
     fn error_counters(&self) -> ErrorCounters {
         ErrorCounters(self.reg.ecr.read())
     }
 
-    fn power_down_mode(&self) {
-        self.reg.cccr.write(|w| w.csr().set_bit());
+    fn request_clock_stop(&self) {
+        self.reg.cccr.modify(|_, w| w.csr().set_bit());
     }
 
-    fn is_ready_for_power_off(&self) -> bool {
-        self.reg.cccr.read().csa().bit_is_set()
+    fn poll_clock_stop_acknowledged(&self) -> nb::Result<(), core::convert::Infallible> {
+        if self.reg.cccr.read().csa().bit_is_set() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn clear_clock_stop_request(&self) {
+        self.reg.cccr.modify(|_, w| w.csr().clear_bit());
     }
 
     fn protocol_status(&self) -> ProtocolStatus {
-        ProtocolStatus(self.reg.psr.read())
+        let read = self.read_protocol_status().bits();
+        let events = self.protocol_events.replace(PSR_NO_EVENTS);
+        ProtocolStatus(PSR::new(read & !PSR_EVENTS | events))
+    }
+
+    fn take_protocol_events(&self) -> ProtocolEvents {
+        self.read_protocol_status();
+        ProtocolEvents::from_bits(self.protocol_events.replace(PSR_NO_EVENTS))
+    }
+
+    fn protocol_state(&self) -> ProtocolState {
+        let status = self.read_protocol_status();
+        ProtocolState {
+            activity: status.activity(),
+            operation_state: status.operation_state(),
+            transmitter_delay: status.transmitter_delay(),
+        }
     }
 
     fn timestamp(&self) -> u16 {
         self.reg.tscv.read().tsc().bits()
     }
+
+    fn high_priority_message_status(&self) -> HighPriorityMessageStatus {
+        self.reg.hpms.read().into()
+    }
+
+    fn will_transmit_esi_passive(&self) -> bool {
+        self.reg.cccr.read().fdoe().bit_is_set() && self.read_protocol_status().ep().bit_is_set()
+    }
+
+    fn is_bus_off(&self) -> bool {
+        self.read_protocol_status().bo().bit_is_set()
+    }
+
+    fn bus_off_recovery(&self) -> BusOffRecovery {
+        BusOffRecovery::from_status(!self.reg.is_operational(), self.is_bus_off())
+    }
+
+    fn initiate_bus_off_recovery(&self) -> bool {
+        let halted = self.bus_off_recovery() == BusOffRecovery::Halted;
+        if halted {
+            self.reg.operational_mode();
+        }
+        halted
+    }
+
+    fn poll_bus_off_recovery(&self) -> nb::Result<(), core::convert::Infallible> {
+        match self.bus_off_recovery() {
+            BusOffRecovery::NotBusOff => Ok(()),
+            BusOffRecovery::Halted => {
+                self.reg.operational_mode();
+                Err(nb::Error::WouldBlock)
+            }
+            BusOffRecovery::Recovering => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    fn poll_bus_state(&mut self) -> Option<BusStateChange> {
+        let current = self.protocol_state().operation_state;
+        let (change, observed) = self.bus_state.transition(current);
+        self.bus_state = observed;
+        change
+    }
+
+    fn clocks(&self) -> (HertzU32, HertzU32) {
+        (
+            self.dependencies.host_clock(),
+            self.dependencies.can_clock(),
+        )
+    }
+
+    fn nominal_bit_timing(&self) -> AppliedBitTiming {
+        let timing =
+            ComputedTiming::from_nominal_register(self.reg.nbtp.read().bits(), Id::REVISION);
+        AppliedBitTiming::new(timing, self.dependencies.can_clock())
+    }
+
+    fn extended_id_and_mask(&self) -> embedded_can::ExtendedId {
+        // The field has 29 bits, so it always holds a valid ID
+        let mask = self.reg.xidam.read().eidm().bits();
+        embedded_can::ExtendedId::new(mask).unwrap_or(embedded_can::ExtendedId::MAX)
+    }
+
+    fn data_bit_timing(&self) -> Option<AppliedBitTiming> {
+        if self.reg.cccr.read().fdoe().bit_is_clear() {
+            return None;
+        }
+        let timing = ComputedTiming::from_data_register(self.reg.dbtp.read().bits());
+        Some(AppliedBitTiming::new(timing, self.dependencies.can_clock()))
+    }
 }
 
 /// A CAN bus in configuration mode. Before messages can be sent and received,
-/// it needs to be [`Self::finalize`]d.
+/// it needs to be [`Self::finalize_recoverable`]d.
 pub struct CanConfigurable<'a, Id, D, C: Capacities>(
     /// The type invariant of CCE=0 is broken while this is wrapped.
     Can<'a, Id, D, C>,
+    /// State of the bus when it was reconfigured by [`Can::configure`]
+    Option<ReconfigurationGuard>,
 );
 
 impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
@@ -274,6 +1425,127 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
         &mut self.0.aux.filters_extended
     }
 
+    /// Appends `filter` to the standard ID filters like
+    /// [`FiltersStandard::push`], after checking that frames of up to
+    /// `declared_max_len` data bytes fit into the dedicated receive buffers.
+    ///
+    /// Meant for [`Filter::StoreBuffer`], as the element size of the dedicated
+    /// buffers is shared by all of them and cannot be chosen per filter.
+    ///
+    /// [`Filter::StoreBuffer`]: crate::filter::Filter::StoreBuffer
+    pub fn push_store_buffer_filter(
+        &mut self,
+        filter: Filter,
+        declared_max_len: Option<usize>,
+    ) -> Result<usize, StoreBufferError<Filter>> {
+        if let Filter::StoreBuffer { offset, .. } = filter {
+            if usize::from(offset) >= C::DedicatedRxBuffers::USIZE {
+                return Err(StoreBufferError::OutOfBounds(filter));
+            }
+        }
+        self.0
+            .rx_dedicated_buffers
+            .check_declared_len(declared_max_len)
+            .map_err(StoreBufferError::ElementTooSmall)?;
+        self.0
+            .aux
+            .filters_standard
+            .push(filter)
+            .map_err(StoreBufferError::FilterListFull)
+    }
+
+    /// Appends `filter` to the extended ID filters like
+    /// [`FiltersExtended::push`], see [`Self::push_store_buffer_filter`].
+    pub fn push_store_buffer_ext_filter(
+        &mut self,
+        filter: ExtFilter,
+        declared_max_len: Option<usize>,
+    ) -> Result<usize, StoreBufferError<ExtFilter>> {
+        if let ExtFilter::StoreBuffer { offset, .. } = filter {
+            if usize::from(offset) >= C::DedicatedRxBuffers::USIZE {
+                return Err(StoreBufferError::OutOfBounds(filter));
+            }
+        }
+        self.0
+            .rx_dedicated_buffers
+            .check_declared_len(declared_max_len)
+            .map_err(StoreBufferError::ElementTooSmall)?;
+        self.0
+            .aux
+            .filters_extended
+            .push(filter)
+            .map_err(StoreBufferError::FilterListFull)
+    }
+
+    /// Routes frames with `id` to dedicated receive buffer `N` and returns a
+    /// handle that receives from this buffer only.
+    ///
+    /// A [`Filter::StoreBuffer`] or [`ExtFilter::StoreBuffer`] is appended to
+    /// the respective filter list, and buffer `N` is no longer served by
+    /// [`RxDedicatedBuffer`]. If `declared_max_len` is given, the binding
+    /// fails unless frames with as many data bytes fit into the buffer.
+    ///
+    /// [`Filter::StoreBuffer`]: crate::filter::Filter::StoreBuffer
+    /// [`ExtFilter::StoreBuffer`]: crate::filter::ExtFilter::StoreBuffer
+    pub fn bind_dedicated_buffer<const N: usize>(
+        &mut self,
+        id: impl Into<embedded_can::Id>,
+        declared_max_len: Option<usize>,
+    ) -> Result<BoundRxBuffer<'a, Id, C::RxBufferMessage, N>, BindError> {
+        self.assign_dedicated_buffer(N, id, declared_max_len)?;
+        self.0.rx_dedicated_buffers.bind::<N>()
+    }
+
+    /// Routes frames with `id` to dedicated receive buffer `index`, to be
+    /// received with [`DynRxDedicatedBuffer::receive`] at the same index, and
+    /// returns the index of the filter in its list
+    ///
+    /// Like [`Self::bind_dedicated_buffer`], this appends a
+    /// [`Filter::StoreBuffer`] or [`ExtFilter::StoreBuffer`] depending on
+    /// `id`, after checking the index against
+    /// [`Capacities::DedicatedRxBuffers`] and the buffers that are bound.
+    /// Several IDs may be assigned to the same buffer.
+    ///
+    /// [`DynRxDedicatedBuffer::receive`]: crate::rx_dedicated_buffers::DynRxDedicatedBuffer::receive
+    /// [`Filter::StoreBuffer`]: crate::filter::Filter::StoreBuffer
+    /// [`ExtFilter::StoreBuffer`]: crate::filter::ExtFilter::StoreBuffer
+    pub fn assign_dedicated_buffer(
+        &mut self,
+        index: usize,
+        id: impl Into<embedded_can::Id>,
+        declared_max_len: Option<usize>,
+    ) -> Result<usize, BindError> {
+        self.0.rx_dedicated_buffers.check_bindable(index)?;
+        self.0
+            .rx_dedicated_buffers
+            .check_declared_len(declared_max_len)
+            .map_err(BindError::ElementTooSmall)?;
+        // Bindable buffer indices are below `DedicatedRxBuffers`, at most 64
+        let offset = index as u8;
+        match id.into() {
+            embedded_can::Id::Standard(id) => self
+                .0
+                .aux
+                .filters_standard
+                .push(Filter::StoreBuffer {
+                    id,
+                    msg_type: SbMsgType::RxBuffer,
+                    offset,
+                })
+                .map_err(|_| BindError::FilterListFull),
+            embedded_can::Id::Extended(id) => self
+                .0
+                .aux
+                .filters_extended
+                .push(ExtFilter::StoreBuffer {
+                    id,
+                    msg_type: SbMsgType::RxBuffer,
+                    offset,
+                })
+                .map_err(|_| BindError::FilterListFull),
+        }
+    }
+
     /// Allows reconfiguring interrupts.
     pub fn interrupt_configuration(&mut self) -> &mut InterruptConfiguration<Id> {
         &mut self.0.interrupt_configuration
@@ -290,6 +1562,34 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
         &mut self.0.aux.config
     }
 
+    /// Reports element sizes of the Message RAM layout `C` that do not fit the
+    /// currently configured [`Mode`].
+    pub fn element_size_warnings(&self) -> impl Iterator<Item = ElementSizeWarning> {
+        CapacitiesSummary::of::<C>()
+            .element_sizes()
+            .warnings(&self.0.aux.config.mode)
+    }
+
+    /// Enters test mode and controls the level of the TX pin, e.g. to verify
+    /// the transceiver wiring during board bring-up.
+    ///
+    /// Test mode is left and the pin is handed back to the CAN core when the
+    /// configuration is finalized.
+    pub fn pin_test_drive(&mut self, level: TxPinState) -> Result<(), LoopbackRequestedError> {
+        if self.0.aux.config.loopback.is_enabled() {
+            return Err(LoopbackRequestedError);
+        }
+        let reg = &self.0.aux.reg;
+        reg.cccr.modify(|_, w| w.test().set_bit());
+        reg.test.modify(|_, w| w.tx().variant(level.into()));
+        Ok(())
+    }
+
+    /// Returns `true` if the RX pin is at the recessive (1) level.
+    pub fn rx_pin_is_recessive(&self) -> bool {
+        self.0.aux.reg.test.read().rx().bit_is_set()
+    }
+
     /// Apply parameters from a bus config struct
     fn apply_configuration(&mut self) -> Result<(), ConfigurationError> {
         let reg = &self.0.aux.reg;
@@ -298,22 +1598,37 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
         if !(1..=16).contains(&config.timestamp.prescaler) {
             return Err(ConfigurationError::InvalidTimeStampPrescaler);
         }
+        let rx_fifo_0_watermark = Self::watermark(
+            Capacity::RxFifo0,
+            config.rx_fifo_0.watermark,
+            C::RxFifo0::USIZE,
+            config.strict_validation,
+        )?;
+        let rx_fifo_1_watermark = Self::watermark(
+            Capacity::RxFifo1,
+            config.rx_fifo_1.watermark,
+            C::RxFifo1::USIZE,
+            config.strict_validation,
+        )?;
+        check_overwrite_read_offset(Capacity::RxFifo0, &config.rx_fifo_0, C::RxFifo0::USIZE)?;
+        check_overwrite_read_offset(Capacity::RxFifo1, &config.rx_fifo_1, C::RxFifo1::USIZE)?;
+        let tx_event_fifo_watermark = Self::watermark(
+            Capacity::TxEventFifo,
+            config.tx.tx_event_fifo_watermark,
+            C::TxEventFifo::USIZE,
+            config.strict_validation,
+        )?;
 
-        let nominal_prescaler = config
-            .nominal_timing
-            .prescaler(dependencies.can_clock(), &NOMINAL_BIT_TIMING_RANGES)?;
-
-        // Safety: The configuration is checked to be valid when computing the prescaler
-        reg.nbtp.write(|w| unsafe {
-            w.nsjw()
-                .bits(config.nominal_timing.sjw - 1)
-                .ntseg1()
-                .bits(config.nominal_timing.phase_seg_1 - 1)
-                .ntseg2()
-                .bits(config.nominal_timing.phase_seg_2 - 1)
-                .nbrp()
-                .bits(nominal_prescaler - 1)
-        });
+        let nominal = config.nominal_timing.compute(
+            dependencies.can_clock(),
+            BitTimingRanges::nominal(Id::REVISION),
+            config.timing_strategy,
+        )?;
+
+        // Safety: The configuration is checked to be valid for the revision when
+        // computing the timing
+        reg.nbtp
+            .write(|w| unsafe { w.bits(nominal.nominal_register(Id::REVISION)) });
 
         // Safety: Every bit pattern of TCP is valid.
         reg.tscc.write(|w| unsafe {
@@ -332,62 +1647,101 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
             } => {
                 reg.cccr
                     .modify(|_, w| w.fdoe().set_bit().brse().bit(allow_bit_rate_switching));
-                let data_prescaler = data_phase_timing
-                    .prescaler(dependencies.can_clock(), &DATA_BIT_TIMING_RANGES)?;
-                // Safety: The configuration is checked to be valid when computing the prescaler
-                reg.dbtp.write(|w| unsafe {
-                    w.dsjw()
-                        .bits(data_phase_timing.sjw - 1)
-                        .dtseg1()
-                        .bits(data_phase_timing.phase_seg_1 - 1)
-                        .dtseg2()
-                        .bits(data_phase_timing.phase_seg_2 - 1)
-                        .dbrp()
-                        .bits((data_prescaler - 1) as u8)
-                });
+                let data = data_phase_timing.compute(
+                    dependencies.can_clock(),
+                    BitTimingRanges::data(Id::REVISION),
+                    config.timing_strategy,
+                )?;
+                let compensation = match config.transmitter_delay_compensation {
+                    Some(tdc) if !tdc.is_valid(Id::REVISION) => {
+                        return Err(ConfigurationError::TransmitterDelayCompensationOutOfRange(
+                            tdc,
+                        ));
+                    }
+                    // FBTP of M_CAN 3.0 holds the offset in TDCO[28:24]
+                    Some(tdc) if Id::REVISION == mcan_core::CoreRevision::V3_0 => {
+                        1 << 23 | u32::from(tdc.offset) << 24
+                    }
+                    Some(tdc) => {
+                        // Safety: The values are checked to fit the fields above
+                        reg.tdcr.write(|w| unsafe {
+                            w.tdco().bits(tdc.offset).tdcf().bits(tdc.window)
+                        });
+                        1 << 23
+                    }
+                    None => 0,
+                };
+                // Safety: The configuration is checked to be valid for the revision when
+                // computing the timing and checking the compensation
+                reg.dbtp
+                    .write(|w| unsafe { w.bits(data.data_register() | compensation) });
             }
         };
         // Repopulate mode configuration in `tx`
         self.0.tx.mode = config.mode;
+        // Repopulate read offsets in the RX FIFOs
+        self.0.rx_fifo_0.read_offset = config.rx_fifo_0.read_offset();
+        self.0.rx_fifo_1.read_offset = config.rx_fifo_1.read_offset();
 
         // Global filter configuration
-        // This setting is redundant and the same behaviour is achievable through main
-        // filter API
+        let global = config.global_filter;
         reg.gfc.write(|w| {
             w.anfs()
-                .variant(crate::reg::gfc::ANFSSELECT_A::REJECT)
+                .variant(global.non_matching_standard.into())
                 .anfe()
-                .variant(crate::reg::gfc::ANFESELECT_A::REJECT)
+                .variant(global.non_matching_extended.into())
+                .rrfs()
+                .bit(global.reject_remote_standard)
+                .rrfe()
+                .bit(global.reject_remote_extended)
         });
+        // Safety: Every 29-bit pattern is a valid mask.
+        reg.xidam
+            .write(|w| unsafe { w.eidm().bits(config.extended_id_and_mask.as_raw()) });
+
+        // Filter list sizes
+        //
+        // The filters are scanned up to the list size for every frame, and at
+        // high bitrates scanning all elements of large lists may not finish
+        // before the next frame. Only the pushed filters are scanned, so the
+        // sizes are programmed here, after the last chance to push filters.
+        //
+        // Safety: The lengths are bounded by the capacities, which are checked
+        // against the register fields on construction.
+        reg.sidfc
+            .modify(|_, w| unsafe { w.lss().bits(self.0.aux.filters_standard.len() as u8) });
+        reg.xidfc
+            .modify(|_, w| unsafe { w.lse().bits(self.0.aux.filters_extended.len() as u8) });
 
-        // Configure test/loopback mode
-        reg.cccr.modify(|_, w| w.test().bit(config.loopback));
-        reg.test.modify(|_, w| w.lbck().bit(config.loopback));
+        // Configure test/loopback mode, handing the TX pin back to the core in
+        // case it was controlled by a pin test
+        reg.test.modify(|_, w| w.tx().core());
+        reg.cccr.modify(|_, w| {
+            w.test()
+                .bit(config.loopback.is_enabled())
+                .mon()
+                .bit(
+                    config.operation == OperationMode::ListenOnly
+                        || config.loopback == Loopback::Internal,
+                )
+                .asm()
+                .bit(config.operation == OperationMode::Restricted)
+        });
+        reg.test
+            .modify(|_, w| w.lbck().bit(config.loopback.is_enabled()));
 
         // Configure RX FIFO 0
         reg.rxf0.c.modify(|_, w| {
             let w = w.fom().bit(config.rx_fifo_0.mode.into());
-            let mut watermark = config.rx_fifo_0.watermark;
-            // According to the spec, any value > 64 is interpreted as watermark interrupt
-            // disabled, as is 0.
-            if watermark > 64 {
-                watermark = 0;
-            }
             // Safety: The value is sanitized before the write
-            unsafe { w.fwm().bits(watermark) }
+            unsafe { w.fwm().bits(rx_fifo_0_watermark) }
         });
 
         // Configure RX FIFO 1
         reg.rxf1.c.modify(|_, w| {
             let w = w.fom().bit(config.rx_fifo_1.mode.into());
-            let mut watermark = config.rx_fifo_1.watermark;
-            // According to the spec, any value > 64 is interpreted as watermark interrupt
-            // disabled, as is 0.
-            if watermark > 64 {
-                watermark = 0;
-            }
             // Safety: The value is sanitized before the write
-            unsafe { w.fwm().bits(watermark) }
+            unsafe { w.fwm().bits(rx_fifo_1_watermark) }
         });
 
         // Configure Tx Buffer
@@ -395,19 +1749,36 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
             .modify(|_, w| w.tfqm().bit(config.tx.tx_queue_submode.into()));
 
         // Configure Tx Event Fifo
-        reg.txefc.modify(|_, w| {
-            let mut watermark = config.tx.tx_event_fifo_watermark;
-            // According to the spec, any value > 32 is interpreted as watermark interrupt
-            // disabled, as is 0.
-            if watermark > 32 {
-                watermark = 0;
-            }
-            // Safety: The value is sanitized before the write
-            unsafe { w.efwm().bits(watermark) }
-        });
+        // Safety: The value is sanitized before the write
+        reg.txefc
+            .modify(|_, w| unsafe { w.efwm().bits(tx_event_fifo_watermark) });
         Ok(())
     }
 
+    /// Sanitizes the `watermark` of a FIFO with `depth` elements
+    ///
+    /// According to the spec, a watermark greater than the depth never
+    /// triggers, so it is clamped to the depth unless `strict`.
+    fn watermark(
+        fifo: Capacity,
+        watermark: u8,
+        depth: usize,
+        strict: bool,
+    ) -> Result<u8, ConfigurationError> {
+        if usize::from(watermark) <= depth {
+            Ok(watermark)
+        } else if strict {
+            Err(ConfigurationError::WatermarkExceedsDepth {
+                fifo,
+                watermark,
+                depth,
+            })
+        } else {
+            // The depths are bounded by `Capacities` to fit in `u8`
+            Ok(depth as u8)
+        }
+    }
+
     /// Apply parameters from a ram config struct
     ///
     /// Ensuring that the RAM config struct is properly defined is basically our
@@ -417,27 +1788,20 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
     fn apply_ram_config(reg: &crate::reg::Can<Id>, mem: &SharedMemoryInner<C>) {
         // Standard id
         //
+        // The list size is programmed from the pushed filters when applying
+        // the configuration.
+        //
         // Safety:
         // - Pointer is valid assuming SharedMemory location is within first 64K of RAM
-        // - Length is checked at compile-time on the `Capacities` constraints level
-        reg.sidfc.write(|w| unsafe {
-            w.flssa()
-                .bits(&mem.filters_standard as *const _ as u16)
-                .lss()
-                .bits(mem.filters_standard.len() as u8)
-        });
+        reg.sidfc
+            .write(|w| unsafe { w.flssa().bits(&mem.filters_standard as *const _ as u16) });
 
         // Extended id
         //
         // Safety:
         // - Pointer is valid assuming SharedMemory location is within first 64K of RAM
-        // - Length is checked at compile-time on the `Capacities` constraints level
-        reg.xidfc.write(|w| unsafe {
-            w.flesa()
-                .bits(&mem.filters_extended as *const _ as u16)
-                .lse()
-                .bits(mem.filters_extended.len() as u8)
-        });
+        reg.xidfc
+            .write(|w| unsafe { w.flesa().bits(&mem.filters_extended as *const _ as u16) });
 
         // RX buffers
         //
@@ -449,11 +1813,11 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
         // Data field size for buffers and FIFOs
         reg.rxesc.write(|w| {
             w.rbds()
-                .bits(C::RxBufferMessage::REG)
+                .bits(C::RxBufferMessage::SIZE.to_code())
                 .f0ds()
-                .bits(C::RxFifo0Message::REG)
+                .bits(C::RxFifo0Message::SIZE.to_code())
                 .f1ds()
-                .bits(C::RxFifo1Message::REG)
+                .bits(C::RxFifo1Message::SIZE.to_code())
         });
 
         // RX FIFO 0
@@ -484,18 +1848,20 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
         //
         // Safety:
         // - Pointer is valid assuming SharedMemory location is within first 64K of RAM
-        // - Lengths are checked at compile-time on the `Capacities` constraints level
+        // - Lengths are checked at compile-time by `TxBufferSplit`
+        let (ndtb, tfqs) = TxBufferSplit::<C>::FIELDS;
         reg.txbc.write(|w| unsafe {
             w.tfqs()
-                .bits(<C::TxBuffers as Unsigned>::U8 - <C::DedicatedTxBuffers as Unsigned>::U8)
+                .bits(tfqs)
                 .ndtb()
-                .bits(<C::DedicatedTxBuffers as Unsigned>::U8)
+                .bits(ndtb)
                 .tbsa()
                 .bits(&mem.tx_buffers as *const _ as u16)
         });
 
         // TX element size config
-        reg.txesc.write(|w| w.tbds().bits(C::TxMessage::REG));
+        reg.txesc
+            .write(|w| w.tbds().bits(C::TxMessage::SIZE.to_code()));
 
         // TX events
         //
@@ -514,15 +1880,31 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
     ///
     /// The hardware requires that SharedMemory is contained within the first
     /// 64K of system RAM. If this condition is not fulfilled, an error is
-    /// returned.
+    /// returned. The same goes for [`Capacities`] exceeding the limits
+    /// declared by the [`mcan_core::CanId`] implementation, for clocks
+    /// violating the contract of [`mcan_core::Dependencies`] and for a
+    /// `bitrate` that is too fast for the CAN clock.
     ///
-    /// The returned peripheral is not operational; use [`Self::finalize`] to
-    /// finish configuration and start transmitting and receiving.
+    /// The returned peripheral is not operational; use
+    /// [`Self::finalize_recoverable`] to finish configuration and start
+    /// transmitting and receiving.
     pub fn new(
         bitrate: HertzU32,
         dependencies: D,
         memory: &'a mut SharedMemory<C>,
-    ) -> Result<Self, MemoryNotAddressableError> {
+    ) -> Result<Self, ConstructionError> {
+        Can::check_construction(&dependencies, memory)?;
+        let can_clock = dependencies.can_clock();
+        let min_time_quanta = *BitTimingRanges::nominal(Id::REVISION)
+            .time_quanta_per_bit
+            .start();
+        if u64::from(bitrate.raw()) * u64::from(min_time_quanta) > u64::from(can_clock.raw()) {
+            return Err(ConstructionError::UnachievableBitrate {
+                bitrate,
+                can: can_clock,
+            });
+        }
+
         // Safety:
         // Since `dependencies` field implies ownership of the HW register pointed to by
         // `Id: CanId`, `can` has a unique access to it
@@ -530,54 +1912,46 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
 
         reg.configuration_mode();
 
-        // Contract:
-        // `mcan_core::Dependencies::eligible_message_ram_start` contract guarantees
-        // `u16::MAX + 1` alignment and points to the beginning of the allocatable CAN
-        // memory region.
-        if !memory.is_addressable(dependencies.eligible_message_ram_start()) {
-            return Err(MemoryNotAddressableError);
-        }
-
         let memory = memory.init();
         Self::apply_ram_config(&reg, memory);
 
         let config = CanConfig::new(bitrate);
 
         // Safety: Since `Can::new` takes a PAC singleton, it can only be called once.
-        // Then no duplicates will be constructed. The registers that are
-        // delegated to these components should not be touched by any other
-        // code. This has to be upheld by all code that has access to the
-        // register block.
-
-        let (interrupt_configuration, interrupts) = unsafe { InterruptConfiguration::new() };
+        // Then no duplicates will be constructed.
+        let can = unsafe {
+            Can::assemble(reg, dependencies, config, memory, |standard, extended| {
+                // Safety: The memory is zeroed by `memory.init`, so all filters are
+                // initially disabled.
+                (
+                    FiltersStandard::new(standard),
+                    FiltersExtended::new(extended),
+                )
+            })
+        };
 
-        let can = CanConfigurable(Can {
-            interrupt_configuration,
-            interrupts,
-            rx_fifo_0: unsafe { RxFifo::new(&mut memory.rx_fifo_0) },
-            rx_fifo_1: unsafe { RxFifo::new(&mut memory.rx_fifo_1) },
-            rx_dedicated_buffers: unsafe {
-                RxDedicatedBuffer::new(&mut memory.rx_dedicated_buffers)
-            },
-            tx: unsafe { Tx::new(&mut memory.tx_buffers, config.mode) },
-            tx_event_fifo: unsafe { TxEventFifo::new(&mut memory.tx_event_fifo) },
-            aux: Aux {
-                reg,
-                dependencies,
-                config,
-                // Safety: The memory is zeroed by `memory.init`, so all filters are initially
-                // disabled.
-                filters_standard: unsafe { FiltersStandard::new(&mut memory.filters_standard) },
-                filters_extended: unsafe { FiltersExtended::new(&mut memory.filters_extended) },
-            },
-        });
+        Ok(CanConfigurable(can, None))
+    }
 
-        Ok(can)
+    /// Locks the configuration and enters initialization mode.
+    #[deprecated(
+        note = "use `finalize_initialized_recoverable`, which gives the bus back on error"
+    )]
+    pub fn finalize_initialized(self) -> Result<Can<'a, Id, D, C>, ConfigurationError> {
+        Ok(self.finalize_initialized_recoverable()?)
     }
 
     /// Locks the configuration and enters initialization mode.
-    pub fn finalize_initialized(mut self) -> Result<Can<'a, Id, D, C>, ConfigurationError> {
-        self.apply_configuration()?;
+    ///
+    /// If the configuration is rejected, the bus is returned in configuration
+    /// mode along with the error, like [`Self::finalize_recoverable`].
+    #[allow(clippy::result_large_err)]
+    pub fn finalize_initialized_recoverable(
+        mut self,
+    ) -> Result<Can<'a, Id, D, C>, FinalizeError<'a, Id, D, C>> {
+        if let Err(error) = self.apply_configuration() {
+            return Err(FinalizeError { can: self, error });
+        }
 
         let can = self.0;
         can.aux.initialization_mode();
@@ -586,8 +1960,25 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
     }
 
     /// Locks the configuration and enters normal operation.
-    pub fn finalize(mut self) -> Result<Can<'a, Id, D, C>, ConfigurationError> {
-        self.apply_configuration()?;
+    #[deprecated(note = "use `finalize_recoverable`, which gives the bus back on error")]
+    pub fn finalize(self) -> Result<Can<'a, Id, D, C>, ConfigurationError> {
+        Ok(self.finalize_recoverable()?)
+    }
+
+    /// Locks the configuration and enters normal operation.
+    ///
+    /// If the configuration is rejected, the bus is returned in configuration
+    /// mode along with the error, so that it can be corrected and finalized
+    /// again.
+    // The error is as large as the bus itself, which cannot be boxed without
+    // an allocator.
+    #[allow(clippy::result_large_err)]
+    pub fn finalize_recoverable(
+        mut self,
+    ) -> Result<Can<'a, Id, D, C>, FinalizeError<'a, Id, D, C>> {
+        if let Err(error) = self.apply_configuration() {
+            return Err(FinalizeError { can: self, error });
+        }
 
         let can = self.0;
 
@@ -597,14 +1988,116 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
         Ok(can)
     }
 
+    /// Locks the configuration and enters normal operation like
+    /// [`Self::finalize_recoverable`], reporting on the configuration window
+    /// if the bus was reconfigured by [`Can::configure`]
+    ///
+    /// The report is `None` for a bus created by [`Self::new`].
+    #[allow(clippy::result_large_err, clippy::type_complexity)]
+    pub fn finalize_with_report(
+        self,
+    ) -> Result<(Can<'a, Id, D, C>, Option<ReconfigurationReport>), FinalizeError<'a, Id, D, C>>
+    {
+        let guard = self.1;
+        let can = self.finalize_recoverable()?;
+        let reg = &can.aux.reg;
+        let report = guard.map(|guard| {
+            guard.report(
+                reg.tscc.read().bits(),
+                reg.nbtp.read().bits(),
+                reg.tscv.read().tsc().bits(),
+            )
+        });
+        Ok((can, report))
+    }
+
     /// Leaves the peripheral non-operational and makes the `Dependencies`
     /// available again.
+    ///
+    /// All interrupts are disabled and the interrupt registers are reset,
+    /// including the interrupt lines, whether or not the interrupt sets split
+    /// off were returned. The borrow of the Message RAM ends, so that it can
+    /// be used for a new instance.
     pub fn release(self) -> D {
+        self.0.interrupt_configuration.reset();
         self.0.aux.dependencies
     }
 }
 
 impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities> Can<'a, Id, D, C> {
+    /// Checks `C` against the limits of the peripheral and the placement of
+    /// `memory` against the eligible region of `dependencies`
+    pub(crate) fn check_construction(
+        dependencies: &D,
+        memory: &SharedMemory<C>,
+    ) -> Result<(), ConstructionError> {
+        // Fail to compile rather than misprogram TXBC
+        let _ = TxBufferSplit::<C>::FIELDS;
+        check_capacities::<Id, C>()?;
+
+        let (host, can) = (dependencies.host_clock(), dependencies.can_clock());
+        if can.raw() == 0 || host < can {
+            return Err(ConstructionError::ClockConfiguration { host, can });
+        }
+
+        // Contract:
+        // `mcan_core::Dependencies::eligible_message_ram_start` contract guarantees
+        // `u16::MAX + 1` alignment and points to the beginning of the allocatable CAN
+        // memory region.
+        let required = memory.check_placement(dependencies.eligible_message_ram_start())?;
+        if let Some(available) = dependencies.eligible_message_ram_len() {
+            if required > available {
+                return Err(ConstructionError::MessageRamExceeded {
+                    required,
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands out the registers and `memory` to the components.
+    ///
+    /// # Safety
+    /// `reg` is the only instance for the peripheral, which is why this must
+    /// not be called more than once for the lifetime of `dependencies`. The
+    /// registers that are delegated to the components should not be touched
+    /// by any other code. This has to be upheld by all code that has access to
+    /// the register block.
+    pub(crate) unsafe fn assemble(
+        reg: crate::reg::Can<Id>,
+        dependencies: D,
+        config: CanConfig,
+        memory: &'a mut SharedMemoryInner<C>,
+        filters: impl FnOnce(
+            &'a mut [VolatileCell<FilterStandardId>],
+            &'a mut [VolatileCell<FilterExtendedId>],
+        ) -> (FiltersStandard<'a, Id>, FiltersExtended<'a, Id>),
+    ) -> Self {
+        let (interrupt_configuration, interrupts) = InterruptConfiguration::new();
+        let (filters_standard, filters_extended) =
+            filters(&mut memory.filters_standard, &mut memory.filters_extended);
+
+        Can {
+            interrupt_configuration,
+            interrupts,
+            rx_fifo_0: RxFifo::new(&mut memory.rx_fifo_0, config.rx_fifo_0.read_offset()),
+            rx_fifo_1: RxFifo::new(&mut memory.rx_fifo_1, config.rx_fifo_1.read_offset()),
+            rx_dedicated_buffers: RxDedicatedBuffer::new(&mut memory.rx_dedicated_buffers),
+            tx: Tx::new(&mut memory.tx_buffers, config.mode),
+            tx_event_fifo: TxEventFifo::new(&mut memory.tx_event_fifo),
+            aux: Aux {
+                reg,
+                dependencies,
+                config,
+                filters_standard,
+                filters_extended,
+                bus_state: OperationState::ErrorActive,
+                protocol_events: Cell::new(PSR_NO_EVENTS),
+            },
+        }
+    }
+
     /// Raw access to the registers.
     ///
     /// # Safety
@@ -617,13 +2110,2438 @@ impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities> Ca
     /// Return to configuration mode. This resets some status registers, which
     /// effectively clears received messages, messages pending transmission and
     /// tranmit events.
+    ///
+    /// The interrupt configuration is left untouched; interrupt sets that were
+    /// split off remain valid and keep their state. Use
+    /// [`Self::configure_with_interrupts`] to start over from a clean slate.
+    ///
+    /// Frames on the bus are missed until the configuration is finalized. The
+    /// state of the bus is recorded beforehand, so that
+    /// [`CanConfigurable::finalize_with_report`] can account for the window,
+    /// see [`ReconfigurationReport`]. Recording it reads PSR, whose events
+    /// remain available through [`DynAux::take_protocol_events`].
     pub fn configure(self) -> CanConfigurable<'a, Id, D, C> {
+        let guard = ReconfigurationGuard::record(&self);
         self.aux.configuration_mode();
-        CanConfigurable(self)
+        CanConfigurable(self, Some(guard))
+    }
+
+    /// Return to configuration mode like [`Self::configure`], while giving
+    /// back interrupt sets that were split off.
+    ///
+    /// The returned interrupts are disabled, their line selection is reset and
+    /// they are joined back into [`CanConfigurable::interrupts`]. If this
+    /// returns all interrupts, the interrupt registers are back in their reset
+    /// state, including the interrupt lines.
+    pub fn configure_with_interrupts(
+        mut self,
+        sets: impl IntoIterator<Item = OwnedInterruptSet<Id>>,
+    ) -> CanConfigurable<'a, Id, D, C> {
+        for set in sets {
+            self.interrupt_configuration
+                .reclaim(&mut self.interrupts, set);
+        }
+        self.configure()
     }
 
-    /// Disables the peripheral and makes the `Dependencies` available again.
+    /// Disables the peripheral and makes the `Dependencies` available again,
+    /// see [`CanConfigurable::release`]
     pub fn release(self) -> D {
         self.configure().release()
     }
+
+    /// Requests clock stop, see [`DynAux::request_clock_stop`], and parks the
+    /// bus so that it cannot be used to transmit or receive until
+    /// [`PoweredDown::wake`]
+    pub fn into_powered_down(self) -> PoweredDown<'a, Id, D, C> {
+        self.aux.request_clock_stop();
+        PoweredDown(self)
+    }
+
+    /// Splits the peripheral into components that can be dropped
+    /// independently, e.g. when only transmission is used.
+    ///
+    /// The peripheral cannot be reconfigured afterwards.
+    pub fn into_parts(self) -> CanParts<'a, Id, D, C> {
+        CanParts {
+            interrupt_configuration: self.interrupt_configuration,
+            interrupts: self.interrupts,
+            rx_fifo_0: Some(self.rx_fifo_0),
+            rx_fifo_1: Some(self.rx_fifo_1),
+            rx_dedicated_buffers: Some(self.rx_dedicated_buffers),
+            tx: self.tx,
+            tx_event_fifo: Some(self.tx_event_fifo),
+            aux: self.aux,
+        }
+    }
+}
+
+/// Bus with a pending or acknowledged clock stop request, see
+/// [`Can::into_powered_down`]
+///
+/// The configuration and the Message RAM are kept, so the bus resumes
+/// without being configured again.
+pub struct PoweredDown<'a, Id, D, C: Capacities>(Can<'a, Id, D, C>);
+
+impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
+    PoweredDown<'a, Id, D, C>
+{
+    /// Returns [`nb::Error::WouldBlock`] until the clocks of the peripheral
+    /// may be stopped, see [`DynAux::poll_clock_stop_acknowledged`]
+    pub fn poll_clock_stopped(&self) -> nb::Result<(), core::convert::Infallible> {
+        self.0.aux.poll_clock_stop_acknowledged()
+    }
+
+    /// Withdraws the clock stop request and enters normal operation again,
+    /// leaving the initialization mode that the peripheral entered for the
+    /// clock stop
+    ///
+    /// The clocks of the peripheral must be running.
+    pub fn wake(self) -> Can<'a, Id, D, C> {
+        self.0.aux.operational_mode();
+        self.0
+    }
+}
+
+/// Components of a [`Can`] whose receiving side can be dropped
+///
+/// The optional components are dropped by setting them to `None`. Dropping a
+/// component does not change the configuration of the peripheral. Frames
+/// routed to a dropped receive queue keep being stored in the Message RAM
+/// until the queue is full and further frames are lost or overwrite the old
+/// ones, depending on the FIFO mode. Likewise, events of transmissions keep
+/// filling a dropped TX event FIFO. The filters should be configured not to
+/// route frames to the dropped queues in the first place.
+///
+/// Interrupts belonging to dropped components are of no use and are refused by
+/// [`Self::enable_checked`].
+pub struct CanParts<'a, Id, D, C: Capacities> {
+    interrupt_configuration: InterruptConfiguration<Id>,
+    /// Set of interrupts in a disabled state
+    pub interrupts: OwnedInterruptSet<Id, state::Disabled>,
+    /// Receive FIFO 0
+    pub rx_fifo_0: Option<RxFifo<'a, Fifo0, Id, C::RxFifo0Message>>,
+    /// Receive FIFO 1
+    pub rx_fifo_1: Option<RxFifo<'a, Fifo1, Id, C::RxFifo1Message>>,
+    /// Dedicated receive buffers
+    pub rx_dedicated_buffers: Option<RxDedicatedBuffer<'a, Id, C::RxBufferMessage>>,
+    /// Message transmission
+    pub tx: Tx<'a, Id, C>,
+    /// Events for successfully transmitted messages
+    pub tx_event_fifo: Option<TxEventFifo<'a, Id>>,
+    /// Auxiliary bits and bobs
+    pub aux: Aux<'a, Id, D>,
+}
+
+/// Interrupts could not be enabled because some of them belong to components
+/// that were dropped from [`CanParts`]
+pub struct DroppedComponentError<Id, State> {
+    /// The interrupts passed in, unchanged
+    pub interrupts: OwnedInterruptSet<Id, State>,
+    /// The interrupts belonging to dropped components
+    pub orphaned: InterruptSet,
+}
+
+impl<Id, State> core::fmt::Debug for DroppedComponentError<Id, State> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DroppedComponentError")
+            .field("orphaned", &self.orphaned)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
+    CanParts<'a, Id, D, C>
+{
+    /// Enables `interrupts` on `line` like [`InterruptConfiguration::enable`],
+    /// unless some of them belong to a dropped component.
+    pub fn enable_checked<State>(
+        &mut self,
+        interrupts: OwnedInterruptSet<Id, State>,
+        line: InterruptLine,
+    ) -> Result<OwnedInterruptSet<Id>, DroppedComponentError<Id, State>> {
+        let orphaned = interrupts.owned().intersection(self.dropped_interrupts());
+        if !orphaned.is_empty() {
+            return Err(DroppedComponentError {
+                interrupts,
+                orphaned,
+            });
+        }
+        Ok(self.interrupt_configuration.enable(interrupts, line))
+    }
+
+    /// Disables `interrupts`
+    pub fn disable<State>(
+        &mut self,
+        interrupts: OwnedInterruptSet<Id, State>,
+    ) -> OwnedInterruptSet<Id, state::Disabled> {
+        self.interrupt_configuration.disable(interrupts)
+    }
+
+    /// Interrupts that only concern the components that were dropped
+    fn dropped_interrupts(&self) -> InterruptSet {
+        [
+            (
+                self.rx_fifo_0.is_none(),
+                RxFifo::<Fifo0, Id, C::RxFifo0Message>::INTERRUPTS,
+            ),
+            (
+                self.rx_fifo_1.is_none(),
+                RxFifo::<Fifo1, Id, C::RxFifo1Message>::INTERRUPTS,
+            ),
+            (
+                self.rx_dedicated_buffers.is_none(),
+                RxDedicatedBuffer::<Id, C::RxBufferMessage>::INTERRUPTS,
+            ),
+            (self.tx_event_fifo.is_none(), TxEventFifo::<Id>::INTERRUPTS),
+        ]
+        .into_iter()
+        .filter(|&(dropped, _)| dropped)
+        .fold(InterruptSet::empty(), |all, (_, interrupts)| {
+            all.union(interrupts)
+        })
+    }
+}
+
+/// Detection of the bitrate of a bus by listening with candidate timings
+///
+/// For each candidate in turn, the peripheral is configured with it as the
+/// nominal timing and listens in [`OperationMode::ListenOnly`], so that it
+/// neither acknowledges frames nor signals errors on the bus. A candidate is
+/// found to work once a frame is received without errors, which resets the
+/// [`LastErrorCode`] to [`LastErrorCode::NoError`], whether or not the filters
+/// accept the frame.
+///
+/// The scan is driven by [`Self::poll`], which is meant to be called once per
+/// tick of a timer of the application. Candidates that do not receive a frame
+/// within the timeout, or that cannot be configured, are skipped.
+///
+/// ```no_run
+/// # use mcan::bus::{BitrateScan, CanConfigurable, ScanState};
+/// # use mcan::config::BitTiming;
+/// # use mcan::messageram::Capacities;
+/// # use fugit::RateExtU32;
+/// # fn example<'a, Id: mcan::core::CanId, D: mcan::core::Dependencies<Id>, C: Capacities>(
+/// #     can: CanConfigurable<'a, Id, D, C>,
+/// #     wait_for_tick: impl Fn(),
+/// # ) {
+/// let candidates = [125, 250, 500, 1000].map(|kbps| BitTiming::new(kbps.kHz()));
+/// // 100 ticks per candidate
+/// let mut scan = BitrateScan::new(can, &candidates, 100);
+/// let found = loop {
+///     match scan.poll() {
+///         ScanState::Listening { .. } => wait_for_tick(),
+///         ScanState::Found { index } => break candidates.get(index).copied(),
+///         ScanState::NotFound => break None,
+///     }
+/// };
+/// let mut can = scan.release();
+/// if let Some(timing) = found {
+///     can.config().nominal_timing = timing;
+/// }
+/// let can = can.finalize_recoverable();
+/// # }
+/// ```
+pub struct BitrateScan<'a, 'c, Id, D, C: Capacities> {
+    /// The bus, out of configuration mode while listening
+    can: CanConfigurable<'a, Id, D, C>,
+    candidates: &'c [BitTiming],
+    /// Ticks to listen with each candidate
+    timeout: u32,
+    /// Ticks spent listening with the current candidate
+    ticks: u32,
+    state: ScanState,
+    /// Settings changed by the scan, restored on release
+    restore: (BitTiming, OperationMode, Loopback),
+}
+
+/// Progress of a [`BitrateScan`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScanState {
+    /// Listening with the candidate at `index`
+    Listening {
+        /// Index of the candidate
+        index: usize,
+    },
+    /// A frame was received with the candidate at `index`
+    Found {
+        /// Index of the candidate
+        index: usize,
+    },
+    /// No candidate received a frame within the timeout
+    NotFound,
+}
+
+impl<'a, 'c, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities>
+    BitrateScan<'a, 'c, Id, D, C>
+{
+    /// Starts listening with the first candidate that can be configured,
+    /// for `timeout` ticks each
+    pub fn new(
+        can: CanConfigurable<'a, Id, D, C>,
+        candidates: &'c [BitTiming],
+        timeout: u32,
+    ) -> Self {
+        let config = &can.0.aux.config;
+        let restore = (config.nominal_timing, config.operation, config.loopback);
+        let mut scan = Self {
+            can,
+            candidates,
+            timeout,
+            ticks: 0,
+            state: ScanState::NotFound,
+            restore,
+        };
+        scan.listen_from(0);
+        scan
+    }
+
+    /// Configures the first candidate from `start` on that can be configured
+    /// and starts listening with it
+    fn listen_from(&mut self, start: usize) {
+        self.ticks = 0;
+        self.state = ScanState::NotFound;
+        let aux = &mut self.can.0.aux;
+        aux.configuration_mode();
+        aux.config.operation = OperationMode::ListenOnly;
+        aux.config.loopback = Loopback::Disabled;
+        for (index, &timing) in self.candidates.iter().enumerate().skip(start) {
+            self.can.0.aux.config.nominal_timing = timing;
+            if self.can.apply_configuration().is_ok() {
+                let aux = &self.can.0.aux;
+                aux.operational_mode();
+                // Drops the events of the previous candidate
+                aux.take_protocol_events();
+                self.state = ScanState::Listening { index };
+                return;
+            }
+        }
+    }
+
+    /// Checks for a frame received with the current candidate, moving on to
+    /// the next candidate after the timeout. Call this once per tick.
+    ///
+    /// Once a candidate is found, the peripheral keeps listening with it.
+    pub fn poll(&mut self) -> ScanState {
+        let ScanState::Listening { index } = self.state else {
+            return self.state;
+        };
+        let received =
+            self.can.0.aux.take_protocol_events().last_error_code == LastErrorCode::NoError;
+        if received {
+            self.state = ScanState::Found { index };
+        } else {
+            self.ticks = self.ticks.saturating_add(1);
+            if self.ticks >= self.timeout {
+                self.listen_from(index.saturating_add(1));
+            }
+        }
+        self.state
+    }
+
+    /// Stops the scan and returns the bus in configuration mode, with the
+    /// nominal timing, operation mode and loopback setting it was scanned
+    /// with restored
+    pub fn release(mut self) -> CanConfigurable<'a, Id, D, C> {
+        let aux = &mut self.can.0.aux;
+        aux.configuration_mode();
+        (
+            aux.config.nominal_timing,
+            aux.config.operation,
+            aux.config.loopback,
+        ) = self.restore;
+        self.can
+    }
+}
+
+/// Transmit queue and one receive FIFO behind the [`embedded_can`] traits
+///
+/// Generic drivers written against [`embedded_can::nb::Can`] or
+/// [`embedded_can::blocking::Can`] only transmit and receive frames. The
+/// adapter combines the two components they need, taken from [`Can`] or
+/// [`CanParts`], and leaves the other components to the rest of the
+/// application. Frames are put in the transmit queue, so dedicated transmit
+/// buffers remain available through [`Self::tx`], and are received from the
+/// FIFO `F`.
+///
+/// Transmission blocks while the queue is full or the peripheral is not
+/// operational, e.g. because it is bus off, which the blocking variant waits
+/// out by spinning.
+pub struct CanBusAdapter<'a, Id, C: Capacities, F, M: rx::AnyMessage> {
+    tx: Tx<'a, Id, C>,
+    rx_fifo: RxFifo<'a, F, Id, M>,
+}
+
+impl<'a, Id, C: Capacities, F, M: rx::AnyMessage> CanBusAdapter<'a, Id, C, F, M> {
+    /// Combines the transmit buffers and a receive FIFO
+    pub fn new(tx: Tx<'a, Id, C>, rx_fifo: RxFifo<'a, F, Id, M>) -> Self {
+        Self { tx, rx_fifo }
+    }
+
+    /// Transmit buffers, e.g. for the dedicated buffers or cancellation
+    pub fn tx(&mut self) -> &mut Tx<'a, Id, C> {
+        &mut self.tx
+    }
+
+    /// Receive FIFO, e.g. for its status
+    pub fn rx_fifo(&mut self) -> &mut RxFifo<'a, F, Id, M> {
+        &mut self.rx_fifo
+    }
+
+    /// Splits the adapter into its components
+    pub fn into_parts(self) -> (Tx<'a, Id, C>, RxFifo<'a, F, Id, M>) {
+        (self.tx, self.rx_fifo)
+    }
+}
+
+/// Errors of [`CanBusAdapter`]
+///
+/// Receiving cannot fail, and bus errors are handled by the peripheral, so
+/// the [`embedded_can::ErrorKind`] of all errors is
+/// [`Other`](embedded_can::ErrorKind::Other).
+#[derive(Debug)]
+pub enum AdapterError {
+    /// The transmit buffers rejected the frame, e.g. a CAN FD frame in
+    /// [`Mode::Classic`]
+    Transmit(crate::tx_buffers::Error),
+    /// A received frame passed for transmission could not be rebuilt as a
+    /// transmit message
+    InvalidFrame,
+}
+
+impl embedded_can::Error for AdapterError {
+    fn kind(&self) -> embedded_can::ErrorKind {
+        embedded_can::ErrorKind::Other
+    }
+}
+
+/// Rebuilds a received frame for transmission, e.g. to forward it
+fn retransmission<const N: usize>(message: &rx::Message<N>) -> Option<tx::Message<N>> {
+    let frame_type = if message.fd_format() {
+        tx::FrameType::FlexibleDatarate {
+            payload: message.data(),
+            bit_rate_switching: message.bit_rate_switching(),
+            force_error_state_indicator: false,
+        }
+    } else if let Some(desired_len) = message.remote_request_len() {
+        tx::FrameType::Classic(tx::ClassicFrameType::Remote { desired_len })
+    } else {
+        tx::FrameType::Classic(tx::ClassicFrameType::Data(message.data()))
+    };
+    tx::MessageBuilder {
+        id: message.id(),
+        frame_type,
+        store_tx_event: None,
+    }
+    .build()
+    .ok()
+}
+
+impl<'a, Id, C, F, const N: usize> embedded_can::nb::Can
+    for CanBusAdapter<'a, Id, C, F, rx::Message<N>>
+where
+    Id: mcan_core::CanId,
+    C: Capacities<TxMessage = tx::Message<N>>,
+    rx::Message<N>: rx::AnyMessage,
+    RxFifo<'a, F, Id, rx::Message<N>>: crate::rx_fifo::DynRxFifo<Message = rx::Message<N>>,
+{
+    type Frame = Message<N>;
+    type Error = AdapterError;
+
+    /// Puts `frame` in the transmit queue; lower priority frames are never
+    /// replaced, so `Ok(None)` is returned on success
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        let message = match frame {
+            Message::Tx(message) => *message,
+            Message::Rx(message) => {
+                retransmission(message).ok_or(nb::Error::Other(AdapterError::InvalidFrame))?
+            }
+        };
+        self.tx
+            .transmit_queued_indexed(message)
+            .map_err(|error| nb::Error::from(error).map(AdapterError::Transmit))?;
+        Ok(None)
+    }
+
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        use crate::rx_fifo::DynRxFifo as _;
+        self.rx_fifo
+            .receive()
+            .map(Message::Rx)
+            .map_err(|error| error.map(|never| match never {}))
+    }
+}
+
+impl<'a, Id, C, F, const N: usize> embedded_can::blocking::Can
+    for CanBusAdapter<'a, Id, C, F, rx::Message<N>>
+where
+    C: Capacities,
+    rx::Message<N>: rx::AnyMessage,
+    Self: embedded_can::nb::Can<Frame = Message<N>, Error = AdapterError>,
+{
+    type Frame = Message<N>;
+    type Error = AdapterError;
+
+    fn transmit(&mut self, frame: &Self::Frame) -> Result<(), Self::Error> {
+        nb::block!(embedded_can::nb::Can::transmit(self, frame)).map(|_| ())
+    }
+
+    fn receive(&mut self) -> Result<Self::Frame, Self::Error> {
+        nb::block!(embedded_can::nb::Can::receive(self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::rx;
+    use crate::mock::{self, mock_can, Caps};
+    use crate::rx_fifo::DynRxFifo;
+    use fugit::RateExtU32;
+
+    mock_can!(Can0);
+
+    const IE: usize = 0x54;
+    const ILS: usize = 0x58;
+    const ILE: usize = 0x5c;
+
+    #[test]
+    fn interrupts_are_reset_across_configuration_cycles() {
+        let regs = Can0::regs();
+        // Stale configuration from before construction is reset
+        regs.write(IE, 0x1234);
+        regs.write(ILS, 0x5678);
+        regs.write(ILE, 0b11);
+
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can0, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        assert_eq!((regs.read(IE), regs.read(ILS), regs.read(ILE)), (0, 0, 0));
+
+        for _ in 0..2 {
+            let line_0 = can
+                .interrupts()
+                .split(
+                    [Interrupt::BusOff, Interrupt::RxFifo0NewMessage]
+                        .into_iter()
+                        .collect(),
+                )
+                .unwrap();
+            let line_0 = can.interrupt_configuration().enable_line_0(line_0);
+            let line_1 = can
+                .interrupts()
+                .split(Interrupt::TxFifoEmpty.into())
+                .unwrap();
+            let line_1 = can.interrupt_configuration().enable_line_1(line_1);
+            assert!(can.interrupts().split(Interrupt::BusOff.into()).is_err());
+
+            let running = can.finalize_recoverable().unwrap();
+            assert_eq!(regs.read(IE), 1 << 25 | 1 << 11 | 1);
+            assert_eq!(regs.read(ILS), 1 << 11);
+            assert_eq!(regs.read(ILE), 0b11);
+
+            can = running.configure_with_interrupts([line_0.into(), line_1.into()]);
+            assert_eq!((regs.read(IE), regs.read(ILS), regs.read(ILE)), (0, 0, 0));
+            let bus_off = can.interrupts().split(Interrupt::BusOff.into()).unwrap();
+            can.interrupts().join(bus_off);
+        }
+    }
+
+    #[test]
+    fn partially_returned_interrupts_leave_others_enabled() {
+        mock_can!(Can1);
+        let regs = Can1::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can1, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+
+        let kept = can.interrupts().split(Interrupt::BusOff.into()).unwrap();
+        let kept = can.interrupt_configuration().enable_line_1(kept);
+        let returned = can
+            .interrupts()
+            .split(Interrupt::RxFifo1Full.into())
+            .unwrap();
+        let returned = can.interrupt_configuration().enable_line_1(returned);
+
+        let can = can
+            .finalize_recoverable()
+            .unwrap()
+            .configure_with_interrupts([returned.into()]);
+        assert_eq!(regs.read(IE), 1 << 25);
+        assert_eq!(regs.read(ILS), 1 << 25);
+        assert_eq!(regs.read(ILE), 0b10);
+        drop((can, kept));
+    }
+
+    #[test]
+    fn release_resets_interrupts_and_ends_the_memory_borrow() {
+        mock_can!(Can37);
+        let regs = Can37::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        for _ in 0..2 {
+            let dependencies = mock::Dependencies::new(&memory);
+            let mut can =
+                CanConfigurable::<Can37, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+            let line_1 = can.interrupts().split(Interrupt::BusOff.into()).unwrap();
+            let _line_1 = can.interrupt_configuration().enable_line_1(line_1);
+            let can = can.finalize_recoverable().unwrap();
+            assert_eq!(
+                (regs.read(IE), regs.read(ILS), regs.read(ILE)),
+                (1 << 25, 1 << 25, 0b10)
+            );
+
+            let _dependencies = can.release();
+            assert_eq!((regs.read(IE), regs.read(ILS), regs.read(ILE)), (0, 0, 0));
+            assert_eq!(regs.read(CCCR) & (INIT | CCE), INIT | CCE);
+        }
+    }
+
+    #[test]
+    fn esi_passive_requires_fd_and_error_passive() {
+        mock_can!(Can2);
+        const PSR: usize = 0x44;
+        let regs = Can2::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can2, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let can_classic = can.finalize_recoverable().unwrap();
+        regs.write(PSR, 1 << 5);
+        assert!(!can_classic.aux.will_transmit_esi_passive());
+
+        let mut can = can_classic.configure();
+        can.config().mode = Mode::Fd {
+            allow_bit_rate_switching: false,
+            data_phase_timing: crate::config::BitTiming::new(500.kHz()),
+        };
+        let can = can.finalize_recoverable().unwrap();
+        assert!(can.aux.will_transmit_esi_passive());
+        regs.write(PSR, 0);
+        assert!(!can.aux.will_transmit_esi_passive());
+    }
+
+    #[test]
+    fn parts_can_be_dropped_independently() {
+        mock_can!(Can3);
+        let regs = Can3::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can3, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut parts = can.finalize_recoverable().unwrap().into_parts();
+
+        let before = regs.snapshot();
+        parts.rx_fifo_0 = None;
+        parts.rx_dedicated_buffers = None;
+        parts.tx_event_fifo = None;
+        assert_eq!(regs.snapshot(), before);
+
+        let rx = parts
+            .interrupts
+            .split(
+                [Interrupt::RxFifo0NewMessage, Interrupt::RxFifo1NewMessage]
+                    .into_iter()
+                    .collect(),
+            )
+            .unwrap();
+        let Err(error) = parts.enable_checked(rx, InterruptLine::Line0) else {
+            panic!("enabled interrupts of a dropped component");
+        };
+        assert!(error.orphaned.rf0n());
+        assert!(!error.orphaned.rf1n());
+        assert_eq!(regs.read(IE), 0);
+        parts.interrupts.join(error.interrupts);
+
+        let fifo_1 = parts
+            .interrupts
+            .split(Interrupt::RxFifo1NewMessage.into())
+            .unwrap();
+        let fifo_1 = parts.enable_checked(fifo_1, InterruptLine::Line1).unwrap();
+        assert_eq!(regs.read(IE), 1 << 4);
+        assert_eq!(regs.read(ILS), 1 << 4);
+
+        assert!(parts.rx_fifo_1.as_ref().unwrap().is_empty());
+        assert!(parts.aux.is_operational());
+        let fifo_1 = parts.disable(fifo_1);
+        parts.interrupts.join(fifo_1);
+        assert_eq!(regs.read(IE), 0);
+    }
+
+    #[test]
+    fn bus_state_transitions_are_reported_once() {
+        use BusStateChange as C;
+        use OperationState::*;
+        let sequence = [
+            (ErrorActive, None),
+            (ErrorWarning, Some(C::ErrorWarning)),
+            (ErrorWarning, None),
+            (ErrorPassive, Some(C::ErrorPassive)),
+            (BusOff, Some(C::BusOff)),
+            (BusOff, None),
+            (ErrorActive, Some(C::BusOffRecovered)),
+            (ErrorActive, None),
+            (BusOff, Some(C::BusOff)),
+            // Recovered and went on to error warning in between two polls
+            (ErrorWarning, Some(C::BusOffRecovered)),
+            (ErrorWarning, Some(C::ErrorWarning)),
+            (ErrorActive, Some(C::ErrorActive)),
+            (ErrorPassive, Some(C::ErrorPassive)),
+            (ErrorActive, Some(C::ErrorActive)),
+        ];
+        let mut state = ErrorActive;
+        for (index, (current, expected)) in sequence.into_iter().enumerate() {
+            let (change, next) = state.transition(current);
+            assert_eq!(change, expected, "step {index}");
+            state = next;
+        }
+    }
+
+    #[test]
+    fn bus_state_is_polled_from_protocol_status() {
+        mock_can!(Can4);
+        const PSR: usize = 0x44;
+        const EP: u32 = 1 << 5;
+        const EW: u32 = 1 << 6;
+        const BO: u32 = 1 << 7;
+        let regs = Can4::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can4, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut can = can.finalize_recoverable().unwrap();
+
+        assert_eq!(can.aux.poll_bus_state(), None);
+        regs.write(PSR, EW);
+        assert_eq!(can.aux.poll_bus_state(), Some(BusStateChange::ErrorWarning));
+        regs.write(PSR, EW | EP);
+        assert_eq!(can.aux.poll_bus_state(), Some(BusStateChange::ErrorPassive));
+        regs.write(PSR, EW | EP | BO);
+        assert_eq!(can.aux.poll_bus_state(), Some(BusStateChange::BusOff));
+        assert_eq!(can.aux.poll_bus_state(), None);
+        regs.write(PSR, 0);
+        assert_eq!(
+            can.aux.poll_bus_state(),
+            Some(BusStateChange::BusOffRecovered)
+        );
+        assert_eq!(can.aux.poll_bus_state(), None);
+    }
+
+    #[test]
+    fn bus_off_recovery_is_decoded() {
+        use BusOffRecovery::*;
+        for (initializing, bus_off, expected) in [
+            (false, false, NotBusOff),
+            // Initialization mode entered by software
+            (true, false, NotBusOff),
+            (true, true, Halted),
+            (false, true, Recovering),
+        ] {
+            assert_eq!(BusOffRecovery::from_status(initializing, bus_off), expected);
+        }
+    }
+
+    #[test]
+    fn bus_off_recovery_is_started_and_polled() {
+        mock_can!(Can32);
+        const CCCR: usize = 0x18;
+        const PSR: usize = 0x44;
+        const INIT: u32 = 1 << 0;
+        const BO: u32 = 1 << 7;
+        let regs = Can32::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can32, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let can = can.finalize_recoverable().unwrap();
+
+        assert!(!can.aux.initiate_bus_off_recovery());
+        assert_eq!(can.aux.poll_bus_off_recovery(), Ok(()));
+
+        // The hardware halts the peripheral on entering bus off
+        regs.write(PSR, BO);
+        regs.write(CCCR, regs.read(CCCR) | INIT);
+        assert_eq!(can.aux.bus_off_recovery(), BusOffRecovery::Halted);
+        assert!(can.aux.initiate_bus_off_recovery());
+        assert_eq!(regs.read(CCCR) & INIT, 0);
+        assert_eq!(can.aux.bus_off_recovery(), BusOffRecovery::Recovering);
+        assert!(!can.aux.initiate_bus_off_recovery());
+        assert_eq!(can.aux.poll_bus_off_recovery(), Err(nb::Error::WouldBlock));
+
+        // Polling starts the recovery as well
+        regs.write(CCCR, regs.read(CCCR) | INIT);
+        assert_eq!(can.aux.poll_bus_off_recovery(), Err(nb::Error::WouldBlock));
+        assert_eq!(regs.read(CCCR) & INIT, 0);
+
+        regs.write(PSR, 0);
+        assert_eq!(can.aux.poll_bus_off_recovery(), Ok(()));
+        assert!(can.aux.is_operational());
+    }
+
+    #[test]
+    fn every_interrupt_belongs_to_exactly_one_component() {
+        mock_can!(Can16);
+        let components = [
+            RxFifo::<Fifo0, Can16, rx::Message<8>>::INTERRUPTS,
+            RxFifo::<Fifo1, Can16, rx::Message<8>>::INTERRUPTS,
+            RxDedicatedBuffer::<Can16, rx::Message<8>>::INTERRUPTS,
+            Tx::<Can16, Caps>::INTERRUPTS,
+            TxEventFifo::<Can16>::INTERRUPTS,
+            Aux::<Can16, mock::Dependencies>::INTERRUPTS,
+            Aux::<Can16, mock::Dependencies>::ERROR_INTERRUPTS,
+        ];
+        let all = InterruptSet::from_raw(0x3fff_ffff).unwrap();
+        let mut union = InterruptSet::empty();
+        for interrupts in components {
+            assert!(union.intersection(interrupts).is_empty(), "{interrupts:?}");
+            union = union.union(interrupts);
+        }
+        assert!(all.difference(union).is_empty());
+        assert_eq!(union.iter().count(), 30);
+        assert_eq!(Can16::regs().snapshot(), [0; 64]);
+    }
+
+    #[test]
+    fn raw_register_dumps_decode_like_the_registers() {
+        mock_can!(Can15);
+        const ECR: usize = 0x40;
+        const PSR: usize = 0x44;
+        const IR: usize = 0x50;
+        let regs = Can15::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can15, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let can = can.finalize_recoverable().unwrap();
+
+        // PSR, ECR and IR as found after reset, during error warning, error
+        // passive with an FD frame received, and bus off
+        let snapshots = [
+            (0x0000_0707, 0x0000_0000, 0x0000_0000),
+            (0x0000_054a, 0x0000_6460, 0x0100_0001),
+            (0x0005_3e35, 0x0012_0082, 0x0080_0200),
+            (0x007f_7fbb, 0x00ff_ffff, 0x3fff_ffff),
+        ];
+        for (psr, ecr, ir) in snapshots {
+            regs.write(PSR, psr);
+            regs.write(ECR, ecr);
+            regs.write(IR, ir);
+
+            let firmware = can.aux.protocol_status();
+            let raw = ProtocolStatus::from_raw(psr).unwrap();
+            assert_eq!(firmware.bits(), raw.bits());
+            assert_eq!(firmware.last_error_code(), raw.last_error_code());
+            assert_eq!(firmware.data_last_error_code(), raw.data_last_error_code());
+            assert_eq!(firmware.activity(), raw.activity());
+            assert_eq!(firmware.operation_state(), raw.operation_state());
+            assert_eq!(
+                raw.last_error_code(),
+                LastErrorCode::from_raw(raw.lec().bits()).unwrap()
+            );
+            assert_eq!(
+                raw.activity(),
+                Activity::from_raw(raw.act().bits()).unwrap()
+            );
+            assert_eq!(
+                raw.operation_state(),
+                OperationState::from_raw(psr).unwrap()
+            );
+
+            let firmware = can.aux.error_counters();
+            let raw = ErrorCounters::from_raw(ecr).unwrap();
+            assert_eq!(firmware.bits(), raw.bits());
+            assert_eq!(firmware.tec().bits(), raw.tec().bits());
+            assert_eq!(firmware.rec().bits(), raw.rec().bits());
+
+            let firmware = can.interrupt_configuration.peek_all_flags();
+            let raw = InterruptSet::from_raw(ir).unwrap();
+            assert!(firmware.iter().eq(raw.iter()));
+        }
+
+        let snapshot = &ProtocolStatus::from_raw(0x0000_054a).unwrap();
+        assert_eq!(snapshot.last_error_code(), LastErrorCode::Form);
+        assert_eq!(snapshot.data_last_error_code(), LastErrorCode::Bit0);
+        assert_eq!(snapshot.activity(), Activity::Idle);
+        assert_eq!(snapshot.operation_state(), OperationState::ErrorWarning);
+        assert_eq!(
+            OperationState::from_raw(0x0000_00bb),
+            Ok(OperationState::BusOff)
+        );
+    }
+
+    #[test]
+    fn protocol_events_accumulate_between_reads() {
+        const FORM: u32 = 2;
+        const STUFF: u32 = 1;
+        let events = [
+            // Form error in the arbitration phase
+            (0x0000_0702, 0x0000_0702),
+            // No change since
+            (0x0000_0707, 0x0000_0702),
+            // FD frame with BRS received, then a stuff error in its data phase
+            (0x0000_3107, 0x0000_3102),
+            // Protocol exception, and a frame received without errors
+            (0x0000_4700, 0x0000_7100),
+        ];
+        let mut accumulated = PSR_NO_EVENTS;
+        for (read, expected) in events {
+            accumulated = accumulate_events(accumulated, read);
+            assert_eq!(accumulated, expected, "{read:#x}");
+        }
+        // Level fields are not accumulated
+        assert_eq!(accumulate_events(PSR_NO_EVENTS, 0x007f_07ff), 0x0000_0707);
+        assert_eq!(
+            ProtocolEvents::from_bits(FORM | STUFF << 8 | 1 << 13),
+            ProtocolEvents {
+                last_error_code: LastErrorCode::Form,
+                data_last_error_code: LastErrorCode::Stuff,
+                protocol_exception: false,
+                received_fd: true,
+                received_bit_rate_switching: false,
+                received_error_state_indicator: false,
+            }
+        );
+    }
+
+    #[test]
+    fn protocol_events_are_not_lost_to_other_readers() {
+        mock_can!(Can36);
+        const PSR: usize = 0x44;
+        const EW: u32 = 1 << 6;
+        const RFDF: u32 = 1 << 13;
+        const FORM: u32 = 2;
+        let regs = Can36::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can36, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut can = can.finalize_recoverable().unwrap();
+
+        // Events cleared by the hardware after reads by other methods
+        regs.write(PSR, 0x0700 | RFDF | EW | FORM);
+        assert!(!can.aux.is_bus_off());
+        assert_eq!(can.aux.poll_bus_state(), Some(BusStateChange::ErrorWarning));
+        regs.write(PSR, PSR_NO_EVENTS | EW | 1 << 16);
+        assert_eq!(
+            can.aux.protocol_state(),
+            ProtocolState {
+                activity: Activity::Synchronizing,
+                operation_state: OperationState::ErrorWarning,
+                transmitter_delay: 1,
+            }
+        );
+        let events = can.aux.take_protocol_events();
+        assert_eq!(events.last_error_code, LastErrorCode::Form);
+        assert!(events.received_fd);
+        let events = can.aux.take_protocol_events();
+        assert_eq!(events.last_error_code, LastErrorCode::NoChange);
+        assert!(!events.received_fd);
+
+        // The raw status includes and consumes recorded events
+        regs.write(PSR, 0x0700 | FORM);
+        can.aux.protocol_state();
+        regs.write(PSR, PSR_NO_EVENTS);
+        let status = can.aux.protocol_status();
+        assert_eq!(status.last_error_code(), LastErrorCode::Form);
+        assert_eq!(status.data_last_error_code(), LastErrorCode::NoChange);
+        assert_eq!(
+            can.aux.take_protocol_events().last_error_code,
+            LastErrorCode::NoChange
+        );
+    }
+
+    #[test]
+    fn last_error_codes_are_decoded_and_counted() {
+        use LastErrorCode::*;
+        let codes = [NoError, Stuff, Form, Ack, Bit1, Bit0, Crc, NoChange];
+        let mut statistics = ErrorStatistics::new();
+        for (raw, &code) in codes.iter().enumerate() {
+            assert_eq!(LastErrorCode::from_raw(raw as u8), Ok(code));
+            // LEC in bits 0 to 2, DLEC in bits 8 to 10
+            let status = ProtocolStatus::from_raw((raw as u32) << 8 | raw as u32).unwrap();
+            assert_eq!(status.last_error_code(), code);
+            assert_eq!(status.data_last_error_code(), code);
+            statistics.record(&ProtocolEvents {
+                last_error_code: code,
+                data_last_error_code: NoChange,
+                protocol_exception: false,
+                received_fd: false,
+                received_bit_rate_switching: false,
+                received_error_state_indicator: false,
+            });
+        }
+        statistics.data.record(Crc);
+
+        for code in codes {
+            let expected = u32::from(!matches!(code, NoError | NoChange));
+            assert_eq!(statistics.arbitration.count(code), expected, "{code:?}");
+        }
+        assert_eq!(statistics.data.count(Crc), 1);
+        assert_eq!(statistics.total(), 7);
+
+        statistics.arbitration.ack = u32::MAX;
+        statistics.arbitration.record(Ack);
+        assert_eq!(statistics.arbitration.count(Ack), u32::MAX);
+        assert_eq!(statistics.total(), u32::MAX);
+        statistics.reset();
+        assert_eq!(statistics, ErrorStatistics::default());
+    }
+
+    #[test]
+    fn reserved_bits_are_rejected_in_raw_register_dumps() {
+        let reserved = |bits| Some(ReservedBitsError { bits });
+        assert_eq!(
+            ProtocolStatus::from_raw(0x0080_8000).err(),
+            reserved(0x0080_8000)
+        );
+        assert_eq!(
+            ErrorCounters::from_raw(0x0100_0000).err(),
+            reserved(0x0100_0000)
+        );
+        assert_eq!(
+            InterruptSet::from_raw(0xc000_0000).err(),
+            reserved(0xc000_0000)
+        );
+        assert_eq!(LastErrorCode::from_raw(8).err(), reserved(8));
+        assert_eq!(Activity::from_raw(4).err(), reserved(4));
+        assert_eq!(OperationState::from_raw(1 << 31).err(), reserved(1 << 31));
+        assert_eq!(
+            HighPriorityMessageStatus::from_raw(0x0001_0000).err(),
+            reserved(0x0001_0000)
+        );
+    }
+
+    #[test]
+    fn high_priority_message_status_is_decoded() {
+        mock_can!(Can35);
+        const HPMS: usize = 0x94;
+        let regs = Can35::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can35, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let can = can.finalize_recoverable().unwrap();
+
+        let status =
+            |storage, buffer_index, filter_index, extended_filter| HighPriorityMessageStatus {
+                storage,
+                buffer_index,
+                filter_index,
+                extended_filter,
+            };
+        for (raw, expected) in [
+            (0x0000_0000, status(MessageStorage::NoFifo, 0, 0, false)),
+            (0x0000_0045, status(MessageStorage::Lost, 5, 0, false)),
+            (0x0000_0382, status(MessageStorage::Fifo0, 2, 3, false)),
+            (0x0000_ffff, status(MessageStorage::Fifo1, 63, 127, true)),
+            (0x0000_8cc0, status(MessageStorage::Fifo1, 0, 12, true)),
+        ] {
+            assert_eq!(HighPriorityMessageStatus::from_raw(raw), Ok(expected));
+            regs.write(HPMS, raw);
+            assert_eq!(can.aux.high_priority_message_status(), expected);
+        }
+    }
+
+    #[test]
+    fn pin_test_is_reverted_on_finalize() {
+        mock_can!(Can5);
+        const CCCR: usize = 0x18;
+        const TEST: usize = 0x10;
+        const CCCR_TEST: u32 = 1 << 7;
+        const TEST_TX: u32 = 0b11 << 5;
+        const TEST_RX: u32 = 1 << 7;
+        let regs = Can5::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can5, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+
+        for (level, bits) in [
+            (TxPinState::ControlledByCore, 0),
+            (TxPinState::SamplePoint, 1 << 5),
+            (TxPinState::Dominant, 2 << 5),
+            (TxPinState::Recessive, 3 << 5),
+        ] {
+            can.pin_test_drive(level).unwrap();
+            assert_ne!(regs.read(CCCR) & CCCR_TEST, 0);
+            assert_eq!(regs.read(TEST) & TEST_TX, bits);
+        }
+
+        regs.write(TEST, regs.read(TEST) | TEST_RX);
+        assert!(can.rx_pin_is_recessive());
+        regs.write(TEST, regs.read(TEST) & !TEST_RX);
+        assert!(!can.rx_pin_is_recessive());
+
+        let mut can = can.finalize_recoverable().unwrap().configure();
+        assert_eq!(regs.read(CCCR) & CCCR_TEST, 0);
+        assert_eq!(regs.read(TEST) & TEST_TX, 0);
+
+        can.config().loopback = Loopback::External;
+        assert!(can.pin_test_drive(TxPinState::Dominant).is_err());
+        assert_eq!(regs.read(TEST) & TEST_TX, 0);
+    }
+
+    #[test]
+    fn capacities_are_checked_against_peripheral_limits() {
+        mock_can!(Can6 {
+            const MAX_EXTENDED_FILTERS: usize = 8;
+            const MAX_TX_BUFFERS: usize = 3;
+        });
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let result = CanConfigurable::<Can6, _, _>::new(500.kHz(), dependencies, &mut memory);
+        assert!(matches!(
+            result,
+            Err(ConstructionError::CapacityExceeded(CapacityExceededError {
+                capacity: crate::messageram::Capacity::TxBuffers,
+                requested: 4,
+                supported: 3,
+            }))
+        ));
+        // Rejected before touching the peripheral
+        assert_eq!(Can6::regs().snapshot(), [0; 64]);
+    }
+
+    const RXF0C: usize = 0xa0;
+    const RXF1C: usize = 0xb0;
+    const TXEFC: usize = 0xf0;
+
+    fn watermarks(regs: &mock::Registers) -> [u32; 3] {
+        [
+            regs.read(RXF0C) >> 24 & 0x7f,
+            regs.read(RXF1C) >> 24 & 0x7f,
+            regs.read(TXEFC) >> 24 & 0x3f,
+        ]
+    }
+
+    #[test]
+    fn watermarks_are_clamped_to_fifo_depth() {
+        use crate::tx_event_fifo::DynTxEventFifo as _;
+
+        mock_can!(Can7);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can7, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().rx_fifo_0.watermark = 48;
+        can.config().rx_fifo_1.watermark = 3;
+        can.config().tx.tx_event_fifo_watermark = 200;
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(watermarks(Can7::regs()), [4, 3, 4]);
+        assert_eq!(
+            [
+                can.rx_fifo_0.watermark(),
+                can.rx_fifo_1.watermark(),
+                can.tx_event_fifo.watermark(),
+            ],
+            [4, 3, 4]
+        );
+    }
+
+    #[test]
+    fn strict_validation_rejects_watermarks_beyond_fifo_depth() {
+        mock_can!(Can8);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can8, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().strict_validation = true;
+        can.config().rx_fifo_1.watermark = 4;
+        can.config().tx.tx_event_fifo_watermark = 5;
+        assert!(matches!(
+            can.apply_configuration(),
+            Err(ConfigurationError::WatermarkExceedsDepth {
+                fifo: Capacity::TxEventFifo,
+                watermark: 5,
+                depth: 4,
+            })
+        ));
+        // Nothing is written on failure
+        assert_eq!(watermarks(Can8::regs()), [0, 0, 0]);
+
+        can.config().tx.tx_event_fifo_watermark = 4;
+        let _can = can.finalize_recoverable().unwrap();
+        assert_eq!(watermarks(Can8::regs()), [0, 4, 4]);
+    }
+
+    #[test]
+    fn bound_dedicated_buffers_get_store_buffer_filters() {
+        use embedded_can::{ExtendedId, StandardId};
+        const NDAT1: usize = 0x98;
+
+        mock_can!(Can9);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can9, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+
+        let standard = can
+            .bind_dedicated_buffer::<1>(StandardId::new(0x123).unwrap(), None)
+            .unwrap();
+        let extended = can
+            .bind_dedicated_buffer::<3>(ExtendedId::new(0x1abc_0001).unwrap(), None)
+            .unwrap();
+        assert!(matches!(
+            can.bind_dedicated_buffer::<1>(StandardId::new(0x124).unwrap(), None),
+            Err(BindError::AlreadyBound)
+        ));
+        assert!(matches!(
+            can.bind_dedicated_buffer::<4>(StandardId::new(0x124).unwrap(), None),
+            Err(BindError::OutOfBounds)
+        ));
+        // Failed bindings do not take up filters
+        let mut free = 0;
+        while can.filters_standard().push(Filter::Disabled).is_ok() {
+            free += 1;
+        }
+        assert_eq!(free, 3);
+        assert!(matches!(
+            can.bind_dedicated_buffer::<2>(StandardId::new(0x124).unwrap(), None),
+            Err(BindError::FilterListFull)
+        ));
+        // The buffer stays available after a failed binding
+        let late = can
+            .bind_dedicated_buffer::<2>(ExtendedId::new(0x124).unwrap(), None)
+            .unwrap();
+
+        let mut can = can.finalize_recoverable().unwrap();
+        let regs = Can9::regs();
+        regs.write(NDAT1, 1 << 1);
+        let mut standard = standard;
+        assert!(standard.receive().is_ok());
+        assert_eq!(regs.read(NDAT1), 1 << 1);
+        assert!(matches!(
+            crate::rx_dedicated_buffers::DynRxDedicatedBuffer::receive(
+                &mut can.rx_dedicated_buffers,
+                1
+            ),
+            Err(nb::Error::Other(_))
+        ));
+        let _ = (can, standard, extended, late);
+
+        // The standard filters are followed by the extended ones, see
+        // `SharedMemoryInner`
+        let filters: [u32; 4 + 2 * 3] =
+            // Safety: `memory` was initialized by the construction above.
+            unsafe { core::ptr::read(&memory as *const _ as *const _) };
+        assert_eq!(filters[0], 0x7 << 27 | 0x123 << 16 | 1);
+        assert_eq!(filters[4..6], [0x7 << 29 | 0x1abc_0001, 3]);
+        assert_eq!(filters[6..8], [0x7 << 29 | 0x124, 2]);
+    }
+
+    #[test]
+    fn declared_lengths_are_checked_against_dedicated_buffers() {
+        use crate::rx_dedicated_buffers::ElementTooSmall;
+        use embedded_can::{ExtendedId, StandardId};
+
+        mock_can!(Can12);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can12, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let too_small = ElementTooSmall {
+            declared_max_len: 12,
+            element_capacity: 8,
+        };
+
+        assert!(matches!(
+            can.bind_dedicated_buffer::<0>(StandardId::new(0x10).unwrap(), Some(12)),
+            Err(BindError::ElementTooSmall(error)) if error == too_small
+        ));
+        let bound = can
+            .bind_dedicated_buffer::<0>(StandardId::new(0x10).unwrap(), Some(8))
+            .unwrap();
+
+        let filter = Filter::StoreBuffer {
+            id: StandardId::new(0x11).unwrap(),
+            msg_type: SbMsgType::RxBuffer,
+            offset: 1,
+        };
+        assert!(matches!(
+            can.push_store_buffer_filter(filter, Some(12)),
+            Err(StoreBufferError::ElementTooSmall(error)) if error == too_small
+        ));
+        assert_eq!(can.push_store_buffer_filter(filter, Some(8)).ok(), Some(1));
+        assert_eq!(can.push_store_buffer_filter(filter, None).ok(), Some(2));
+        let filter = ExtFilter::StoreBuffer {
+            id: ExtendedId::new(0x1000).unwrap(),
+            msg_type: SbMsgType::RxBuffer,
+            offset: 2,
+        };
+        assert!(matches!(
+            can.push_store_buffer_ext_filter(filter, Some(12)),
+            Err(StoreBufferError::ElementTooSmall(error)) if error == too_small
+        ));
+        assert_eq!(
+            can.push_store_buffer_ext_filter(filter, Some(8)).ok(),
+            Some(0)
+        );
+        for _ in 0..3 {
+            can.filters_extended().push(ExtFilter::Disabled).ok();
+        }
+        assert!(matches!(
+            can.push_store_buffer_ext_filter(filter, None),
+            Err(StoreBufferError::FilterListFull(ExtFilter::StoreBuffer {
+                offset: 2,
+                ..
+            }))
+        ));
+
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(can.rx_dedicated_buffers.element_capacity(), 8);
+        let _ = (bound, Can12::regs());
+    }
+
+    #[test]
+    fn dedicated_buffers_are_assigned_within_bounds() {
+        use embedded_can::{ExtendedId, StandardId};
+
+        mock_can!(Can40);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can40, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+
+        let standard = StandardId::new(0x321).unwrap();
+        let extended = ExtendedId::new(0x1000_0abc).unwrap();
+        assert!(matches!(
+            can.assign_dedicated_buffer(0, standard, None),
+            Ok(0)
+        ));
+        assert!(matches!(
+            can.assign_dedicated_buffer(3, extended, Some(8)),
+            Ok(0)
+        ));
+        // Several IDs may share a buffer
+        assert!(matches!(
+            can.assign_dedicated_buffer(3, standard, None),
+            Ok(1)
+        ));
+        assert!(matches!(
+            can.assign_dedicated_buffer(4, standard, None),
+            Err(BindError::OutOfBounds)
+        ));
+        let bound = can.bind_dedicated_buffer::<1>(standard, None).unwrap();
+        assert!(matches!(
+            can.assign_dedicated_buffer(1, extended, None),
+            Err(BindError::AlreadyBound)
+        ));
+
+        let beyond = Filter::StoreBuffer {
+            id: standard,
+            msg_type: SbMsgType::RxBuffer,
+            offset: 4,
+        };
+        assert_eq!(
+            can.push_store_buffer_filter(beyond, None),
+            Err(StoreBufferError::OutOfBounds(beyond))
+        );
+        let beyond = ExtFilter::StoreBuffer {
+            id: extended,
+            msg_type: SbMsgType::DebugA,
+            offset: 4,
+        };
+        assert_eq!(
+            can.push_store_buffer_ext_filter(beyond, None),
+            Err(StoreBufferError::OutOfBounds(beyond))
+        );
+        // Rejected filters are not pushed
+        assert_eq!(
+            (can.filters_standard().len(), can.filters_extended().len()),
+            (3, 1)
+        );
+
+        let _can = can.finalize_recoverable().unwrap();
+        let _ = (bound, Can40::regs());
+    }
+
+    #[test]
+    fn nominal_timing_is_encoded_for_the_core_revision() {
+        use crate::config::BitTiming;
+        use mcan_core::CoreRevision;
+
+        const NBTP: usize = 0x1c;
+        mock_can!(Can13 {
+            const REVISION: CoreRevision = CoreRevision::V3_0;
+        });
+        mock_can!(Can14);
+        let mut memory = SharedMemory::<Caps>::new();
+
+        // 16 time quanta at the 8 MHz clock of the mock
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can13, _, _>::new(500.kHz(), dependencies, &mut memory);
+        let _ = can.unwrap().finalize_recoverable().unwrap();
+        assert_eq!(Can13::regs().read(NBTP), 0x0000_0a33);
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can14, _, _>::new(500.kHz(), dependencies, &mut memory);
+        let _ = can.unwrap().finalize_recoverable().unwrap();
+        assert_eq!(Can14::regs().read(NBTP), 0x0600_0a03);
+
+        // Phase segment 2 beyond the 4 bits of TSEG2
+        let long_phase_seg_2 = BitTiming {
+            sjw: 4,
+            phase_seg_1: 11,
+            phase_seg_2: 20,
+            bitrate: 250.kHz(),
+        };
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can13, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().nominal_timing = long_phase_seg_2;
+        assert!(matches!(
+            can.finalize_recoverable(),
+            Err(FinalizeError {
+                error: ConfigurationError::BitTiming(BitTimingError::PhaseSeg2OutOfRange(_)),
+                ..
+            })
+        ));
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can14, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().nominal_timing = long_phase_seg_2;
+        assert!(can.finalize_recoverable().is_ok());
+    }
+
+    #[test]
+    fn powered_down_bus_wakes_into_normal_operation() {
+        const CCCR: usize = 0x18;
+        const INIT: u32 = 1 << 0;
+        const CSA: u32 = 1 << 3;
+        const CSR: u32 = 1 << 4;
+        const FDOE: u32 = 1 << 8;
+        mock_can!(Can43);
+        let regs = Can43::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory).with_can_clock(80.MHz());
+        let mut can =
+            CanConfigurable::<Can43, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().mode = Mode::Fd {
+            allow_bit_rate_switching: false,
+            data_phase_timing: crate::config::BitTiming::new(1.MHz()),
+        };
+        let can = can.finalize_recoverable().unwrap();
+        // Unlike the hardware, the mock keeps CCE
+        let cccr = regs.read(CCCR);
+        assert_eq!(cccr & (FDOE | INIT), FDOE);
+
+        // The configuration is kept
+        let parked = can.into_powered_down();
+        assert_eq!(regs.read(CCCR), cccr | CSR);
+        assert!(parked.poll_clock_stopped().is_err());
+        regs.write(CCCR, cccr | CSR | CSA | INIT);
+        assert!(parked.poll_clock_stopped().is_ok());
+
+        // The peripheral withdraws CSA once the clocks run again
+        regs.write(CCCR, cccr | CSR | INIT);
+        let can = parked.wake();
+        assert_eq!(regs.read(CCCR), cccr);
+        assert!(can.aux.is_operational());
+        can.aux.request_clock_stop();
+        can.aux.clear_clock_stop_request();
+        assert_eq!(regs.read(CCCR), cccr);
+    }
+
+    #[test]
+    fn finalizing_into_initialization_mode_is_recoverable() {
+        const CCCR: usize = 0x18;
+        mock_can!(Can42);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can42, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().timestamp.prescaler = 0;
+        let Err(FinalizeError { mut can, error }) = can.finalize_initialized_recoverable() else {
+            panic!("the prescaler is rejected");
+        };
+        assert!(matches!(
+            error,
+            ConfigurationError::InvalidTimeStampPrescaler
+        ));
+        can.config().timestamp.prescaler = 1;
+        let can = can.finalize_initialized_recoverable().unwrap();
+        assert!(!can.aux.is_operational());
+        assert_eq!(Can42::regs().read(CCCR) & 1, 1);
+    }
+
+    #[test]
+    fn applied_bit_timing_is_read_back() {
+        use crate::config::BitTiming;
+
+        const DBTP: usize = 0x0c;
+        mock_can!(Can38);
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory).with_can_clock(80.MHz());
+        let can = CanConfigurable::<Can38, _, _>::new(500.kHz(), dependencies, &mut memory);
+        let can = can.unwrap().finalize_recoverable().unwrap();
+        let nominal = can.aux.nominal_bit_timing();
+        assert_eq!(nominal.bitrate, 500.kHz::<1, 1>());
+        assert_eq!(
+            nominal.time_quantum_ns,
+            u32::from(nominal.timing.prescaler) * 1_000 / 80
+        );
+        assert_eq!(can.aux.data_bit_timing(), None);
+
+        let mut can = can.configure();
+        can.config().mode = Mode::Fd {
+            allow_bit_rate_switching: true,
+            data_phase_timing: BitTiming {
+                sjw: 4,
+                phase_seg_1: 15,
+                phase_seg_2: 4,
+                bitrate: 2.MHz(),
+            },
+        };
+        let can = can.finalize_recoverable().unwrap();
+        let data = can.aux.data_bit_timing().unwrap();
+        assert_eq!(
+            (
+                data.timing.sjw,
+                data.timing.phase_seg_1,
+                data.timing.phase_seg_2
+            ),
+            (4, 15, 4)
+        );
+        assert_eq!(data.timing.prescaler, 2);
+        assert_eq!(data.bitrate, 2.MHz::<1, 1>());
+        assert_eq!(data.sample_point_permille, 800);
+        assert_eq!(data.time_quantum_ns, 25);
+        assert_eq!(Can38::regs().read(DBTP), 0x0001_0e33);
+        assert_eq!(can.aux.nominal_bit_timing(), nominal);
+    }
+
+    #[test]
+    fn global_filter_is_programmed() {
+        use crate::config::{GlobalFilterConfig, NonMatchingAction};
+
+        const GFC: usize = 0x80;
+        mock_can!(Can31);
+        let mut memory = SharedMemory::<Caps>::new();
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can31, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let _can = can.finalize_recoverable().unwrap();
+        // Non-matching frames rejected, remote frames filtered
+        assert_eq!(Can31::regs().read(GFC), 0x0000_0028);
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can31, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().global_filter = GlobalFilterConfig {
+            non_matching_standard: NonMatchingAction::StoreFifo0,
+            non_matching_extended: NonMatchingAction::StoreFifo1,
+            reject_remote_standard: true,
+            reject_remote_extended: false,
+        };
+        let _can = can.finalize_recoverable().unwrap();
+        assert_eq!(Can31::regs().read(GFC), 0x0000_0006);
+    }
+
+    #[test]
+    fn extended_id_and_mask_is_programmed() {
+        use embedded_can::ExtendedId;
+
+        const XIDAM: usize = 0x90;
+        mock_can!(Can41);
+        let mut memory = SharedMemory::<Caps>::new();
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can41, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(Can41::regs().read(XIDAM), 0x1fff_ffff);
+        assert_eq!(can.aux.extended_id_and_mask(), ExtendedId::MAX);
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can41, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        // J1939 without the source address
+        let mask = ExtendedId::new(0x1fff_ff00).unwrap();
+        can.config().extended_id_and_mask = mask;
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(Can41::regs().read(XIDAM), 0x1fff_ff00);
+        assert_eq!(can.aux.extended_id_and_mask(), mask);
+    }
+
+    #[test]
+    fn transmitter_delay_compensation_is_programmed_for_the_core_revision() {
+        use crate::config::{BitTiming, Mode, TransmitterDelayCompensation};
+        use mcan_core::CoreRevision;
+
+        const DBTP: usize = 0x0c;
+        const TDCR: usize = 0x48;
+        mock_can!(Can28);
+        mock_can!(Can29 {
+            const REVISION: CoreRevision = CoreRevision::V3_0;
+        });
+        let mut memory = SharedMemory::<Caps>::new();
+        // 2 Mbit/s with 4 time quanta at the 8 MHz clock of the mock
+        let fd = Mode::Fd {
+            allow_bit_rate_switching: true,
+            data_phase_timing: BitTiming {
+                sjw: 1,
+                phase_seg_1: 2,
+                phase_seg_2: 1,
+                bitrate: 2.MHz(),
+            },
+        };
+        let compensation = |offset, window| Some(TransmitterDelayCompensation { offset, window });
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can28, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().mode = fd;
+        can.config().transmitter_delay_compensation = compensation(3, 2);
+        let _can = can.finalize_recoverable().unwrap();
+        assert_eq!(Can28::regs().read(DBTP), 0x0080_0100);
+        assert_eq!(Can28::regs().read(TDCR), 0x0000_0302);
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can29, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().mode = fd;
+        can.config().transmitter_delay_compensation = compensation(3, 0);
+        let _can = can.finalize_recoverable().unwrap();
+        assert_eq!(Can29::regs().read(DBTP), 0x0380_0100);
+        assert_eq!(Can29::regs().read(TDCR), 0);
+
+        // M_CAN 3.0 has no filter, and later revisions a 7 bit offset
+        for (offset, window) in [(3, 1), (32, 0)] {
+            let dependencies = mock::Dependencies::new(&memory);
+            let mut can =
+                CanConfigurable::<Can29, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+            can.config().mode = fd;
+            can.config().transmitter_delay_compensation = compensation(offset, window);
+            assert!(matches!(
+                can.finalize_recoverable(),
+                Err(FinalizeError {
+                    error: ConfigurationError::TransmitterDelayCompensationOutOfRange(_),
+                    ..
+                })
+            ));
+        }
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can28, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().mode = fd;
+        can.config().transmitter_delay_compensation = compensation(128, 0);
+        assert!(can.finalize_recoverable().is_err());
+    }
+
+    #[test]
+    fn message_ram_beyond_the_eligible_length_is_rejected() {
+        mock_can!(Can10);
+        let size = core::mem::size_of::<SharedMemory<Caps>>();
+        let mut memory = SharedMemory::<Caps>::new();
+
+        let dependencies = mock::Dependencies::new(&memory).with_message_ram_len(size - 1);
+        assert!(matches!(
+            CanConfigurable::<Can10, _, _>::new(500.kHz(), dependencies, &mut memory),
+            Err(ConstructionError::MessageRamExceeded {
+                required,
+                available
+            }) if required == size && available == size - 1
+        ));
+
+        let dependencies = mock::Dependencies::new(&memory).with_message_ram_len(size);
+        let can = CanConfigurable::<Can10, _, _>::new(500.kHz(), dependencies, &mut memory);
+        assert!(can.is_ok());
+        drop(can);
+
+        // Without a length, only addressability is checked
+        let dependencies = mock::Dependencies::new(&memory);
+        assert!(CanConfigurable::<Can10, _, _>::new(500.kHz(), dependencies, &mut memory).is_ok());
+        // Construction left the peripheral in configuration mode
+        assert_eq!(Can10::regs().read(0x18) & 1, 1);
+    }
+
+    #[test]
+    fn message_ram_outside_the_addressable_window_is_rejected() {
+        mock_can!(Can39);
+        let size = core::mem::size_of::<SharedMemory<Caps>>();
+        let mut memory = SharedMemory::<Caps>::new();
+        let start = &memory as *const _ as usize;
+        let mut construct = |eligible_start: usize| {
+            let dependencies =
+                mock::Dependencies::new(&memory).with_message_ram_start(eligible_start);
+            CanConfigurable::<Can39, _, _>::new(500.kHz(), dependencies, &mut memory).map(drop)
+        };
+
+        assert!(matches!(
+            construct(start + 4),
+            Err(ConstructionError::Memory(MemoryError::NotAddressable))
+        ));
+        assert!(matches!(
+            construct(start - 0x1_0000),
+            Err(ConstructionError::Memory(MemoryError::NotAddressable))
+        ));
+        assert!(matches!(
+            construct(start - 2),
+            Err(ConstructionError::Memory(MemoryError::Misaligned))
+        ));
+        // Ending one word beyond the window
+        assert!(matches!(
+            construct(start + size - 0x1_0004),
+            Err(ConstructionError::Memory(MemoryError::TooLarge {
+                size: 0x1_0004,
+                max: 0x1_0000
+            }))
+        ));
+        // Ending at the end of the window
+        assert!(construct(start + size - 0x1_0000).is_ok());
+        assert_eq!(Can39::regs().read(0x18) & 1, 1);
+    }
+
+    #[test]
+    fn clocks_are_checked_against_each_other() {
+        mock_can!(Can23);
+        let mut memory = SharedMemory::<Caps>::new();
+
+        let dependencies = mock::Dependencies::new(&memory)
+            .with_can_clock(8.MHz())
+            .with_host_clock(4.MHz());
+        assert!(matches!(
+            CanConfigurable::<Can23, _, _>::new(500.kHz(), dependencies, &mut memory),
+            Err(ConstructionError::ClockConfiguration { host, can })
+                if host == 4.MHz::<1, 1>() && can == 8.MHz::<1, 1>()
+        ));
+
+        let dependencies = mock::Dependencies::new(&memory).with_can_clock(0.Hz());
+        assert!(matches!(
+            CanConfigurable::<Can23, _, _>::new(500.kHz(), dependencies, &mut memory),
+            Err(ConstructionError::ClockConfiguration { can, .. }) if can.raw() == 0
+        ));
+        // Rejected before touching the peripheral
+        assert_eq!(Can23::regs().read(0x18), 0);
+
+        // Equal clocks are fine
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can23, _, _>::new(500.kHz(), dependencies, &mut memory)
+            .unwrap()
+            .finalize_recoverable()
+            .unwrap();
+        assert_eq!(can.aux.clocks(), (8.MHz(), 8.MHz()));
+    }
+
+    #[test]
+    fn filter_list_sizes_follow_the_pushed_filters() {
+        mock_can!(Can25);
+        const SIDFC: usize = 0x84;
+        const XIDFC: usize = 0x88;
+        let regs = Can25::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let list_sizes = || (regs.read(SIDFC) >> 16 & 0xff, regs.read(XIDFC) >> 16 & 0x7f);
+
+        for (standard, extended) in [(0, 0), (1, 0), (0, 4), (3, 2), (4, 4)] {
+            let dependencies = mock::Dependencies::new(&memory);
+            let mut can =
+                CanConfigurable::<Can25, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+            // Nothing is scanned until the configuration is applied
+            assert_eq!(list_sizes(), (0, 0));
+            for _ in 0..standard {
+                can.filters_standard().push(Filter::Disabled).ok().unwrap();
+            }
+            for _ in 0..extended {
+                can.filters_extended()
+                    .push(ExtFilter::Disabled)
+                    .ok()
+                    .unwrap();
+            }
+            let sidfc_start = regs.read(SIDFC) & 0xffff;
+            let _can = can.finalize_recoverable().unwrap();
+            assert_eq!(list_sizes(), (standard, extended));
+            // The start addresses are kept
+            assert_eq!(regs.read(SIDFC) & 0xffff, sidfc_start);
+        }
+
+        // Filters pushed in a reconfiguration are scanned afterwards
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can25, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.filters_standard().push(Filter::Disabled).ok().unwrap();
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(list_sizes(), (1, 0));
+        let mut can = can.configure();
+        can.filters_standard().push(Filter::Disabled).ok().unwrap();
+        can.filters_extended()
+            .push(ExtFilter::Disabled)
+            .ok()
+            .unwrap();
+        assert_eq!(can.filters_standard().len(), 2);
+        let _can = can.finalize_recoverable().unwrap();
+        assert_eq!(list_sizes(), (2, 1));
+    }
+
+    #[test]
+    fn unowned_registers_are_borrowed_without_unsafe() {
+        use crate::reg::{crel::CREL_SPEC, cust::CUST_SPEC, test::TEST_SPEC};
+        mock_can!(Can26);
+        const CREL: usize = 0x00;
+        const CUST: usize = 0x08;
+        const TEST: usize = 0x10;
+        let regs = Can26::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can26, _, _>::new(500.kHz(), dependencies, &mut memory)
+            .unwrap()
+            .finalize_recoverable()
+            .unwrap();
+
+        regs.write(CREL, 0x3210_0000);
+        regs.write(TEST, 1 << 7);
+        assert_eq!(
+            can.aux.borrow_register::<CREL_SPEC>().read().rel().bits(),
+            3
+        );
+        assert!(can
+            .aux
+            .borrow_register::<TEST_SPEC>()
+            .read()
+            .rx()
+            .bit_is_set());
+
+        regs.start_recording();
+        can.aux
+            .borrow_register::<CUST_SPEC>()
+            .write_bits(0xdead_beef);
+        assert_eq!(regs.take_recording(), [(CUST, 0xdead_beef)]);
+        assert_eq!(
+            can.aux.borrow_register::<CUST_SPEC>().read().bits(),
+            0xdead_beef
+        );
+    }
+
+    #[test]
+    fn bitrates_beyond_the_shortest_bit_time_are_rejected() {
+        mock_can!(Can24);
+        let mut memory = SharedMemory::<Caps>::new();
+
+        // 5 time quanta of 125 ns at the 8 MHz clock of the mock
+        let dependencies = mock::Dependencies::new(&memory);
+        assert!(matches!(
+            CanConfigurable::<Can24, _, _>::new(2.MHz(), dependencies, &mut memory),
+            Err(ConstructionError::UnachievableBitrate { bitrate, can })
+                if bitrate == 2.MHz::<1, 1>() && can == 8.MHz::<1, 1>()
+        ));
+        assert_eq!(Can24::regs().read(0x18), 0);
+
+        let dependencies = mock::Dependencies::new(&memory);
+        assert!(CanConfigurable::<Can24, _, _>::new(1600.kHz(), dependencies, &mut memory).is_ok());
+    }
+
+    #[test]
+    fn every_event_is_pollable_without_interrupts() {
+        use crate::tx_buffers::DynTx;
+        use crate::tx_event_fifo::DynTxEventFifo;
+
+        mock_can!(Can11);
+        const IR: usize = 0x50;
+        const PSR: usize = 0x44;
+        const TXFQS: usize = 0xc4;
+        const TXBRP: usize = 0xcc;
+        const TXBAR: usize = 0xd0;
+        const TXBTO: usize = 0xd8;
+        const TXEFS: usize = 0xf4;
+        let regs = Can11::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can11, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().tx.tx_event_fifo_watermark = 2;
+        let mut can = can.finalize_recoverable().unwrap();
+
+        // Transmission through the queue
+        regs.write(TXFQS, 2 << 16 | 2 << 8);
+        assert!(can.tx.is_queue_empty());
+        let message = crate::message::tx::MessageBuilder {
+            id: embedded_can::StandardId::new(0x10).unwrap().into(),
+            frame_type: crate::message::tx::FrameType::Classic(
+                crate::message::tx::ClassicFrameType::Data(&[]),
+            ),
+            store_tx_event: None,
+        }
+        .build()
+        .unwrap();
+        let token = can.tx.transmit_queued_indexed(message).unwrap();
+        assert!(!can.tx.is_queue_empty());
+        // A pending dedicated buffer does not count
+        regs.write(TXBAR, 0);
+        regs.write(TXBRP, 1 << 1);
+        assert!(can.tx.is_queue_empty());
+        regs.write(TXBTO, 1 << token.index());
+        assert_eq!(can.tx.take_completions().buffers().0, 1 << token.index());
+
+        // Transmit events
+        regs.write(TXEFS, 1);
+        assert!(!can.tx_event_fifo.watermark_reached());
+        regs.write(TXEFS, 2);
+        assert!(can.tx_event_fifo.watermark_reached());
+        assert!(!can.tx_event_fifo.is_full() && !can.tx_event_fifo.element_lost());
+        regs.write(TXEFS, 1 << 25 | 1 << 24 | 4);
+        assert!(can.tx_event_fifo.is_full() && can.tx_event_fifo.element_lost());
+
+        // Bus off and recovery
+        regs.write(PSR, 1 << 7);
+        assert!(can.aux.is_bus_off());
+        can.aux.operational_mode();
+        regs.write(PSR, 0);
+        assert!(!can.aux.is_bus_off());
+
+        // Events without a counterpart are visible in the flags
+        regs.write(IR, 1 << 16);
+        assert!(can
+            .interrupt_configuration
+            .peek_all_flags()
+            .contains(Interrupt::TimestampWraparound));
+        assert_eq!(regs.read(IR), 1 << 16);
+    }
+
+    /// Drives every component and returns the resulting registers, except
+    /// for the Message RAM addresses
+    fn exercise<Id: mcan_core::CanId>(regs: &mock::Registers) -> [u32; 64] {
+        use crate::message::tx;
+        use crate::rx_dedicated_buffers::DynRxDedicatedBuffer;
+        use crate::tx_buffers::DynTx;
+        use crate::tx_event_fifo::DynTxEventFifo;
+
+        const IR: usize = 0x50;
+        const PSR: usize = 0x44;
+        const RXF0S: usize = 0xa4;
+        const RXF1S: usize = 0xb4;
+        const NDAT1: usize = 0x98;
+        const TXFQS: usize = 0xc4;
+        const TXEFS: usize = 0xf4;
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Id, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut can = can.finalize_recoverable().unwrap();
+
+        let interrupts = can
+            .interrupts
+            .split(
+                [Interrupt::RxFifo0NewMessage, Interrupt::BusOff]
+                    .into_iter()
+                    .collect(),
+            )
+            .unwrap();
+        let interrupts = can.interrupt_configuration.enable_line_1(interrupts);
+        regs.write(IR, 1 << 25);
+        assert!(interrupts.iter_flagged().eq([Interrupt::BusOff]));
+
+        regs.write(RXF0S, 2 << 8 | 1);
+        regs.write(RXF1S, 3 << 8 | 1);
+        can.rx_fifo_0.receive().unwrap();
+        can.rx_fifo_1.receive().unwrap();
+        regs.write(NDAT1, 1 << 2);
+        can.rx_dedicated_buffers.receive(2).unwrap();
+
+        regs.write(TXFQS, 3 << 16 | 3 << 8);
+        let message = tx::MessageBuilder {
+            id: embedded_can::StandardId::new(0x10).unwrap().into(),
+            frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(&[])),
+            store_tx_event: Some(1),
+        }
+        .build()
+        .unwrap();
+        can.tx.transmit_queued_indexed(message).unwrap();
+        can.tx.transmit_dedicated(1, message).unwrap();
+        regs.write(TXEFS, 1 << 8 | 1);
+        can.tx_event_fifo.pop().unwrap();
+
+        regs.write(PSR, 1 << 7);
+        assert!(can.aux.is_bus_off());
+
+        let mut registers = regs.snapshot();
+        // Start addresses of the Message RAM sections
+        for register in &mut registers[0x80 / 4..0xf8 / 4] {
+            *register &= !0xfffc;
+        }
+        registers
+    }
+
+    #[test]
+    fn register_block_offset_is_applied_to_every_component() {
+        mock_can!(Can17 {
+            const REGISTER_BLOCK_OFFSET: usize = 0x100;
+        });
+        mock_can!(Can18);
+        let registers = exercise::<Can17>(Can17::regs());
+        assert_eq!(registers, exercise::<Can18>(Can18::regs()));
+        // The core is in normal operation and has been written to
+        assert_eq!(registers[0x18 / 4] & 1, 0);
+        assert_ne!(registers[0xd0 / 4], 0);
+        // The vendor registers before the core are untouched
+        assert_eq!(Can17::wrapper_regs().snapshot(), [0; 64]);
+    }
+
+    const CCCR: usize = 0x18;
+    const TEST: usize = 0x10;
+    const INIT: u32 = 1;
+    /// Not cleared along with INIT by the mock
+    const CCE: u32 = 1 << 1;
+    const MON: u32 = 1 << 5;
+    const TEST_MODE: u32 = 1 << 7;
+    const LBCK: u32 = 1 << 4;
+    /// TX pin driven dominant, as left by a pin test
+    const TX_DOMINANT: u32 = 2 << 5;
+
+    #[test]
+    fn loopback_is_enabled_temporarily() {
+        mock_can!(Can19);
+        let regs = Can19::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can19, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut can = can.finalize_recoverable().unwrap();
+        regs.write(TEST, TX_DOMINANT);
+        let cccr = regs.read(CCCR);
+
+        let result = can.aux.with_loopback(|aux| {
+            assert!(aux.is_operational());
+            assert_eq!(regs.read(CCCR) & (TEST_MODE | MON), TEST_MODE | MON);
+            assert_eq!(regs.read(TEST), TX_DOMINANT | LBCK);
+            42
+        });
+        assert!(matches!(result, Ok(42)));
+        assert_eq!(regs.read(TEST), TX_DOMINANT);
+        assert_eq!(regs.read(CCCR) & !CCE, cccr & !CCE);
+
+        regs.write(CCCR, cccr | INIT);
+        let result = can.aux.with_loopback(|_| unreachable!());
+        assert!(matches!(result, Err(ConfigurationError::NotOperational)));
+        assert_eq!(regs.read(CCCR), cccr | INIT);
+    }
+
+    #[test]
+    fn loopback_is_refused_while_transmissions_are_pending() {
+        mock_can!(Can44);
+        const TXBRP: usize = 0xcc;
+        const TXBAR: usize = 0xd0;
+        let regs = Can44::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can44, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut can = can.finalize_recoverable().unwrap();
+        let cccr = regs.read(CCCR);
+
+        // Entering configuration mode would cancel the pending request
+        regs.write(TXBRP, 1 << 2);
+        regs.start_recording();
+        let result = can.aux.with_loopback(|_| unreachable!());
+        assert!(matches!(
+            result,
+            Err(ConfigurationError::TransmissionsPending)
+        ));
+        assert!(regs.take_recording().is_empty());
+        assert_eq!(regs.read(CCCR), cccr);
+        assert_eq!(regs.read(TXBRP), 1 << 2);
+        assert!(matches!(can.tx.is_pending(2), Ok(true)));
+
+        // A request that the peripheral has not added to TXBRP yet
+        regs.write(TXBRP, 0);
+        regs.write(TXBAR, 1 << 3);
+        let result = can.aux.with_loopback(|_| unreachable!());
+        assert!(matches!(
+            result,
+            Err(ConfigurationError::TransmissionsPending)
+        ));
+        assert_eq!(regs.read(CCCR), cccr);
+
+        // Once the transmissions have completed
+        regs.write(TXBAR, 0);
+        assert!(matches!(can.aux.with_loopback(|_| 42), Ok(42)));
+    }
+
+    #[test]
+    fn operation_mode_is_programmed_and_reported() {
+        mock_can!(Can33);
+        const ASM: u32 = 1 << 2;
+        let regs = Can33::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+
+        // Internal loopback
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can33, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().loopback = Loopback::Internal;
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(CCCR) & (TEST_MODE | MON | ASM), TEST_MODE | MON);
+        assert_eq!(regs.read(TEST) & LBCK, LBCK);
+        assert_eq!(can.aux.operation_mode(), OperationMode::ListenOnly);
+        // Bus monitoring takes precedence
+        regs.write(CCCR, regs.read(CCCR) | ASM);
+        assert_eq!(can.aux.operation_mode(), OperationMode::ListenOnly);
+
+        // External loopback, which transmits on the bus
+        let mut can = can.configure();
+        can.config().loopback = Loopback::External;
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(CCCR) & (TEST_MODE | MON), TEST_MODE);
+        assert_eq!(regs.read(TEST) & LBCK, LBCK);
+        assert_eq!(can.aux.operation_mode(), OperationMode::Normal);
+        // Combined with bus monitoring
+        let mut can = can.configure();
+        can.config().operation = OperationMode::ListenOnly;
+        let _can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(CCCR) & (TEST_MODE | MON), TEST_MODE | MON);
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can33, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().operation = OperationMode::Restricted;
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(CCCR) & (TEST_MODE | MON | ASM), ASM);
+        assert_eq!(regs.read(TEST) & LBCK, 0);
+        assert_eq!(can.aux.operation_mode(), OperationMode::Restricted);
+
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can33, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(CCCR) & (TEST_MODE | MON | ASM), 0);
+        assert_eq!(can.aux.operation_mode(), OperationMode::Normal);
+    }
+
+    #[test]
+    fn bitrate_scan_listens_with_each_candidate() {
+        use crate::config::BitTiming;
+
+        mock_can!(Can34);
+        const NBTP: usize = 0x1c;
+        const PSR: usize = 0x44;
+        const NO_CHANGE: u32 = 7;
+        let regs = Can34::regs();
+        let prescaler = || (regs.read(NBTP) >> 16 & 0x1ff) + 1;
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can =
+            CanConfigurable::<Can34, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        // No exact prescaler for 3 Mbit/s at the 8 MHz clock of the mock
+        let candidates = [3.MHz(), 125.kHz(), 250.kHz()].map(BitTiming::new);
+
+        regs.write(PSR, NO_CHANGE);
+        let mut scan = BitrateScan::new(can, &candidates, 2);
+        assert_eq!(scan.poll(), ScanState::Listening { index: 1 });
+        assert_eq!(prescaler(), 4);
+        assert_eq!(regs.read(CCCR) & (INIT | MON), MON);
+        assert_eq!(scan.poll(), ScanState::Listening { index: 2 });
+        assert_eq!(prescaler(), 2);
+
+        // A frame received without errors
+        regs.write(PSR, 0);
+        assert_eq!(scan.poll(), ScanState::Found { index: 2 });
+        regs.write(PSR, NO_CHANGE);
+        assert_eq!(scan.poll(), ScanState::Found { index: 2 });
+
+        let mut can = scan.release();
+        assert_eq!(can.config().operation, OperationMode::Normal);
+        assert_eq!(can.config().nominal_timing.bitrate, 500.kHz::<1, 1>());
+        can.config().nominal_timing = candidates[2];
+        let can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(CCCR) & (INIT | MON), 0);
+        assert_eq!(prescaler(), 2);
+
+        let mut scan = BitrateScan::new(can.configure(), &candidates, 1);
+        assert_eq!(scan.poll(), ScanState::Listening { index: 2 });
+        assert_eq!(scan.poll(), ScanState::NotFound);
+        assert_eq!(scan.poll(), ScanState::NotFound);
+        let _can = scan.release().finalize_recoverable().unwrap();
+    }
+
+    #[test]
+    fn loopback_is_left_if_the_closure_panics() {
+        extern crate std;
+
+        mock_can!(Can20);
+        let regs = Can20::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can20, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        // Monitoring as part of a configured loopback is kept
+        can.config().loopback = Loopback::Internal;
+        let mut can = can.finalize_recoverable().unwrap();
+        let (cccr, test) = (regs.read(CCCR), regs.read(TEST));
+        assert_eq!((cccr & MON, test & LBCK), (MON, LBCK));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            can.aux
+                .with_loopback(|_| panic!("self-test failed"))
+                .unwrap();
+        }));
+        assert!(result.is_err());
+        assert_eq!(regs.read(CCCR) & !CCE, cccr & !CCE);
+        assert_eq!(regs.read(CCCR) & INIT, 0);
+        assert_eq!(regs.read(TEST), test);
+    }
+
+    #[test]
+    fn overwrite_mode_requires_a_read_offset_within_the_fifo() {
+        mock_can!(Can21);
+        const RXF0S: usize = 0xa4;
+        const RXF0A: usize = 0xa8;
+        const FOM: u32 = 1 << 31;
+        let regs = Can21::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can21, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().rx_fifo_0.mode = crate::config::RxFifoMode::overwrite();
+        for offset in [None, Some(0), Some(4)] {
+            can.config().rx_fifo_0.overwrite_read_offset = offset;
+            assert!(matches!(
+                can.apply_configuration(),
+                Err(ConfigurationError::InvalidOverwriteReadOffset {
+                    fifo: Capacity::RxFifo0,
+                    offset: o,
+                    depth: 4,
+                }) if o == offset
+            ));
+        }
+        // Ignored in blocking mode
+        can.config().rx_fifo_1.overwrite_read_offset = Some(0);
+        can.config().rx_fifo_0.overwrite_read_offset = Some(1);
+        let mut can = can.finalize_recoverable().unwrap();
+        assert_eq!(regs.read(RXF0C) & FOM, FOM);
+
+        // The oldest message of the full FIFO is skipped
+        regs.write(RXF0S, 1 << 8 | 4);
+        can.rx_fifo_0.receive().unwrap();
+        assert_eq!(regs.read(RXF0A), 2);
+        assert_eq!(can.rx_fifo_0.read_count(), 2);
+    }
+
+    #[test]
+    fn rx_fifo_mode_is_switched_during_operation() {
+        use crate::config::RxFifoMode;
+        mock_can!(Can27);
+        const CCCR: usize = 0x18;
+        const RXF0S: usize = 0xa4;
+        const RXF0A: usize = 0xa8;
+        const RXF1C: usize = 0xb0;
+        const TXBRP: usize = 0xcc;
+        const TXBAR: usize = 0xd0;
+        const FOM: u32 = 1 << 31;
+        let regs = Can27::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can = CanConfigurable::<Can27, _, _>::new(500.kHz(), dependencies, &mut memory)
+            .unwrap()
+            .finalize_recoverable()
+            .unwrap();
+
+        regs.start_recording();
+        can.rx_fifo_0
+            .set_mode(&mut can.aux, RxFifoMode::overwrite(), Some(2))
+            .unwrap();
+        let writes = regs.take_recording();
+        // FOM is written between setting CCE and leaving initialization
+        let fom = writes.iter().position(|&(offset, _)| offset == RXF0C);
+        let cce = writes
+            .iter()
+            .position(|&(offset, value)| offset == CCCR && value & 0b11 == 0b11);
+        assert!(cce < fom);
+        assert_eq!(fom.map(|index| writes[index].1 & FOM), Some(FOM));
+        // Unlike the hardware, the mock does not clear CCE along with INIT
+        assert_eq!(
+            writes.last().map(|&(offset, value)| (offset, value & 1)),
+            Some((CCCR, 0))
+        );
+        assert_eq!(can.rx_fifo_0.read_offset, Some(2));
+        assert_eq!(can.aux.config.rx_fifo_0.read_offset(), Some(2));
+        // The handle reads with the new offset, skipping the two oldest
+        // messages of the full FIFO
+        regs.write(RXF0S, 1 << 8 | 4);
+        can.rx_fifo_0.receive().unwrap();
+        assert_eq!(regs.read(RXF0A), 3);
+
+        // Invalid offsets and leaving operation change nothing
+        regs.start_recording();
+        assert!(matches!(
+            can.rx_fifo_1
+                .set_mode(&mut can.aux, RxFifoMode::overwrite(), None),
+            Err(ConfigurationError::InvalidOverwriteReadOffset {
+                fifo: Capacity::RxFifo1,
+                offset: None,
+                depth: 4,
+            })
+        ));
+        can.aux.initialization_mode();
+        assert!(matches!(
+            can.rx_fifo_1
+                .set_mode(&mut can.aux, RxFifoMode::overwrite(), Some(1)),
+            Err(ConfigurationError::NotOperational)
+        ));
+        // Only the write of INIT by `initialization_mode`
+        let writes = regs.take_recording();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes.first().map(|&(offset, _)| offset), Some(CCCR));
+        assert_eq!(regs.read(RXF1C) & FOM, 0);
+        assert_eq!(can.rx_fifo_1.read_offset, None);
+        assert_eq!(can.aux.config.rx_fifo_1.read_offset(), None);
+
+        // Entering configuration mode would cancel the pending request
+        can.aux.operational_mode();
+        regs.write(TXBRP, 1);
+        regs.start_recording();
+        assert!(matches!(
+            can.rx_fifo_1
+                .set_mode(&mut can.aux, RxFifoMode::overwrite(), Some(1)),
+            Err(ConfigurationError::TransmissionsPending)
+        ));
+        assert!(regs.take_recording().is_empty());
+        assert_eq!(regs.read(TXBRP), 1);
+        assert_eq!(regs.read(RXF1C) & FOM, 0);
+        assert_eq!(can.rx_fifo_1.read_offset, None);
+        // Also before the peripheral has added the request to TXBRP
+        regs.write(TXBRP, 0);
+        regs.write(TXBAR, 1);
+        assert!(matches!(
+            can.rx_fifo_1
+                .set_mode(&mut can.aux, RxFifoMode::overwrite(), Some(1)),
+            Err(ConfigurationError::TransmissionsPending)
+        ));
+        assert_eq!(regs.read(RXF1C) & FOM, 0);
+        regs.write(TXBAR, 0);
+
+        can.rx_fifo_0
+            .set_mode(&mut can.aux, RxFifoMode::blocking(), None)
+            .unwrap();
+        assert_eq!(regs.read(RXF0C) & FOM, 0);
+        assert_eq!(can.rx_fifo_0.read_offset, None);
+        assert_eq!(can.aux.config.rx_fifo_0.read_offset(), None);
+    }
+
+    #[test]
+    fn reconfiguration_report_is_assembled_from_the_recorded_state() {
+        const INTERNAL: u32 = 0b01;
+        const EXTERNAL: u32 = 0b10;
+        let guard = |tscc, activity| ReconfigurationGuard {
+            tscc,
+            nbtp: 0x0600_0a03,
+            timestamp: 0xfff0,
+            activity,
+            rx_fifo_levels: [1, 3],
+        };
+        assert_eq!(
+            guard(INTERNAL, Activity::Receiver).report(INTERNAL, 0x0600_0a03, 0x0010),
+            ReconfigurationReport {
+                duration_ticks: Some(0x20),
+                traffic_present: true,
+                discarded_rx_fifo_messages: [1, 3],
+            }
+        );
+        let report = |guard: ReconfigurationGuard, tscc, nbtp| {
+            let report = guard.report(tscc, nbtp, 0x0010);
+            (report.duration_ticks, report.traffic_present)
+        };
+        // The tick length of the internal counter changed
+        assert_eq!(
+            report(guard(INTERNAL, Activity::Idle), INTERNAL, 0x0600_0a07),
+            (None, false)
+        );
+        assert_eq!(
+            report(
+                guard(INTERNAL, Activity::Transmitter),
+                INTERNAL | 1 << 16,
+                0x0600_0a03
+            ),
+            (None, true)
+        );
+        // The external counter does not depend on the bit timing
+        assert_eq!(
+            report(guard(EXTERNAL, Activity::Synchronizing), EXTERNAL, 0),
+            (Some(0x20), false)
+        );
+        assert_eq!(
+            report(guard(0, Activity::Idle), 0, 0x0600_0a03),
+            (None, false)
+        );
+    }
+
+    #[test]
+    fn reconfiguration_is_reported_on_finalization() {
+        mock_can!(Can22);
+        const TSCV: usize = 0x24;
+        const PSR: usize = 0x44;
+        const RXF0S: usize = 0xa4;
+        const ACT_RECEIVER: u32 = 0b10 << 3;
+        let regs = Can22::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can22, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        can.config().timestamp.select = crate::config::TimeStampSelect::INC;
+        let (can, report) = can.finalize_with_report().unwrap();
+        assert_eq!(report, None);
+
+        regs.write(TSCV, 100);
+        regs.write(PSR, ACT_RECEIVER);
+        regs.write(RXF0S, 2);
+        let can = can.configure();
+        regs.write(TSCV, 250);
+        let (_can, report) = can.finalize_with_report().unwrap();
+        assert_eq!(
+            report,
+            Some(ReconfigurationReport {
+                duration_ticks: Some(150),
+                traffic_present: true,
+                discarded_rx_fifo_messages: [2, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn adapter_implements_the_embedded_can_traits() {
+        use embedded_can::{blocking, nb::Can as _, Error as _, ErrorKind, Frame as _};
+        mock_can!(Can30);
+        const RXF0S: usize = 0xa4;
+        const TXBAR: usize = 0xd0;
+        let regs = Can30::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can30, _, _>::new(500.kHz(), dependencies, &mut memory)
+            .unwrap()
+            .finalize_recoverable()
+            .unwrap();
+        let mut adapter = CanBusAdapter::new(can.tx, can.rx_fifo_0);
+        let id = embedded_can::StandardId::new(0x123).unwrap();
+
+        // Into the first buffer of the queue, after the 2 dedicated ones
+        let frame = Message::new(id, &[1, 2, 3]).unwrap();
+        assert!(matches!(adapter.transmit(&frame), Ok(None)));
+        assert_eq!(regs.read(TXBAR), 1 << 2);
+
+        assert!(matches!(adapter.receive(), Err(nb::Error::WouldBlock)));
+        regs.write(RXF0S, 1);
+        let received = blocking::Can::receive(&mut adapter).unwrap();
+        assert!(matches!(received, Message::Rx(_)));
+        // Received frames can be forwarded, once the first one is sent
+        regs.write(TXBAR, 0);
+        assert!(blocking::Can::transmit(&mut adapter, &received).is_ok());
+
+        let fd = tx::MessageBuilder {
+            id: id.into(),
+            frame_type: tx::FrameType::FlexibleDatarate {
+                payload: &[],
+                bit_rate_switching: false,
+                force_error_state_indicator: false,
+            },
+            store_tx_event: None,
+        }
+        .build()
+        .unwrap();
+        regs.write(TXBAR, 0);
+        match adapter.transmit(&Message::Tx(fd)) {
+            Err(nb::Error::Other(error)) => {
+                assert!(matches!(
+                    error,
+                    AdapterError::Transmit(crate::tx_buffers::Error::FdDisabled)
+                ));
+                assert_eq!(error.kind(), ErrorKind::Other);
+            }
+            _ => panic!("FD frame accepted in classic mode"),
+        }
+        let (_tx, _rx_fifo) = adapter.into_parts();
+    }
 }
@@ -0,0 +1,144 @@
+//! Deprecated entry points tested against their replacements
+//!
+//! Deprecated entry points are kept for one release so that downstream crates
+//! can support both the previous and the current release. These tests verify
+//! that they keep working, and behave like their replacements, until they are
+//! removed. The interrupt entry points of the previous release are unchanged
+//! and are covered as well.
+
+#![allow(deprecated)]
+
+use crate::bus::{Can, CanConfigurable, ConfigurationError, FinalizeError};
+use crate::interrupt::{Interrupt, InterruptLine};
+use crate::messageram::SharedMemory;
+use crate::mock::{self, mock_can, Caps};
+use crate::prelude::*;
+use crate::tx_buffers::{TransmitError, TxBlockedReason};
+use fugit::RateExtU32;
+
+const CCCR: usize = 0x18;
+const TXFQS: usize = 0xc4;
+const TXBAR: usize = 0xd0;
+const IE: usize = 0x54;
+const ILS: usize = 0x58;
+/// Tx FIFO/Queue Full
+const TFQF: u32 = 1 << 21;
+
+fn configurable<Id: mcan_core::CanId>(
+    memory: &mut SharedMemory<Caps>,
+) -> CanConfigurable<'_, Id, mock::Dependencies, Caps> {
+    let dependencies = mock::Dependencies::new(memory);
+    CanConfigurable::new(500.kHz(), dependencies, memory).unwrap()
+}
+
+#[test]
+fn finalize_and_finalize_recoverable_configure_alike() {
+    mock_can!(Can0);
+    mock_can!(Can1);
+    let mut memory_0 = SharedMemory::<Caps>::new();
+    let mut memory_1 = SharedMemory::<Caps>::new();
+    let _old = configurable::<Can0>(&mut memory_0).finalize().unwrap();
+    let _new = configurable::<Can1>(&mut memory_1)
+        .finalize_recoverable()
+        .unwrap();
+    // The Message RAM addresses differ between the two
+    let registers = |snapshot: [u32; 64]| {
+        let mut snapshot = snapshot;
+        snapshot[0x80 / 4..0xf8 / 4].fill(0);
+        snapshot
+    };
+    assert_eq!(
+        registers(Can0::regs().snapshot()),
+        registers(Can1::regs().snapshot())
+    );
+}
+
+#[test]
+fn rejected_configuration_is_reported_by_both() {
+    mock_can!(Can2);
+    mock_can!(Can3);
+    let mut memory = SharedMemory::<Caps>::new();
+    let mut can = configurable::<Can2>(&mut memory);
+    can.config().timestamp.prescaler = 0;
+    assert!(matches!(
+        can.finalize(),
+        Err(ConfigurationError::InvalidTimeStampPrescaler)
+    ));
+    assert_eq!(Can2::regs().read(CCCR) & 1, 1);
+
+    let mut can = configurable::<Can3>(&mut memory);
+    can.config().timestamp.prescaler = 0;
+    let Err(FinalizeError { mut can, error }) = can.finalize_recoverable() else {
+        panic!("invalid configuration was accepted");
+    };
+    assert!(matches!(
+        error,
+        ConfigurationError::InvalidTimeStampPrescaler
+    ));
+    // Both leave the peripheral in configuration mode, but only the new one
+    // hands it back
+    assert_eq!(Can3::regs().read(CCCR) & 1, 1);
+    can.config().timestamp.prescaler = 1;
+    let _: Can<'_, Can3, _, _> = can.finalize_recoverable().unwrap();
+}
+
+#[test]
+fn transmit_queued_and_transmit_queued_indexed_use_the_queue_alike() {
+    mock_can!(Can4);
+    let regs = Can4::regs();
+    let mut memory = SharedMemory::<Caps>::new();
+    let mut can = configurable::<Can4>(&mut memory)
+        .finalize_recoverable()
+        .unwrap();
+
+    regs.write(TXFQS, 3 << 16 | 3 << 8);
+    assert!(matches!(
+        can.tx.transmit_queued(mock::message(0x42)),
+        Ok(())
+    ));
+    assert_eq!(regs.read(TXBAR), 1 << 3);
+    regs.write(TXBAR, 0);
+    let token = can.tx.transmit_queued_indexed(mock::message(0x42)).unwrap();
+    assert_eq!(token.index(), 3);
+    assert_eq!(regs.read(TXBAR), 1 << 3);
+
+    regs.write(TXFQS, TFQF | 2 << 16 | 2 << 8);
+    assert!(matches!(
+        can.tx.transmit_queued(mock::message(0x42)),
+        Err(nb::Error::WouldBlock)
+    ));
+    assert!(matches!(
+        can.tx.transmit_queued_indexed(mock::message(0x42)),
+        Err(TransmitError::WouldBlock(TxBlockedReason::QueueFull))
+    ));
+}
+
+#[test]
+fn interrupts_are_enabled_alike_with_a_static_and_a_dynamic_line() {
+    mock_can!(Can5);
+    mock_can!(Can6);
+    let mut memory_0 = SharedMemory::<Caps>::new();
+    let mut memory_1 = SharedMemory::<Caps>::new();
+    let mut can_0 = configurable::<Can5>(&mut memory_0)
+        .finalize_recoverable()
+        .unwrap();
+    let mut can_1 = configurable::<Can6>(&mut memory_1)
+        .finalize_recoverable()
+        .unwrap();
+    let interrupts = || {
+        [Interrupt::RxFifo0NewMessage, Interrupt::BusOff]
+            .into_iter()
+            .collect()
+    };
+
+    let set_0 = can_0.interrupts.split(interrupts()).unwrap();
+    let _set_0 = can_0.interrupt_configuration.enable_line_1(set_0);
+    let set_1 = can_1.interrupts.split(interrupts()).unwrap();
+    let _set_1 = can_1
+        .interrupt_configuration
+        .enable(set_1, InterruptLine::Line1);
+    for register in [IE, ILS] {
+        assert_ne!(Can5::regs().read(register), 0);
+        assert_eq!(Can5::regs().read(register), Can6::regs().read(register));
+    }
+}
@@ -1,16 +1,21 @@
 //! CAN bus configuration
 
+use crate::reg::gfc::{ANFESELECT_A, ANFSSELECT_A};
 pub use crate::reg::{self, tscc::TSSSELECT_A as TimeStampSelect};
 use core::ops::RangeInclusive;
+use embedded_can::ExtendedId;
 use fugit::HertzU32;
+use mcan_core::CoreRevision;
 
 /// Configuration for the CAN bus
 #[derive(Copy, Clone)]
 pub struct CanConfig {
     /// Run peripheral in CAN-FD mode
     pub mode: Mode,
-    /// Modes of testing
-    pub loopback: bool,
+    /// Loopback test mode, in which transmitted frames are received back
+    pub loopback: Loopback,
+    /// Participation in bus communication
+    pub operation: OperationMode,
     /// Bit timing parameters for everything except the data phase of bit rate
     /// switched FD frames.
     pub nominal_timing: BitTiming,
@@ -22,6 +27,37 @@ pub struct CanConfig {
     pub rx_fifo_1: RxFifoConfig,
     /// Tx configuration
     pub tx: TxConfig,
+    /// Strategy resolving the nominal and data phase bitrates into register
+    /// values
+    ///
+    /// `None` uses the segments configured in each [`BitTiming`] and requires
+    /// an exact prescaler, see [`ExactDivisor`]. A strategy instead only uses
+    /// [`BitTiming::bitrate`] and picks segments and prescaler on its own.
+    pub timing_strategy: Option<&'static dyn TimingStrategy>,
+    /// Reject settings that would otherwise be adjusted silently
+    ///
+    /// Currently this concerns FIFO watermarks exceeding the depth of their
+    /// FIFO, which fail with [`ConfigurationError::WatermarkExceedsDepth`]
+    /// instead of being clamped.
+    ///
+    /// [`ConfigurationError::WatermarkExceedsDepth`]: crate::bus::ConfigurationError::WatermarkExceedsDepth
+    pub strict_validation: bool,
+    /// Transmitter delay compensation in the data phase of bit rate switched
+    /// frames, disabled with `None`
+    ///
+    /// Only used in [`Mode::Fd`].
+    pub transmitter_delay_compensation: Option<TransmitterDelayCompensation>,
+    /// Handling of frames that match no filter, and of remote frames
+    pub global_filter: GlobalFilterConfig,
+    /// Mask ANDed with the ID of every received extended frame before it is
+    /// compared with the extended filters (XIDAM)
+    ///
+    /// It applies globally, to all filters except [`ExtFilter::Range`], e.g.
+    /// to ignore the source address of J1939 frames. With all bits set, the
+    /// default, IDs are compared unchanged.
+    ///
+    /// [`ExtFilter::Range`]: crate::filter::ExtFilter::Range
+    pub extended_id_and_mask: ExtendedId,
 }
 
 /// Denotes a TX related configuration
@@ -30,8 +66,11 @@ pub struct TxConfig {
     /// Denotes TX Event queue fullness required to trigger a corresponding
     /// interrupt
     ///
-    /// Any value greater than 32 is interpreted as 32; 0 means that interrupt
-    /// is disabled
+    /// Any value greater than the depth selected by
+    /// [`Capacities::TxEventFifo`] is interpreted as the depth, see
+    /// [`CanConfig::strict_validation`]; 0 means that interrupt is disabled
+    ///
+    /// [`Capacities::TxEventFifo`]: crate::messageram::Capacities::TxEventFifo
     pub tx_event_fifo_watermark: u8,
     /// TX queue submode
     pub tx_queue_submode: TxQueueMode,
@@ -43,6 +82,7 @@ pub struct TxConfig {
 /// - the time quantum `t_q`, which is a fraction of the peripheral clock
 /// - the number of time quanta in a bit time, determined by `phase_seg_1` and
 ///   `phase_seg_2`
+///
 /// The configurable ranges of the parameters depend on which timing is changed.
 ///
 /// This struct expects *real* values, extra subtractions and additions expected
@@ -51,7 +91,10 @@ pub struct TxConfig {
 /// Default values are:
 /// - swj: 0x4
 /// - phase_seg_1: 0xB
-/// - phase_seg_1: 0x4
+/// - phase_seg_2: 0x4
+///
+/// `sjw` must not exceed `phase_seg_2`; this holds for the defaults and is
+/// checked for both nominal and data phase timing during configuration.
 ///
 /// Default time quanta in a bit time is 16 (phase_seg_1 + phase_seg_2 +
 /// synchronization segment (1))
@@ -96,6 +139,13 @@ pub struct Timestamp {
     pub prescaler: u8,
 }
 
+impl Timestamp {
+    /// Returns `true` if the timestamp counter is not stuck at zero
+    pub fn is_enabled(&self) -> bool {
+        self.select != TimeStampSelect::ZERO
+    }
+}
+
 impl Default for Timestamp {
     fn default() -> Self {
         Self {
@@ -116,12 +166,24 @@ pub enum BitTimingError {
     PhaseSeg2OutOfRange(RangeInclusive<u32>),
     /// Total bit time quanta is outside the wrapped `RangeInclusive`
     BitTimeOutOfRange(RangeInclusive<u32>),
+    /// SJW is larger than phase segment 2
+    ///
+    /// Resynchronization cannot shorten a bit by more than phase segment 2, so
+    /// the hardware requires `sjw <= phase_seg_2`.
+    SjwExceedsPhaseSeg2 {
+        /// Synchronization jump width selected by [`BitTiming`]
+        sjw: u8,
+        /// Phase segment 2 selected by [`BitTiming`]
+        phase_seg_2: u8,
+    },
     /// Prescaler is outside the wrapped `RangeInclusive`
     PrescalerOutOfRange(RangeInclusive<u32>),
     /// No valid prescaler could be found
     ///
     /// The following requirement must be met:
     /// - `can_clock` must be divisible by `bitrate * bit_time_quanta`
+    ///
+    /// `nearest` lists what could be achieved instead.
     NoValidPrescaler {
         /// Provided peripheral clock
         can_clock: HertzU32,
@@ -129,27 +191,147 @@ pub enum BitTimingError {
         bitrate: HertzU32,
         /// Time quanta per bit selected by [`BitTiming`]
         bit_time_quanta: u32,
+        /// Sample point of the segments selected by [`BitTiming`], in per
+        /// mille of the bit time
+        sample_point_permille: u32,
+        /// Achievable alternatives close to the requested timing
+        nearest: NearestTimings,
+    },
+    /// [`BestEffort`] or [`NearestPrescaler`] found no timing within its
+    /// tolerance
+    NoSolutionWithinTolerance {
+        /// Provided peripheral clock
+        can_clock: HertzU32,
+        /// Bitrate requested in [`BitTiming`]
+        bitrate: HertzU32,
+        /// Tolerance of the strategy
+        max_deviation_ppm: u32,
+        /// Closest timing the strategy could achieve beyond its tolerance, or
+        /// `None` if no prescaler is within range at all
+        closest: Option<ClosestTiming>,
     },
 }
 
-/// Valid values of a BitTiming struct
-#[derive(Clone)]
-pub(crate) struct BitTimingRanges {
-    sjw: RangeInclusive<u32>,
-    phase_seg_1: RangeInclusive<u32>,
-    phase_seg_2: RangeInclusive<u32>,
+/// Bitrate achieved by the closest timing outside of a tolerance
+///
+/// Reported in [`BitTimingError::NoSolutionWithinTolerance`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClosestTiming {
+    /// Achieved bitrate, rounded to whole Hz
+    pub bitrate: HertzU32,
+    /// Deviation of the achieved bitrate from the requested one, in parts per
+    /// million
+    pub deviation_ppm: u32,
+}
+
+impl ClosestTiming {
+    /// Timing dividing `can_clock` by `divisor`, the prescaler times the time
+    /// quanta per bit; neither `divisor` nor `target` may be 0
+    fn new(can_clock: u64, target: u64, divisor: u64) -> Self {
+        let achieved = (can_clock + divisor / 2) / divisor;
+        // |f_can / divisor - target| / target, in ppm
+        let exact = u128::from(target) * u128::from(divisor);
+        let deviation_ppm = u128::from(can_clock).abs_diff(exact) * 1_000_000 / exact;
+        Self {
+            bitrate: HertzU32::from_raw(achieved.try_into().unwrap_or(u32::MAX)),
+            deviation_ppm: deviation_ppm.try_into().unwrap_or(u32::MAX),
+        }
+    }
+}
+
+/// Alternatives to a timing that has no valid prescaler
+///
+/// Reported in [`BitTimingError::NoValidPrescaler`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NearestTimings {
+    /// Closest achievable bitrate below the requested one with the same time
+    /// quanta per bit, rounded to whole Hz
+    pub below: Option<HertzU32>,
+    /// Closest achievable bitrate above the requested one with the same time
+    /// quanta per bit, rounded to whole Hz
+    pub above: Option<HertzU32>,
+    /// Time quanta per bit closest to the selected ones that give the
+    /// requested bitrate exactly
+    pub exact_bit_time_quanta: Option<u32>,
+}
+
+impl NearestTimings {
+    fn new(
+        can_clock: HertzU32,
+        bitrate: HertzU32,
+        bit_time_quanta: u32,
+        ranges: &BitTimingRanges,
+    ) -> Self {
+        let can_clock = u64::from(can_clock.to_Hz());
+        let bitrate = u64::from(bitrate.to_Hz());
+        if bitrate == 0 {
+            return Self {
+                below: None,
+                above: None,
+                exact_bit_time_quanta: None,
+            };
+        }
+        let prescaler_min = u64::from(*ranges.prescaler.start());
+        let prescaler_max = u64::from(*ranges.prescaler.end());
+        let bitrate_with = |prescaler: u64| {
+            let divisor = prescaler * u64::from(bit_time_quanta);
+            let rounded = (can_clock + divisor / 2) / divisor;
+            HertzU32::from_raw(rounded as u32)
+        };
+        let f_q = bitrate * u64::from(bit_time_quanta);
+        // A larger prescaler gives a lower bitrate
+        let slower = can_clock.div_ceil(f_q).max(prescaler_min);
+        let faster = (can_clock / f_q).min(prescaler_max);
+        let below = (slower <= prescaler_max).then(|| bitrate_with(slower));
+        let above = (faster >= prescaler_min).then(|| bitrate_with(faster));
+        let exact_bit_time_quanta = ranges
+            .time_quanta_per_bit
+            .clone()
+            .filter(|&quanta| {
+                let f_q = bitrate * u64::from(quanta);
+                can_clock % f_q == 0 && (prescaler_min..=prescaler_max).contains(&(can_clock / f_q))
+            })
+            // Ties go to more time quanta
+            .min_by_key(|&quanta| (quanta.abs_diff(bit_time_quanta), u32::MAX - quanta));
+        Self {
+            below,
+            above,
+            exact_bit_time_quanta,
+        }
+    }
+}
+
+/// Valid values of a [`BitTiming`] for one of the bit timing registers
+///
+/// [`BitTimingRanges::nominal`] and [`BitTimingRanges::data`] describe what
+/// the hardware accepts; [`TimingStrategy`] implementations are expected to
+/// stay within them.
+#[derive(Clone, Debug)]
+pub struct BitTimingRanges {
+    /// Synchronization jump width
+    pub sjw: RangeInclusive<u32>,
+    /// Phase segment 1
+    pub phase_seg_1: RangeInclusive<u32>,
+    /// Phase segment 2
+    pub phase_seg_2: RangeInclusive<u32>,
     /// The bit time, in time quanta
-    time_quanta_per_bit: RangeInclusive<u32>,
-    prescaler: RangeInclusive<u32>,
+    pub time_quanta_per_bit: RangeInclusive<u32>,
+    /// Bit rate prescaler
+    pub prescaler: RangeInclusive<u32>,
 }
-pub(crate) const NOMINAL_BIT_TIMING_RANGES: BitTimingRanges = BitTimingRanges {
+
+/// Valid values for [`CanConfig::nominal_timing`] on M_CAN 3.1 and later
+pub const NOMINAL_BIT_TIMING_RANGES: BitTimingRanges = BitTimingRanges {
     sjw: 1..=128,
     phase_seg_1: 2..=256,
     phase_seg_2: 2..=128,
     time_quanta_per_bit: 5..=385,
     prescaler: 1..=512,
 };
-pub(crate) const DATA_BIT_TIMING_RANGES: BitTimingRanges = BitTimingRanges {
+
+/// Valid values for the data phase timing of [`Mode::Fd`] on M_CAN 3.1 and
+/// later
+pub const DATA_BIT_TIMING_RANGES: BitTimingRanges = BitTimingRanges {
     sjw: 1..=16,
     phase_seg_1: 1..=32,
     phase_seg_2: 1..=16,
@@ -157,6 +339,43 @@ pub(crate) const DATA_BIT_TIMING_RANGES: BitTimingRanges = BitTimingRanges {
     prescaler: 1..=32,
 };
 
+/// Valid values for [`CanConfig::nominal_timing`] on M_CAN 3.0
+pub const NOMINAL_BIT_TIMING_RANGES_V3_0: BitTimingRanges = BitTimingRanges {
+    sjw: 1..=16,
+    phase_seg_1: 2..=64,
+    phase_seg_2: 1..=16,
+    time_quanta_per_bit: 4..=81,
+    prescaler: 1..=1024,
+};
+
+/// Valid values for the data phase timing of [`Mode::Fd`] on M_CAN 3.0
+pub const DATA_BIT_TIMING_RANGES_V3_0: BitTimingRanges = BitTimingRanges {
+    sjw: 1..=4,
+    phase_seg_1: 2..=16,
+    phase_seg_2: 1..=8,
+    time_quanta_per_bit: 4..=25,
+    prescaler: 1..=32,
+};
+
+impl BitTimingRanges {
+    /// Valid values for [`CanConfig::nominal_timing`] on a core of `revision`
+    pub fn nominal(revision: CoreRevision) -> &'static Self {
+        match revision {
+            CoreRevision::V3_0 => &NOMINAL_BIT_TIMING_RANGES_V3_0,
+            _ => &NOMINAL_BIT_TIMING_RANGES,
+        }
+    }
+
+    /// Valid values for the data phase timing of [`Mode::Fd`] on a core of
+    /// `revision`
+    pub fn data(revision: CoreRevision) -> &'static Self {
+        match revision {
+            CoreRevision::V3_0 => &DATA_BIT_TIMING_RANGES_V3_0,
+            _ => &DATA_BIT_TIMING_RANGES,
+        }
+    }
+}
+
 impl BitTiming {
     /// Returns the number of time quanta that make up one bit time, `t_bit /
     /// t_q`
@@ -164,6 +383,219 @@ impl BitTiming {
         1 + u32::from(self.phase_seg_1) + u32::from(self.phase_seg_2)
     }
 
+    /// Resolve the register values for this timing
+    ///
+    /// Without a `strategy`, the segments configured in `self` are used as-is
+    /// and `can_clock` must be exactly divisible, see [`ExactDivisor`].
+    /// Otherwise, only [`BitTiming::bitrate`] is taken from `self` and the
+    /// strategy picks everything else.
+    ///
+    /// The result is checked against `valid` regardless of where it came from.
+    pub fn compute(
+        &self,
+        can_clock: HertzU32,
+        valid: &BitTimingRanges,
+        strategy: Option<&dyn TimingStrategy>,
+    ) -> Result<ComputedTiming, BitTimingError> {
+        let timing = match strategy {
+            Some(strategy) => strategy.solve(can_clock, self.bitrate, valid)?,
+            None => ExactDivisor::from(*self).solve(can_clock, self.bitrate, valid)?,
+        };
+        timing.check(valid)?;
+        Ok(timing)
+    }
+}
+
+/// Bit timing programmed in the peripheral, see
+/// [`DynAux::nominal_bit_timing`] and [`DynAux::data_bit_timing`]
+///
+/// The derived quantities are computed from the CAN clock of the
+/// [`Dependencies`], e.g. to log the timing that was actually applied at
+/// startup.
+///
+/// [`DynAux::nominal_bit_timing`]: crate::bus::DynAux::nominal_bit_timing
+/// [`DynAux::data_bit_timing`]: crate::bus::DynAux::data_bit_timing
+/// [`Dependencies`]: mcan_core::Dependencies
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AppliedBitTiming {
+    /// Values read back from the register
+    pub timing: ComputedTiming,
+    /// Effective bitrate, rounded down to whole Hz
+    pub bitrate: HertzU32,
+    /// Sample point as a fraction of the bit time, in per mille
+    pub sample_point_permille: u32,
+    /// Length of a time quantum in nanoseconds, rounded down
+    pub time_quantum_ns: u32,
+}
+
+impl AppliedBitTiming {
+    /// Derives the quantities of `timing` with the given CAN clock
+    pub fn new(timing: ComputedTiming, can_clock: HertzU32) -> Self {
+        let time_quantum_ns = (u64::from(timing.prescaler) * 1_000_000_000)
+            .checked_div(u64::from(can_clock.raw()))
+            .unwrap_or(0);
+        Self {
+            timing,
+            bitrate: timing.bitrate(can_clock),
+            sample_point_permille: timing.sample_point_permille(),
+            time_quantum_ns: u32::try_from(time_quantum_ns).unwrap_or(u32::MAX),
+        }
+    }
+}
+
+/// Register values resolved by a [`TimingStrategy`]
+///
+/// Like [`BitTiming`], these are *real* values; the register offsets are
+/// handled within the MCAN HAL.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ComputedTiming {
+    /// Synchronization jump width
+    pub sjw: u8,
+    /// Propagation time and phase time before sample point
+    pub phase_seg_1: u8,
+    /// Time after sample point
+    pub phase_seg_2: u8,
+    /// Number of CAN clock periods per time quantum
+    pub prescaler: u16,
+}
+
+impl ComputedTiming {
+    /// Returns the number of time quanta that make up one bit time, `t_bit /
+    /// t_q`
+    pub fn time_quanta_per_bit(&self) -> u32 {
+        1 + u32::from(self.phase_seg_1) + u32::from(self.phase_seg_2)
+    }
+
+    /// Returns the bitrate this timing produces with the given CAN clock
+    ///
+    /// The result is rounded down to whole Hz.
+    pub fn bitrate(&self, can_clock: HertzU32) -> HertzU32 {
+        can_clock / (u32::from(self.prescaler) * self.time_quanta_per_bit())
+    }
+
+    /// Returns the sample point as a fraction of the bit time, in per mille
+    pub fn sample_point_permille(&self) -> u32 {
+        (1 + u32::from(self.phase_seg_1)) * 1000 / self.time_quanta_per_bit()
+    }
+
+    /// Register value for NBTP, or BTP on M_CAN 3.0, on a core of `revision`
+    ///
+    /// The timing must be within [`BitTimingRanges::nominal`] of `revision`.
+    pub(crate) fn nominal_register(&self, revision: CoreRevision) -> u32 {
+        let sjw = u32::from(self.sjw) - 1;
+        let phase_seg_1 = u32::from(self.phase_seg_1) - 1;
+        let phase_seg_2 = u32::from(self.phase_seg_2) - 1;
+        let prescaler = u32::from(self.prescaler) - 1;
+        match revision {
+            // BTP: BRP[25:16], TSEG1[13:8], TSEG2[7:4], SJW[3:0]
+            CoreRevision::V3_0 => prescaler << 16 | phase_seg_1 << 8 | phase_seg_2 << 4 | sjw,
+            // NBTP: NSJW[31:25], NBRP[24:16], NTSEG1[15:8], NTSEG2[6:0]
+            _ => sjw << 25 | prescaler << 16 | phase_seg_1 << 8 | phase_seg_2,
+        }
+    }
+
+    /// Register value for DBTP, or FBTP on M_CAN 3.0
+    ///
+    /// The narrower fields of FBTP start at the same bits, so the value is the
+    /// same as long as the timing is within [`BitTimingRanges::data`] of the
+    /// revision.
+    pub(crate) fn data_register(&self) -> u32 {
+        // DBRP[20:16], DTSEG1[12:8], DTSEG2[7:4], DSJW[3:0]
+        (u32::from(self.prescaler) - 1) << 16
+            | (u32::from(self.phase_seg_1) - 1) << 8
+            | (u32::from(self.phase_seg_2) - 1) << 4
+            | (u32::from(self.sjw) - 1)
+    }
+
+    /// Timing programmed in NBTP, or BTP on M_CAN 3.0, the inverse of
+    /// [`Self::nominal_register`]
+    pub(crate) fn from_nominal_register(bits: u32, revision: CoreRevision) -> Self {
+        let field = |low: u32, width: u32| (bits >> low & ((1 << width) - 1)) + 1;
+        let (sjw, phase_seg_1, phase_seg_2, prescaler) = match revision {
+            CoreRevision::V3_0 => (field(0, 4), field(8, 6), field(4, 4), field(16, 10)),
+            _ => (field(25, 7), field(8, 8), field(0, 7), field(16, 9)),
+        };
+        Self {
+            sjw: sjw as u8,
+            phase_seg_1: phase_seg_1 as u8,
+            phase_seg_2: phase_seg_2 as u8,
+            prescaler: prescaler as u16,
+        }
+    }
+
+    /// Timing programmed in DBTP, or FBTP on M_CAN 3.0, the inverse of
+    /// [`Self::data_register`]
+    ///
+    /// The reserved bits of the narrower FBTP fields read as zero.
+    pub(crate) fn from_data_register(bits: u32) -> Self {
+        let field = |low: u32, width: u32| (bits >> low & ((1 << width) - 1)) + 1;
+        Self {
+            sjw: field(0, 4) as u8,
+            phase_seg_1: field(8, 5) as u8,
+            phase_seg_2: field(4, 4) as u8,
+            prescaler: field(16, 5) as u16,
+        }
+    }
+
+    fn check(&self, valid: &BitTimingRanges) -> Result<(), BitTimingError> {
+        ExactDivisor {
+            sjw: self.sjw,
+            phase_seg_1: self.phase_seg_1,
+            phase_seg_2: self.phase_seg_2,
+        }
+        .check(valid)?;
+        if !valid.prescaler.contains(&self.prescaler.into()) {
+            Err(BitTimingError::PrescalerOutOfRange(valid.prescaler.clone()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Resolution of a target bitrate into register values
+///
+/// Strategies are pure functions of their inputs and are selected through
+/// [`CanConfig::timing_strategy`]. The HAL checks the returned
+/// [`ComputedTiming`] against `ranges` before applying it.
+pub trait TimingStrategy {
+    /// Find a timing producing `target` from `can_clock` within `ranges`
+    fn solve(
+        &self,
+        can_clock: HertzU32,
+        target: HertzU32,
+        ranges: &BitTimingRanges,
+    ) -> Result<ComputedTiming, BitTimingError>;
+}
+
+/// Fixed segments with a prescaler that divides the CAN clock exactly
+///
+/// This is what the HAL does when no [`CanConfig::timing_strategy`] is
+/// given, using the segments from [`BitTiming`].
+#[derive(Copy, Clone, Debug)]
+pub struct ExactDivisor {
+    /// Synchronization jump width
+    pub sjw: u8,
+    /// Propagation time and phase time before sample point
+    pub phase_seg_1: u8,
+    /// Time after sample point
+    pub phase_seg_2: u8,
+}
+
+impl From<BitTiming> for ExactDivisor {
+    fn from(value: BitTiming) -> Self {
+        Self {
+            sjw: value.sjw,
+            phase_seg_1: value.phase_seg_1,
+            phase_seg_2: value.phase_seg_2,
+        }
+    }
+}
+
+impl ExactDivisor {
+    fn time_quanta_per_bit(&self) -> u32 {
+        1 + u32::from(self.phase_seg_1) + u32::from(self.phase_seg_2)
+    }
+
     fn check(&self, valid: &BitTimingRanges) -> Result<(), BitTimingError> {
         if !valid.sjw.contains(&self.sjw.into()) {
             Err(BitTimingError::SynchronizationJumpWidthOutOfRange(
@@ -184,37 +616,235 @@ impl BitTiming {
             Err(BitTimingError::BitTimeOutOfRange(
                 valid.time_quanta_per_bit.clone(),
             ))
+        } else if self.sjw > self.phase_seg_2 {
+            Err(BitTimingError::SjwExceedsPhaseSeg2 {
+                sjw: self.sjw,
+                phase_seg_2: self.phase_seg_2,
+            })
         } else {
             Ok(())
         }
     }
+}
 
-    pub(crate) fn prescaler(
+impl TimingStrategy for ExactDivisor {
+    fn solve(
         &self,
-        f_can: HertzU32,
-        valid: &BitTimingRanges,
-    ) -> Result<u16, BitTimingError> {
-        self.check(valid)?;
-        let f_out = self.bitrate;
+        can_clock: HertzU32,
+        target: HertzU32,
+        ranges: &BitTimingRanges,
+    ) -> Result<ComputedTiming, BitTimingError> {
+        self.check(ranges)?;
         let bit_time_quanta = self.time_quanta_per_bit();
-        let f_q = f_out * bit_time_quanta;
-        if let Some(0) = f_can.to_Hz().checked_rem(f_q.to_Hz()) {
-            let prescaler = f_can / f_q;
-            if !valid.prescaler.contains(&prescaler) {
-                Err(BitTimingError::PrescalerOutOfRange(valid.prescaler.clone()))
+        let f_q = target * bit_time_quanta;
+        if let Some(0) = can_clock.to_Hz().checked_rem(f_q.to_Hz()) {
+            let prescaler = can_clock / f_q;
+            if !ranges.prescaler.contains(&prescaler) {
+                Err(BitTimingError::PrescalerOutOfRange(
+                    ranges.prescaler.clone(),
+                ))
             } else {
-                Ok(prescaler as u16)
+                Ok(ComputedTiming {
+                    sjw: self.sjw,
+                    phase_seg_1: self.phase_seg_1,
+                    phase_seg_2: self.phase_seg_2,
+                    prescaler: prescaler as u16,
+                })
             }
         } else {
             Err(BitTimingError::NoValidPrescaler {
-                can_clock: f_can,
-                bitrate: f_out,
+                can_clock,
+                bitrate: target,
                 bit_time_quanta,
+                sample_point_permille: (1 + u32::from(self.phase_seg_1)) * 1000 / bit_time_quanta,
+                nearest: NearestTimings::new(can_clock, target, bit_time_quanta, ranges),
             })
         }
     }
 }
 
+/// Which solution [`BestEffort`] picks among those within tolerance
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preference {
+    /// The bitrate closest to the target; ties go to more time quanta
+    MinDeviation,
+    /// The most time quanta per bit (and thus the smallest prescaler); ties go
+    /// to the smaller deviation
+    ///
+    /// This gives the finest resolution of the sample point, the most SJW
+    /// headroom and the most precise transmitter delay compensation.
+    MaxTimeQuanta,
+}
+
+/// Search over all prescalers for a bitrate within a tolerance of the target
+///
+/// The segments are split for a sample point as close to 87.5 % as the ranges
+/// allow, with the SJW as large as phase segment 2 permits.
+#[derive(Copy, Clone, Debug)]
+pub struct BestEffort {
+    /// Largest accepted deviation from the target bitrate, in parts per
+    /// million
+    pub max_deviation_ppm: u32,
+    /// Tie breaking between solutions within tolerance
+    pub prefer: Preference,
+}
+
+impl BestEffort {
+    /// Split `time_quanta` into segments, or `None` if `ranges` do not allow it
+    fn segments(time_quanta: u32, ranges: &BitTimingRanges) -> Option<ComputedTiming> {
+        let tseg = time_quanta - 1;
+        // 12.5 % of the bit time after the sample point, rounded
+        let phase_seg_2 = ((time_quanta * 125 + 500) / 1000)
+            .max(*ranges.phase_seg_2.start())
+            .max(tseg.saturating_sub(*ranges.phase_seg_1.end()))
+            .min(*ranges.phase_seg_2.end());
+        let phase_seg_1 = tseg.checked_sub(phase_seg_2)?;
+        let sjw = phase_seg_2.min(*ranges.sjw.end());
+        let timing = ComputedTiming {
+            sjw: sjw.try_into().ok()?,
+            phase_seg_1: phase_seg_1.try_into().ok()?,
+            phase_seg_2: phase_seg_2.try_into().ok()?,
+            prescaler: 0,
+        };
+        ExactDivisor {
+            sjw: timing.sjw,
+            phase_seg_1: timing.phase_seg_1,
+            phase_seg_2: timing.phase_seg_2,
+        }
+        .check(ranges)
+        .ok()
+        .map(|_| timing)
+    }
+}
+
+impl TimingStrategy for BestEffort {
+    fn solve(
+        &self,
+        can_clock: HertzU32,
+        target: HertzU32,
+        ranges: &BitTimingRanges,
+    ) -> Result<ComputedTiming, BitTimingError> {
+        let can_clock_hz = u64::from(can_clock.to_Hz());
+        let target_hz = u64::from(target.to_Hz());
+        let mut best: Option<(ComputedTiming, u32)> = None;
+        let mut closest: Option<ClosestTiming> = None;
+        for prescaler in ranges.prescaler.clone() {
+            let f_q = u64::from(prescaler) * target_hz;
+            if f_q == 0 {
+                break;
+            }
+            // Nearest whole number of time quanta per bit
+            let time_quanta = (can_clock_hz + f_q / 2) / f_q;
+            let Ok(time_quanta) = u32::try_from(time_quanta) else {
+                continue;
+            };
+            if !ranges.time_quanta_per_bit.contains(&time_quanta) {
+                continue;
+            }
+            let Some(timing) = Self::segments(time_quanta, ranges) else {
+                continue;
+            };
+            let timing = ComputedTiming {
+                prescaler: prescaler as u16,
+                ..timing
+            };
+            let divisor = u64::from(prescaler) * u64::from(time_quanta);
+            let achieved = ClosestTiming::new(can_clock_hz, target_hz, divisor);
+            let deviation_ppm = achieved.deviation_ppm;
+            if deviation_ppm > self.max_deviation_ppm {
+                if closest.is_none_or(|closest| closest.deviation_ppm > deviation_ppm) {
+                    closest = Some(achieved);
+                }
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((current, current_deviation)) => {
+                    let by_quanta = timing
+                        .time_quanta_per_bit()
+                        .cmp(&current.time_quanta_per_bit());
+                    let by_deviation = current_deviation.cmp(&deviation_ppm);
+                    match self.prefer {
+                        Preference::MinDeviation => by_deviation.then(by_quanta),
+                        Preference::MaxTimeQuanta => by_quanta.then(by_deviation),
+                    }
+                    .is_gt()
+                }
+            };
+            if better {
+                best = Some((timing, deviation_ppm));
+            }
+        }
+        best.map(|(timing, _)| timing)
+            .ok_or(BitTimingError::NoSolutionWithinTolerance {
+                can_clock,
+                bitrate: target,
+                max_deviation_ppm: self.max_deviation_ppm,
+                closest,
+            })
+    }
+}
+
+/// Fixed segments with the prescaler giving the bitrate closest to the target,
+/// within a tolerance
+///
+/// Unlike [`BestEffort`], this keeps the segments, and thus the sample point,
+/// chosen in [`BitTiming`], and unlike [`ExactDivisor`] it accepts bitrates
+/// such as 83.333 kbit/s that no prescaler produces exactly.
+#[derive(Copy, Clone, Debug)]
+pub struct NearestPrescaler {
+    /// Segments of the bit time
+    pub segments: ExactDivisor,
+    /// Largest accepted deviation from the target bitrate, in parts per
+    /// million
+    pub max_deviation_ppm: u32,
+}
+
+impl TimingStrategy for NearestPrescaler {
+    fn solve(
+        &self,
+        can_clock: HertzU32,
+        target: HertzU32,
+        ranges: &BitTimingRanges,
+    ) -> Result<ComputedTiming, BitTimingError> {
+        self.segments.check(ranges)?;
+        let can_clock_hz = u64::from(can_clock.to_Hz());
+        let target_hz = u64::from(target.to_Hz());
+        let time_quanta = u64::from(self.segments.time_quanta_per_bit());
+        let prescaler_min = u64::from(*ranges.prescaler.start());
+        let prescaler_max = u64::from(*ranges.prescaler.end());
+        let f_q = target_hz * time_quanta;
+        // The bitrate is not linear in the prescaler, so the closest one is
+        // either of the two around the exact quotient
+        let closest = (f_q != 0)
+            .then(|| [can_clock_hz / f_q, can_clock_hz.div_ceil(f_q)])
+            .into_iter()
+            .flatten()
+            .map(|prescaler| prescaler.clamp(prescaler_min.max(1), prescaler_max))
+            .map(|prescaler| {
+                let achieved = ClosestTiming::new(can_clock_hz, target_hz, prescaler * time_quanta);
+                (prescaler, achieved)
+            })
+            .min_by_key(|(_, achieved)| achieved.deviation_ppm);
+        match closest {
+            Some((prescaler, achieved)) if achieved.deviation_ppm <= self.max_deviation_ppm => {
+                Ok(ComputedTiming {
+                    sjw: self.segments.sjw,
+                    phase_seg_1: self.segments.phase_seg_1,
+                    phase_seg_2: self.segments.phase_seg_2,
+                    prescaler: prescaler as u16,
+                })
+            }
+            closest => Err(BitTimingError::NoSolutionWithinTolerance {
+                can_clock,
+                bitrate: target,
+                max_deviation_ppm: self.max_deviation_ppm,
+                closest: closest.map(|(_, achieved)| achieved),
+            }),
+        }
+    }
+}
+
 /// Enable/disable CAN-FD and related features
 #[derive(Default, Copy, Clone)]
 pub enum Mode {
@@ -238,6 +868,66 @@ pub enum Mode {
     },
 }
 
+/// Participation of the peripheral in bus communication
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OperationMode {
+    /// Frames are received, acknowledged and transmitted
+    #[default]
+    Normal,
+    /// Bus monitoring mode (CCCR.MON): frames are received, but neither
+    /// acknowledged nor transmitted, and errors are not signalled on the bus,
+    /// e.g. for detecting the bitrate of a bus or for diagnostic tools
+    ListenOnly,
+    /// Restricted operation mode (CCCR.ASM): frames are received and
+    /// acknowledged, but neither transmitted nor errors signalled on the bus
+    ///
+    /// The peripheral also enters this mode on its own if the Message RAM is
+    /// not accessed in time, see
+    /// [`Interrupt::MessageRamAccessFailure`](crate::interrupt::Interrupt::MessageRamAccessFailure).
+    Restricted,
+}
+
+/// Loopback test mode (TEST.LBCK), in which the peripheral receives its own
+/// transmitted frames
+///
+/// The looped back frames pass the filters like frames from the bus. In both
+/// loopback modes, the RX pin is disregarded, so nothing from the bus is
+/// received, and missing acknowledgements are not treated as errors.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Loopback {
+    /// Frames are received from the bus only
+    #[default]
+    Disabled,
+    /// The TX pin is held recessive, so a self-test does not disturb a
+    /// running bus
+    ///
+    /// This combines loopback with bus monitoring (CCCR.MON), which takes
+    /// precedence over [`CanConfig::operation`].
+    Internal,
+    /// Frames are transmitted on the TX pin as well, so the peripheral takes
+    /// part in bus communication
+    External,
+}
+
+impl Loopback {
+    /// Returns `true` unless loopback is disabled
+    pub fn is_enabled(self) -> bool {
+        self != Self::Disabled
+    }
+}
+
+impl OperationMode {
+    /// Decodes the mode from CCCR.MON and CCCR.ASM, of which bus monitoring
+    /// takes precedence
+    pub(crate) fn from_bits(monitoring: bool, restricted: bool) -> Self {
+        match (monitoring, restricted) {
+            (true, _) => Self::ListenOnly,
+            (false, true) => Self::Restricted,
+            (false, false) => Self::Normal,
+        }
+    }
+}
+
 impl CanConfig {
     /// Create an instance
     ///
@@ -247,11 +937,52 @@ impl CanConfig {
         Self {
             mode: Default::default(),
             loopback: Default::default(),
+            operation: Default::default(),
             nominal_timing: BitTiming::new(bitrate),
             timestamp: Default::default(),
             rx_fifo_0: Default::default(),
             rx_fifo_1: Default::default(),
             tx: Default::default(),
+            timing_strategy: None,
+            strict_validation: false,
+            transmitter_delay_compensation: None,
+            global_filter: Default::default(),
+            extended_id_and_mask: ExtendedId::MAX,
+        }
+    }
+}
+
+/// Secondary sample point for the data phase of bit rate switched frames
+///
+/// While transmitting, the peripheral samples its own bits from the receive
+/// pin. At data bitrates of about 1 Mbit/s and above, the loop delay through
+/// the transceiver approaches the bit time and these samples would see the
+/// previous bit, causing bit errors. With compensation enabled, the
+/// peripheral measures the delay at the start of each bit rate switched frame
+/// and checks transmitted bits at a secondary sample point `offset` after the
+/// measured delay.
+///
+/// Both values are in CAN clock periods. The offset is usually set to the
+/// sample point of the data phase, `prescaler * (1 + phase_seg_1)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TransmitterDelayCompensation {
+    /// Offset of the secondary sample point from the measured delay, TDCO
+    ///
+    /// Valid values are 0 to 127, or 0 to 31 on M_CAN 3.0.
+    pub offset: u8,
+    /// Shortest measured delay that is accepted, TDCF, which filters dominant
+    /// glitches at the start of the measurement; 0 disables the filter
+    ///
+    /// Valid values are 0 to 127. M_CAN 3.0 has no filter and only accepts 0.
+    pub window: u8,
+}
+
+impl TransmitterDelayCompensation {
+    /// Returns whether the values fit the registers of a core of `revision`
+    pub(crate) fn is_valid(&self, revision: CoreRevision) -> bool {
+        match revision {
+            CoreRevision::V3_0 => self.offset <= 31 && self.window == 0,
+            _ => self.offset <= 127 && self.window <= 127,
         }
     }
 }
@@ -263,9 +994,35 @@ pub struct RxFifoConfig {
     pub mode: RxFifoMode,
     /// Denotes queue fullness required to trigger a corresponding interrupt
     ///
-    /// Any value greater than 64 is interpreted as 64; 0 means that interrupt
-    /// is disabled
+    /// Any value greater than the depth of the FIFO selected by
+    /// [`Capacities`] is interpreted as the depth, see
+    /// [`CanConfig::strict_validation`]; 0 means that interrupt is disabled
+    ///
+    /// [`Capacities`]: crate::messageram::Capacities
     pub watermark: u8,
+    /// Number of messages that may arrive while [`DynRxFifo::receive`] reads
+    /// one, required in [overwrite mode](RxFifoMode::overwrite) and ignored
+    /// otherwise
+    ///
+    /// Has to be at least 1 and less than the depth of the FIFO, otherwise
+    /// the configuration fails with
+    /// [`ConfigurationError::InvalidOverwriteReadOffset`].
+    ///
+    /// [`DynRxFifo::receive`]: crate::rx_fifo::DynRxFifo::receive
+    /// [`ConfigurationError::InvalidOverwriteReadOffset`]: crate::bus::ConfigurationError::InvalidOverwriteReadOffset
+    pub overwrite_read_offset: Option<u8>,
+}
+
+impl RxFifoConfig {
+    /// Read offset used by [`DynRxFifo::receive`], `None` in blocking mode
+    ///
+    /// [`DynRxFifo::receive`]: crate::rx_fifo::DynRxFifo::receive
+    pub(crate) fn read_offset(&self) -> Option<u8> {
+        match self.mode.0 {
+            RxFifoModeVariant::Blocking => None,
+            RxFifoModeVariant::Overwrite => self.overwrite_read_offset,
+        }
+    }
 }
 
 /// Mode of operation for the RX FIFO
@@ -285,7 +1042,6 @@ impl RxFifoMode {
     /// When the RX FIFO is full, the oldest messsage will be deleted and a new
     /// message will take its place.
     ///
-    /// # Safety
     /// For the RX FIFO running in this mode, MCAN *does NOT provide* any
     /// synchronization primitives that user can rely on in order to guarantee
     /// integrity of the data being received.
@@ -296,9 +1052,13 @@ impl RxFifoMode {
     /// more (counting from the oldest message) depending on the speed of the
     /// CPU.
     ///
-    /// Thus, it is up to the application developer to provide such an
-    /// index offset so read out messages are correct.
-    pub unsafe fn overwrite() -> Self {
+    /// This offset is configured by [`RxFifoConfig::overwrite_read_offset`]
+    /// and respected by [`DynRxFifo::receive`], which skips the messages
+    /// that may be overwritten while a message is read if the FIFO is close
+    /// to full.
+    ///
+    /// [`DynRxFifo::receive`]: crate::rx_fifo::DynRxFifo::receive
+    pub fn overwrite() -> Self {
         Self(RxFifoModeVariant::Overwrite)
     }
 }
@@ -343,6 +1103,11 @@ pub enum TxQueueMode {
     /// Lower ID means higher priority. Messages of the same ID are sent in an
     /// arbitrary order. This is the same order as arbitration on the bus would
     /// give.
+    ///
+    /// Completions are thus not reported in the order messages were enqueued;
+    /// see [`DynTx::take_completions`] for correlating them by ID.
+    ///
+    /// [`DynTx::take_completions`]: crate::tx_buffers::DynTx::take_completions
     Priority,
 }
 
@@ -354,3 +1119,542 @@ impl From<TxQueueMode> for bool {
         }
     }
 }
+
+/// Global filter configuration
+///
+/// The default rejects all frames that match no filter and filters remote
+/// frames like data frames.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GlobalFilterConfig {
+    /// Action on standard frames that match no filter
+    pub non_matching_standard: NonMatchingAction,
+    /// Action on extended frames that match no filter
+    pub non_matching_extended: NonMatchingAction,
+    /// Reject all standard remote frames, regardless of the filters
+    pub reject_remote_standard: bool,
+    /// Reject all extended remote frames, regardless of the filters
+    pub reject_remote_extended: bool,
+}
+
+/// Action on frames that match no filter
+///
+/// Frames accepted this way are reported by
+/// [`AnyMessage::accepted_non_matching_frame`].
+///
+/// [`AnyMessage::accepted_non_matching_frame`]: crate::message::rx::AnyMessage::accepted_non_matching_frame
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NonMatchingAction {
+    /// Store in RX FIFO 0
+    StoreFifo0,
+    /// Store in RX FIFO 1
+    StoreFifo1,
+    /// Discard the frame
+    #[default]
+    Reject,
+}
+
+impl From<NonMatchingAction> for ANFSSELECT_A {
+    fn from(val: NonMatchingAction) -> Self {
+        match val {
+            NonMatchingAction::StoreFifo0 => ANFSSELECT_A::RXF0,
+            NonMatchingAction::StoreFifo1 => ANFSSELECT_A::RXF1,
+            NonMatchingAction::Reject => ANFSSELECT_A::REJECT,
+        }
+    }
+}
+
+impl From<NonMatchingAction> for ANFESELECT_A {
+    fn from(val: NonMatchingAction) -> Self {
+        match val {
+            NonMatchingAction::StoreFifo0 => ANFESELECT_A::RXF0,
+            NonMatchingAction::StoreFifo1 => ANFESELECT_A::RXF1,
+            NonMatchingAction::Reject => ANFESELECT_A::REJECT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fugit::RateExtU32;
+
+    fn timing(sjw: u8, phase_seg_2: u8) -> ExactDivisor {
+        ExactDivisor {
+            sjw,
+            phase_seg_2,
+            ..BitTiming::new(500.kHz()).into()
+        }
+    }
+
+    #[test]
+    fn default_timing_is_valid() {
+        let timing = ExactDivisor::from(BitTiming::new(500.kHz()));
+        assert!(timing.check(&NOMINAL_BIT_TIMING_RANGES).is_ok());
+        assert!(timing.check(&DATA_BIT_TIMING_RANGES).is_ok());
+    }
+
+    #[test]
+    fn sjw_equal_to_phase_seg_2_is_allowed() {
+        assert!(timing(4, 4).check(&NOMINAL_BIT_TIMING_RANGES).is_ok());
+        assert!(timing(4, 4).check(&DATA_BIT_TIMING_RANGES).is_ok());
+    }
+
+    #[test]
+    fn sjw_exceeding_phase_seg_2_is_rejected() {
+        for ranges in [&NOMINAL_BIT_TIMING_RANGES, &DATA_BIT_TIMING_RANGES] {
+            assert!(matches!(
+                timing(5, 4).check(ranges),
+                Err(BitTimingError::SjwExceedsPhaseSeg2 {
+                    sjw: 5,
+                    phase_seg_2: 4
+                })
+            ));
+        }
+    }
+
+    fn computed(sjw: u8, phase_seg_1: u8, phase_seg_2: u8, prescaler: u16) -> ComputedTiming {
+        ComputedTiming {
+            sjw,
+            phase_seg_1,
+            phase_seg_2,
+            prescaler,
+        }
+    }
+
+    #[test]
+    fn nominal_register_boundaries_per_revision() {
+        let v3_0 = BitTimingRanges::nominal(CoreRevision::V3_0);
+        let min = computed(1, 2, 1, 1);
+        let max = computed(16, 64, 16, 1024);
+        assert!(min.check(v3_0).is_ok() && max.check(v3_0).is_ok());
+        assert_eq!(min.nominal_register(CoreRevision::V3_0), 0x0000_0100);
+        // BRP[25:16], TSEG1[13:8], TSEG2[7:4], SJW[3:0] all set
+        assert_eq!(max.nominal_register(CoreRevision::V3_0), 0x03ff_3fff);
+        for beyond in [
+            computed(17, 64, 17, 1),
+            computed(1, 65, 16, 1),
+            computed(1, 2, 1, 1025),
+        ] {
+            assert!(beyond.check(v3_0).is_err());
+        }
+
+        let v3_1 = BitTimingRanges::nominal(CoreRevision::V3_1);
+        let min = computed(1, 2, 2, 1);
+        let max = computed(128, 255, 128, 512);
+        assert!(min.check(v3_1).is_ok() && max.check(v3_1).is_ok());
+        assert_eq!(min.nominal_register(CoreRevision::V3_1), 0x0000_0101);
+        // NSJW[31:25], NBRP[24:16], NTSEG1[15:8] up to 254, NTSEG2[6:0] all set
+        assert_eq!(max.nominal_register(CoreRevision::V3_1), 0xffff_fe7f);
+        // Values that only fit the wider fields of 3.1 are rejected for 3.0
+        let wide_sjw = computed(17, 20, 17, 1);
+        assert!(wide_sjw.check(v3_1).is_ok());
+        assert!(wide_sjw.check(v3_0).is_err());
+    }
+
+    #[test]
+    fn data_register_boundaries_per_revision() {
+        let v3_0 = BitTimingRanges::data(CoreRevision::V3_0);
+        let max = computed(4, 16, 8, 32);
+        assert!(max.check(v3_0).is_ok());
+        // FBRP[20:16], FTSEG1[11:8], FTSEG2[6:4], FSJW[1:0] all set
+        assert_eq!(max.data_register(), 0x001f_0f73);
+        for beyond in [
+            computed(5, 16, 8, 1),
+            computed(1, 17, 8, 1),
+            computed(1, 16, 9, 1),
+        ] {
+            assert!(beyond.check(v3_0).is_err());
+        }
+
+        let v3_1 = BitTimingRanges::data(CoreRevision::V3_1);
+        let min = computed(1, 1, 1, 1);
+        let max = computed(16, 32, 16, 32);
+        assert!(min.check(v3_1).is_ok() && max.check(v3_1).is_ok());
+        assert_eq!(min.data_register(), 0);
+        // DBRP[20:16], DTSEG1[12:8], DTSEG2[7:4], DSJW[3:0] all set
+        assert_eq!(max.data_register(), 0x001f_1fff);
+    }
+
+    #[test]
+    fn registers_are_decoded_into_the_applied_timing() {
+        for timing in [computed(1, 2, 1, 1), computed(16, 64, 16, 1024)] {
+            let bits = timing.nominal_register(CoreRevision::V3_0);
+            assert_eq!(
+                ComputedTiming::from_nominal_register(bits, CoreRevision::V3_0),
+                timing
+            );
+        }
+        for timing in [computed(1, 2, 2, 1), computed(128, 255, 128, 512)] {
+            let bits = timing.nominal_register(CoreRevision::V3_1);
+            assert_eq!(
+                ComputedTiming::from_nominal_register(bits, CoreRevision::V3_1),
+                timing
+            );
+        }
+        for timing in [computed(1, 1, 1, 1), computed(16, 32, 16, 32)] {
+            assert_eq!(
+                ComputedTiming::from_data_register(timing.data_register()),
+                timing
+            );
+        }
+        // The TDC bit and offset of FBTP are ignored
+        assert_eq!(
+            ComputedTiming::from_data_register(0x1f80_0000 | 0x0001_0e33),
+            computed(4, 15, 4, 2)
+        );
+
+        // NSJW=3 NBRP=4 NTSEG1=12 NTSEG2=1: 2 + 13 + 1 = 16 time quanta of
+        // 5 clock periods at 40 MHz
+        let nominal = AppliedBitTiming::new(
+            ComputedTiming::from_nominal_register(0x0604_0c01, CoreRevision::V3_1),
+            40.MHz(),
+        );
+        assert_eq!(nominal.timing, computed(4, 13, 2, 5));
+        assert_eq!(nominal.bitrate, 500.kHz::<1, 1>());
+        assert_eq!(nominal.sample_point_permille, 875);
+        assert_eq!(nominal.time_quantum_ns, 125);
+
+        // 20 time quanta of 2 clock periods at 80 MHz, sampled at 80 %
+        let data = AppliedBitTiming::new(computed(4, 15, 4, 2), 80.MHz());
+        assert_eq!(data.bitrate, 2.MHz::<1, 1>());
+        assert_eq!(data.sample_point_permille, 800);
+        assert_eq!(data.time_quantum_ns, 25);
+
+        // Rounded down where the clock does not divide evenly
+        let uneven = AppliedBitTiming::new(computed(1, 5, 3, 3), 48.MHz());
+        assert_eq!(uneven.bitrate, 1_777_777.Hz::<1, 1>());
+        assert_eq!(uneven.sample_point_permille, 666);
+        assert_eq!(uneven.time_quantum_ns, 62);
+    }
+
+    /// Solutions of each strategy for `(can_clock, bitrate, ranges)`
+    ///
+    /// Each entry is `(sjw, phase_seg_1, phase_seg_2, prescaler)`; `None`
+    /// means the strategy rejects the pair.
+    type Solution = Option<(u8, u8, u8, u16)>;
+
+    #[allow(clippy::type_complexity)]
+    const TABLE: &[(u32, u32, &BitTimingRanges, Solution, Solution, Solution)] = &[
+        // (can_clock, bitrate, ranges, exact divisor, min deviation, max quanta)
+        (
+            80_000_000,
+            500_000,
+            &NOMINAL_BIT_TIMING_RANGES,
+            Some((4, 11, 4, 10)),
+            Some((20, 139, 20, 1)),
+            Some((20, 139, 20, 1)),
+        ),
+        (
+            48_000_000,
+            1_000_000,
+            &NOMINAL_BIT_TIMING_RANGES,
+            Some((4, 11, 4, 3)),
+            Some((6, 41, 6, 1)),
+            Some((6, 41, 6, 1)),
+        ),
+        (
+            30_000_000,
+            500_000,
+            &NOMINAL_BIT_TIMING_RANGES,
+            None,
+            Some((8, 51, 8, 1)),
+            Some((8, 51, 8, 1)),
+        ),
+        (
+            16_000_000,
+            125_000,
+            &NOMINAL_BIT_TIMING_RANGES,
+            Some((4, 11, 4, 8)),
+            Some((16, 111, 16, 1)),
+            Some((16, 111, 16, 1)),
+        ),
+        (
+            100_000_000,
+            125_000,
+            &NOMINAL_BIT_TIMING_RANGES,
+            Some((4, 11, 4, 50)),
+            Some((25, 174, 25, 4)),
+            Some((33, 233, 33, 3)),
+        ),
+        (
+            36_864_000,
+            500_000,
+            &NOMINAL_BIT_TIMING_RANGES,
+            None,
+            Some((9, 64, 9, 1)),
+            Some((9, 64, 9, 1)),
+        ),
+        (
+            40_000_000,
+            2_000_000,
+            &DATA_BIT_TIMING_RANGES,
+            None,
+            Some((3, 16, 3, 1)),
+            Some((3, 16, 3, 1)),
+        ),
+        (
+            80_000_000,
+            5_000_000,
+            &DATA_BIT_TIMING_RANGES,
+            Some((4, 11, 4, 1)),
+            Some((2, 13, 2, 1)),
+            Some((2, 13, 2, 1)),
+        ),
+        (
+            24_000_000,
+            5_000_000,
+            &DATA_BIT_TIMING_RANGES,
+            None,
+            None,
+            None,
+        ),
+    ];
+
+    fn solution(result: Result<ComputedTiming, BitTimingError>) -> Solution {
+        result
+            .ok()
+            .map(|t| (t.sjw, t.phase_seg_1, t.phase_seg_2, t.prescaler))
+    }
+
+    #[test]
+    fn strategies_over_clock_and_bitrate_table() {
+        let exact = ExactDivisor::from(BitTiming::new(0.Hz()));
+        let min_deviation = BestEffort {
+            max_deviation_ppm: 5000,
+            prefer: Preference::MinDeviation,
+        };
+        let max_quanta = BestEffort {
+            max_deviation_ppm: 5000,
+            prefer: Preference::MaxTimeQuanta,
+        };
+        for &(can_clock, bitrate, ranges, e, d, q) in TABLE {
+            let (can_clock, bitrate) = (can_clock.Hz(), bitrate.Hz());
+            assert_eq!(solution(exact.solve(can_clock, bitrate, ranges)), e);
+            assert_eq!(solution(min_deviation.solve(can_clock, bitrate, ranges)), d);
+            assert_eq!(solution(max_quanta.solve(can_clock, bitrate, ranges)), q);
+        }
+    }
+
+    #[test]
+    fn best_effort_aims_for_sample_point_of_87_5_percent() {
+        let strategy = BestEffort {
+            max_deviation_ppm: 0,
+            prefer: Preference::MaxTimeQuanta,
+        };
+        let timing = strategy
+            .solve(80.MHz(), 500.kHz(), &NOMINAL_BIT_TIMING_RANGES)
+            .unwrap();
+        assert_eq!(timing.sample_point_permille(), 875);
+        assert_eq!(timing.bitrate(80.MHz()), 500.kHz::<1, 1>());
+    }
+
+    #[test]
+    fn nearest_prescaler_accepts_odd_bitrates_within_tolerance() {
+        let strategy = NearestPrescaler {
+            segments: BitTiming::new(0.Hz()).into(),
+            max_deviation_ppm: 10,
+        };
+        let timing = BitTiming::new(83_333.Hz());
+        assert!(matches!(
+            timing.compute(8.MHz(), &NOMINAL_BIT_TIMING_RANGES, None),
+            Err(BitTimingError::NoValidPrescaler { .. })
+        ));
+        let computed = timing
+            .compute(8.MHz(), &NOMINAL_BIT_TIMING_RANGES, Some(&strategy))
+            .unwrap();
+        assert_eq!(
+            (computed.prescaler, computed.time_quanta_per_bit()),
+            (6, 16)
+        );
+
+        // Beyond the smallest prescaler
+        match strategy.solve(8.MHz(), 700.kHz(), &NOMINAL_BIT_TIMING_RANGES) {
+            Err(BitTimingError::NoSolutionWithinTolerance { closest, .. }) => assert_eq!(
+                closest,
+                Some(ClosestTiming {
+                    bitrate: 500.kHz(),
+                    deviation_ppm: 285_714,
+                })
+            ),
+            result => panic!("unexpected {result:?}"),
+        }
+    }
+
+    #[test]
+    fn best_effort_reports_closest_timing_beyond_tolerance() {
+        let strategy = BestEffort {
+            max_deviation_ppm: 0,
+            prefer: Preference::MinDeviation,
+        };
+        match strategy.solve(8.MHz(), 83_333.Hz(), &NOMINAL_BIT_TIMING_RANGES) {
+            Err(BitTimingError::NoSolutionWithinTolerance {
+                max_deviation_ppm: 0,
+                closest,
+                ..
+            }) => assert_eq!(
+                closest,
+                Some(ClosestTiming {
+                    bitrate: 83_333.Hz(),
+                    deviation_ppm: 4,
+                })
+            ),
+            result => panic!("unexpected {result:?}"),
+        }
+        assert!(matches!(
+            // Shorter than the shortest bit time
+            strategy.solve(8.MHz(), 2.MHz(), &NOMINAL_BIT_TIMING_RANGES),
+            Err(BitTimingError::NoSolutionWithinTolerance { closest: None, .. })
+        ));
+    }
+
+    struct Fixed(ComputedTiming);
+
+    impl TimingStrategy for Fixed {
+        fn solve(
+            &self,
+            _: HertzU32,
+            _: HertzU32,
+            _: &BitTimingRanges,
+        ) -> Result<ComputedTiming, BitTimingError> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn compute_checks_strategy_result_against_ranges() {
+        let strategy = Fixed(ComputedTiming {
+            sjw: 4,
+            phase_seg_1: 11,
+            phase_seg_2: 4,
+            prescaler: 33,
+        });
+        let timing = BitTiming::new(500.kHz());
+        assert!(timing
+            .compute(16.MHz(), &NOMINAL_BIT_TIMING_RANGES, Some(&strategy))
+            .is_ok());
+        assert!(matches!(
+            timing.compute(16.MHz(), &DATA_BIT_TIMING_RANGES, Some(&strategy)),
+            Err(BitTimingError::PrescalerOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn no_valid_prescaler_reports_nearest_timings() {
+        // (can_clock, bitrate, phase_seg_1, phase_seg_2, ranges, below, above,
+        // exact quanta)
+        #[allow(clippy::type_complexity)]
+        let table: &[(
+            u32,
+            u32,
+            u8,
+            u8,
+            &BitTimingRanges,
+            Option<u32>,
+            Option<u32>,
+            Option<u32>,
+        )] = &[
+            // Awkward crystal frequency, no exact match at any quanta
+            (
+                159_744_000,
+                83_333,
+                14,
+                5,
+                &NOMINAL_BIT_TIMING_RANGES,
+                Some(83_200),
+                Some(84_076),
+                None,
+            ),
+            // Exact matches exist at 10 and 16 quanta, 16 is closer to 15
+            (
+                80_000_000,
+                500_000,
+                10,
+                4,
+                &NOMINAL_BIT_TIMING_RANGES,
+                Some(484_848),
+                Some(533_333),
+                Some(16),
+            ),
+            // Clock too slow for the quanta, even at prescaler 1
+            (
+                8_000_000,
+                1_000_000,
+                11,
+                4,
+                &NOMINAL_BIT_TIMING_RANGES,
+                Some(500_000),
+                None,
+                Some(8),
+            ),
+            (
+                24_000_000,
+                5_000_000,
+                11,
+                4,
+                &DATA_BIT_TIMING_RANGES,
+                Some(1_500_000),
+                None,
+                None,
+            ),
+            // Clock too fast for the prescaler range
+            (
+                100_000_000,
+                10_000,
+                12,
+                4,
+                &NOMINAL_BIT_TIMING_RANGES,
+                None,
+                Some(11_489),
+                Some(20),
+            ),
+            // Equally close exact matches at 10 and 12 quanta
+            (
+                12_000_000,
+                100_000,
+                7,
+                3,
+                &DATA_BIT_TIMING_RANGES,
+                Some(99_174),
+                Some(109_091),
+                Some(12),
+            ),
+        ];
+        for &(can_clock, bitrate, phase_seg_1, phase_seg_2, ranges, below, above, exact) in table {
+            let strategy = ExactDivisor {
+                sjw: 1,
+                phase_seg_1,
+                phase_seg_2,
+            };
+            let Err(BitTimingError::NoValidPrescaler { nearest, .. }) =
+                strategy.solve(can_clock.Hz(), bitrate.Hz(), ranges)
+            else {
+                panic!("{can_clock} Hz can make {bitrate} bps");
+            };
+            assert_eq!(
+                nearest,
+                NearestTimings {
+                    below: below.map(HertzU32::from_raw),
+                    above: above.map(HertzU32::from_raw),
+                    exact_bit_time_quanta: exact,
+                },
+                "{can_clock} Hz, {bitrate} bps",
+            );
+        }
+    }
+
+    #[test]
+    fn no_valid_prescaler_reports_sample_point() {
+        let timing = BitTiming {
+            phase_seg_1: 10,
+            ..BitTiming::new(500.kHz())
+        };
+        assert!(matches!(
+            ExactDivisor::from(timing).solve(80.MHz(), 500.kHz(), &NOMINAL_BIT_TIMING_RANGES),
+            Err(BitTimingError::NoValidPrescaler {
+                bit_time_quanta: 15,
+                sample_point_permille: 733,
+                ..
+            })
+        ));
+    }
+}
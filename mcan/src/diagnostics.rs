@@ -0,0 +1,900 @@
+//! Detection of stalled communication and link checks
+//!
+//! A peripheral can look operational while nothing gets through, e.g. when a
+//! lost interrupt leaves the application waiting for a completion that was
+//! already signalled. [`Liveness`] watches the transmit queue and a receive
+//! FIFO from a periodic task and reports when either has not made progress
+//! for longer than its [`Expectations`] allow. Deciding what to do about it,
+//! e.g. whether to let an external watchdog reset the system, is left to the
+//! application.
+//!
+//! Time is measured with the timestamp counter, which has to be enabled in
+//! [`Timestamp`]. [`Liveness::poll`] has to be called at least once every half
+//! counter period (32768 ticks) to keep track of its wraparounds, see
+//! [`timestamp::Extender`]. Polling less often delays the detection of
+//! stalls, but never reports one that did not happen.
+//!
+//! ```no_run
+//! # use mcan::bus::DynAux;
+//! # use mcan::rx_fifo::DynRxFifo;
+//! # use mcan::tx_buffers::DynTx;
+//! # fn feed_watchdog() {}
+//! # fn run(aux: impl DynAux, tx: impl DynTx, rx_fifo_0: impl DynRxFifo) {
+//! use mcan::diagnostics::{Expectations, Liveness, LivenessVerdict};
+//!
+//! let mut liveness = Liveness::new(Expectations {
+//!     tx_drain_within: Some(10_000),
+//!     // Other nodes may legitimately be quiet
+//!     rx_activity_within: None,
+//! });
+//! loop {
+//!     if let LivenessVerdict::Alive | LivenessVerdict::NotOperational =
+//!         liveness.poll(&aux, &tx, &rx_fifo_0)
+//!     {
+//!         feed_watchdog();
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! # Ping
+//!
+//! During commissioning, [`Ping`] checks the link to a peer running the same
+//! firmware: it sends a frame with the configured identifier and waits for
+//! the peer to echo it with the next higher identifier, which the peer does
+//! by passing the frames it receives to [`pong`]. The round trip is measured
+//! with the timestamp counter, which has to be enabled on the pinging node,
+//! and [`Ping::poll`] has the same requirement on the polling period as
+//! [`Liveness::poll`].
+//!
+//! ```no_run
+//! # use mcan::bus::DynAux;
+//! # use mcan::message::{rx, tx};
+//! # use mcan::rx_fifo::DynRxFifo;
+//! # use mcan::tx_buffers::DynTx;
+//! # use mcan::embedded_can::StandardId;
+//! # fn run(
+//! #     aux: impl DynAux,
+//! #     mut tx: impl DynTx<Message = tx::Message<8>>,
+//! #     mut rx_fifo_0: impl DynRxFifo<Message = rx::Message<8>>,
+//! # ) {
+//! use mcan::diagnostics::{ping, PingConfig};
+//!
+//! let config = PingConfig {
+//!     id: StandardId::new(0x7f0).unwrap().into(),
+//!     payload: b"ping",
+//!     timeout_ticks: 10_000,
+//! };
+//! match ping(&mut tx, &mut rx_fifo_0, &aux, config) {
+//!     Ok(report) => { /* report.rtt_ticks, report.payload_matches */ }
+//!     Err(error) => { /* no link */ }
+//! }
+//! # }
+//! ```
+//!
+//! [`Timestamp`]: crate::config::Timestamp
+//! [`timestamp::Extender`]: crate::timestamp::Extender
+
+use crate::bus::DynAux;
+use crate::message::{rx, tx, Raw as _};
+use crate::rx_fifo::DynRxFifo;
+use crate::timestamp::Extender;
+use crate::tx_buffers::{DynTx, Error, TransmitError, TxSlotStatus, TxToken};
+use embedded_can::{ExtendedId, Id, StandardId};
+
+/// Longest periods in timestamp ticks that the subsystems may go without
+/// progress
+///
+/// A limit of `None` disables the check for that subsystem.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Expectations {
+    /// Longest period the transmit queue may hold messages without any of
+    /// them leaving it. An empty queue is never stalled.
+    pub tx_drain_within: Option<u64>,
+    /// Longest period without any message stored in the receive FIFO. Only
+    /// useful on buses with periodic traffic from other nodes.
+    pub rx_activity_within: Option<u64>,
+}
+
+/// Part of the peripheral that stopped making progress
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Subsystem {
+    /// The transmit queue
+    Tx,
+    /// The receive FIFO
+    Rx,
+}
+
+/// Result of [`Liveness::poll`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LivenessVerdict {
+    /// All subsystems made progress within their expectations
+    Alive,
+    /// A subsystem did not make progress for longer than expected. If both
+    /// subsystems are stalled, the transmit queue is reported.
+    Stalled {
+        /// The stalled subsystem
+        subsystem: Subsystem,
+        /// Ticks since the subsystem last made progress
+        since_ticks: u64,
+    },
+    /// The peripheral is not in normal operation or is bus off, so no
+    /// progress can be expected. The progress of the subsystems is tracked
+    /// anew once it is operational again.
+    NotOperational,
+}
+
+/// Watchdog-style check that the transmit queue and a receive FIFO make
+/// progress, see the [module documentation](self)
+#[derive(Clone, Debug)]
+pub struct Liveness {
+    expectations: Expectations,
+    clock: Option<Extender>,
+    tx: Option<TxProgress>,
+    rx: Option<RxProgress>,
+}
+
+/// Transmit queue as observed by the previous poll
+#[derive(Copy, Clone, Debug)]
+struct TxProgress {
+    len: usize,
+    queued_count: u32,
+    /// Since when the queue holds messages without any leaving it
+    waiting_since: Option<u64>,
+}
+
+/// Receive FIFO as observed by the previous poll
+#[derive(Copy, Clone, Debug)]
+struct RxProgress {
+    len: usize,
+    read_count: u32,
+    /// When a message was last stored
+    active_at: u64,
+}
+
+impl TxProgress {
+    fn new(now: u64, len: usize, queued_count: u32) -> Self {
+        Self {
+            len,
+            queued_count,
+            waiting_since: (len != 0).then_some(now),
+        }
+    }
+
+    fn update(&mut self, now: u64, len: usize, queued_count: u32) {
+        // Messages in the queue now were either there before or queued since
+        let queued = queued_count.wrapping_sub(self.queued_count);
+        let left = (self.len as u32)
+            .wrapping_add(queued)
+            .wrapping_sub(len as u32);
+        self.waiting_since = match self.waiting_since {
+            _ if len == 0 => None,
+            Some(since) if left == 0 => Some(since),
+            _ => Some(now),
+        };
+        self.len = len;
+        self.queued_count = queued_count;
+    }
+
+    fn stalled_for(&self, now: u64) -> u64 {
+        self.waiting_since.map_or(0, |since| now - since)
+    }
+}
+
+impl RxProgress {
+    fn new(now: u64, len: usize, read_count: u32) -> Self {
+        Self {
+            len,
+            read_count,
+            active_at: now,
+        }
+    }
+
+    fn update(&mut self, now: u64, len: usize, read_count: u32) {
+        // Messages in the FIFO now or read out since were either there before
+        // or stored since
+        let read = read_count.wrapping_sub(self.read_count);
+        let stored = (len as u32)
+            .wrapping_add(read)
+            .wrapping_sub(self.len as u32);
+        if stored != 0 {
+            self.active_at = now;
+        }
+        self.len = len;
+        self.read_count = read_count;
+    }
+
+    fn stalled_for(&self, now: u64) -> u64 {
+        now - self.active_at
+    }
+}
+
+impl Liveness {
+    /// Create a check against `expectations`
+    ///
+    /// The periods start with the first call to [`Self::poll`].
+    pub const fn new(expectations: Expectations) -> Self {
+        Self {
+            expectations,
+            clock: None,
+            tx: None,
+            rx: None,
+        }
+    }
+
+    /// Observe the transmit queue of `tx` and the receive FIFO `rx`, and
+    /// check whether they made progress within the expectations
+    ///
+    /// `rx` has to be the FIFO the application receives from, and all
+    /// messages have to be queued and received through `tx` and `rx`, as
+    /// their progress is derived from [`DynTx::queued_count`] and
+    /// [`DynRxFifo::read_count`].
+    pub fn poll(
+        &mut self,
+        aux: &impl DynAux,
+        tx: &impl DynTx,
+        rx: &impl DynRxFifo,
+    ) -> LivenessVerdict {
+        let operational = aux.is_operational() && !aux.is_bus_off();
+        self.observe(
+            aux.timestamp(),
+            operational.then(|| (tx.queue_len(), tx.queued_count())),
+            operational.then(|| (rx.len(), rx.read_count())),
+        )
+    }
+
+    /// Pure part of [`Self::poll`], with the fill level and counter of the
+    /// transmit queue and the receive FIFO, if the peripheral is operational
+    fn observe(
+        &mut self,
+        timestamp: u16,
+        tx: Option<(usize, u32)>,
+        rx: Option<(usize, u32)>,
+    ) -> LivenessVerdict {
+        let now = self
+            .clock
+            .get_or_insert_with(|| Extender::new(timestamp))
+            .update(timestamp);
+        let (Some((tx_len, queued_count)), Some((rx_len, read_count))) = (tx, rx) else {
+            self.tx = None;
+            self.rx = None;
+            return LivenessVerdict::NotOperational;
+        };
+        let tx = match &mut self.tx {
+            Some(tx) => {
+                tx.update(now, tx_len, queued_count);
+                tx
+            }
+            tx @ None => tx.insert(TxProgress::new(now, tx_len, queued_count)),
+        };
+        let tx_stalled_for = tx.stalled_for(now);
+        let rx = match &mut self.rx {
+            Some(rx) => {
+                rx.update(now, rx_len, read_count);
+                rx
+            }
+            rx @ None => rx.insert(RxProgress::new(now, rx_len, read_count)),
+        };
+        let rx_stalled_for = rx.stalled_for(now);
+
+        let exceeds = |stalled_for: u64, limit: Option<u64>| matches!(limit, Some(limit) if stalled_for > limit);
+        if exceeds(tx_stalled_for, self.expectations.tx_drain_within) {
+            LivenessVerdict::Stalled {
+                subsystem: Subsystem::Tx,
+                since_ticks: tx_stalled_for,
+            }
+        } else if exceeds(rx_stalled_for, self.expectations.rx_activity_within) {
+            LivenessVerdict::Stalled {
+                subsystem: Subsystem::Rx,
+                since_ticks: rx_stalled_for,
+            }
+        } else {
+            LivenessVerdict::Alive
+        }
+    }
+}
+
+/// Parameters of a [`Ping`] and of the responding [`pong`]
+#[derive(Copy, Clone, Debug)]
+pub struct PingConfig<'a> {
+    /// Identifier of the ping. The peer echoes it with the next higher
+    /// identifier of the same format, see [`Self::echo_id`].
+    pub id: Id,
+    /// Data of the ping, which the peer echoes unchanged
+    pub payload: &'a [u8],
+    /// Longest period in timestamp ticks to wait for the echo
+    pub timeout_ticks: u64,
+}
+
+impl PingConfig<'_> {
+    /// Identifier of the echo, `None` if [`Self::id`] is the highest one of its
+    /// format
+    pub fn echo_id(&self) -> Option<Id> {
+        echo_id(self.id)
+    }
+}
+
+fn echo_id(id: Id) -> Option<Id> {
+    match id {
+        Id::Standard(id) => StandardId::new(id.as_raw().checked_add(1)?).map(Id::Standard),
+        Id::Extended(id) => ExtendedId::new(id.as_raw().checked_add(1)?).map(Id::Extended),
+    }
+}
+
+/// Outcome of a successful [`Ping`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PingReport {
+    /// Ticks from queueing the ping to the start of the reception of the echo
+    pub rtt_ticks: u64,
+    /// `true` if the echo carried the payload of the ping
+    pub payload_matches: bool,
+}
+
+/// Reasons for a [`Ping`] or [`pong`] to fail
+#[derive(Debug)]
+pub enum PingError {
+    /// The identifier of the ping is the highest one of its format, so there
+    /// is no identifier for the echo
+    NoEchoId,
+    /// The payload does not fit in a transmit element
+    TooMuchData,
+    /// The message was rejected by the transmit buffers
+    Transmit(Error),
+    /// The transmission of the ping was cancelled or aborted
+    Cancelled,
+    /// No echo arrived within [`PingConfig::timeout_ticks`]
+    Timeout,
+    /// The peripheral is not in normal operation or is bus off
+    NotOperational,
+}
+
+impl From<TransmitError> for nb::Error<PingError> {
+    fn from(error: TransmitError) -> Self {
+        match error {
+            TransmitError::WouldBlock(_) => nb::Error::WouldBlock,
+            TransmitError::Other(error) => nb::Error::Other(PingError::Transmit(error)),
+        }
+    }
+}
+
+/// Poll-style link check sending a ping and waiting for its echo, see the
+/// [module documentation](self#ping)
+#[derive(Clone, Debug)]
+pub struct Ping<'a> {
+    config: PingConfig<'a>,
+    state: Option<PingState>,
+}
+
+/// Progress of a [`Ping`] since its first poll
+#[derive(Copy, Clone, Debug)]
+struct PingState {
+    clock: Extender,
+    started_at: u64,
+    /// The queued ping and when it was queued
+    sent: Option<(TxToken, u64)>,
+}
+
+impl<'a> Ping<'a> {
+    /// Create a ping with `config`
+    ///
+    /// The ping is sent by the first call to [`Self::poll`].
+    pub const fn new(config: PingConfig<'a>) -> Self {
+        Self {
+            config,
+            state: None,
+        }
+    }
+
+    /// Queue the ping through `tx` if it has not been queued yet, and look for
+    /// the echo in `rx`
+    ///
+    /// Other messages received from `rx` are discarded, so it should be a
+    /// FIFO the filters only store the echo in while pinging. Fails with
+    /// [`nb::Error::WouldBlock`] until the echo arrives or the timeout, which
+    /// starts with the first poll, expires. The next poll after that sends a
+    /// new ping.
+    pub fn poll<T, R>(
+        &mut self,
+        tx: &mut T,
+        rx: &mut R,
+        aux: &impl DynAux,
+    ) -> nb::Result<PingReport, PingError>
+    where
+        T: DynTx,
+        T::Message: tx::AnyMessage,
+        R: DynRxFifo,
+        R::Message: rx::AnyMessage,
+    {
+        let result = self.step(tx, rx, aux);
+        if !matches!(result, Err(nb::Error::WouldBlock)) {
+            self.state = None;
+        }
+        result
+    }
+
+    fn step<T, R>(
+        &mut self,
+        tx: &mut T,
+        rx: &mut R,
+        aux: &impl DynAux,
+    ) -> nb::Result<PingReport, PingError>
+    where
+        T: DynTx,
+        T::Message: tx::AnyMessage,
+        R: DynRxFifo,
+        R::Message: rx::AnyMessage,
+    {
+        let echo_id = self.config.echo_id().ok_or(PingError::NoEchoId)?;
+        if !aux.is_operational() || aux.is_bus_off() {
+            return Err(PingError::NotOperational.into());
+        }
+        let timestamp = aux.timestamp();
+        let state = self.state.get_or_insert_with(|| PingState {
+            clock: Extender::new(timestamp),
+            started_at: u64::from(timestamp),
+            sent: None,
+        });
+        let now = state.clock.update(timestamp);
+        let sent_at = match state.sent {
+            Some((token, sent_at)) => {
+                if tx.status_of(token) == TxSlotStatus::Cancelled {
+                    return Err(PingError::Cancelled.into());
+                }
+                sent_at
+            }
+            None if now - state.started_at > self.config.timeout_ticks => {
+                return Err(PingError::Timeout.into());
+            }
+            None => {
+                let message = data_frame(self.config.id, self.config.payload)?;
+                let token = tx.transmit_queued_indexed(message)?;
+                state.sent = Some((token, now));
+                return Err(nb::Error::WouldBlock);
+            }
+        };
+        while let Ok(message) = rx.receive() {
+            if message.id() == echo_id && !message.is_remote_frame() {
+                let received_at = state.clock.extend(rx::AnyMessage::timestamp(&message));
+                return Ok(PingReport {
+                    rtt_ticks: received_at.saturating_sub(sent_at),
+                    payload_matches: message.data() == self.config.payload,
+                });
+            }
+        }
+        if now - state.started_at > self.config.timeout_ticks {
+            Err(PingError::Timeout.into())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+/// Send a ping with `config` through `tx` and wait for its echo in `rx`,
+/// see [`Ping::poll`]
+pub fn ping<T, R>(
+    tx: &mut T,
+    rx: &mut R,
+    aux: &impl DynAux,
+    config: PingConfig<'_>,
+) -> Result<PingReport, PingError>
+where
+    T: DynTx,
+    T::Message: tx::AnyMessage,
+    R: DynRxFifo,
+    R::Message: rx::AnyMessage,
+{
+    let mut ping = Ping::new(config);
+    nb::block!(ping.poll(tx, rx, aux))
+}
+
+/// Echo `message` through `tx` with the next higher identifier if it is a
+/// ping with the identifier `id`
+///
+/// Returns `false` for other messages, which are left to the application.
+/// Fails with [`nb::Error::WouldBlock`] if the transmit queue is full, in
+/// which case the call can be repeated with the same message.
+pub fn pong<T: DynTx>(
+    tx: &mut T,
+    message: &impl rx::AnyMessage,
+    id: Id,
+) -> nb::Result<bool, PingError>
+where
+    T::Message: tx::AnyMessage,
+{
+    if message.id() != id || message.is_remote_frame() {
+        return Ok(false);
+    }
+    let echo_id = echo_id(id).ok_or(PingError::NoEchoId)?;
+    tx.transmit_queued_indexed(data_frame(echo_id, message.data())?)?;
+    Ok(true)
+}
+
+/// Classic data frame with `id` and `payload`
+fn data_frame<M: tx::AnyMessage>(id: Id, payload: &[u8]) -> Result<M, PingError> {
+    M::new(tx::MessageBuilder {
+        id,
+        frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(payload)),
+        store_tx_event: None,
+    })
+    .map_err(|_| PingError::TooMuchData)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stalled(subsystem: Subsystem, since_ticks: u64) -> LivenessVerdict {
+        LivenessVerdict::Stalled {
+            subsystem,
+            since_ticks,
+        }
+    }
+
+    fn tx_only(limit: u64) -> Liveness {
+        Liveness::new(Expectations {
+            tx_drain_within: Some(limit),
+            rx_activity_within: None,
+        })
+    }
+
+    fn rx_only(limit: u64) -> Liveness {
+        Liveness::new(Expectations {
+            tx_drain_within: None,
+            rx_activity_within: Some(limit),
+        })
+    }
+
+    const RX_IDLE: Option<(usize, u32)> = Some((0, 0));
+    const TX_IDLE: Option<(usize, u32)> = Some((0, 0));
+
+    #[test]
+    fn tx_queue_is_stalled_when_nothing_leaves_it() {
+        let mut liveness = tx_only(100);
+        assert_eq!(
+            liveness.observe(0, Some((2, 2)), RX_IDLE),
+            LivenessVerdict::Alive
+        );
+        assert_eq!(
+            liveness.observe(100, Some((2, 2)), RX_IDLE),
+            LivenessVerdict::Alive
+        );
+        assert_eq!(
+            liveness.observe(101, Some((2, 2)), RX_IDLE),
+            stalled(Subsystem::Tx, 101)
+        );
+        // Queueing more does not count as progress
+        assert_eq!(
+            liveness.observe(150, Some((3, 3)), RX_IDLE),
+            stalled(Subsystem::Tx, 150)
+        );
+        // One message left the queue
+        assert_eq!(
+            liveness.observe(160, Some((2, 3)), RX_IDLE),
+            LivenessVerdict::Alive
+        );
+        assert_eq!(
+            liveness.observe(261, Some((2, 3)), RX_IDLE),
+            stalled(Subsystem::Tx, 101)
+        );
+    }
+
+    #[test]
+    fn refilled_tx_queue_makes_progress() {
+        let mut liveness = tx_only(100);
+        liveness.observe(0, Some((4, 4)), RX_IDLE);
+        // Full at every poll, but refilled in between
+        for (poll, timestamp) in (1..10).zip((80..).step_by(80)) {
+            assert_eq!(
+                liveness.observe(timestamp, Some((4, 4 + poll * 4)), RX_IDLE),
+                LivenessVerdict::Alive
+            );
+        }
+        // Including across the wraparound of the counter
+        liveness.observe(800, Some((4, u32::MAX)), RX_IDLE);
+        assert_eq!(
+            liveness.observe(890, Some((4, 3)), RX_IDLE),
+            LivenessVerdict::Alive
+        );
+    }
+
+    #[test]
+    fn empty_tx_queue_is_never_stalled() {
+        let mut liveness = tx_only(100);
+        liveness.observe(0, Some((1, 1)), RX_IDLE);
+        assert_eq!(
+            liveness.observe(1000, Some((0, 1)), RX_IDLE),
+            LivenessVerdict::Alive
+        );
+        // The period starts when a message is queued
+        liveness.observe(3000, Some((1, 2)), RX_IDLE);
+        assert_eq!(
+            liveness.observe(3101, Some((1, 2)), RX_IDLE),
+            stalled(Subsystem::Tx, 101)
+        );
+    }
+
+    #[test]
+    fn rx_fifo_is_stalled_without_stored_messages() {
+        let mut liveness = rx_only(100);
+        liveness.observe(0, TX_IDLE, Some((0, 0)));
+        // Read out in between
+        assert_eq!(
+            liveness.observe(90, TX_IDLE, Some((0, 3))),
+            LivenessVerdict::Alive
+        );
+        // Still waiting to be read out
+        assert_eq!(
+            liveness.observe(180, TX_IDLE, Some((1, 3))),
+            LivenessVerdict::Alive
+        );
+        assert_eq!(
+            liveness.observe(270, TX_IDLE, Some((0, 4))),
+            LivenessVerdict::Alive
+        );
+        assert_eq!(
+            liveness.observe(281, TX_IDLE, Some((0, 4))),
+            stalled(Subsystem::Rx, 101)
+        );
+    }
+
+    #[test]
+    fn stalls_are_measured_across_timestamp_wraparounds() {
+        let mut liveness = tx_only(0x1_0000);
+        liveness.observe(0xff00, Some((1, 1)), RX_IDLE);
+        for timestamp in [0x7f00, 0xff00] {
+            assert_eq!(
+                liveness.observe(timestamp, Some((1, 1)), RX_IDLE),
+                LivenessVerdict::Alive
+            );
+        }
+        assert_eq!(
+            liveness.observe(0xff01, Some((1, 1)), RX_IDLE),
+            stalled(Subsystem::Tx, 0x1_0001)
+        );
+    }
+
+    #[test]
+    fn tracking_restarts_after_leaving_operation() {
+        let mut liveness = Liveness::new(Expectations {
+            tx_drain_within: Some(100),
+            rx_activity_within: Some(100),
+        });
+        liveness.observe(0, Some((1, 1)), RX_IDLE);
+        assert_eq!(
+            liveness.observe(200, None, None),
+            LivenessVerdict::NotOperational
+        );
+        assert_eq!(
+            liveness.observe(250, Some((1, 1)), RX_IDLE),
+            LivenessVerdict::Alive
+        );
+        // Both stalled, the transmit queue is reported
+        assert_eq!(
+            liveness.observe(351, Some((1, 1)), RX_IDLE),
+            stalled(Subsystem::Tx, 101)
+        );
+        assert_eq!(
+            liveness.observe(352, Some((0, 1)), RX_IDLE),
+            stalled(Subsystem::Rx, 102)
+        );
+    }
+
+    #[test]
+    fn components_are_polled() {
+        use crate::bus::CanConfigurable;
+        use crate::message::tx;
+        use crate::messageram::SharedMemory;
+        use crate::mock::{self, mock_can, Caps};
+        use fugit::RateExtU32;
+
+        mock_can!(Can0);
+        const CCCR: usize = 0x18;
+        const TSCV: usize = 0x24;
+        const TXFQS: usize = 0xc4;
+        const TXBAR: usize = 0xd0;
+        let regs = Can0::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = mock::Dependencies::new(&memory);
+        let can = CanConfigurable::<Can0, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        let mut can = can.finalize_recoverable().unwrap();
+        let mut liveness = tx_only(100);
+
+        regs.write(TXFQS, 2 << 16);
+        let message = tx::MessageBuilder {
+            id: embedded_can::StandardId::new(0x42).unwrap().into(),
+            frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(&[])),
+            store_tx_event: None,
+        }
+        .build()
+        .unwrap();
+        can.tx.transmit_queued_indexed(message).unwrap();
+        assert_eq!(can.tx.queue_len(), 1);
+        assert_eq!(can.tx.queued_count(), 1);
+        assert_eq!(
+            liveness.poll(&can.aux, &can.tx, &can.rx_fifo_0),
+            LivenessVerdict::Alive
+        );
+        regs.write(TSCV, 200);
+        assert_eq!(
+            liveness.poll(&can.aux, &can.tx, &can.rx_fifo_0),
+            stalled(Subsystem::Tx, 200)
+        );
+        regs.write(TXBAR, 0);
+        assert_eq!(
+            liveness.poll(&can.aux, &can.tx, &can.rx_fifo_0),
+            LivenessVerdict::Alive
+        );
+        regs.write(CCCR, regs.read(CCCR) | 1);
+        assert_eq!(
+            liveness.poll(&can.aux, &can.tx, &can.rx_fifo_0),
+            LivenessVerdict::NotOperational
+        );
+    }
+
+    #[test]
+    fn idle_bus_is_alive_without_expectations() {
+        let mut liveness = Liveness::new(Expectations::default());
+        for timestamp in (0..=0xffff).step_by(0x1000) {
+            assert_eq!(
+                liveness.observe(timestamp, Some((4, 4)), RX_IDLE),
+                LivenessVerdict::Alive
+            );
+        }
+    }
+
+    mod link {
+        use super::*;
+        use crate::bus::{Can, CanConfigurable};
+        use crate::messageram::SharedMemory;
+        use crate::mock::{self, mock_can, Caps};
+        use fugit::RateExtU32;
+
+        const TSCV: usize = 0x24;
+        const RXF0C: usize = 0xa0;
+        const RXF0S: usize = 0xa4;
+        const TXBC: usize = 0xc0;
+        const TXBAR: usize = 0xd0;
+        /// First buffer of the transmit queue, after the dedicated ones
+        const QUEUE_START: usize = 2;
+
+        const CONFIG: PingConfig<'static> = PingConfig {
+            id: Id::Standard(StandardId::MAX),
+            payload: b"ping",
+            timeout_ticks: 100,
+        };
+
+        fn config() -> PingConfig<'static> {
+            PingConfig {
+                id: StandardId::new(0x7f0).unwrap().into(),
+                ..CONFIG
+            }
+        }
+
+        /// Mock peripheral with the start of its Message RAM, standing in for
+        /// a node on the bus
+        struct Node {
+            regs: &'static mock::Registers,
+            memory_start: usize,
+        }
+
+        impl Node {
+            /// Address of element `index` of the Message RAM section whose
+            /// start address is in `register`
+            fn element<T>(&self, register: usize, index: usize) -> *mut T {
+                let offset = (self.regs.read(register) as usize).wrapping_sub(self.memory_start);
+                (self.memory_start + (offset & 0xfffc) + index * core::mem::size_of::<T>())
+                    as *mut T
+            }
+
+            /// Stores `message` in RX FIFO 0 as if it was received at
+            /// `timestamp`
+            fn receive(&self, message: impl Into<rx::Message<8>>, timestamp: u16) {
+                let mut message = message.into();
+                message.set_timestamp(timestamp);
+                // Safety: The element is within the Message RAM of the node.
+                unsafe {
+                    self.element::<rx::Message<8>>(RXF0C, 0)
+                        .write_volatile(message)
+                };
+                self.regs.write(RXF0S, 1);
+            }
+
+            /// Delivers the message last queued by this node to `to`, like a
+            /// loopback wiring of the bus
+            fn wire(&self, to: &Node, timestamp: u16) {
+                // Safety: The element is within the Message RAM of the node.
+                let message: tx::Message<8> = unsafe {
+                    self.element::<tx::Message<8>>(TXBC, QUEUE_START)
+                        .read_volatile()
+                };
+                to.receive(message, timestamp);
+            }
+        }
+
+        fn node<'a, P: mcan_core::CanId>(
+            regs: &'static mock::Registers,
+            memory: &'a mut SharedMemory<Caps>,
+        ) -> (Node, Can<'a, P, mock::Dependencies, Caps>) {
+            let memory_start = memory as *const _ as usize;
+            let dependencies = mock::Dependencies::new(memory);
+            let can = CanConfigurable::<P, _, _>::new(500.kHz(), dependencies, memory).unwrap();
+            let can = can.finalize_recoverable().unwrap();
+            (Node { regs, memory_start }, can)
+        }
+
+        #[test]
+        fn ping_is_echoed_by_pong() {
+            mock_can!(Can1);
+            mock_can!(Can2);
+            let (mut memory_a, mut memory_b) = (SharedMemory::new(), SharedMemory::new());
+            let (a, mut can_a) = node::<Can1>(Can1::regs(), &mut memory_a);
+            let (b, mut can_b) = node::<Can2>(Can2::regs(), &mut memory_b);
+            let mut ping = Ping::new(config());
+
+            a.regs.write(TSCV, 100);
+            let poll = |ping: &mut Ping, can: &mut Can<'_, Can1, _, _>| {
+                ping.poll(&mut can.tx, &mut can.rx_fifo_0, &can.aux)
+            };
+            assert!(matches!(
+                poll(&mut ping, &mut can_a),
+                Err(nb::Error::WouldBlock)
+            ));
+            a.wire(&b, 0);
+
+            let received = can_b.rx_fifo_0.receive().unwrap();
+            let other = StandardId::new(0x100).unwrap().into();
+            assert!(matches!(pong(&mut can_b.tx, &received, other), Ok(false)));
+            assert!(matches!(
+                pong(&mut can_b.tx, &received, config().id),
+                Ok(true)
+            ));
+            b.wire(&a, 150);
+
+            a.regs.write(TSCV, 160);
+            assert_eq!(
+                poll(&mut ping, &mut can_a).unwrap(),
+                PingReport {
+                    rtt_ticks: 50,
+                    payload_matches: true,
+                }
+            );
+        }
+
+        #[test]
+        fn ping_times_out_and_checks_the_echoed_payload() {
+            mock_can!(Can3);
+            let mut memory = SharedMemory::new();
+            let (a, mut can) = node::<Can3>(Can3::regs(), &mut memory);
+            let mut poll =
+                |config| Ping::new(config).poll(&mut can.tx, &mut can.rx_fifo_0, &can.aux);
+            assert!(matches!(
+                poll(CONFIG),
+                Err(nb::Error::Other(PingError::NoEchoId))
+            ));
+
+            let mut ping = Ping::new(config());
+            let mut poll = |ping: &mut Ping| ping.poll(&mut can.tx, &mut can.rx_fifo_0, &can.aux);
+            assert!(matches!(poll(&mut ping), Err(nb::Error::WouldBlock)));
+            a.regs.write(TSCV, 100);
+            assert!(matches!(poll(&mut ping), Err(nb::Error::WouldBlock)));
+            a.regs.write(TSCV, 101);
+            assert!(matches!(
+                poll(&mut ping),
+                Err(nb::Error::Other(PingError::Timeout))
+            ));
+
+            // The next poll starts over once the first ping left the queue
+            a.regs.write(TXBAR, 0);
+            assert!(matches!(poll(&mut ping), Err(nb::Error::WouldBlock)));
+            let echo: tx::Message<8> = data_frame(config().echo_id().unwrap(), b"pong").unwrap();
+            a.receive(echo, 120);
+            assert_eq!(
+                poll(&mut ping).unwrap(),
+                PingReport {
+                    rtt_ticks: 19,
+                    payload_matches: false,
+                }
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 //! Message filters
+use crate::safety::crc16_ccitt;
 use core::marker::PhantomData;
 use embedded_can::{ExtendedId, StandardId};
 use vcell::VolatileCell;
@@ -10,13 +11,21 @@ pub type FiltersExtended<'a, P> = Filters<'a, P, FilterExtendedId>;
 
 /// Acceptance filters for incoming messages. It is recommended to use the type
 /// aliases [`FiltersStandard`] and [`FiltersExtended`].
+///
+/// The peripheral scans the filters in order for every received frame, and
+/// the scan time grows with the number of filters. Only the pushed filters
+/// are scanned, not the whole capacity, so that large capacities do not slow
+/// down filtering. The list sizes are programmed when the configuration is
+/// finalized, including after a reconfiguration.
 pub struct Filters<'a, P, T> {
     memory: &'a mut [VolatileCell<T>],
     len: usize,
+    /// CRC-16-CCITT over the pushed filters, for [`Self::verify`]
+    checksum: u16,
     _markers: PhantomData<P>,
 }
 
-impl<'a, P, T: Copy> Filters<'a, P, T> {
+impl<'a, P, T: RawFilter> Filters<'a, P, T> {
     /// # Safety
     /// All filters are assumed to be disabled initially. This is the case if
     /// the memory is zeroed.
@@ -28,26 +37,226 @@ impl<'a, P, T: Copy> Filters<'a, P, T> {
         Self {
             memory,
             len: 0,
+            checksum: CHECKSUM_INIT,
             _markers: PhantomData,
         }
     }
 
-    /// Overwrites the `filter` at `index`.
-    /// Returns back the `filter` if the `index` is out of range.
-    fn set<F: Copy + Into<T>>(&mut self, index: usize, filter: F) -> Result<(), F> {
+    /// Takes over filters programmed before, e.g. by a bootloader. Filters
+    /// pushed later are appended after the last programmed one.
+    ///
+    /// # Safety
+    /// The memory is initialized. All-zero elements are considered disabled.
+    #[cfg(feature = "handoff")]
+    pub(crate) unsafe fn new_programmed(memory: &'a mut [VolatileCell<T>]) -> Self {
+        let len = memory
+            .iter()
+            .rposition(|f| !f.get().is_zeroed())
+            .map_or(0, |index| index + 1);
+        let checksum = memory
+            .iter()
+            .take(len)
+            .fold(CHECKSUM_INIT, |checksum, f| f.get().checksum(checksum));
+        Self {
+            memory,
+            len,
+            checksum,
+            _markers: PhantomData,
+        }
+    }
+
+    /// Writes the `filter` to the element at `index`.
+    /// Returns back the `filter` if the `index` is beyond the capacity.
+    fn write<F: Copy + Into<T>>(&mut self, index: usize, filter: F) -> Result<(), F> {
         self.memory
             .get_mut(index)
             .map(|f| f.set(filter.into()))
             .ok_or(filter)
     }
+
+    /// Checksum of the pushed filters as read back from the Message RAM
+    fn pushed_checksum(&self) -> u16 {
+        self.memory
+            .iter()
+            .take(self.len)
+            .fold(CHECKSUM_INIT, |checksum, f| f.get().checksum(checksum))
+    }
+
     /// Appends a `filter` to the back of the list. Returns the assigned index
     /// if successful. Returns back the `filter` if the list is full.
     pub fn push<F: Copy + Into<T>>(&mut self, filter: F) -> Result<usize, F> {
         let index = self.len;
-        self.set(index, filter)?;
+        self.write(index, filter)?;
         self.len += 1;
+        self.checksum = filter.into().checksum(self.checksum);
         Ok(index)
     }
+
+    /// Overwrites the pushed filter at `index`. Returns back the `filter` if
+    /// `index` is not below [`Self::len`].
+    ///
+    /// Overwriting a filter with a disabled one keeps the indices of the
+    /// following filters, unlike [`Self::remove`].
+    pub fn set<F: Copy + Into<T>>(&mut self, index: usize, filter: F) -> Result<(), F> {
+        if index >= self.len {
+            return Err(filter);
+        }
+        self.write(index, filter)?;
+        self.checksum = self.pushed_checksum();
+        Ok(())
+    }
+
+    /// Decodes the pushed filter at `index` from the Message RAM. Returns
+    /// `None` if `index` is not below [`Self::len`].
+    pub fn get(&self, index: usize) -> Option<Result<T::Filter, FilterDecodeError<T::Raw>>> {
+        self.memory
+            .get(index)
+            .filter(|_| index < self.len)
+            .map(|f| T::Filter::try_from(f.get()))
+    }
+
+    /// Removes the pushed filter at `index` and shifts the following filters
+    /// down by one. Returns the raw words of the removed element, or `None`
+    /// if `index` is not below [`Self::len`].
+    pub fn remove(&mut self, index: usize) -> Option<T::Raw> {
+        let shifted = self.memory.get(index..self.len)?;
+        let removed = shifted.first()?.get().raw();
+        for (to, from) in shifted.iter().zip(shifted.iter().skip(1)) {
+            to.set(from.get());
+        }
+        if let Some(last) = shifted.last() {
+            last.set(T::disabled());
+        }
+        self.len -= 1;
+        self.checksum = self.pushed_checksum();
+        Some(removed)
+    }
+
+    /// Disables all pushed filters
+    pub fn clear(&mut self) {
+        self.memory
+            .iter()
+            .take(self.len)
+            .for_each(|f| f.set(T::disabled()));
+        self.len = 0;
+        self.checksum = CHECKSUM_INIT;
+    }
+
+    /// Number of pushed filters
+    ///
+    /// The peripheral only evaluates this many filters, as the list size is
+    /// programmed from it when the configuration is applied.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no filters have been pushed
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of filter elements allocated in the Message RAM
+    pub fn capacity(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Reads back the element at `index` from the Message RAM, e.g. for
+    /// dumping the filter configuration. Returns `None` if `index` is beyond
+    /// the capacity.
+    pub fn raw(&self, index: usize) -> Option<T::Raw> {
+        self.memory.get(index).map(|f| f.get().raw())
+    }
+
+    /// Reads back the filters from the Message RAM and checks them against a
+    /// checksum of the pushed filters, e.g. in a self-test to detect
+    /// corruption of the Message RAM.
+    ///
+    /// The elements after the pushed filters have to be disabled; the first
+    /// one that is not is reported as [`FilterVerifyError::Mismatch`].
+    pub fn verify(&self) -> Result<(), FilterVerifyError<T::Raw>> {
+        let unused = self.memory.get(self.len..).unwrap_or(&[]);
+        let actual = self.pushed_checksum();
+        if actual != self.checksum {
+            return Err(FilterVerifyError::ChecksumMismatch {
+                expected: self.checksum,
+                actual,
+            });
+        }
+        let disabled = T::disabled().raw();
+        match unused.iter().position(|f| f.get().raw() != disabled) {
+            Some(position) => Err(FilterVerifyError::Mismatch {
+                index: self.len + position,
+                expected: disabled,
+                actual: unused.get(position).map_or(disabled, |f| f.get().raw()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads back the filters from the Message RAM and compares them with
+    /// `expected`, element by element. The elements after the expected ones
+    /// have to be disabled.
+    ///
+    /// If more filters are expected than fit in the Message RAM, the first
+    /// one that does not fit is reported as mismatching a disabled element.
+    pub fn verify_against<F: Copy + Into<T>>(
+        &self,
+        expected: &[F],
+    ) -> Result<(), FilterVerifyError<T::Raw>> {
+        let disabled = T::disabled().raw();
+        let expected_raw = |index: usize| {
+            expected
+                .get(index)
+                .map_or(disabled, |&filter| filter.into().raw())
+        };
+        let mismatch = self
+            .memory
+            .iter()
+            .map(|f| f.get().raw())
+            .enumerate()
+            .find(|&(index, actual)| actual != expected_raw(index))
+            .or((expected.len() > self.memory.len()).then_some((self.memory.len(), disabled)));
+        match mismatch {
+            Some((index, actual)) => Err(FilterVerifyError::Mismatch {
+                index,
+                expected: expected_raw(index),
+                actual,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Initial value of the checksum of [`Filters`]
+const CHECKSUM_INIT: u16 = 0xffff;
+
+/// Difference between the filters in the Message RAM and the expected ones
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FilterVerifyError<R> {
+    /// The element at `index` differs from the expected one
+    Mismatch {
+        /// Index of the first mismatching element
+        index: usize,
+        /// Raw words of the expected element
+        expected: R,
+        /// Raw words read back from the Message RAM
+        actual: R,
+    },
+    /// The filters read back do not match the checksum of the pushed ones
+    ChecksumMismatch {
+        /// Checksum of the pushed filters
+        expected: u16,
+        /// Checksum of the filters read back
+        actual: u16,
+    },
+}
+
+/// Filter element that does not decode into a [`Filter`] or [`ExtFilter`],
+/// e.g. because reserved bits or filter event pins are set
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FilterDecodeError<R> {
+    /// Raw words of the element
+    pub raw: R,
 }
 
 /// 11-bit filter in the peripheral's representation
@@ -59,8 +268,82 @@ pub struct FilterStandardId(pub(super) u32);
 #[derive(Copy, Clone)]
 pub struct FilterExtendedId(pub(super) [u32; 2]);
 
+mod private {
+    pub trait Sealed {
+        /// The element of a disabled filter
+        fn disabled() -> Self;
+        /// Whether all bits are zero, as in a filter that was never programmed
+        #[cfg(feature = "handoff")]
+        fn is_zeroed(&self) -> bool;
+        /// Continues the CRC-16-CCITT `checksum` over the element
+        fn checksum(&self, checksum: u16) -> u16;
+    }
+}
+
+/// Filter element in the peripheral's representation, implemented by
+/// [`FilterStandardId`] and [`FilterExtendedId`]
+pub trait RawFilter: Copy + private::Sealed {
+    /// Raw words of the element
+    type Raw: Copy + PartialEq + core::fmt::Debug;
+    /// Filter that encodes into the element
+    type Filter: Copy + Into<Self> + TryFrom<Self, Error = FilterDecodeError<Self::Raw>>;
+
+    /// Returns the raw words of the element
+    fn raw(&self) -> Self::Raw;
+}
+
+impl private::Sealed for FilterStandardId {
+    fn disabled() -> Self {
+        Filter::Disabled.into()
+    }
+
+    #[cfg(feature = "handoff")]
+    fn is_zeroed(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn checksum(&self, checksum: u16) -> u16 {
+        crc16_ccitt(checksum, &self.0.to_le_bytes())
+    }
+}
+
+impl RawFilter for FilterStandardId {
+    type Raw = u32;
+    type Filter = Filter;
+
+    fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+impl private::Sealed for FilterExtendedId {
+    fn disabled() -> Self {
+        ExtFilter::Disabled.into()
+    }
+
+    #[cfg(feature = "handoff")]
+    fn is_zeroed(&self) -> bool {
+        self.0 == [0; 2]
+    }
+
+    fn checksum(&self, checksum: u16) -> u16 {
+        self.0.iter().fold(checksum, |checksum, word| {
+            crc16_ccitt(checksum, &word.to_le_bytes())
+        })
+    }
+}
+
+impl RawFilter for FilterExtendedId {
+    type Raw = [u32; 2];
+    type Filter = ExtFilter;
+
+    fn raw(&self) -> [u32; 2] {
+        self.0
+    }
+}
+
 /// Message filter field for 11-bit RX messages
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Filter {
     /// The filter is skipped
     Disabled,
@@ -104,7 +387,7 @@ pub enum Filter {
 }
 
 /// Store buffer message types
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SbMsgType {
     /// Store into RX buffer slot poitner to by id
     #[default]
@@ -118,11 +401,14 @@ pub enum SbMsgType {
 }
 
 /// Message filter field for 28-bit RX messages
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ExtFilter {
     /// The filter is skipped
     Disabled,
-    /// Range filter from low to high IDs with XIDAM
+    /// Range filter from low to high IDs with XIDAM, see
+    /// [`CanConfig::extended_id_and_mask`]
+    ///
+    /// [`CanConfig::extended_id_and_mask`]: crate::config::CanConfig::extended_id_and_mask
     MaskedRange {
         /// Action to take on a matched element
         action: Action,
@@ -171,7 +457,7 @@ pub enum ExtFilter {
 }
 
 /// Filter element configurations
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Action {
     /// Store in RX FIFO 0 if filter matches
     StoreFifo0,
@@ -200,6 +486,33 @@ impl From<Action> for u32 {
     }
 }
 
+impl Action {
+    /// Decodes the SFEC or EFEC field, `None` for disabled and store buffer
+    /// elements
+    fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            0x1 => Some(Action::StoreFifo0),
+            0x2 => Some(Action::StoreFifo1),
+            0x3 => Some(Action::Reject),
+            0x4 => Some(Action::Priority),
+            0x5 => Some(Action::PriorityFifo0),
+            0x6 => Some(Action::PriorityFifo1),
+            _ => None,
+        }
+    }
+}
+
+impl SbMsgType {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0x3 {
+            0 => SbMsgType::RxBuffer,
+            1 => SbMsgType::DebugA,
+            2 => SbMsgType::DebugB,
+            _ => SbMsgType::DebugC,
+        }
+    }
+}
+
 impl From<Filter> for FilterStandardId {
     fn from(val: Filter) -> Self {
         let v = match val {
@@ -277,3 +590,359 @@ impl From<ExtFilter> for FilterExtendedId {
         FilterExtendedId([v1, v2])
     }
 }
+
+/// Bits of a standard filter element that are neither reserved nor filter
+/// event pins, by whether it stores into a buffer
+const STANDARD_FILTER_BITS: u32 = 0xffff_07ff;
+const STANDARD_STORE_BUFFER_BITS: u32 = 0x3fff_063f;
+/// Bits of F1 of an extended filter element that are neither reserved nor
+/// filter event pins, by whether it stores into a buffer
+const EXTENDED_FILTER_BITS: u32 = 0xdfff_ffff;
+const EXTENDED_STORE_BUFFER_BITS: u32 = 0x0000_063f;
+
+impl TryFrom<FilterStandardId> for Filter {
+    type Error = FilterDecodeError<u32>;
+
+    /// Decodes an element as the peripheral evaluates it: elements with a
+    /// SFEC of 0 or a SFT of 3 are disabled, whatever their other fields
+    fn try_from(element: FilterStandardId) -> Result<Self, Self::Error> {
+        let raw = element.0;
+        let error = FilterDecodeError { raw };
+        let sfec = raw >> 27 & 0x7;
+        let id1 = StandardId::new((raw >> 16) as u16 & 0x7ff).ok_or(error)?;
+        let id2 = StandardId::new(raw as u16 & 0x7ff).ok_or(error)?;
+        if sfec == 0x7 {
+            if raw & !STANDARD_STORE_BUFFER_BITS != 0 {
+                return Err(error);
+            }
+            return Ok(Filter::StoreBuffer {
+                id: id1,
+                msg_type: SbMsgType::from_bits(raw >> 9),
+                offset: (raw & 0x3f) as u8,
+            });
+        }
+        if raw & !STANDARD_FILTER_BITS != 0 {
+            return Err(error);
+        }
+        let Some(action) = Action::from_bits(sfec) else {
+            return Ok(Filter::Disabled);
+        };
+        Ok(match raw >> 30 {
+            0 => Filter::Range {
+                action,
+                low: id1,
+                high: id2,
+            },
+            1 => Filter::Dual { action, id1, id2 },
+            2 => Filter::Classic {
+                action,
+                filter: id1,
+                mask: id2,
+            },
+            _ => Filter::Disabled,
+        })
+    }
+}
+
+impl TryFrom<FilterExtendedId> for ExtFilter {
+    type Error = FilterDecodeError<[u32; 2]>;
+
+    /// Decodes an element as the peripheral evaluates it: elements with an
+    /// EFEC of 0 are disabled, whatever their other fields
+    fn try_from(element: FilterExtendedId) -> Result<Self, Self::Error> {
+        let [f0, f1] = element.0;
+        let error = FilterDecodeError { raw: element.0 };
+        let efec = f0 >> 29;
+        let id1 = ExtendedId::new(f0 & 0x1fff_ffff).ok_or(error)?;
+        if efec == 0x7 {
+            if f1 & !EXTENDED_STORE_BUFFER_BITS != 0 {
+                return Err(error);
+            }
+            return Ok(ExtFilter::StoreBuffer {
+                id: id1,
+                msg_type: SbMsgType::from_bits(f1 >> 9),
+                offset: (f1 & 0x3f) as u8,
+            });
+        }
+        if f1 & !EXTENDED_FILTER_BITS != 0 {
+            return Err(error);
+        }
+        let Some(action) = Action::from_bits(efec) else {
+            return Ok(ExtFilter::Disabled);
+        };
+        let id2 = ExtendedId::new(f1 & 0x1fff_ffff).ok_or(error)?;
+        Ok(match f1 >> 30 {
+            0 => ExtFilter::MaskedRange {
+                action,
+                low: id1,
+                high: id2,
+            },
+            1 => ExtFilter::Dual { action, id1, id2 },
+            2 => ExtFilter::Classic {
+                action,
+                filter: id1,
+                mask: id2,
+            },
+            _ => ExtFilter::Range {
+                action,
+                low: id1,
+                high: id2,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn standard(raw: u16) -> Filter {
+        Filter::Classic {
+            action: Action::StoreFifo0,
+            filter: StandardId::new(raw).unwrap(),
+            mask: StandardId::MAX,
+        }
+    }
+
+    fn extended(raw: u32) -> ExtFilter {
+        ExtFilter::Classic {
+            action: Action::StoreFifo1,
+            filter: ExtendedId::new(raw).unwrap(),
+            mask: ExtendedId::MAX,
+        }
+    }
+
+    #[test]
+    fn corrupted_standard_filters_are_detected() {
+        let mut memory: [VolatileCell<FilterStandardId>; 4] =
+            core::array::from_fn(|_| VolatileCell::new(FilterStandardId(0)));
+        let expected = [standard(0x100), standard(0x200)];
+        let raw = [
+            FilterStandardId::from(expected[0]).0,
+            FilterStandardId::from(expected[1]).0,
+        ];
+        let mut filters = unsafe { Filters::<(), _>::new(&mut memory) };
+        for filter in expected {
+            filters.push(filter).ok().unwrap();
+        }
+        assert_eq!(filters.verify(), Ok(()));
+        assert_eq!(filters.verify_against(&expected), Ok(()));
+        assert_eq!(filters.raw(1), Some(raw[1]));
+        assert_eq!(filters.raw(4), None);
+
+        // A flipped bit in a pushed element
+        filters.memory[1].set(FilterStandardId(raw[1] ^ 1 << 3));
+        assert!(matches!(
+            filters.verify(),
+            Err(FilterVerifyError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(
+            filters.verify_against(&expected),
+            Err(FilterVerifyError::Mismatch {
+                index: 1,
+                expected: raw[1],
+                actual: raw[1] ^ 1 << 3,
+            })
+        );
+
+        // A stray element after the pushed ones
+        filters.memory[1].set(FilterStandardId(raw[1]));
+        filters.memory[3].set(FilterStandardId(raw[0]));
+        assert_eq!(
+            filters.verify(),
+            Err(FilterVerifyError::Mismatch {
+                index: 3,
+                expected: 0,
+                actual: raw[0],
+            })
+        );
+        assert_eq!(
+            filters.verify_against(&expected),
+            Err(FilterVerifyError::Mismatch {
+                index: 3,
+                expected: 0,
+                actual: raw[0],
+            })
+        );
+    }
+
+    #[test]
+    fn corrupted_extended_filters_are_detected() {
+        let mut memory: [VolatileCell<FilterExtendedId>; 2] =
+            core::array::from_fn(|_| VolatileCell::new(FilterExtendedId([0; 2])));
+        let expected = [extended(0x1000), extended(0x2000)];
+        let raw = FilterExtendedId::from(expected[0]).0;
+        let mut filters = unsafe { Filters::<(), _>::new(&mut memory) };
+        filters.push(expected[0]).ok().unwrap();
+        assert_eq!(filters.verify(), Ok(()));
+        assert_eq!(filters.raw(0), Some(raw));
+
+        // The second expected filter was never pushed
+        assert_eq!(
+            filters.verify_against(&expected),
+            Err(FilterVerifyError::Mismatch {
+                index: 1,
+                expected: FilterExtendedId::from(expected[1]).0,
+                actual: [0; 2],
+            })
+        );
+
+        // More filters are expected than fit
+        filters.push(expected[1]).ok().unwrap();
+        let too_many = [expected[0], expected[1], extended(0x3000)];
+        assert_eq!(
+            filters.verify_against(&too_many),
+            Err(FilterVerifyError::Mismatch {
+                index: 2,
+                expected: FilterExtendedId::from(too_many[2]).0,
+                actual: [0; 2],
+            })
+        );
+
+        // A flipped bit in the second word
+        filters.memory[0].set(FilterExtendedId([raw[0], raw[1] ^ 1 << 30]));
+        assert!(matches!(
+            filters.verify(),
+            Err(FilterVerifyError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn standard_filters_round_trip() {
+        let id = |raw| StandardId::new(raw).unwrap();
+        let filters = [
+            Filter::Disabled,
+            Filter::Range {
+                action: Action::StoreFifo0,
+                low: id(0x100),
+                high: id(0x1ff),
+            },
+            Filter::Dual {
+                action: Action::Reject,
+                id1: id(0x7ff),
+                id2: id(0),
+            },
+            Filter::Classic {
+                action: Action::PriorityFifo1,
+                filter: id(0x123),
+                mask: id(0x7f0),
+            },
+            Filter::StoreBuffer {
+                id: id(0x456),
+                msg_type: SbMsgType::DebugC,
+                offset: 0x3f,
+            },
+        ];
+        for filter in filters {
+            assert_eq!(Filter::try_from(FilterStandardId::from(filter)), Ok(filter));
+        }
+        for action in [Action::StoreFifo1, Action::Priority, Action::PriorityFifo0] {
+            let filter = Filter::Dual {
+                action,
+                id1: id(1),
+                id2: id(2),
+            };
+            assert_eq!(Filter::try_from(FilterStandardId::from(filter)), Ok(filter));
+        }
+
+        // Disabled by SFT, with other fields set
+        assert_eq!(
+            Filter::try_from(FilterStandardId(3 << 30 | 1 << 27 | 0x100)),
+            Ok(Filter::Disabled)
+        );
+        // Reserved bits and filter event pins
+        for raw in [1 << 27 | 1 << 11, 7 << 27 | 1 << 6] {
+            assert_eq!(
+                Filter::try_from(FilterStandardId(raw)),
+                Err(FilterDecodeError { raw })
+            );
+        }
+    }
+
+    #[test]
+    fn extended_filters_round_trip() {
+        let id = |raw| ExtendedId::new(raw).unwrap();
+        let filters = [
+            ExtFilter::Disabled,
+            ExtFilter::MaskedRange {
+                action: Action::StoreFifo1,
+                low: id(0x1000),
+                high: id(0x1fff),
+            },
+            ExtFilter::Dual {
+                action: Action::Priority,
+                id1: id(0x1fff_ffff),
+                id2: id(0),
+            },
+            ExtFilter::Classic {
+                action: Action::PriorityFifo0,
+                filter: id(0x1234_5678),
+                mask: id(0x1fff_ff00),
+            },
+            ExtFilter::Range {
+                action: Action::Reject,
+                low: id(0x10),
+                high: id(0x20),
+            },
+            ExtFilter::StoreBuffer {
+                id: id(0x0abc_def0),
+                msg_type: SbMsgType::DebugA,
+                offset: 5,
+            },
+        ];
+        for filter in filters {
+            assert_eq!(
+                ExtFilter::try_from(FilterExtendedId::from(filter)),
+                Ok(filter)
+            );
+        }
+
+        // Reserved bit of F1 and filter event pins
+        for raw in [[1 << 29, 1 << 29], [7 << 29, 1 << 8]] {
+            assert_eq!(
+                ExtFilter::try_from(FilterExtendedId(raw)),
+                Err(FilterDecodeError { raw })
+            );
+        }
+    }
+
+    #[test]
+    fn pushed_filters_are_overwritten_and_removed() {
+        let mut memory: [VolatileCell<FilterStandardId>; 4] =
+            core::array::from_fn(|_| VolatileCell::new(FilterStandardId(0)));
+        let mut filters = unsafe { Filters::<(), _>::new(&mut memory) };
+        assert_eq!(filters.capacity(), 4);
+        for raw in [0x100, 0x200, 0x300] {
+            filters.push(standard(raw)).ok().unwrap();
+        }
+        assert_eq!(filters.get(1), Some(Ok(standard(0x200))));
+        assert_eq!(filters.get(3), None);
+
+        // Only pushed filters can be overwritten
+        assert!(filters.set(3, standard(0x400)).is_err());
+        filters.set(1, Filter::Disabled).unwrap();
+        assert_eq!(filters.get(1), Some(Ok(Filter::Disabled)));
+        assert_eq!(filters.verify(), Ok(()));
+
+        assert_eq!(filters.remove(3), None);
+        assert_eq!(
+            filters.remove(0),
+            Some(FilterStandardId::from(standard(0x100)).0)
+        );
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters.get(0), Some(Ok(Filter::Disabled)));
+        assert_eq!(filters.get(1), Some(Ok(standard(0x300))));
+        assert_eq!(filters.raw(2), Some(0));
+        assert_eq!(filters.verify(), Ok(()));
+        assert_eq!(
+            filters.verify_against(&[Filter::Disabled, standard(0x300)]),
+            Ok(())
+        );
+
+        filters.clear();
+        assert!(filters.is_empty());
+        assert_eq!(filters.verify(), Ok(()));
+        assert_eq!(filters.verify_against::<Filter>(&[]), Ok(()));
+        assert_eq!(filters.push(standard(0x500)), Ok(0));
+    }
+}
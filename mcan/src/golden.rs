@@ -0,0 +1,246 @@
+//! Register writes of canonical configurations checked against golden files
+//!
+//! The configuration computes many register fields, e.g. the bit timings with
+//! their `-1` adjustments, and has to write them in a particular order, e.g.
+//! with CCE set before the protected registers. These tests record every
+//! register write from [`CanConfigurable::new`] to
+//! [`CanConfigurable::finalize_recoverable`] and compare them with the files
+//! in `golden/`, one write per line with decoded fields where it helps
+//! review. Unlike the hardware, the mock does not clear CCE along with INIT.
+//!
+//! If a change of the writes is intended, rerun the tests with
+//! `MCAN_UPDATE_GOLDEN=1` to rewrite the files and review their diff.
+
+extern crate std;
+
+use crate::bus::CanConfigurable;
+use crate::config::{BitTiming, Loopback, Mode};
+use crate::filter::{Action, ExtFilter, Filter};
+use crate::messageram::SharedMemory;
+use crate::mock::{self, mock_can, Caps};
+use core::fmt::Write as _;
+use embedded_can::{ExtendedId, StandardId};
+use fugit::{HertzU32, RateExtU32};
+use std::string::String;
+
+/// Names of the registers by offset
+const NAMES: [(usize, &str); 47] = [
+    (0x00, "CREL"),
+    (0x04, "ENDN"),
+    (0x08, "CUST"),
+    (0x0c, "DBTP"),
+    (0x10, "TEST"),
+    (0x14, "RWD"),
+    (0x18, "CCCR"),
+    (0x1c, "NBTP"),
+    (0x20, "TSCC"),
+    (0x24, "TSCV"),
+    (0x28, "TOCC"),
+    (0x2c, "TOCV"),
+    (0x40, "ECR"),
+    (0x44, "PSR"),
+    (0x48, "TDCR"),
+    (0x50, "IR"),
+    (0x54, "IE"),
+    (0x58, "ILS"),
+    (0x5c, "ILE"),
+    (0x80, "GFC"),
+    (0x84, "SIDFC"),
+    (0x88, "XIDFC"),
+    (0x90, "XIDAM"),
+    (0x94, "HPMS"),
+    (0x98, "NDAT1"),
+    (0x9c, "NDAT2"),
+    (0xa0, "RXF0C"),
+    (0xa4, "RXF0S"),
+    (0xa8, "RXF0A"),
+    (0xac, "RXBC"),
+    (0xb0, "RXF1C"),
+    (0xb4, "RXF1S"),
+    (0xb8, "RXF1A"),
+    (0xbc, "RXESC"),
+    (0xc0, "TXBC"),
+    (0xc4, "TXFQS"),
+    (0xc8, "TXESC"),
+    (0xcc, "TXBRP"),
+    (0xd0, "TXBAR"),
+    (0xd4, "TXBCR"),
+    (0xd8, "TXBTO"),
+    (0xdc, "TXBCF"),
+    (0xe0, "TXBTIE"),
+    (0xe4, "TXBCIE"),
+    (0xf0, "TXEFC"),
+    (0xf4, "TXEFS"),
+    (0xf8, "TXEFA"),
+];
+
+/// Registers with a Message RAM start address in bits 2 to 15
+const START_ADDRESSES: [usize; 7] = [0x84, 0x88, 0xa0, 0xac, 0xb0, 0xc0, 0xf0];
+
+/// Names of the CCCR bits, from bit 0
+const CCCR_BITS: [&str; 16] = [
+    "INIT", "CCE", "ASM", "CSA", "CSR", "MON", "DAR", "TEST", "FDOE", "BRSE", "", "", "PXHD",
+    "EFBI", "TXP", "NISO",
+];
+
+fn field(value: u32, low: u32, width: u32) -> u32 {
+    value >> low & ((1 << width) - 1)
+}
+
+/// Renders the writes one per line, with start addresses relative to
+/// `memory` so that the output does not depend on the placement of the
+/// Message RAM
+fn render(writes: &[(usize, u32)], memory: usize) -> String {
+    let mut out = String::new();
+    for &(offset, value) in writes {
+        let name = NAMES
+            .iter()
+            .find(|(register, _)| *register == offset)
+            .map_or("?", |(_, name)| name);
+        let (value, start) = if START_ADDRESSES.contains(&offset) {
+            let start = (field(value, 0, 16) as usize).wrapping_sub(memory) & 0xfffc;
+            (value & !0xfffc, Some(start))
+        } else {
+            (value, None)
+        };
+        let _ = write!(out, "{name:<6} {value:#010x}");
+        if let Some(start) = start {
+            let _ = write!(out, "  start=+{start:#06x}");
+        }
+        let _ = match name {
+            "CCCR" => {
+                out.push(' ');
+                CCCR_BITS
+                    .iter()
+                    .enumerate()
+                    .filter(|&(bit, name)| !name.is_empty() && value & 1 << bit != 0)
+                    .try_for_each(|(_, name)| write!(out, " {name}"))
+            }
+            "NBTP" => write!(
+                out,
+                "  NSJW={} NBRP={} NTSEG1={} NTSEG2={}",
+                field(value, 25, 7),
+                field(value, 16, 9),
+                field(value, 8, 8),
+                field(value, 0, 7),
+            ),
+            "DBTP" => write!(
+                out,
+                "  TDC={} DBRP={} DTSEG1={} DTSEG2={} DSJW={}",
+                field(value, 23, 1),
+                field(value, 16, 5),
+                field(value, 8, 5),
+                field(value, 4, 4),
+                field(value, 0, 4),
+            ),
+            "TSCC" => write!(
+                out,
+                "  TCP={} TSS={}",
+                field(value, 16, 4),
+                field(value, 0, 2)
+            ),
+            _ => Ok(()),
+        };
+        out.push('\n');
+    }
+    out
+}
+
+/// Compares the writes with the golden file `name`, or rewrites it if
+/// `MCAN_UPDATE_GOLDEN` is set
+fn check(name: &str, actual: &str) {
+    let path = std::format!("{}/golden/{name}", env!("CARGO_MANIFEST_DIR"));
+    if std::env::var_os("MCAN_UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    let mismatch = expected
+        .lines()
+        .zip(actual.lines())
+        .position(|(expected, actual)| expected != actual)
+        .or((expected.lines().count() != actual.lines().count())
+            .then(|| expected.lines().count().min(actual.lines().count())));
+    if let Some(line) = mismatch {
+        panic!(
+            "register writes differ from {path} at line {}\n\
+             expected: {:?}\n\
+             actual:   {:?}\n\
+             rerun with MCAN_UPDATE_GOLDEN=1 to accept\n\n{actual}",
+            line + 1,
+            expected.lines().nth(line),
+            actual.lines().nth(line),
+        );
+    }
+}
+
+/// Records the writes of configuring a peripheral clocked with `clock`,
+/// adjusting the configuration with `configure`
+fn record<Id: mcan_core::CanId>(
+    regs: &mock::Registers,
+    clock: HertzU32,
+    configure: impl FnOnce(&mut CanConfigurable<'_, Id, mock::Dependencies, Caps>),
+) -> String {
+    let mut memory = SharedMemory::<Caps>::new();
+    let memory_start = &memory as *const _ as usize;
+    let dependencies = mock::Dependencies::new(&memory).with_can_clock(clock);
+    regs.start_recording();
+    let mut can = CanConfigurable::<Id, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+    configure(&mut can);
+    let _can = can.finalize_recoverable().unwrap();
+    render(&regs.take_recording(), memory_start)
+}
+
+#[test]
+fn classic_500k_at_40mhz() {
+    mock_can!(Can0);
+    let writes = record::<Can0>(Can0::regs(), 40.MHz(), |can| {
+        let config = can.config();
+        config.timestamp.prescaler = 4;
+        config.rx_fifo_0.watermark = 3;
+        config.tx.tx_event_fifo_watermark = 2;
+        can.filters_standard()
+            .push(Filter::Classic {
+                action: Action::StoreFifo0,
+                filter: StandardId::new(0x100).unwrap(),
+                mask: StandardId::new(0x700).unwrap(),
+            })
+            .ok()
+            .unwrap();
+        can.filters_extended()
+            .push(ExtFilter::Classic {
+                action: Action::StoreFifo1,
+                filter: ExtendedId::new(0x1000).unwrap(),
+                mask: ExtendedId::MAX,
+            })
+            .ok()
+            .unwrap();
+    });
+    check("classic_500k_40mhz.txt", &writes);
+}
+
+#[test]
+fn fd_500k_2m_at_80mhz() {
+    mock_can!(Can1);
+    let writes = record::<Can1>(Can1::regs(), 80.MHz(), |can| {
+        can.config().mode = Mode::Fd {
+            allow_bit_rate_switching: true,
+            data_phase_timing: BitTiming {
+                sjw: 4,
+                phase_seg_1: 15,
+                phase_seg_2: 4,
+                bitrate: 2.MHz(),
+            },
+        };
+    });
+    check("fd_500k_2m_80mhz.txt", &writes);
+}
+
+#[test]
+fn loopback_at_40mhz() {
+    mock_can!(Can2);
+    let writes = record::<Can2>(Can2::regs(), 40.MHz(), |can| {
+        can.config().loopback = Loopback::External;
+    });
+    check("loopback_40mhz.txt", &writes);
+}
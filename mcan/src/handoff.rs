@@ -0,0 +1,261 @@
+//! Taking over a peripheral configured by a bootloader
+//!
+//! A bootloader that talks CAN, e.g. for firmware download, can jump to the
+//! application without resetting the peripheral to keep the bus session
+//! alive. [`CanConfigurable::new`] cannot be used by the application in this
+//! case, as it enters configuration mode and initializes the Message RAM,
+//! which drops frames that are pending reception and transmission.
+//!
+//! [`Can::from_raw_parts`] instead builds the components around the running
+//! peripheral without touching its configuration. Its caller vouches for the
+//! state of the peripheral with a [`Configured`] marker, whose safety
+//! requirements list everything the components rely on. These are met if
+//!
+//! - the bootloader and the application share the [`Capacities`] type and the
+//!   placement of the [`SharedMemory`], e.g. through a common crate and linker
+//!   script section,
+//! - the bootloader configured the peripheral through this crate with the
+//!   [`CanConfig`] passed to [`Configured::assume`], and
+//! - the bootloader stops using the peripheral before jumping, without
+//!   [releasing](Can::release) or [reconfiguring](Can::configure) it.
+//!
+//! The section holding the `SharedMemory` has to be `NOLOAD` and must not be
+//! zeroed by the startup code of the application.
+//!
+//! ```no_run
+//! # use mcan::generic_array::typenum::consts::*;
+//! # use mcan::message::{rx, tx};
+//! # use mcan::messageram::SharedMemory;
+//! # use fugit::RateExtU32 as _;
+//! # struct Capacities;
+//! # impl mcan::messageram::Capacities for Capacities {
+//! #     type StandardFilters = U8;
+//! #     type ExtendedFilters = U8;
+//! #     type RxBufferMessage = rx::Message<8>;
+//! #     type DedicatedRxBuffers = U0;
+//! #     type RxFifo0Message = rx::Message<8>;
+//! #     type RxFifo0 = U8;
+//! #     type RxFifo1Message = rx::Message<8>;
+//! #     type RxFifo1 = U8;
+//! #     type TxMessage = tx::Message<8>;
+//! #     type TxBuffers = U8;
+//! #     type DedicatedTxBuffers = U0;
+//! #     type TxEventFifo = U8;
+//! # }
+//! # struct Can0;
+//! # unsafe impl mcan::core::CanId for Can0 {
+//! #     const ADDRESS: *const () = 0xDEAD0000 as *const _;
+//! # }
+//! # struct Dependencies;
+//! # unsafe impl<ID: mcan::core::CanId> mcan::core::Dependencies<ID> for Dependencies {
+//! #     fn eligible_message_ram_start(&self) -> *const () { unreachable!() }
+//! #     fn host_clock(&self) -> fugit::HertzU32 { unreachable!() }
+//! #     fn can_clock(&self) -> fugit::HertzU32 { unreachable!() }
+//! # }
+//! # fn example(memory: &'static mut SharedMemory<Capacities>) {
+//! # let dependencies = Dependencies;
+//! use mcan::bus::Can;
+//! use mcan::config::CanConfig;
+//! use mcan::handoff::Configured;
+//!
+//! // The configuration the bootloader finalized the peripheral with
+//! let config = CanConfig::new(500.kHz());
+//! // Safety: The bootloader configured `Can0` with `config` and the same
+//! // `Capacities` and `memory`, and left it running when jumping here.
+//! let configured = unsafe { Configured::<Can0, Capacities>::assume(config) };
+//! // Safety: This is the only handoff.
+//! let can = unsafe { Can::from_raw_parts(dependencies, memory, configured) }.unwrap();
+//! # }
+//! ```
+//!
+//! [`CanConfigurable::new`]: crate::bus::CanConfigurable::new
+//! [`CanConfig`]: crate::config::CanConfig
+//! [`SharedMemory`]: crate::messageram::SharedMemory
+
+use crate::bus::{Can, ConstructionError};
+use crate::config::CanConfig;
+use crate::filter::{FiltersExtended, FiltersStandard};
+use crate::messageram::{Capacities, SharedMemory};
+use core::marker::PhantomData;
+
+/// Assertion that peripheral `Id` is running with the Message RAM layout of
+/// `C` and a [`CanConfig`]
+pub struct Configured<Id, C> {
+    config: CanConfig,
+    _markers: PhantomData<(Id, C)>,
+}
+
+impl<Id: mcan_core::CanId, C: Capacities> Configured<Id, C> {
+    /// # Safety
+    /// Until the marker is passed to [`Can::from_raw_parts`],
+    /// - the Message RAM configuration registers (SIDFC, XIDFC, RXF0C, RXF1C,
+    ///   RXBC, RXESC, TXEFC, TXBC, TXESC) of `Id` describe the layout of
+    ///   [`SharedMemory<C>`] at the address passed to [`Can::from_raw_parts`],
+    ///   as programmed by
+    ///   [`CanConfigurable::new`](crate::bus::CanConfigurable::new)
+    /// - the remaining configuration registers match `config` as applied by
+    ///   [`CanConfigurable::finalize_recoverable`](crate::bus::CanConfigurable::finalize_recoverable)
+    /// - the filters are programmed in the Message RAM, with all-zero elements
+    ///   after the last one in use, and the list sizes of SIDFC and XIDFC cover
+    ///   at least the filters in use
+    /// - the peripheral is in normal operation (CCCR.INIT and CCCR.CCE are 0)
+    /// - neither the peripheral nor the Message RAM are accessed by any other
+    ///   code, including the bootloader that configured them
+    pub const unsafe fn assume(config: CanConfig) -> Self {
+        Self {
+            config,
+            _markers: PhantomData,
+        }
+    }
+}
+
+impl<'a, Id: mcan_core::CanId, D: mcan_core::Dependencies<Id>, C: Capacities> Can<'a, Id, D, C> {
+    /// Take over a peripheral that is already running, e.g. after a
+    /// bootloader, without touching its configuration or the content of the
+    /// Message RAM.
+    ///
+    /// The same checks as in
+    /// [`CanConfigurable::new`](crate::bus::CanConfigurable::new) are applied
+    /// to `C` and the placement of `memory`. Interrupts are disabled and their
+    /// line selection is reset, as the returned interrupt set is disabled.
+    /// Filters pushed after [reconfiguring](Self::configure) are appended
+    /// after the programmed ones.
+    ///
+    /// # Safety
+    /// - The requirements of [`Configured::assume`] hold
+    /// - `memory` has been initialized by
+    ///   [`CanConfigurable::new`](crate::bus::CanConfigurable::new) for the
+    ///   same `C`, possibly by another program, and has since only been
+    ///   modified by the peripheral and the components of this crate
+    /// - No other instance of `Can` or its components exists for `Id`
+    pub unsafe fn from_raw_parts(
+        dependencies: D,
+        memory: &'a mut SharedMemory<C>,
+        configured: Configured<Id, C>,
+    ) -> Result<Self, ConstructionError> {
+        Self::check_construction(&dependencies, memory)?;
+        // Safety: `dependencies` implies ownership of the registers, and the
+        // caller guarantees that no other instance exists.
+        let reg = crate::reg::Can::<Id>::new();
+        let memory = memory.assume_init_mut();
+        Ok(Self::assemble(
+            reg,
+            dependencies,
+            configured.config,
+            memory,
+            |standard, extended| {
+                (
+                    FiltersStandard::new_programmed(standard),
+                    FiltersExtended::new_programmed(extended),
+                )
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::CanConfigurable;
+    use crate::filter::{Action, Filter};
+    use crate::message::{tx, Raw as _};
+    use crate::mock::{self, mock_can, Caps};
+    use crate::prelude::*;
+    use embedded_can::StandardId;
+    use fugit::RateExtU32;
+
+    mock_can!(Can0);
+
+    const RXF0S: usize = 0xa4;
+    const RXF0A: usize = 0xa8;
+    const TXFQS: usize = 0xc4;
+    const TXBAR: usize = 0xd0;
+    const CCCR: usize = 0x18;
+
+    /// Receives a frame from FIFO 0 at get index 1 and queues one for
+    /// transmission at put index 2
+    fn exercise<D: mcan_core::Dependencies<Can0>>(
+        can: &mut Can<'_, Can0, D, Caps>,
+    ) -> (embedded_can::Id, [u32; 64]) {
+        let regs = Can0::regs();
+        regs.write(RXF0S, 1 << 8 | 1);
+        regs.write(RXF0A, 0);
+        regs.write(TXFQS, 2 << 16 | 2 << 8);
+        regs.write(TXBAR, 0);
+        let received = can.rx_fifo_0.receive().unwrap();
+        let message = tx::MessageBuilder {
+            id: StandardId::new(0x321).unwrap().into(),
+            frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(&[1, 2])),
+            store_tx_event: None,
+        }
+        .build()
+        .unwrap();
+        can.tx.transmit_queued_indexed(message).unwrap();
+        (received.id(), regs.snapshot())
+    }
+
+    #[test]
+    fn handed_off_peripheral_operates_like_a_configured_one() {
+        let regs = Can0::regs();
+        let mut memory = SharedMemory::<Caps>::new();
+        let filter = Filter::Classic {
+            action: Action::StoreFifo0,
+            filter: StandardId::new(0x100).unwrap(),
+            mask: StandardId::new(0x700).unwrap(),
+        };
+
+        // Bootloader
+        let dependencies = mock::Dependencies::new(&memory);
+        let mut can =
+            CanConfigurable::<Can0, _, _>::new(500.kHz(), dependencies, &mut memory).unwrap();
+        assert_eq!(can.filters_standard().push(filter).ok(), Some(0));
+        let mut can = can.finalize_recoverable().unwrap();
+        let (_, configured_registers) = exercise(&mut can);
+        let _ = can;
+
+        // A frame arrives in between
+        // Safety: Initialized by the construction above.
+        unsafe { memory.assume_init_mut() }.rx_fifo_0[1].set(mock::message(0x123));
+        let registers = regs.snapshot();
+
+        // Application
+        let dependencies = mock::Dependencies::new(&memory);
+        // Safety: Configured above with the default configuration.
+        let configured = unsafe { Configured::<Can0, Caps>::assume(CanConfig::new(500.kHz())) };
+        // Safety: The bootloader components are gone.
+        let mut can =
+            unsafe { Can::from_raw_parts(dependencies, &mut memory, configured) }.unwrap();
+        assert_eq!(regs.snapshot(), registers);
+        assert_eq!(regs.read(CCCR) & 1, 0);
+
+        let (received, handed_off_registers) = exercise(&mut can);
+        assert_eq!(received, StandardId::new(0x123).unwrap().into());
+        assert_eq!(handed_off_registers, configured_registers);
+
+        // New filters are appended to the programmed ones
+        let mut can = can.configure();
+        assert_eq!(can.filters_standard().push(filter).ok(), Some(1));
+        let _ = can.finalize_recoverable().unwrap();
+    }
+
+    #[test]
+    fn handoff_checks_the_memory_placement() {
+        mock_can!(Can1);
+        let mut memory = SharedMemory::<Caps>::new();
+        let size = core::mem::size_of::<SharedMemory<Caps>>();
+        let dependencies = mock::Dependencies::new(&memory).with_message_ram_len(size - 1);
+        // Safety: Rejected before anything is accessed.
+        let result = unsafe {
+            Can::from_raw_parts(
+                dependencies,
+                &mut memory,
+                Configured::<Can1, Caps>::assume(CanConfig::new(500.kHz())),
+            )
+        };
+        assert!(matches!(
+            result,
+            Err(ConstructionError::MessageRamExceeded { .. })
+        ));
+        assert_eq!(Can1::regs().snapshot(), [0; 64]);
+    }
+}
@@ -0,0 +1,250 @@
+//! Dispatch of received messages by identifier
+//!
+//! A few broad filters often accept the frames of many handlers into the same
+//! queue, so the handler of a frame has to be determined by its identifier
+//! after reception. [`IdDispatch`] looks up the handler index of an
+//! identifier by binary search in a sorted table, which can be a `static`.
+//!
+//! The table is ordered by arbitration priority, as defined by the [`Ord`]
+//! implementation of [`Id`]: by the 11 bit base identifier first, then
+//! standard identifiers before extended identifiers with the same base, and
+//! finally by the remaining 18 bits of extended identifiers. This is a total
+//! order across both identifier spaces, so a table can mix them.
+//!
+//! ```
+//! # use mcan::embedded_can::{ExtendedId, Id, StandardId};
+//! use mcan::id_dispatch::IdDispatch;
+//!
+//! const fn standard(raw: u16) -> Id {
+//!     match StandardId::new(raw) {
+//!         Some(id) => Id::Standard(id),
+//!         None => panic!("invalid standard ID"),
+//!     }
+//! }
+//!
+//! const fn extended(raw: u32) -> Id {
+//!     match ExtendedId::new(raw) {
+//!         Some(id) => Id::Extended(id),
+//!         None => panic!("invalid extended ID"),
+//!     }
+//! }
+//!
+//! const ENGINE: usize = 0;
+//! const GEARBOX: usize = 1;
+//!
+//! static TABLE: [(Id, usize); 3] = [
+//!     (standard(0x100), ENGINE),
+//!     // Base identifier 0x100
+//!     (extended(0x0400_0001), GEARBOX),
+//!     (standard(0x200), GEARBOX),
+//! ];
+//!
+//! let dispatch = IdDispatch::new(&TABLE).unwrap();
+//! assert_eq!(dispatch.lookup(standard(0x200)), Some(GEARBOX));
+//! assert_eq!(dispatch.lookup(standard(0x300)), None);
+//! ```
+//!
+//! [`IdDispatch::router`] adapts the table to the routing closure of a
+//! [`RxDemux`], with one consumer per handler.
+//!
+//! [`RxDemux`]: crate::rx_demux::RxDemux
+
+use crate::message::Raw;
+use crate::rx_demux::ConsumerMask;
+use embedded_can::Id;
+
+/// Table mapping identifiers to handler indices
+#[derive(Copy, Clone, Debug)]
+pub struct IdDispatch<'a> {
+    entries: &'a [(Id, usize)],
+}
+
+impl<'a> IdDispatch<'a> {
+    /// Creates a dispatch table from `entries`, checking that they are sorted
+    /// by identifier and that every identifier appears only once
+    pub fn new(entries: &'a [(Id, usize)]) -> Result<Self, DispatchTableError<'a>> {
+        let mut pairs = entries.iter().zip(entries.iter().skip(1)).enumerate();
+        if let Some((index, _)) = pairs.find(|(_, ((a, _), (b, _)))| a > b) {
+            return Err(DispatchTableError::Unsorted { index: index + 1 });
+        }
+        let duplicates = DuplicateIds { entries };
+        if duplicates.iter().next().is_some() {
+            return Err(DispatchTableError::Duplicates(duplicates));
+        }
+        Ok(Self { entries })
+    }
+
+    /// Creates a dispatch table from `entries` without checking them, e.g. in
+    /// constant expressions
+    ///
+    /// The entries have to be sorted by identifier, without duplicates.
+    /// Otherwise, lookups may miss entries or return any of the handlers of a
+    /// duplicated identifier.
+    pub const fn new_unchecked(entries: &'a [(Id, usize)]) -> Self {
+        Self { entries }
+    }
+
+    /// Returns the handler index of `id`, if it is in the table
+    pub fn lookup(&self, id: Id) -> Option<usize> {
+        let index = self
+            .entries
+            .binary_search_by(|(entry, _)| entry.cmp(&id))
+            .ok()?;
+        self.entries.get(index).map(|&(_, handler)| handler)
+    }
+
+    /// Entries of the table
+    pub fn entries(&self) -> &'a [(Id, usize)] {
+        self.entries
+    }
+
+    /// Returns a routing closure for a [`RxDemux`] that routes messages to
+    /// the consumer with the index of their handler, and messages with
+    /// identifiers missing from the table to `fallback`
+    ///
+    /// Handler indices of 32 and above select no consumer.
+    ///
+    /// [`RxDemux`]: crate::rx_demux::RxDemux
+    pub fn router<M: Raw>(self, fallback: ConsumerMask) -> impl Fn(&M) -> ConsumerMask + 'a {
+        move |message| {
+            self.lookup(message.id())
+                .map_or(fallback, ConsumerMask::single)
+        }
+    }
+}
+
+/// Reasons for rejecting the entries of an [`IdDispatch`]
+#[derive(Copy, Clone, Debug)]
+pub enum DispatchTableError<'a> {
+    /// The identifier of entry `index` is smaller than that of its
+    /// predecessor
+    Unsorted {
+        /// Index of the first entry out of order
+        index: usize,
+    },
+    /// Identifiers appear more than once
+    Duplicates(DuplicateIds<'a>),
+}
+
+/// Identifiers appearing more than once in sorted entries
+#[derive(Copy, Clone, Debug)]
+pub struct DuplicateIds<'a> {
+    entries: &'a [(Id, usize)],
+}
+
+impl<'a> DuplicateIds<'a> {
+    /// Iterates over the duplicated identifiers, each reported once
+    pub fn iter(&self) -> impl Iterator<Item = Id> + 'a {
+        let entries = self.entries;
+        entries
+            .iter()
+            .zip(entries.iter().skip(1))
+            .enumerate()
+            .filter(move |&(index, ((a, _), (b, _)))| {
+                a == b
+                    && index
+                        .checked_sub(1)
+                        .and_then(|previous| entries.get(previous))
+                        .is_none_or(|(previous, _)| previous != a)
+            })
+            .map(|(_, ((id, _), _))| *id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::{rx, tx};
+    use crate::rx_demux::{OverflowPolicy, RxDemux};
+    use embedded_can::{ExtendedId, StandardId};
+
+    fn standard(raw: u16) -> Id {
+        StandardId::new(raw).unwrap().into()
+    }
+
+    fn extended(raw: u32) -> Id {
+        ExtendedId::new(raw).unwrap().into()
+    }
+
+    #[test]
+    fn mixed_identifier_spaces_are_ordered_by_priority() {
+        let entries = [
+            (extended(0x0000_0001), 0),
+            (standard(0x001), 1),
+            // Base identifier 0x001
+            (extended(0x0004_0000), 2),
+            (extended(0x0004_0001), 3),
+            (standard(0x7ff), 4),
+            (extended(0x1fff_ffff), 5),
+        ];
+        let dispatch = IdDispatch::new(&entries).unwrap();
+        for &(id, handler) in &entries {
+            assert_eq!(dispatch.lookup(id), Some(handler));
+        }
+        assert_eq!(dispatch.lookup(standard(0x000)), None);
+        assert_eq!(dispatch.lookup(extended(0x002)), None);
+        assert_eq!(dispatch.lookup(standard(0x002)), None);
+        assert_eq!(IdDispatch::new(&[]).unwrap().lookup(standard(0)), None);
+
+        // A standard identifier sorts before extended ones with its base
+        let swapped = [(extended(0x0004_0000), 0), (standard(0x001), 1)];
+        assert!(matches!(
+            IdDispatch::new(&swapped),
+            Err(DispatchTableError::Unsorted { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn duplicates_are_listed_once() {
+        let entries = [
+            (standard(0x100), 0),
+            (standard(0x100), 1),
+            (standard(0x100), 2),
+            (standard(0x200), 3),
+            // Base identifier 0x200
+            (extended(0x0800_0000), 4),
+            (extended(0x0800_0000), 5),
+        ];
+        let Err(DispatchTableError::Duplicates(duplicates)) = IdDispatch::new(&entries) else {
+            panic!("duplicates were accepted");
+        };
+        assert!(duplicates
+            .iter()
+            .eq([standard(0x100), extended(0x0800_0000)]));
+    }
+
+    #[test]
+    fn unchecked_tables_are_const() {
+        static ENTRIES: [(Id, usize); 0] = [];
+        const DISPATCH: IdDispatch<'static> = IdDispatch::new_unchecked(&ENTRIES);
+        assert_eq!(DISPATCH.lookup(standard(0x100)), None);
+    }
+
+    #[test]
+    fn router_selects_the_consumer_of_the_handler() {
+        let entries = [(standard(0x100), 0), (standard(0x200), 1)];
+        let message = |id| -> rx::Message<8> {
+            tx::MessageBuilder {
+                id,
+                frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(&[])),
+                store_tx_event: None,
+            }
+            .build()
+            .unwrap()
+            .into()
+        };
+        let mut demux: RxDemux<rx::Message<8>, 3, 4> = RxDemux::new([OverflowPolicy::Reject; 3]);
+        let router = IdDispatch::new(&entries)
+            .unwrap()
+            .router(ConsumerMask::single(2));
+        let (mut producer, [_, _, _]) = demux.split(router);
+        assert_eq!(
+            producer.push(message(standard(0x200))),
+            ConsumerMask::single(1)
+        );
+        assert_eq!(
+            producer.push(message(standard(0x300))),
+            ConsumerMask::single(2)
+        );
+    }
+}
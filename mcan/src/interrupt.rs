@@ -38,7 +38,7 @@
 //! #     type DedicatedTxBuffers = U0;
 //! #     type TxEventFifo = U32;
 //! # }
-//! # let mut can: Can<'static, Can0, (), Caps> = unsafe { std::mem::transmute([0u8; 176]) };
+//! # fn example(mut can: Can<'static, Can0, (), Caps>) {
 //! use mcan::interrupt::{Interrupt, InterruptLine};
 //! // During initialization
 //! let enabled_interrupts = can
@@ -66,9 +66,185 @@
 //!         _ => (),
 //!     }
 //! }
+//! # }
+//! ```
+//!
+//! # Components
+//!
+//! Each interrupt concerns one component, as listed by the `INTERRUPTS`
+//! constants of [`RxFifo`], [`RxDedicatedBuffer`], [`Tx`], [`TxEventFifo`]
+//! and [`Aux`], and by [`Aux::ERROR_INTERRUPTS`]. These can be passed to
+//! [`OwnedInterruptSet::split`] to hand the interrupts of a component to the
+//! code handling it.
+//!
+//! A handler concerned with a single interrupt can instead
+//! [`OwnedInterruptSet::take`] an [`OwnedInterrupt`], which checks and clears
+//! its flag without iterating over a set. It is enabled and disabled through
+//! [`InterruptConfiguration::enable_interrupt`] and
+//! [`InterruptConfiguration::disable_interrupt`], and merged back into a set
+//! with [`From`].
+//!
+//! # Shared interrupt lines
+//!
+//! If the lines of several peripherals are routed to the same interrupt of
+//! the processor, the handler services the sets of all of them. A
+//! [`MultiLine`] holds the sets, keeping their types, and reports each flag
+//! along with the position of its peripheral:
+//!
+//! ```no_run
+//! # use mcan::interrupt::{Interrupt, OwnedInterruptSet, state::EnabledLine0};
+//! # fn example<Can0: mcan::core::CanId, Can1: mcan::core::CanId>(
+//! #     can0: OwnedInterruptSet<Can0, EnabledLine0>,
+//! #     can1: OwnedInterruptSet<Can1, EnabledLine0>,
+//! # ) {
+//! use mcan::interrupt::MultiLine;
+//!
+//! // During initialization, with the sets enabled on line 0 of each peripheral
+//! let shared = MultiLine((can0, can1));
+//!
+//! // In the handler of the shared interrupt
+//! for (bus, interrupt) in shared.iter_flagged_all() {
+//!     match (bus, interrupt) {
+//!         (0, Interrupt::RxFifo0NewMessage) => {
+//!             // ...
+//!         }
+//!         (1, Interrupt::RxFifo0NewMessage) => {
+//!             // ...
+//!         }
+//!         _ => (),
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! # Polling
+//!
+//! Enabling interrupts is optional. Interrupt flags are raised whether or not
+//! the interrupts are enabled, and every event has a counterpart that can be
+//! polled on the component it concerns:
+//!
+//! | Interrupts                            | Polled through                                  |
+//! |---------------------------------------|-------------------------------------------------|
+//! | `RxFifo*NewMessage`                   | [`DynRxFifo::receive`], [`DynRxFifo::len`]      |
+//! | `RxFifo*WatermarkReached`             | [`DynRxFifo::watermark_reached`]                |
+//! | `RxFifo*Full`                         | [`DynRxFifo::is_full`]                          |
+//! | `RxFifo*MessageLost`                  | [`DynRxFifo::message_lost`]                     |
+//! | `MessageStoredToDedicatedRxBuffer`    | [`DynRxDedicatedBuffer::receive_any`]           |
+//! | `TransmissionCompleted`               | [`DynTx::take_completions`]                     |
+//! | `TransmissionCancellationFinished`    | [`DynTx::get_cancellation_flags`]               |
+//! | `TxFifoEmpty`                         | [`DynTx::is_queue_empty`]                       |
+//! | `TxEventFifoNewEntry`                 | [`DynTxEventFifo::pop`], [`DynTxEventFifo::len`] |
+//! | `TxEventFifoWatermarkReached`         | [`DynTxEventFifo::watermark_reached`]           |
+//! | `TxEventFifoFull`                     | [`DynTxEventFifo::is_full`]                     |
+//! | `TxEventFifoElementLost`              | [`DynTxEventFifo::element_lost`]                |
+//! | `BusOff`                              | [`DynAux::poll_bus_off_recovery`]               |
+//! | `ErrorPassive`, `WarningStatusChanged` | [`DynAux::poll_bus_state`]                     |
+//! | `ErrorLoggingOverflow`                | [`DynAux::error_counters`]                      |
+//! | `ProtocolError*`                      | [`DynAux::protocol_status`]                     |
+//!
+//! The remaining ones, such as [`Interrupt::TimestampWraparound`] or
+//! [`Interrupt::MessageRamAccessFailure`], are reported by
+//! [`InterruptConfiguration::peek_all_flags`] without taking ownership of
+//! them. Flags that mirror a status bit, like the message lost flags, are
+//! reset by clearing them through the [`OwnedInterruptSet`] holding them,
+//! which works in the disabled state as well.
+//!
+//! A superloop that uses no interrupts at all:
+//!
+//! ```no_run
+//! # use mcan::bus::Can;
+//! # use mcan::core::CanId;
+//! # use mcan::generic_array::typenum::consts::*;
+//! # use mcan::messageram::Capacities;
+//! # use mcan::message::{tx, rx};
+//! # use mcan::prelude::*;
+//! # struct Can0;
+//! # unsafe impl CanId for Can0 {
+//! #     const ADDRESS: *const () = 0xDEAD0000 as *const _;
+//! # }
+//! # struct Caps;
+//! # impl Capacities for Caps {
+//! #     type StandardFilters = U8;
+//! #     type ExtendedFilters = U8;
+//! #     type RxBufferMessage = rx::Message<8>;
+//! #     type DedicatedRxBuffers = U0;
+//! #     type RxFifo0Message = rx::Message<8>;
+//! #     type RxFifo0 = U8;
+//! #     type RxFifo1Message = rx::Message<8>;
+//! #     type RxFifo1 = U8;
+//! #     type TxMessage = tx::Message<8>;
+//! #     type TxBuffers = U8;
+//! #     type DedicatedTxBuffers = U0;
+//! #     type TxEventFifo = U8;
+//! # }
+//! # fn handle(_: rx::Message<8>) {}
+//! # fn example<D: mcan::core::Dependencies<Can0>>(mut can: Can<'static, Can0, D, Caps>, message: tx::Message<8>) {
+//! use mcan::interrupt::Interrupt;
+//! use mcan::tx_buffers::TransmitError;
+//!
+//! // All interrupts stay in `can.interrupts`, disabled
+//! let mut lost = can.interrupts.split(Interrupt::RxFifo0MessageLost.into()).unwrap();
+//! loop {
+//!     // Reception
+//!     while let Ok(frame) = can.rx_fifo_0.receive() {
+//!         handle(frame);
+//!     }
+//!     if can.rx_fifo_0.message_lost() {
+//!         // Acknowledge the loss, which resets the status bit
+//!         lost.clear_interrupts(lost.owned());
+//!     }
+//!
+//!     // Transmission
+//!     if can.tx.is_queue_empty() {
+//!         match can.tx.transmit_queued_indexed(message) {
+//!             Ok(_) | Err(TransmitError::WouldBlock(_)) => {}
+//!             Err(TransmitError::Other(_)) => { /* invalid message */ }
+//!         }
+//!     }
+//!     for (_index, _id) in can.tx.take_completions().iter() {
+//!         // ...
+//!     }
+//!
+//!     // Errors and recovery
+//!     // Leaves the initialization mode entered on bus off
+//!     can.aux.poll_bus_off_recovery().ok();
+//!     if let Some(change) = can.aux.poll_bus_state() {
+//!         // ...
+//!     }
+//!     if can.interrupt_configuration.peek_all_flags().contains(Interrupt::MessageRamAccessFailure) {
+//!         // ...
+//!     }
+//! }
+//! # }
 //! ```
+//!
+//! [`DynRxFifo::receive`]: crate::rx_fifo::DynRxFifo::receive
+//! [`DynRxFifo::len`]: crate::rx_fifo::DynRxFifo::len
+//! [`DynRxFifo::watermark_reached`]: crate::rx_fifo::DynRxFifo::watermark_reached
+//! [`DynRxFifo::is_full`]: crate::rx_fifo::DynRxFifo::is_full
+//! [`DynRxFifo::message_lost`]: crate::rx_fifo::DynRxFifo::message_lost
+//! [`DynRxDedicatedBuffer::receive_any`]: crate::rx_dedicated_buffers::DynRxDedicatedBuffer::receive_any
+//! [`DynTx::take_completions`]: crate::tx_buffers::DynTx::take_completions
+//! [`DynTx::get_cancellation_flags`]: crate::tx_buffers::DynTx::get_cancellation_flags
+//! [`DynTx::is_queue_empty`]: crate::tx_buffers::DynTx::is_queue_empty
+//! [`DynTxEventFifo::pop`]: crate::tx_event_fifo::DynTxEventFifo::pop
+//! [`DynTxEventFifo::len`]: crate::tx_event_fifo::DynTxEventFifo::len
+//! [`DynTxEventFifo::watermark_reached`]: crate::tx_event_fifo::DynTxEventFifo::watermark_reached
+//! [`DynTxEventFifo::is_full`]: crate::tx_event_fifo::DynTxEventFifo::is_full
+//! [`DynTxEventFifo::element_lost`]: crate::tx_event_fifo::DynTxEventFifo::element_lost
+//! [`DynAux::poll_bus_off_recovery`]: crate::bus::DynAux::poll_bus_off_recovery
+//! [`DynAux::poll_bus_state`]: crate::bus::DynAux::poll_bus_state
+//! [`DynAux::error_counters`]: crate::bus::DynAux::error_counters
+//! [`DynAux::protocol_status`]: crate::bus::DynAux::protocol_status
+//! [`RxFifo`]: crate::rx_fifo::RxFifo
+//! [`RxDedicatedBuffer`]: crate::rx_dedicated_buffers::RxDedicatedBuffer
+//! [`Tx`]: crate::tx_buffers::Tx
+//! [`TxEventFifo`]: crate::tx_event_fifo::TxEventFifo
+//! [`Aux`]: crate::bus::Aux
+//! [`Aux::ERROR_INTERRUPTS`]: crate::bus::Aux::ERROR_INTERRUPTS
 pub mod state;
 
+use crate::bus::ReservedBitsError;
 use crate::reg;
 use bitfield::bitfield;
 use core::marker::PhantomData;
@@ -273,7 +449,7 @@ impl core::fmt::Debug for InterruptSet {
 }
 
 /// A single interrupt.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Interrupt {
     /// RF0N
     RxFifo0NewMessage = 0,
@@ -337,9 +513,17 @@ pub enum Interrupt {
     AccessToReservedAddress = 29,
 }
 
+impl Interrupt {
+    /// Bit of the interrupt in the interrupt registers, usable in constant
+    /// expressions
+    pub const fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
 impl From<Interrupt> for u32 {
     fn from(x: Interrupt) -> Self {
-        1 << x as u32
+        x.mask()
     }
 }
 
@@ -389,45 +573,111 @@ impl TryFrom<u8> for Interrupt {
 }
 
 impl InterruptSet {
+    /// Set without any interrupts, to be extended with [`Self::with`] in
+    /// constant expressions
+    ///
+    /// ```
+    /// use mcan::interrupt::{Interrupt, InterruptSet};
+    ///
+    /// const LINE_0: InterruptSet = InterruptSet::empty()
+    ///     .with(Interrupt::RxFifo0NewMessage)
+    ///     .with(Interrupt::RxFifo0MessageLost);
+    /// ```
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// `self` with `interrupt` added, usable in constant expressions
+    pub const fn with(self, interrupt: Interrupt) -> Self {
+        Self(self.0 | interrupt.mask())
+    }
+
+    /// Set of `interrupts`, usable in constant expressions
+    pub const fn from_slice(interrupts: &[Interrupt]) -> Self {
+        let mut set = Self::empty();
+        let mut remaining = interrupts;
+        while let [interrupt, rest @ ..] = remaining {
+            set = set.with(*interrupt);
+            remaining = rest;
+        }
+        set
+    }
+
+    /// Decodes a raw value of the interrupt registers (IR, IE or ILS), e.g.
+    /// from a register dump taken by a crash handler
+    pub fn from_raw(bits: u32) -> Result<Self, ReservedBitsError> {
+        ReservedBitsError::check(bits, Self::VALID)?;
+        Ok(Self(bits))
+    }
+
     /// An iterator visiting all elements in arbitrary order.
     pub fn iter(&self) -> Iter {
         Iter {
-            flags: *self,
-            index: 0,
+            remaining: self.0 & Self::VALID,
         }
     }
 
     /// Helper function checking if an `InterruptSet` is empty
-    pub fn is_empty(&self) -> bool {
+    pub const fn is_empty(&self) -> bool {
         self.0 == 0
     }
+
+    /// Returns `true` if `interrupt` is contained in `self`
+    pub const fn contains(&self, interrupt: Interrupt) -> bool {
+        self.0 & interrupt.mask() != 0
+    }
+
+    /// Interrupts contained in `self` but not in `other`
+    pub const fn difference(&self, other: InterruptSet) -> InterruptSet {
+        InterruptSet(self.0 & !other.0)
+    }
+
+    /// Interrupts contained in both `self` and `other`
+    pub const fn intersection(&self, other: InterruptSet) -> InterruptSet {
+        InterruptSet(self.0 & other.0)
+    }
+
+    /// Interrupts contained in either `self` or `other`
+    pub const fn union(&self, other: InterruptSet) -> InterruptSet {
+        InterruptSet(self.0 | other.0)
+    }
+
+    /// Bits corresponding to an [`Interrupt`]; the others are reserved
+    const VALID: u32 = 0x3fff_ffff;
 }
 
 /// An iterator over the items of an [`InterruptSet`].
 ///
 /// This `struct` is created by [`InterruptSet::iter`].
 pub struct Iter {
-    flags: InterruptSet,
-    index: u8,
+    /// Interrupts not yet visited, without reserved bits
+    remaining: u32,
 }
 
 impl Iterator for Iter {
     type Item = Interrupt;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let i = self.index;
-        self.index = self.index.saturating_add(1);
-        // Since there are no gaps in the interrupt flags, this will be `Some` until all
-        // interrupts have been checked.
-        let int = i.try_into().ok()?;
-        if self.flags.0 & (1 << i) != 0 {
-            Some(int)
-        } else {
-            self.next()
+        if self.remaining == 0 {
+            return None;
         }
+        let i = self.remaining.trailing_zeros();
+        self.remaining &= self.remaining - 1;
+        // Since there are no gaps in the interrupt flags and reserved bits have been
+        // masked out, this is always `Some`.
+        (i as u8).try_into().ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.remaining.count_ones() as usize;
+        (len, Some(len))
     }
 }
 
+impl ExactSizeIterator for Iter {}
+
+impl core::iter::FusedIterator for Iter {}
+
 #[must_use]
 /// Has exclusive access to a set of interrupts for `Id` CAN peripheral.
 /// Permits safe access to the owned interrupt flags.
@@ -458,7 +708,7 @@ impl<Id: mcan_core::CanId, State> OwnedInterruptSet<Id, State> {
     ///
     /// # Safety
     /// - Each interrupt of a CAN peripheral can only be contained in one
-    /// `OwnedInterruptSet`, otherwise registers will be mutably aliased.
+    ///   `OwnedInterruptSet`, otherwise registers will be mutably aliased.
     /// - The reserved bits must not be included.
     /// - `State` type parameter must match the state in runtime.
     unsafe fn new(interrupts: InterruptSet) -> Self {
@@ -492,6 +742,27 @@ impl<Id: mcan_core::CanId, State> OwnedInterruptSet<Id, State> {
         self.0 .0 |= other.0 .0;
     }
 
+    /// Returns `true` if `self` owns `interrupt`
+    pub fn contains(&self, interrupt: Interrupt) -> bool {
+        self.0.contains(interrupt)
+    }
+
+    /// The interrupts owned by `self`
+    pub fn owned(&self) -> InterruptSet {
+        self.0
+    }
+
+    /// Moves ownership of `interrupt` from `self` to the return value. If
+    /// `self` does not own `interrupt`, e.g. because it was taken before, an
+    /// error is returned.
+    pub fn take(&mut self, interrupt: Interrupt) -> Result<OwnedInterrupt<Id, State>, MaskError> {
+        // The split set holds just `interrupt`, which is handed over to the
+        // returned handle instead.
+        let _ = self.split(interrupt.into())?;
+        // Safety: The interrupt was moved out of `self` and its state is kept.
+        Ok(unsafe { OwnedInterrupt::new(interrupt) })
+    }
+
     /// Moves ownership of the interrupts described by `subset` from `self` to
     /// the return value. Ones not owned by `self` are ignored.
     fn split_leniently(&mut self, subset: InterruptSet) -> Self {
@@ -512,7 +783,9 @@ impl<Id: mcan_core::CanId, State> OwnedInterruptSet<Id, State> {
     }
 }
 
-impl<Id: mcan_core::CanId, State: state::MaybeEnabled> OwnedInterruptSet<Id, State> {
+/// Flags are raised whether or not the interrupts are enabled, so sets in any
+/// state can be polled.
+impl<Id: mcan_core::CanId, State> OwnedInterruptSet<Id, State> {
     /// Moves ownership of the interrupts that were flagged to
     /// the return value.
     pub fn split_flagged(&mut self) -> Self {
@@ -546,6 +819,18 @@ impl<Id: mcan_core::CanId, State: state::MaybeEnabled> OwnedInterruptSet<Id, Sta
         }
     }
 
+    /// Returns `true` if `interrupt` is owned by this `OwnedInterruptSet` and
+    /// flagged, without clearing it
+    pub fn is_flagged(&self, interrupt: Interrupt) -> bool {
+        self.interrupt_flags().contains(interrupt)
+    }
+
+    /// Clears the flag of `interrupt`. An interrupt not owned by this
+    /// `OwnedInterruptSet` is silently ignored.
+    pub fn clear(&self, interrupt: Interrupt) {
+        self.clear_interrupts(interrupt.into());
+    }
+
     /// # Safety
     /// This gives access to reads and (through interior mutability) writes of
     /// IR. The bits not owned by this set must not be affected by these writes
@@ -555,12 +840,370 @@ impl<Id: mcan_core::CanId, State: state::MaybeEnabled> OwnedInterruptSet<Id, Sta
     }
 }
 
+#[must_use]
+/// Has exclusive access to a single interrupt for `Id` CAN peripheral, see
+/// [`OwnedInterruptSet::take`].
+///
+/// Unlike a set, it accesses the flag with single-bit masks and without
+/// iteration, for the cheapest possible check in a single-purpose handler.
+pub struct OwnedInterrupt<Id, State = state::Dynamic>(Interrupt, PhantomData<(Id, State)>);
+
+impl<Id: mcan_core::CanId, State> From<OwnedInterrupt<Id, State>> for OwnedInterruptSet<Id, State> {
+    fn from(value: OwnedInterrupt<Id, State>) -> Self {
+        // Safety: No aliasing is introduced since `value` is moved, and the state
+        // is kept.
+        unsafe { OwnedInterruptSet::new(value.0.into()) }
+    }
+}
+
+impl<Id: mcan_core::CanId, State> OwnedInterrupt<Id, State> {
+    /// Assumes exclusive ownership of `interrupt`.
+    ///
+    /// # Safety
+    /// - Each interrupt of a CAN peripheral can only be owned once, otherwise
+    ///   registers will be mutably aliased.
+    /// - `State` type parameter must match the state in runtime.
+    unsafe fn new(interrupt: Interrupt) -> Self {
+        Self(interrupt, PhantomData)
+    }
+
+    /// The owned interrupt
+    pub fn interrupt(&self) -> Interrupt {
+        self.0
+    }
+
+    /// Returns `true` if the interrupt is flagged. The flag is raised whether
+    /// or not the interrupt is enabled.
+    pub fn is_flagged(&self) -> bool {
+        // Safety: Only the owned flag is looked at.
+        unsafe { self.ir().read().bits() & u32::from(self.0) != 0 }
+    }
+
+    /// Clears the flag of the interrupt
+    pub fn clear(&self) {
+        // Safety: Writing a 0 bit leaves the flag unchanged, so only the owned flag
+        // is affected.
+        unsafe {
+            self.ir().write(|w| w.bits(self.0.into()));
+        }
+    }
+
+    /// # Safety
+    /// This gives access to reads and (through interior mutability) writes of
+    /// IR. The bits other than the owned one must not be affected by these
+    /// writes and must not be relied on by these reads.
+    unsafe fn ir(&self) -> &reg::IR {
+        &(*Id::register_block()).ir
+    }
+}
+
+/// Position of a peripheral's set in a [`MultiLine`]
+pub type BusIndex = usize;
+
+/// Owned interrupt sets of several CAN peripherals that share an interrupt
+/// line of the processor, e.g. because the line 0 of both instances is routed
+/// to the same vector
+///
+/// The wrapper is implemented for pairs and triples of [`OwnedInterruptSet`]s
+/// with any `Id`s and states. Each set keeps its type, the wrapper only
+/// services them in sequence, identifying each by its position in the tuple.
+pub struct MultiLine<T>(pub T);
+
+/// Clears the flagged interrupts of `set` and tags them with `bus`
+fn flagged_on<Id: mcan_core::CanId, State>(
+    bus: BusIndex,
+    set: &OwnedInterruptSet<Id, State>,
+) -> impl Iterator<Item = (BusIndex, Interrupt)> {
+    set.iter_flagged().map(move |interrupt| (bus, interrupt))
+}
+
+impl<Id0: mcan_core::CanId, State0, Id1: mcan_core::CanId, State1>
+    MultiLine<(
+        OwnedInterruptSet<Id0, State0>,
+        OwnedInterruptSet<Id1, State1>,
+    )>
+{
+    /// Clears the flagged interrupts of all sets and provides an iterator
+    /// over the flags that were cleared, in the order of the sets
+    pub fn iter_flagged_all(&self) -> impl Iterator<Item = (BusIndex, Interrupt)> {
+        let (set0, set1) = &self.0;
+        flagged_on(0, set0).chain(flagged_on(1, set1))
+    }
+
+    /// Clears `interrupts` on every peripheral. Interrupts not owned by the
+    /// set of a peripheral are silently ignored for it.
+    pub fn clear_all(&self, interrupts: InterruptSet) {
+        let (set0, set1) = &self.0;
+        set0.clear_interrupts(interrupts);
+        set1.clear_interrupts(interrupts);
+    }
+}
+
+impl<
+        Id0: mcan_core::CanId,
+        State0,
+        Id1: mcan_core::CanId,
+        State1,
+        Id2: mcan_core::CanId,
+        State2,
+    >
+    MultiLine<(
+        OwnedInterruptSet<Id0, State0>,
+        OwnedInterruptSet<Id1, State1>,
+        OwnedInterruptSet<Id2, State2>,
+    )>
+{
+    /// Clears the flagged interrupts of all sets and provides an iterator
+    /// over the flags that were cleared, in the order of the sets
+    pub fn iter_flagged_all(&self) -> impl Iterator<Item = (BusIndex, Interrupt)> {
+        let (set0, set1, set2) = &self.0;
+        flagged_on(0, set0)
+            .chain(flagged_on(1, set1))
+            .chain(flagged_on(2, set2))
+    }
+
+    /// Clears `interrupts` on every peripheral. Interrupts not owned by the
+    /// set of a peripheral are silently ignored for it.
+    pub fn clear_all(&self, interrupts: InterruptSet) {
+        let (set0, set1, set2) = &self.0;
+        set0.clear_interrupts(interrupts);
+        set1.clear_interrupts(interrupts);
+        set2.clear_interrupts(interrupts);
+    }
+}
+
+/// Flagged interrupts summarized by the source that needs servicing
+///
+/// Created with [`From`] on an [`OwnedInterruptSet`], which reads IR once and
+/// clears exactly the owned flags that were read, like
+/// [`OwnedInterruptSet::iter_flagged`]. Reading the flags once avoids losing
+/// a flag raised between two reads, cleared without being seen.
+///
+/// None of the flags clears itself: each stays raised until cleared, whether
+/// or not the condition persists. Conditions such as [`Self::bus_off`] and
+/// [`Self::error_passive`] only report a change, so the current state has to
+/// be read from the protocol status, e.g. with
+/// [`DynAux::protocol_status`](crate::bus::DynAux::protocol_status).
+///
+/// Several interrupts may map to the same field, but every interrupt maps to
+/// exactly one.
+#[derive(Copy, Clone, Debug)]
+pub struct FlaggedSources {
+    /// The flags that were read and cleared
+    pub flags: InterruptSet,
+    /// RX FIFO 0 has messages to receive: [`Interrupt::RxFifo0NewMessage`],
+    /// [`Interrupt::RxFifo0WatermarkReached`] or [`Interrupt::RxFifo0Full`]
+    pub rx_fifo_0_new: bool,
+    /// RX FIFO 0 dropped a message: [`Interrupt::RxFifo0MessageLost`]
+    pub rx_fifo_0_lost: bool,
+    /// RX FIFO 1 has messages to receive: [`Interrupt::RxFifo1NewMessage`],
+    /// [`Interrupt::RxFifo1WatermarkReached`] or [`Interrupt::RxFifo1Full`]
+    pub rx_fifo_1_new: bool,
+    /// RX FIFO 1 dropped a message: [`Interrupt::RxFifo1MessageLost`]
+    pub rx_fifo_1_lost: bool,
+    /// A dedicated RX buffer received a message:
+    /// [`Interrupt::MessageStoredToDedicatedRxBuffer`]
+    pub dedicated_rx: bool,
+    /// A high priority message was received:
+    /// [`Interrupt::HighPriorityMessage`]
+    pub high_priority: bool,
+    /// TX buffers finished: [`Interrupt::TransmissionCompleted`] or
+    /// [`Interrupt::TransmissionCancellationFinished`]
+    pub tx_finished: bool,
+    /// The TX queue has no pending message: [`Interrupt::TxFifoEmpty`]
+    pub tx_fifo_empty: bool,
+    /// The TX event FIFO has events to receive:
+    /// [`Interrupt::TxEventFifoNewEntry`],
+    /// [`Interrupt::TxEventFifoWatermarkReached`] or
+    /// [`Interrupt::TxEventFifoFull`]
+    pub tx_event_new: bool,
+    /// The TX event FIFO dropped an event:
+    /// [`Interrupt::TxEventFifoElementLost`]
+    pub tx_event_lost: bool,
+    /// The bus off state changed: [`Interrupt::BusOff`]
+    pub bus_off: bool,
+    /// The error passive state changed: [`Interrupt::ErrorPassive`]
+    pub error_passive: bool,
+    /// The error warning state changed: [`Interrupt::WarningStatusChanged`]
+    pub warning: bool,
+    /// Protocol errors were detected: [`Interrupt::ProtocolErrorArbitration`],
+    /// [`Interrupt::ProtocolErrorData`] or [`Interrupt::ErrorLoggingOverflow`]
+    pub protocol_error: bool,
+    /// The timestamp counter wrapped around:
+    /// [`Interrupt::TimestampWraparound`]
+    pub timestamp_wraparound: bool,
+    /// The timeout counter reached zero: [`Interrupt::TimeoutOccured`]
+    pub timeout: bool,
+    /// The Message RAM or the registers were accessed erroneously:
+    /// [`Interrupt::MessageRamAccessFailure`],
+    /// [`Interrupt::BitErrorCorrected`], [`Interrupt::BitErrorUncorrected`],
+    /// [`Interrupt::Watchdog`] or [`Interrupt::AccessToReservedAddress`]
+    pub fault: bool,
+}
+
+impl FlaggedSources {
+    /// Summarizes `flags` without accessing the peripheral
+    pub fn from_flags(flags: InterruptSet) -> Self {
+        let mut sources = Self {
+            flags,
+            rx_fifo_0_new: false,
+            rx_fifo_0_lost: false,
+            rx_fifo_1_new: false,
+            rx_fifo_1_lost: false,
+            dedicated_rx: false,
+            high_priority: false,
+            tx_finished: false,
+            tx_fifo_empty: false,
+            tx_event_new: false,
+            tx_event_lost: false,
+            bus_off: false,
+            error_passive: false,
+            warning: false,
+            protocol_error: false,
+            timestamp_wraparound: false,
+            timeout: false,
+            fault: false,
+        };
+        for interrupt in flags.iter() {
+            *sources.field(interrupt) = true;
+        }
+        sources
+    }
+
+    fn field(&mut self, interrupt: Interrupt) -> &mut bool {
+        use Interrupt::*;
+        match interrupt {
+            RxFifo0NewMessage | RxFifo0WatermarkReached | RxFifo0Full => &mut self.rx_fifo_0_new,
+            RxFifo0MessageLost => &mut self.rx_fifo_0_lost,
+            RxFifo1NewMessage | RxFifo1WatermarkReached | RxFifo1Full => &mut self.rx_fifo_1_new,
+            RxFifo1MessageLost => &mut self.rx_fifo_1_lost,
+            MessageStoredToDedicatedRxBuffer => &mut self.dedicated_rx,
+            HighPriorityMessage => &mut self.high_priority,
+            TransmissionCompleted | TransmissionCancellationFinished => &mut self.tx_finished,
+            TxFifoEmpty => &mut self.tx_fifo_empty,
+            TxEventFifoNewEntry | TxEventFifoWatermarkReached | TxEventFifoFull => {
+                &mut self.tx_event_new
+            }
+            TxEventFifoElementLost => &mut self.tx_event_lost,
+            BusOff => &mut self.bus_off,
+            ErrorPassive => &mut self.error_passive,
+            WarningStatusChanged => &mut self.warning,
+            ProtocolErrorArbitration | ProtocolErrorData | ErrorLoggingOverflow => {
+                &mut self.protocol_error
+            }
+            TimestampWraparound => &mut self.timestamp_wraparound,
+            TimeoutOccured => &mut self.timeout,
+            MessageRamAccessFailure
+            | BitErrorCorrected
+            | BitErrorUncorrected
+            | Watchdog
+            | AccessToReservedAddress => &mut self.fault,
+        }
+    }
+}
+
+impl<Id: mcan_core::CanId, State> From<&OwnedInterruptSet<Id, State>> for FlaggedSources {
+    fn from(set: &OwnedInterruptSet<Id, State>) -> Self {
+        let flags = set.interrupt_flags();
+        set.clear_interrupts(flags);
+        Self::from_flags(flags)
+    }
+}
+
+/// Wakers of the asynchronous operations on a peripheral, woken from the
+/// interrupt flags
+///
+/// Meant to be placed in a `static`, one per peripheral. Every interrupt
+/// handler servicing the peripheral, on either line, passes the flags it
+/// cleared to [`Self::wake_on`]. A future waiting for an interrupt that is not
+/// enabled on any line is never woken.
+#[cfg(feature = "async")]
+pub struct Wakers {
+    /// Woken on [`Interrupt::TxFifoEmpty`],
+    /// [`Interrupt::TransmissionCompleted`] and
+    /// [`Interrupt::TransmissionCancellationFinished`], see
+    /// [`Tx::reserve_slot`](crate::tx_buffers::Tx::reserve_slot)
+    pub tx: crate::tx_async::TxWaker,
+    /// Woken on the interrupts of RX FIFO 0, see
+    /// [`RxFifo::receive_async`](crate::rx_fifo::RxFifo::receive_async)
+    pub rx_fifo_0: crate::rx_async::RxWaker,
+    /// Woken on the interrupts of RX FIFO 1
+    pub rx_fifo_1: crate::rx_async::RxWaker,
+}
+
+#[cfg(feature = "async")]
+impl Wakers {
+    const TX: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::TxFifoEmpty,
+        Interrupt::TransmissionCompleted,
+        Interrupt::TransmissionCancellationFinished,
+    ]);
+    const RX_FIFO_0: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::RxFifo0NewMessage,
+        Interrupt::RxFifo0WatermarkReached,
+        Interrupt::RxFifo0Full,
+        Interrupt::RxFifo0MessageLost,
+    ]);
+    const RX_FIFO_1: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::RxFifo1NewMessage,
+        Interrupt::RxFifo1WatermarkReached,
+        Interrupt::RxFifo1Full,
+        Interrupt::RxFifo1MessageLost,
+    ]);
+
+    /// Create an instance with no registered tasks
+    pub const fn new() -> Self {
+        Self {
+            tx: crate::tx_async::TxWaker::new(),
+            rx_fifo_0: crate::rx_async::RxWaker::new(),
+            rx_fifo_1: crate::rx_async::RxWaker::new(),
+        }
+    }
+
+    /// Wakes the tasks waiting for any of the interrupts in `flags`
+    pub fn wake_on(&self, flags: InterruptSet) {
+        if !flags.intersection(Self::TX).is_empty() {
+            self.tx.wake();
+        }
+        if !flags.intersection(Self::RX_FIFO_0).is_empty() {
+            self.rx_fifo_0.wake();
+        }
+        if !flags.intersection(Self::RX_FIFO_1).is_empty() {
+            self.rx_fifo_1.wake();
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Default for Wakers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// All bits of the interrupt registers that are not reserved
+const ALL_INTERRUPTS: u32 = 0x3fff_ffff;
+
 /// Controls enabling and line selection of interrupts.
 pub struct InterruptConfiguration<P>(PhantomData<P>);
 
 impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
+    /// Returns all interrupts that are currently flagged, regardless of which
+    /// [`OwnedInterruptSet`] they belong to and whether they are enabled.
+    ///
+    /// The flags are only read, not cleared. Reading IR has no side effects,
+    /// so this does not interfere with the owners of the interrupts, who
+    /// remain responsible for clearing them.
+    pub fn peek_all_flags(&self) -> InterruptSet {
+        // Safety: Reading IR has no side effects and nothing is written.
+        let flags = unsafe { (*Id::register_block()).ir.read().bits() };
+        InterruptSet(flags & ALL_INTERRUPTS)
+    }
+
     /// Enable interrupts contained in an `interrupt` or switch them to the line
     /// 0.
+    ///
+    /// Interrupts that are already enabled stay enabled while they are moved.
     pub fn enable_line_0<State>(
         &mut self,
         interrupt: OwnedInterruptSet<Id, State>,
@@ -571,6 +1214,8 @@ impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
 
     /// Enable interrupts contained in an `interrupt` or switch them to the line
     /// 1.
+    ///
+    /// Interrupts that are already enabled stay enabled while they are moved.
     pub fn enable_line_1<State>(
         &mut self,
         interrupt: OwnedInterruptSet<Id, State>,
@@ -594,6 +1239,25 @@ impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
         }
     }
 
+    /// Switch the interrupts contained in `interrupt` to the specified
+    /// `line`, without enabling or disabling any of them
+    ///
+    /// Unlike [`Self::enable`], interrupts of a [`Dynamic`](state::Dynamic)
+    /// set that are disabled stay disabled, so that sets mixing enabled and
+    /// disabled interrupts can be moved between lines at runtime, e.g. to
+    /// balance the load of interrupt handlers. Only the line selection of the
+    /// interrupts in the set changes.
+    pub fn reassign<State: state::MaybeEnabled>(
+        &mut self,
+        interrupt: OwnedInterruptSet<Id, State>,
+        line: InterruptLine,
+    ) -> OwnedInterruptSet<Id> {
+        // Safety: A `Dynamic` set can contain interrupts in any state
+        let interrupt = unsafe { interrupt.convert() };
+        self.set_line(&interrupt, line);
+        interrupt
+    }
+
     /// Disable interrupts
     pub fn disable<State>(
         &mut self,
@@ -607,6 +1271,72 @@ impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
         unsafe { interrupt.convert() }
     }
 
+    /// Enable `interrupt` or switch it to the specified `line`.
+    ///
+    /// Returned interrupt is in a dynamic state.
+    pub fn enable_interrupt<State>(
+        &mut self,
+        interrupt: OwnedInterrupt<Id, State>,
+        line: InterruptLine,
+    ) -> OwnedInterrupt<Id> {
+        let interrupt = interrupt.0;
+        // Safety: A `Dynamic` set can contain interrupts in any state
+        let set = unsafe { OwnedInterruptSet::<Id>::new(interrupt.into()) };
+        self.set_line(&set, line);
+        self.set_enabled(&set, true);
+        // Safety: The interrupt is moved from `set` and its state is dynamic.
+        unsafe { OwnedInterrupt::new(interrupt) }
+    }
+
+    /// Disable `interrupt`
+    pub fn disable_interrupt<State>(
+        &mut self,
+        interrupt: OwnedInterrupt<Id, State>,
+    ) -> OwnedInterrupt<Id, state::Disabled> {
+        let interrupt = interrupt.0;
+        // Safety: A `Dynamic` set can contain interrupts in any state
+        let set = unsafe { OwnedInterruptSet::<Id>::new(interrupt.into()) };
+        self.set_enabled(&set, false);
+        // Safety: The interrupt was disabled so type state is `Disabled`
+        unsafe { OwnedInterrupt::new(interrupt) }
+    }
+
+    /// Enable the interrupt `line`, so that the enabled interrupts assigned
+    /// to it trigger it.
+    ///
+    /// Lines are enabled on their own as soon as interrupts are enabled on or
+    /// moved to them, so this is only needed after [`Self::disable_line`].
+    pub fn enable_line(&mut self, line: InterruptLine) {
+        self.ile().modify(|_, w| match line {
+            InterruptLine::Line0 => w.eint0().set_bit(),
+            InterruptLine::Line1 => w.eint1().set_bit(),
+        });
+    }
+
+    /// Disable the interrupt `line`, e.g. to mask all interrupts assigned to
+    /// it during a critical section without touching the system interrupt
+    /// controller.
+    ///
+    /// The interrupts keep their enabled state and line selection, and their
+    /// flags are still set. Enabling interrupts on the line or moving them to
+    /// it, with [`Self::enable`], [`Self::reassign`] and the like, enables the
+    /// line again.
+    pub fn disable_line(&mut self, line: InterruptLine) {
+        self.ile().modify(|_, w| match line {
+            InterruptLine::Line0 => w.eint0().clear_bit(),
+            InterruptLine::Line1 => w.eint1().clear_bit(),
+        });
+    }
+
+    /// Returns `true` if the interrupt `line` is enabled
+    pub fn is_line_enabled(&self, line: InterruptLine) -> bool {
+        let ile = self.ile().read();
+        match line {
+            InterruptLine::Line0 => ile.eint0().bit_is_set(),
+            InterruptLine::Line1 => ile.eint1().bit_is_set(),
+        }
+    }
+
     /// # Safety
     /// Caller must make sure that the type state matches the selected `line`.
     unsafe fn raw_enable<In, Out: state::MaybeEnabled>(
@@ -632,17 +1362,43 @@ impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
     /// - IE
     /// - IR
     pub(crate) unsafe fn new() -> (Self, OwnedInterruptSet<Id, state::Disabled>) {
-        const RESERVED_BITS: u32 = 0x3fff_ffff;
         let v = Self(PhantomData);
-        // Disable all interrupts on the peripheral by writing the reset value.
-        v.ils().write(|w| w);
+        // Disable all interrupts on the peripheral by writing the reset values.
+        v.reset();
         // Safety: The reserved bits are omitted and interrupts are disabled
         // and thus the state is correct.
         (v, unsafe {
-            OwnedInterruptSet::<_, state::Disabled>::new(InterruptSet(RESERVED_BITS))
+            OwnedInterruptSet::<_, state::Disabled>::new(InterruptSet(ALL_INTERRUPTS))
         })
     }
 
+    /// Disables `interrupt` and resets its line selection to line 0, which is
+    /// the reset value. If `interrupt` completes the set of all interrupts in
+    /// `disabled`, the interrupt lines are disabled as well.
+    pub(crate) fn reclaim(
+        &mut self,
+        disabled: &mut OwnedInterruptSet<Id, state::Disabled>,
+        interrupt: OwnedInterruptSet<Id>,
+    ) {
+        self.set_enabled(&interrupt, false);
+        let mask = interrupt.0 .0;
+        // Safety: The reserved bits are 0 by type invariant on `OwnedInterruptSet`.
+        self.ils()
+            .modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        // Safety: Interrupt was disabled so type state is `Disabled`
+        disabled.join(unsafe { interrupt.convert() });
+        if disabled.0 .0 == ALL_INTERRUPTS {
+            self.reset();
+        }
+    }
+
+    /// Writes the reset values to the interrupt configuration registers.
+    pub(crate) fn reset(&self) {
+        self.ie().write(|w| w);
+        self.ils().write(|w| w);
+        self.ile().write(|w| w);
+    }
+
     fn ils(&self) -> &reg::ILS {
         // Safety: The constructor sets self up to have exclusive access to ILS.
         &unsafe { &*Id::register_block() }.ils
@@ -672,13 +1428,6 @@ impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
         });
     }
 
-    fn enable_line(&mut self, line: InterruptLine) {
-        self.ile().modify(|_, w| match line {
-            InterruptLine::Line0 => w.eint0().set_bit(),
-            InterruptLine::Line1 => w.eint1().set_bit(),
-        });
-    }
-
     fn set_enabled(&mut self, interrupts: &OwnedInterruptSet<Id>, enabled: bool) {
         let mask = interrupts.0 .0;
         // Safety: The reserved bits are 0 by type invariant on `OwnedInterruptSet`.
@@ -695,6 +1444,7 @@ impl<Id: mcan_core::CanId> InterruptConfiguration<Id> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::mock::mock_can;
 
     #[test]
     fn iter_preserves_length() {
@@ -722,4 +1472,329 @@ mod test {
     fn iter_collect_drops_reserved_bits() {
         assert_eq!(iter_collect(0xffff_ffff), 0x3fff_ffff);
     }
+
+    #[test]
+    fn iter_len_shrinks_as_interrupts_are_consumed() {
+        let mut iter = InterruptSet(0b1011 | 1 << 29).iter();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next(), Some(Interrupt::RxFifo0NewMessage));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(Interrupt::RxFifo0WatermarkReached));
+        assert_eq!(iter.next(), Some(Interrupt::RxFifo0MessageLost));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next(), Some(Interrupt::AccessToReservedAddress));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_len_excludes_reserved_bits() {
+        assert_eq!(InterruptSet(0xffff_ffff).iter().len(), 30);
+        assert_eq!(InterruptSet(0xc000_0000).iter().len(), 0);
+    }
+
+    const LINE_0: InterruptSet = InterruptSet::empty()
+        .with(Interrupt::RxFifo0NewMessage)
+        .with(Interrupt::RxFifo0MessageLost);
+    const LINE_1: InterruptSet = InterruptSet::empty().with(Interrupt::BusOff);
+    const _: () = assert!(LINE_0.0 == 0b1001);
+    const _: () = assert!(LINE_0.contains(Interrupt::RxFifo0MessageLost));
+    const _: () = assert!(LINE_0.intersection(LINE_1).is_empty());
+    const _: () = assert!(LINE_0.union(LINE_1).difference(LINE_0).0 == LINE_1.0);
+    const _: () = assert!(
+        InterruptSet::from_slice(&[Interrupt::RxFifo0NewMessage, Interrupt::RxFifo0MessageLost]).0
+            == LINE_0.0
+    );
+    const _: () = assert!(Interrupt::AccessToReservedAddress.mask() == 1 << 29);
+
+    #[test]
+    fn const_sets_match_collected_ones() {
+        let collected: InterruptSet = [Interrupt::RxFifo0NewMessage, Interrupt::RxFifo0MessageLost]
+            .into_iter()
+            .collect();
+        assert_eq!(LINE_0.0, collected.0);
+        assert_eq!(LINE_1.0, InterruptSet::from(Interrupt::BusOff).0);
+        assert_eq!(InterruptSet::empty().iter().count(), 0);
+        // Adding an interrupt twice is idempotent
+        assert_eq!(LINE_1.with(Interrupt::BusOff).0, LINE_1.0);
+    }
+
+    #[test]
+    fn set_arithmetic() {
+        let a = InterruptSet(0b0110);
+        let b = InterruptSet(0b1100);
+        assert_eq!(a.difference(b).0, 0b0010);
+        assert_eq!(a.intersection(b).0, 0b0100);
+        assert_eq!(a.union(b).0, 0b1110);
+        assert!(a.contains(Interrupt::RxFifo0WatermarkReached));
+        assert!(!a.contains(Interrupt::RxFifo0NewMessage));
+    }
+
+    fn sources_fields(sources: FlaggedSources) -> [bool; 17] {
+        let FlaggedSources {
+            flags: _,
+            rx_fifo_0_new,
+            rx_fifo_0_lost,
+            rx_fifo_1_new,
+            rx_fifo_1_lost,
+            dedicated_rx,
+            high_priority,
+            tx_finished,
+            tx_fifo_empty,
+            tx_event_new,
+            tx_event_lost,
+            bus_off,
+            error_passive,
+            warning,
+            protocol_error,
+            timestamp_wraparound,
+            timeout,
+            fault,
+        } = sources;
+        [
+            rx_fifo_0_new,
+            rx_fifo_0_lost,
+            rx_fifo_1_new,
+            rx_fifo_1_lost,
+            dedicated_rx,
+            high_priority,
+            tx_finished,
+            tx_fifo_empty,
+            tx_event_new,
+            tx_event_lost,
+            bus_off,
+            error_passive,
+            warning,
+            protocol_error,
+            timestamp_wraparound,
+            timeout,
+            fault,
+        ]
+    }
+
+    #[test]
+    fn every_interrupt_maps_to_one_source() {
+        let mut covered = [false; 17];
+        for interrupt in InterruptSet(u32::MAX).iter() {
+            let sources = FlaggedSources::from_flags(interrupt.into());
+            let fields = sources_fields(sources);
+            assert_eq!(
+                fields.iter().filter(|&&set| set).count(),
+                1,
+                "{interrupt:?}"
+            );
+            covered
+                .iter_mut()
+                .zip(fields)
+                .for_each(|(covered, set)| *covered |= set);
+            assert_eq!(sources.flags.0, interrupt.mask());
+        }
+        assert!(covered.iter().all(|&covered| covered));
+        let none = FlaggedSources::from_flags(InterruptSet::empty());
+        assert!(!sources_fields(none).contains(&true));
+    }
+
+    #[test]
+    fn sources_are_read_and_cleared_once() {
+        mock_can!(Can6);
+        const IR: usize = 0x50;
+        let owned = InterruptSet::from_slice(&[
+            Interrupt::RxFifo0NewMessage,
+            Interrupt::RxFifo1MessageLost,
+            Interrupt::BusOff,
+        ]);
+        // Safety: The registers belong to this test only.
+        let set = unsafe { OwnedInterruptSet::<Can6>::new(owned) };
+        Can6::regs().write(
+            IR,
+            Interrupt::RxFifo0NewMessage.mask()
+                | Interrupt::BusOff.mask()
+                | Interrupt::TxFifoEmpty.mask(),
+        );
+        let sources = FlaggedSources::from(&set);
+        assert!(sources.rx_fifo_0_new && sources.bus_off);
+        // Not flagged, and flagged but not owned
+        assert!(!sources.rx_fifo_1_lost && !sources.tx_fifo_empty);
+        assert_eq!(
+            Can6::regs().read(IR),
+            Interrupt::RxFifo0NewMessage.mask() | Interrupt::BusOff.mask()
+        );
+    }
+
+    mock_can!(Can0);
+
+    #[test]
+    fn owned_set_introspection() {
+        const IR: usize = 0x50;
+        // Safety: The registers belong to this test only.
+        let mut owned = unsafe { OwnedInterruptSet::<Can0>::new(InterruptSet(0b0110)) };
+        Can0::regs().write(IR, 0b0011);
+        // Owned regardless of being flagged
+        assert_eq!(owned.owned().0, 0b0110);
+        assert_eq!(owned.interrupt_flags().0, 0b0010);
+        assert!(owned.contains(Interrupt::RxFifo0Full));
+        let split = owned
+            .split(Interrupt::RxFifo0WatermarkReached.into())
+            .unwrap();
+        assert_eq!(owned.owned().0, 0b0100);
+        assert_eq!(split.owned().0, 0b0010);
+        assert!(!owned.contains(Interrupt::RxFifo0WatermarkReached));
+
+        // Flagged, but owned by the split set
+        assert!(!owned.is_flagged(Interrupt::RxFifo0WatermarkReached));
+        assert!(split.is_flagged(Interrupt::RxFifo0WatermarkReached));
+        // Not owned by either
+        assert!(!split.is_flagged(Interrupt::RxFifo0NewMessage));
+        // The mock keeps the last write: zero bits leave the flags unchanged
+        owned.clear(Interrupt::RxFifo0WatermarkReached);
+        assert_eq!(Can0::regs().read(IR), 0);
+        split.clear(Interrupt::RxFifo0WatermarkReached);
+        assert_eq!(Can0::regs().read(IR), 0b0010);
+        owned.join(split);
+        assert_eq!(owned.owned().0, 0b0110);
+    }
+
+    #[test]
+    fn flags_can_be_polled_without_enabling() {
+        mock_can!(Can1);
+        const IR: usize = 0x50;
+        const IE: usize = 0x54;
+        let regs = Can1::regs();
+        // Safety: The registers belong to this test only.
+        let (configuration, mut disabled) = unsafe { InterruptConfiguration::<Can1>::new() };
+        let bus_off = disabled.split(Interrupt::BusOff.into()).unwrap();
+        regs.write(IR, 1 << 25 | 1 << 1 | 1 << 30);
+
+        // Reserved bits are dropped, nothing is cleared
+        assert_eq!(configuration.peek_all_flags().0, 1 << 25 | 1 << 1);
+        assert_eq!(regs.read(IR), 1 << 25 | 1 << 1 | 1 << 30);
+
+        assert_eq!(bus_off.interrupt_flags().0, 1 << 25);
+        assert_eq!(bus_off.iter_flagged().collect::<InterruptSet>().0, 1 << 25);
+        // Write 1 to clear, only the owned flag
+        assert_eq!(regs.read(IR), 1 << 25);
+        assert_eq!(regs.read(IE), 0);
+    }
+
+    #[test]
+    fn single_interrupts_are_taken_once() {
+        mock_can!(Can2);
+        const IR: usize = 0x50;
+        const IE: usize = 0x54;
+        const ILS: usize = 0x58;
+        const BUS_OFF: u32 = 1 << 25;
+        let regs = Can2::regs();
+        // Safety: The registers belong to this test only.
+        let (mut configuration, mut disabled) = unsafe { InterruptConfiguration::<Can2>::new() };
+        let bus_off = disabled.take(Interrupt::BusOff).unwrap();
+        assert_eq!(bus_off.interrupt(), Interrupt::BusOff);
+        assert!(!disabled.contains(Interrupt::BusOff));
+        assert_eq!(
+            disabled.take(Interrupt::BusOff).err().map(|e| e.0 .0),
+            Some(BUS_OFF)
+        );
+
+        regs.write(IR, BUS_OFF | 1);
+        assert!(bus_off.is_flagged());
+        bus_off.clear();
+        // Write 1 to clear, only the owned flag
+        assert_eq!(regs.read(IR), BUS_OFF);
+
+        let bus_off = configuration.enable_interrupt(bus_off, InterruptLine::Line1);
+        assert_eq!(regs.read(IE), BUS_OFF);
+        assert_eq!(regs.read(ILS), BUS_OFF);
+        let bus_off = configuration.disable_interrupt(bus_off);
+        assert_eq!(regs.read(IE), 0);
+
+        disabled.join(bus_off.into());
+        assert_eq!(disabled.owned().0, ALL_INTERRUPTS);
+    }
+
+    #[test]
+    fn reassignment_keeps_interrupts_enabled_or_disabled() {
+        mock_can!(Can5);
+        const IE: usize = 0x54;
+        const ILS: usize = 0x58;
+        const ILE: usize = 0x5c;
+        let regs = Can5::regs();
+        // Safety: The registers belong to this test only.
+        let (mut configuration, mut disabled) = unsafe { InterruptConfiguration::<Can5>::new() };
+        let rx_fifo_0 = configuration.enable_line_0(disabled.split(InterruptSet(0b0001)).unwrap());
+        let rx_fifo_1 = configuration.enable_line_1(disabled.split(InterruptSet(0b0110)).unwrap());
+        let mut mixed: OwnedInterruptSet<Can5> = rx_fifo_1.into();
+        mixed.join(disabled.split(InterruptSet(0b1000)).unwrap().into());
+        assert_eq!((regs.read(IE), regs.read(ILS)), (0b0111, 0b0110));
+
+        let mixed = configuration.reassign(mixed, InterruptLine::Line0);
+        assert_eq!((regs.read(IE), regs.read(ILS)), (0b0111, 0));
+        let _mixed = configuration.reassign(mixed, InterruptLine::Line1);
+        assert_eq!((regs.read(IE), regs.read(ILS)), (0b0111, 0b1110));
+        assert_eq!(regs.read(ILE), 0b11);
+        let _rx_fifo_0 = configuration.reassign(rx_fifo_0, InterruptLine::Line1);
+        assert_eq!((regs.read(IE), regs.read(ILS)), (0b0111, 0b1111));
+    }
+
+    #[test]
+    fn lines_are_disabled_and_enabled_again() {
+        mock_can!(Can7);
+        const IE: usize = 0x54;
+        const ILE: usize = 0x5c;
+        let regs = Can7::regs();
+        // Safety: The registers belong to this test only.
+        let (mut configuration, mut disabled) = unsafe { InterruptConfiguration::<Can7>::new() };
+        let enabled = |configuration: &InterruptConfiguration<Can7>| {
+            [InterruptLine::Line0, InterruptLine::Line1]
+                .map(|line| configuration.is_line_enabled(line))
+        };
+        assert_eq!(enabled(&configuration), [false, false]);
+        let rx = configuration.enable_line_1(disabled.split(InterruptSet(0b0011)).unwrap());
+        assert_eq!(enabled(&configuration), [false, true]);
+
+        configuration.enable_line(InterruptLine::Line0);
+        assert_eq!(regs.read(ILE), 0b11);
+        configuration.disable_line(InterruptLine::Line1);
+        assert_eq!(regs.read(ILE), 0b01);
+        assert_eq!(enabled(&configuration), [true, false]);
+        // The interrupts stay enabled
+        assert_eq!(regs.read(IE), 0b0011);
+        configuration.disable_line(InterruptLine::Line0);
+        assert_eq!(regs.read(ILE), 0);
+
+        let _rx = configuration.reassign(rx, InterruptLine::Line1);
+        assert_eq!(enabled(&configuration), [false, true]);
+    }
+
+    #[test]
+    fn shared_lines_are_serviced_per_peripheral() {
+        mock_can!(Can3);
+        mock_can!(Can4);
+        const IR: usize = 0x50;
+        let rx = InterruptSet::empty()
+            .with(Interrupt::RxFifo0NewMessage)
+            .with(Interrupt::RxFifo1NewMessage);
+        // Safety: The registers belong to this test only.
+        let shared = unsafe {
+            MultiLine((
+                OwnedInterruptSet::<Can3, state::EnabledLine0>::new(rx),
+                OwnedInterruptSet::<Can4, state::EnabledLine0>::new(rx.with(Interrupt::BusOff)),
+            ))
+        };
+        assert_ne!(Can3::regs() as *const _, Can4::regs() as *const _);
+
+        Can3::regs().write(IR, 1 << 4 | 1 << 25);
+        Can4::regs().write(IR, 1 << 0 | 1 << 25);
+        assert!(shared.iter_flagged_all().eq([
+            (0, Interrupt::RxFifo1NewMessage),
+            (1, Interrupt::RxFifo0NewMessage),
+            (1, Interrupt::BusOff),
+        ]));
+        // The mock keeps the last write, which clears only the owned flags
+        assert_eq!(Can3::regs().read(IR), 1 << 4);
+        assert_eq!(Can4::regs().read(IR), 1 << 0 | 1 << 25);
+
+        shared.clear_all(InterruptSet::empty().with(Interrupt::BusOff));
+        assert_eq!(Can3::regs().read(IR), 0);
+        assert_eq!(Can4::regs().read(IR), 1 << 25);
+    }
 }
@@ -1,5 +1,14 @@
 #![no_std]
 #![warn(missing_docs)]
+#![cfg_attr(
+    all(feature = "panic-free", not(test)),
+    deny(
+        clippy::indexing_slicing,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic
+    )
+)]
 //! # MCAN
 //!
 //! ## Overview
@@ -14,8 +23,16 @@
 //!   interrupt lines
 //! - message transmission using dedicated buffers, FIFO and priority queue
 //! - message transmission cancellation
+//! - cancellation-safe `async` transmission and reception (`async` feature)
+//! - taking over a peripheral configured by a bootloader (`handoff` feature)
 //! - message reception using dedicated buffers and two FIFOs
+//! - lock-free distribution of received messages to multiple consumers
 //! - filter settings
+//! - extension of RX and TX event timestamps to 64 bits
+//! - sequence counter and CRC sealing of frames for safety protocols
+//! - lint enforcement of [panic freedom](#panic-freedom) (`panic-free` feature)
+//! - host-side stand-ins for the peripheral to test applications with
+//!   (`test-util` feature)
 //!
 //! MCAN is embedded in the MCU like all other peripherals. The interface
 //! between them includes two clock signal lines, two HW interrupt lines, a
@@ -29,9 +46,9 @@
 //! expected to implement.
 //!
 //! In order to use MCAN, one has to instantiate [`CanConfigurable`] and
-//! [`finalize`] it. Its constructor requires an instance of an
-//! [`Dependencies`] implementing struct and holds onto it until it's
-//! [`released`]. Safety requirements of the `Dependencies` trait
+//! finalize it with [`finalize_recoverable`]. Its constructor requires an
+//! instance of an [`Dependencies`] implementing struct and holds onto it until
+//! it's [`released`]. Safety requirements of the `Dependencies` trait
 //! guarantee a correct state of MCAN interfaces during its operation.
 //!
 //! ## Message RAM Configuration
@@ -77,7 +94,7 @@
 //!
 //! ```no_run
 //! use mcan::generic_array::typenum::consts::*;
-//! use mcan::messageram::SharedMemory;
+//! use mcan::messageram::{SharedMemoryCell, SharedMemoryGrant};
 //! use mcan::message::{tx, rx};
 //! use mcan::prelude::*;
 //! struct Capacities;
@@ -97,9 +114,17 @@
 //! }
 //!
 //! #[link_section = ".can"]
-//! static mut MESSAGE_RAM: SharedMemory<Capacities> = SharedMemory::new();
+//! static MESSAGE_RAM: SharedMemoryCell<Capacities> = SharedMemoryCell::new();
+//! // Safety: This is the only grant for `MESSAGE_RAM` and it is never stolen.
+//! static MESSAGE_RAM_GRANT: SharedMemoryGrant<Capacities> =
+//!     unsafe { SharedMemoryGrant::new(&MESSAGE_RAM) };
 //! ```
 //!
+//! [`SharedMemoryCell`] allows the Message RAM to be declared without `static
+//! mut`. The accompanying [`SharedMemoryGrant`] hands out a `&'static mut`
+//! reference to it exactly once. Note that the grant stores a flag and has to
+//! be placed in memory that is initialized at startup.
+//!
 //! When it comes to the [`RTIC`] framework, suggested way of setting the shared
 //! memory up would be to use task-local resource in an `init` task. Reference
 //! to a task-local resource in an `init` has a static lifetime which is
@@ -130,7 +155,7 @@
 //!
 //! ```no_run
 //! # use mcan::generic_array::typenum::consts::*;
-//! # use mcan::messageram::SharedMemory;
+//! # use mcan::messageram::{SharedMemoryCell, SharedMemoryGrant};
 //! # use mcan::message::{tx, rx};
 //! # use mcan::prelude::*;
 //! # use fugit::RateExtU32 as _;
@@ -150,7 +175,9 @@
 //! #     type TxEventFifo = U32;
 //! # }
 //! # #[link_section = ".can"]
-//! # static mut MESSAGE_RAM: SharedMemory<Capacities> = SharedMemory::new();
+//! # static MESSAGE_RAM: SharedMemoryCell<Capacities> = SharedMemoryCell::new();
+//! # static MESSAGE_RAM_GRANT: SharedMemoryGrant<Capacities> =
+//! #     unsafe { SharedMemoryGrant::new(&MESSAGE_RAM) };
 //! # struct Can0;
 //! # unsafe impl mcan::core::CanId for Can0 {
 //! #     const ADDRESS: *const () = 0xDEAD0000 as *const _;
@@ -179,7 +206,7 @@
 //! let mut can = mcan::bus::CanConfigurable::<'_, Can0, _, _>::new(
 //!     500.kHz(),
 //!     dependencies,
-//!     unsafe { &mut MESSAGE_RAM }
+//!     MESSAGE_RAM_GRANT.take().unwrap(),
 //! ).unwrap();
 //!
 //! // MCAN is still disabled and user can access and modify the underlying
@@ -241,8 +268,8 @@
 //!     })
 //!     .unwrap_or_else(|_| panic!("Extended filter application failed"));
 //!
-//! // Call to `finalize` puts MCAN into operational mode
-//! let can = can.finalize().unwrap();
+//! // Call to `finalize_recoverable` puts MCAN into operational mode
+//! let can = can.finalize_recoverable().unwrap();
 //!
 //! // `can` object can be split into independent pieces
 //! let rx_fifo_0 = can.rx_fifo_0;
@@ -252,31 +279,104 @@
 //! let aux = can.aux;
 //! ```
 //!
+//! ## Panic freedom
+//!
+//! The crate does not panic once the peripheral is configured. Receiving,
+//! transmitting, reading TX events and handling interrupts access the
+//! Message RAM only through checked indexing, and indices reported by the
+//! peripheral are wrapped or clamped to the configured capacities, so that
+//! even a misconfigured peripheral cannot make them panic. The exceptions are
+//! debug assertions of invariants that only `unsafe` code can break, and
+//! compile-time checks of the [`Capacities`].
+//!
+//! The `panic-free` feature enforces this by denying indexing, `unwrap`,
+//! `expect` and `panic!` in the crate through clippy lints. It does not
+//! change the code.
+//!
 //! [`RTIC`]: https://rtic.rs
 //! [`CanConfigurable`]: crate::bus::CanConfigurable
-//! [`finalize`]: crate::bus::CanConfigurable::finalize
+//! [`finalize_recoverable`]: crate::bus::CanConfigurable::finalize_recoverable
 //! [`released`]: crate::bus::Can::release
 //! [`Dependencies`]: mcan_core::Dependencies
 //! [`Dependencies::eligible_message_ram_start`]: mcan_core::Dependencies::eligible_message_ram_start
 //! [`Capacities`]: crate::messageram::Capacities
 //! [`SharedMemory`]: crate::messageram::SharedMemory
+//! [`SharedMemoryCell`]: crate::messageram::SharedMemoryCell
+//! [`SharedMemoryGrant`]: crate::messageram::SharedMemoryGrant
 
 pub mod bus;
+#[cfg(test)]
+mod compat;
 pub mod config;
+pub mod diagnostics;
 pub mod filter;
+#[cfg(test)]
+mod golden;
+#[cfg(feature = "handoff")]
+pub mod handoff;
+pub mod id_dispatch;
 pub mod interrupt;
 pub mod message;
 pub mod messageram;
+#[cfg(test)]
+mod mock;
+// The register abstractions generated by svd2rust write through
+// `vcell::VolatileCell`. Unit tests resolve `vcell` to this crate, and with it
+// to the stand-in of `mock` that records the writes, so that the generated
+// code stays as generated.
+#[cfg(test)]
+extern crate self as vcell;
+#[cfg(test)]
+use mock::VolatileCell;
 pub mod prelude;
 pub mod reg;
+mod ring;
+#[cfg(feature = "async")]
+pub mod rx_async;
 pub mod rx_dedicated_buffers;
+pub mod rx_demux;
 pub mod rx_fifo;
+pub mod rx_merge;
+pub mod safety;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test;
+pub mod timestamp;
+#[cfg(feature = "async")]
+pub mod tx_async;
 pub mod tx_buffers;
 pub mod tx_event_fifo;
 
 pub use embedded_can;
+// `generic_array` 0.14 is deprecated upstream but is part of the public API.
+// Its deprecated items are only named in `array`, so that allowing the
+// deprecation does not hide the ones of this crate.
+#[allow(deprecated)]
 pub use generic_array;
 pub use mcan_core as core;
 
 // For svd2rust generated code that refers to everything via `crate::...`
 use reg::generic::*;
+
+#[allow(deprecated)]
+mod array {
+    use generic_array::typenum::{IsLessOrEqual, LeEq, Same, True};
+    use generic_array::ArrayLength;
+
+    pub(crate) type GenericArray<T, N> = generic_array::GenericArray<T, N>;
+
+    /// [`generic_array::ArrayLength`] with an upper bound.
+    pub trait LimitedArrayLength<T, MaxLength>: ArrayLength<T> {}
+    impl<T, N, MaxLength> LimitedArrayLength<T, MaxLength> for N
+    where
+        N: ArrayLength<T> + IsLessOrEqual<MaxLength>,
+        LeEq<N, MaxLength>: Same<True>,
+    {
+    }
+}
+
+mod sealed {
+    /// Super trait of the traits that erase the generic parametrization of
+    /// the components, so that methods can be added to them without breaking
+    /// dependents
+    pub trait Sealed {}
+}
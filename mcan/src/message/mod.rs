@@ -1,5 +1,7 @@
 //! Handling of messages/frames
 
+#[cfg(test)]
+mod properties;
 pub mod rx;
 pub mod tx;
 mod tx_event;
@@ -12,31 +14,107 @@ use embedded_can::{ExtendedId, Frame, Id, StandardId};
 /// This trait is only implemented for the data sizes that the peripheral can be
 /// configured to use.
 pub trait AnyMessage: Copy + Raw {
+    /// Size of the data field of the message element
+    const SIZE: ElementSize;
+
     /// The value of the data size field that indicates this data size
-    const REG: u8;
+    #[deprecated(note = "use `SIZE.to_code()` instead")]
+    const REG: u8 = Self::SIZE.to_code();
 }
 
 macro_rules! impl_any_message {
-    ($len:literal, $reg:literal) => {
+    ($len:literal, $size:ident) => {
         impl AnyMessage for RawMessage<$len> {
-            const REG: u8 = $reg;
+            const SIZE: ElementSize = ElementSize::$size;
         }
     };
 }
 
-impl_any_message!(8, 0);
-impl_any_message!(12, 1);
-impl_any_message!(16, 2);
-impl_any_message!(20, 3);
-impl_any_message!(24, 4);
-impl_any_message!(32, 5);
-impl_any_message!(48, 6);
-impl_any_message!(64, 7);
+impl_any_message!(8, B8);
+impl_any_message!(12, B12);
+impl_any_message!(16, B16);
+impl_any_message!(20, B20);
+impl_any_message!(24, B24);
+impl_any_message!(32, B32);
+impl_any_message!(48, B48);
+impl_any_message!(64, B64);
+
+/// Data field size of the elements of a buffer or FIFO in the Message RAM
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ElementSize {
+    /// 8 byte data field
+    B8,
+    /// 12 byte data field
+    B12,
+    /// 16 byte data field
+    B16,
+    /// 20 byte data field
+    B20,
+    /// 24 byte data field
+    B24,
+    /// 32 byte data field
+    B32,
+    /// 48 byte data field
+    B48,
+    /// 64 byte data field
+    B64,
+}
+
+impl ElementSize {
+    /// Decodes the value of a data field size register field
+    pub const fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => Self::B8,
+            1 => Self::B12,
+            2 => Self::B16,
+            3 => Self::B20,
+            4 => Self::B24,
+            5 => Self::B32,
+            6 => Self::B48,
+            7 => Self::B64,
+            _ => return None,
+        })
+    }
+
+    /// Value of the data field size register fields for this size
+    pub const fn to_code(self) -> u8 {
+        self as u8
+    }
+
+    /// Size of the data field in bytes
+    pub const fn bytes(self) -> usize {
+        match self {
+            Self::B8 => 8,
+            Self::B12 => 12,
+            Self::B16 => 16,
+            Self::B20 => 20,
+            Self::B24 => 24,
+            Self::B32 => 32,
+            Self::B48 => 48,
+            Self::B64 => 64,
+        }
+    }
+}
 
 /// Data does not fit in the backing buffer
 #[derive(Debug)]
 pub struct TooMuchData;
 
+/// Reason why [`Message::new_checked`] cannot construct a Classic CAN frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// The data does not fit the backing buffer or any frame
+    TooMuchData,
+    /// The data fits a CAN FD frame only, see [`Message::new_fd`]
+    FdRequired,
+}
+
+impl From<TooMuchData> for FrameError {
+    fn from(_: TooMuchData) -> Self {
+        Self::TooMuchData
+    }
+}
+
 /// CAN frame/message.
 pub enum Message<const N: usize> {
     /// Message received from a CAN bus
@@ -51,18 +129,84 @@ impl<const N: usize> Message<N> {
             Self::Rx(rx::Message(m)) | Self::Tx(tx::Message(m)) => m,
         }
     }
+
+    /// Data length code of the header, unlike [`Frame::dlc`] which returns
+    /// the length in bytes that it encodes
+    ///
+    /// The two differ for CAN FD frames with more than 8 bytes, e.g. a 32
+    /// byte frame has the code 13.
+    pub fn raw_dlc(&self) -> u8 {
+        self.raw().dlc()
+    }
+
+    /// `true` if the frame uses the CAN FD format, see [`Raw::fd_format`]
+    pub fn is_fd(&self) -> bool {
+        self.raw().fd_format()
+    }
+
+    /// `true` if the data phase of the frame uses the data bit rate, see
+    /// [`Raw::bit_rate_switching`]
+    pub fn bit_rate_switched(&self) -> bool {
+        self.raw().bit_rate_switching()
+    }
+
+    /// `true` if the transmitter of the frame indicates that it is error
+    /// passive, see [`Raw::is_transmitter_error_passive`]
+    pub fn error_state_indicator(&self) -> bool {
+        self.raw().is_transmitter_error_passive()
+    }
+
+    /// Timestamp of the reception, see [`rx::AnyMessage::timestamp`]. `None`
+    /// for messages to transmit.
+    pub fn timestamp(&self) -> Option<u16> {
+        match self {
+            Self::Rx(m) => Some(m.rx_timestamp()),
+            Self::Tx(_) => None,
+        }
+    }
+
+    /// Index of the filter that accepted the frame, see
+    /// [`rx::AnyMessage::filter_index`]. `None` for messages to transmit.
+    pub fn filter_index(&self) -> Option<u8> {
+        match self {
+            Self::Rx(m) => m.rx_filter_index(),
+            Self::Tx(_) => None,
+        }
+    }
+
+    /// CAN FD data frame with up to 64 bytes of `data`, padded to the next
+    /// length that a data length code encodes
+    ///
+    /// `None` if `data` does not fit in `N` bytes.
+    pub fn new_fd(id: impl Into<Id>, data: &[u8], bit_rate_switching: bool) -> Option<Self> {
+        let builder = tx::MessageBuilder::fd(id, data);
+        let builder = if bit_rate_switching {
+            builder.with_brs()
+        } else {
+            builder
+        };
+        builder.build().ok().map(Self::Tx)
+    }
+
+    /// Classic CAN data frame, like [`Frame::new`] but telling apart data
+    /// that needs a CAN FD frame from data that fits no frame
+    pub fn new_checked(id: impl Into<Id>, data: &[u8]) -> Result<Self, FrameError> {
+        if data.len() > N || len_to_dlc(data.len(), true).is_err() {
+            return Err(FrameError::TooMuchData);
+        }
+        len_to_dlc(data.len(), false).map_err(|_| FrameError::FdRequired)?;
+        Ok(Self::Tx(tx::MessageBuilder::classic(id, data).build()?))
+    }
 }
 
 impl<const N: usize> Frame for Message<N> {
+    /// Classic CAN data frame with up to 8 bytes of `data`
+    ///
+    /// `None` for more than 8 bytes, even if they would fit in `N`, as the
+    /// trait has no notion of CAN FD. Use [`Message::new_fd`] for CAN FD
+    /// frames, or [`Message::new_checked`] for the reason of the failure.
     fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
-        tx::MessageBuilder {
-            id: id.into(),
-            frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(data)),
-            store_tx_event: None,
-        }
-        .build()
-        .ok()
-        .map(Self::Tx)
+        Self::new_checked(id, data).ok()
     }
 
     fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
@@ -93,8 +237,10 @@ impl<const N: usize> Frame for Message<N> {
         self.raw().id()
     }
 
+    /// Data length in bytes, which for CAN FD frames can be up to 64, see
+    /// [`Message::raw_dlc`] for the data length code
     fn dlc(&self) -> usize {
-        self.raw().dlc().into()
+        self.raw().decoded_dlc()
     }
 
     fn data(&self) -> &[u8] {
@@ -102,6 +248,345 @@ impl<const N: usize> Frame for Message<N> {
     }
 }
 
+#[cfg(test)]
+impl<const N: usize> From<tx::Message<N>> for rx::Message<N> {
+    /// Loops a message back as if it was received, for testing purposes.
+    fn from(value: tx::Message<N>) -> Self {
+        rx::Message(value.0)
+    }
+}
+
+#[cfg(test)]
+impl<const N: usize> rx::Message<N> {
+    /// Overrides the RX timestamp, for testing purposes.
+    pub(crate) fn set_timestamp(&mut self, timestamp: u16) {
+        self.0.header[1] = self.0.header[1] & !0xffff | u32::from(timestamp);
+    }
+}
+
+#[cfg(test)]
+impl TxEvent {
+    /// Event of a classic frame with standard ID 0 and message marker
+    /// `marker`, for testing purposes.
+    pub(crate) fn with_marker(marker: u8) -> Self {
+        Self(RawMessage {
+            header: [0, u32::from(marker) << 24 | 1 << 22],
+            data: [],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn element_size_round_trip() {
+        for code in 0..=7 {
+            let size = ElementSize::from_code(code).unwrap();
+            assert_eq!(size.to_code(), code);
+            assert_eq!(dlc_to_len(code + 8, true), size.bytes());
+        }
+        assert_eq!(ElementSize::from_code(8), None);
+        assert_eq!(RawMessage::<8>::SIZE.bytes(), 8);
+        assert_eq!(rx::Message::<48>::SIZE, ElementSize::B48);
+        assert_eq!(tx::Message::<64>::SIZE.to_code(), 7);
+        #[allow(deprecated)]
+        let reg = tx::Message::<20>::REG;
+        assert_eq!(reg, 3);
+    }
+
+    #[test]
+    fn remote_frame_dlc_round_trip() {
+        let id = StandardId::new(0x321).unwrap();
+        for dlc in 0..=8 {
+            let frame = Message::<8>::new_remote(id, dlc).unwrap();
+            assert!(frame.is_remote_frame());
+            assert_eq!(frame.dlc(), dlc);
+            assert_eq!(frame.data(), &[]);
+            assert_eq!(frame.raw().remote_request_len(), Some(dlc));
+        }
+    }
+
+    #[test]
+    fn fd_frame_accessors() {
+        let payload = [0x55; 32];
+        let message = build::<64>(
+            ExtendedId::MAX.into(),
+            tx::FrameType::FlexibleDatarate {
+                payload: &payload,
+                bit_rate_switching: true,
+                force_error_state_indicator: true,
+            },
+        );
+        let frame = Message::Tx(message);
+        assert_eq!((frame.dlc(), frame.raw_dlc()), (32, 13));
+        assert!(frame.is_fd() && frame.bit_rate_switched() && frame.error_state_indicator());
+        assert_eq!((frame.timestamp(), frame.filter_index()), (None, None));
+
+        let mut received = rx::Message::from(message);
+        received.set_timestamp(0x1234);
+        received.0.header[1] |= 3 << 24;
+        let frame = Message::Rx(received);
+        assert_eq!((frame.dlc(), frame.raw_dlc()), (32, 13));
+        assert_eq!(frame.data(), &payload);
+        assert_eq!(
+            (frame.timestamp(), frame.filter_index()),
+            (Some(0x1234), Some(3))
+        );
+        received.0.header[1] |= 1 << 31;
+        assert_eq!(Message::Rx(received).filter_index(), None);
+
+        let frame = Message::<8>::new(StandardId::ZERO, &[1]).unwrap();
+        assert!(!frame.is_fd() && !frame.bit_rate_switched() && !frame.error_state_indicator());
+    }
+
+    #[test]
+    fn data_frame_has_no_remote_request_len() {
+        let frame = Message::<8>::new(StandardId::ZERO, &[1, 2, 3]).unwrap();
+        assert_eq!(frame.raw().remote_request_len(), None);
+        assert_eq!(frame.dlc(), 3);
+    }
+
+    #[test]
+    fn frame_constructors_at_the_length_boundaries() {
+        let id = StandardId::new(0x42).unwrap();
+        let data = [0xa5; 65];
+        assert!(Message::<64>::new(id, &data[..8]).is_some());
+        assert!(Message::<64>::new(id, &data[..9]).is_none());
+        assert!(Message::<64>::new(id, &data[..64]).is_none());
+        assert_eq!(
+            Message::<64>::new_checked(id, &data[..9]).err(),
+            Some(FrameError::FdRequired)
+        );
+        assert_eq!(
+            Message::<64>::new_checked(id, &data[..64]).err(),
+            Some(FrameError::FdRequired)
+        );
+        assert_eq!(
+            Message::<64>::new_checked(id, &data).err(),
+            Some(FrameError::TooMuchData)
+        );
+        // More than the backing buffer holds
+        assert_eq!(
+            Message::<8>::new_checked(id, &data[..9]).err(),
+            Some(FrameError::TooMuchData)
+        );
+        let frame = Message::<64>::new_checked(id, &data[..8]).unwrap();
+        assert!(!frame.is_fd());
+        assert_eq!(frame.data(), &data[..8]);
+
+        for len in [8, 9, 64] {
+            let frame = Message::<64>::new_fd(id, &data[..len], true).unwrap();
+            assert!(frame.is_fd() && frame.bit_rate_switched());
+            assert_eq!(frame.dlc(), if len == 9 { 12 } else { len });
+            assert_eq!(&frame.data()[..len], &data[..len]);
+        }
+        assert!(!Message::<64>::new_fd(id, &data[..9], false)
+            .unwrap()
+            .bit_rate_switched());
+        assert!(Message::<64>::new_fd(id, &data, false).is_none());
+        assert!(Message::<8>::new_fd(id, &data[..9], false).is_none());
+    }
+
+    fn build<const N: usize>(id: Id, frame_type: tx::FrameType) -> tx::Message<N> {
+        tx::MessageBuilder {
+            id,
+            frame_type,
+            store_tx_event: None,
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn fd(bit_rate_switching: bool) -> tx::FrameType<'static> {
+        tx::FrameType::FlexibleDatarate {
+            payload: &[],
+            bit_rate_switching,
+            force_error_state_indicator: false,
+        }
+    }
+
+    /// Checks the header fields of a zero-length data frame, before and after
+    /// looping it back
+    fn assert_empty<const N: usize>(message: tx::Message<N>, id: Id, fd: bool, brs: bool) {
+        let received = rx::Message::from(message);
+        for raw in [&message as &dyn Raw, &received] {
+            assert_eq!(raw.id(), id);
+            assert_eq!(raw.is_extended(), matches!(id, Id::Extended(_)));
+            assert_eq!(raw.dlc(), 0);
+            assert_eq!(raw.decoded_dlc(), 0);
+            assert_eq!(raw.data(), &[]);
+            assert!(!raw.is_remote_frame());
+            assert_eq!(raw.remote_request_len(), None);
+            assert_eq!(raw.fd_format(), fd);
+            assert_eq!(raw.bit_rate_switching(), brs);
+            assert!(!raw.is_transmitter_error_passive());
+        }
+        assert_eq!(message.0.data, [0; N]);
+    }
+
+    #[test]
+    fn zero_length_classic_frames() {
+        let classic = || tx::FrameType::Classic(tx::ClassicFrameType::Data(&[]));
+        for id in [
+            StandardId::ZERO.into(),
+            StandardId::MAX.into(),
+            ExtendedId::ZERO.into(),
+            ExtendedId::MAX.into(),
+        ] {
+            let built = build::<8>(id, classic());
+            assert_empty(built, id, false, false);
+            assert_empty(build::<64>(id, classic()), id, false, false);
+            let empty = tx::Message::<8>::empty(id);
+            assert_empty(empty, id, false, false);
+            assert_eq!(empty.0.header, built.0.header);
+
+            let frame = Message::<8>::new(id, &[]).unwrap();
+            assert_eq!(frame.dlc(), 0);
+            assert_eq!(frame.data(), &[]);
+            assert!(!frame.is_remote_frame());
+            assert_eq!(frame.id(), id);
+        }
+    }
+
+    #[test]
+    fn zero_length_fd_frames() {
+        for id in [StandardId::MAX.into(), ExtendedId::MAX.into()] {
+            for brs in [false, true] {
+                let built = build::<64>(id, fd(brs));
+                assert_empty(built, id, true, brs);
+                assert_empty(build::<8>(id, fd(brs)), id, true, brs);
+                let empty = tx::Message::<64>::empty_fd(id, brs);
+                assert_empty(empty, id, true, brs);
+                assert_eq!(empty.0.header, built.0.header);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_length_remote_frames() {
+        let id = ExtendedId::MAX.into();
+        let remote = |desired_len| {
+            tx::MessageBuilder {
+                id,
+                frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Remote { desired_len }),
+                store_tx_event: None,
+            }
+            .build::<8>()
+        };
+        let message = remote(0).unwrap();
+        assert!(message.is_remote_frame());
+        assert_eq!(message.dlc(), 0);
+        assert_eq!(message.data(), &[]);
+        assert_eq!(message.remote_request_len(), Some(0));
+        assert_eq!(rx::Message::from(message).remote_request_len(), Some(0));
+        // Lengths that wrap around to zero in a byte are too long
+        assert!(remote(256).is_err());
+        assert!(len_to_dlc(256, true).is_err());
+    }
+
+    #[test]
+    fn messages_are_resized() {
+        let id = ExtendedId::new(0x1234_5678).unwrap();
+        let payload: [u8; 64] = core::array::from_fn(|index| index as u8);
+        let classic = tx::MessageBuilder::classic(id, &payload[..8])
+            .with_tx_event(0xa5)
+            .build::<8>()
+            .unwrap();
+        let wide = classic.resize::<64>().unwrap();
+        assert_eq!(wide.0.header, classic.0.header);
+        assert_eq!(wide.data(), &payload[..8]);
+        assert_eq!(wide.resize::<8>().unwrap().0.header, classic.0.header);
+
+        let fd = tx::MessageBuilder::fd(id, &payload)
+            .with_brs()
+            .with_forced_esi()
+            .build::<64>()
+            .unwrap();
+        assert!(fd.resize::<8>().is_err());
+        assert!(fd.resize::<48>().is_err());
+        let copy = fd.resize::<64>().unwrap();
+        assert_eq!(copy.0.header, fd.0.header);
+        assert_eq!(copy.data(), &payload);
+        // Padding up to the encoded length of 12 bytes is carried over
+        let padded = tx::MessageBuilder::fd(id, &payload[..10])
+            .build::<64>()
+            .unwrap();
+        assert!(padded.resize::<8>().is_err());
+        let narrow = padded.resize::<12>().unwrap();
+        assert_eq!(narrow.data(), padded.data());
+        assert_eq!(narrow.resize::<64>().unwrap().data(), padded.data());
+
+        let remote = tx::MessageBuilder::remote(id, 8)
+            .with_tx_event(1)
+            .build::<64>()
+            .unwrap();
+        let narrow = remote.resize::<8>().unwrap();
+        assert_eq!(narrow.0.header, remote.0.header);
+        assert_eq!(narrow.remote_request_len(), Some(8));
+    }
+
+    #[test]
+    fn received_messages_convert_to_tx() {
+        let id = StandardId::new(0x7df).unwrap();
+        let payload: [u8; 64] = core::array::from_fn(|index| !index as u8);
+        let loop_back = |message: tx::Message<64>| {
+            let mut received = rx::Message::from(message);
+            received.set_timestamp(0xbeef);
+            // Non-matching frame
+            received.0.header[1] |= 1 << 31;
+            received
+        };
+
+        let sent = tx::MessageBuilder::fd(id, &payload)
+            .with_brs()
+            .with_forced_esi()
+            .build::<64>()
+            .unwrap();
+        let forwarded = loop_back(sent).to_tx::<64>().unwrap();
+        assert_eq!(forwarded.0.header, sent.0.header);
+        assert_eq!(forwarded.data(), &payload);
+        assert!(loop_back(sent).to_tx::<8>().is_err());
+
+        let sent = tx::MessageBuilder::classic(id, &payload[..5])
+            .build::<64>()
+            .unwrap();
+        let forwarded = loop_back(sent).to_tx::<8>().unwrap();
+        assert_eq!(forwarded.0.header, sent.0.header);
+        assert_eq!(forwarded.data(), &payload[..5]);
+
+        for desired_len in [0, 8] {
+            let sent = tx::MessageBuilder::remote(id, desired_len)
+                .build::<64>()
+                .unwrap();
+            let forwarded = loop_back(sent).to_tx::<8>().unwrap();
+            assert_eq!(forwarded.0.header, sent.0.header);
+            assert_eq!(forwarded.remote_request_len(), Some(desired_len));
+        }
+
+        // 64 bytes announced, but only 8 stored
+        let truncated = rx::Message::<8>(RawMessage {
+            header: [0, 0xf << 16 | 1 << 21],
+            data: [0; 8],
+        });
+        assert!(truncated.to_tx::<64>().is_err());
+    }
+
+    #[test]
+    fn zeroed_header_is_an_empty_classic_frame() {
+        let message = RawMessage::<8> {
+            header: [0, 0],
+            data: [0xff; 8],
+        };
+        assert_eq!(message.id(), Id::Standard(StandardId::ZERO));
+        assert!(!message.is_extended() && !message.fd_format());
+        assert!(!message.is_remote_frame() && !message.bit_rate_switching());
+        assert_eq!(message.dlc(), 0);
+        assert_eq!(message.data(), &[]);
+    }
+}
+
 /// RX or TX message in the peripheral's representation
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -123,8 +608,14 @@ pub trait Raw {
     fn fd_format(&self) -> bool;
     /// Remote Transmission Request
     fn is_remote_frame(&self) -> bool;
-    /// Data field
+    /// Data field. Empty for remote frames, see
+    /// [`remote_request_len`](Self::remote_request_len).
     fn data(&self) -> &[u8];
+    /// Length in bytes of the data frame requested by a remote frame. `None`
+    /// for data frames.
+    fn remote_request_len(&self) -> Option<usize> {
+        self.is_remote_frame().then(|| self.decoded_dlc())
+    }
     /// Check if the frame uses and extended (29-bit) ID
     fn is_extended(&self) -> bool;
     /// `true` if the sender of the message indicates that it is in "error
@@ -188,10 +679,34 @@ impl<const N: usize> Raw for RawMessage<N> {
     }
 }
 
+impl<const N: usize> RawMessage<N> {
+    /// Copies the message into an element with `M` bytes of data, with
+    /// `header` as the header words
+    ///
+    /// Fails with [`TooMuchData`] if the data does not fit in `M` bytes or is
+    /// already cut short in this element.
+    fn resize<const M: usize>(&self, header: [u32; 2]) -> Result<RawMessage<M>, TooMuchData> {
+        let data = self.data();
+        if !self.is_remote_frame() && data.len() < self.decoded_dlc() {
+            return Err(TooMuchData);
+        }
+        let mut resized = RawMessage {
+            header,
+            data: [0; M],
+        };
+        resized
+            .data
+            .get_mut(..data.len())
+            .ok_or(TooMuchData)?
+            .copy_from_slice(data);
+        Ok(resized)
+    }
+}
+
 /// Finds the smallest data length code that encodes at least len bytes
-fn len_to_dlc(len: usize, fd_format: bool) -> Result<u8, TooMuchData> {
+pub(crate) fn len_to_dlc(len: usize, fd_format: bool) -> Result<u8, TooMuchData> {
     if fd_format {
-        match len as u8 {
+        match len {
             0..=8 => Ok(len as u8),
             9..=12 => Ok(9),
             13..=16 => Ok(10),
@@ -203,7 +718,7 @@ fn len_to_dlc(len: usize, fd_format: bool) -> Result<u8, TooMuchData> {
             65.. => Err(TooMuchData),
         }
     } else {
-        match len as u8 {
+        match len {
             0..=8 => Ok(len as u8),
             9.. => Err(TooMuchData),
         }
@@ -211,7 +726,7 @@ fn len_to_dlc(len: usize, fd_format: bool) -> Result<u8, TooMuchData> {
 }
 
 /// Converts data length code to a length in bytes
-fn dlc_to_len(dlc: u8, fd_format: bool) -> usize {
+pub(crate) fn dlc_to_len(dlc: u8, fd_format: bool) -> usize {
     if fd_format {
         match dlc {
             0..=8 => dlc.into(),
@@ -13,18 +13,55 @@ pub trait AnyMessage: super::AnyMessage {
 
     /// Index of the filter that accepted the frame. `None` if no filter
     /// matched, but the message was accepted due to peripheral-wide settings.
+    ///
+    /// The index refers to the standard or extended filter list depending on
+    /// the ID type of the frame. It is valid regardless of whether the filter
+    /// stored the frame in a FIFO, in a dedicated buffer or as a debug
+    /// message.
     fn filter_index(&self) -> Option<u8>;
 
     /// `true` if no filter matched, but the message was accepted due to
     /// peripheral-wide settings. See also [`Self::filter_index`]
     fn accepted_non_matching_frame(&self) -> bool;
+
+    /// Describes why the frame was accepted
+    fn acceptance_source(&self) -> AcceptanceSource {
+        match self.filter_index() {
+            Some(index) => AcceptanceSource::Filter(index),
+            None => AcceptanceSource::NonMatching,
+        }
+    }
+
+    /// `true` if the frame carried more data than the element it was stored
+    /// in holds, so that [`Raw::data`] only returns the beginning of it.
+    ///
+    /// This happens to CAN FD frames stored in FIFOs or dedicated buffers
+    /// whose elements are smaller than the frame.
+    fn is_truncated(&self) -> bool {
+        !self.is_remote_frame() && self.data().len() < self.decoded_dlc()
+    }
+}
+
+/// Reason for a received frame to be accepted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AcceptanceSource {
+    /// The frame matched the filter with the wrapped index. This includes
+    /// [`Filter::StoreBuffer`] and [`ExtFilter::StoreBuffer`] filters placing
+    /// frames in dedicated buffers.
+    ///
+    /// [`Filter::StoreBuffer`]: crate::filter::Filter::StoreBuffer
+    /// [`ExtFilter::StoreBuffer`]: crate::filter::ExtFilter::StoreBuffer
+    Filter(u8),
+    /// No filter matched, but the frame was accepted due to peripheral-wide
+    /// settings. Such frames are only ever stored in the FIFOs.
+    NonMatching,
 }
 
 impl<const N: usize> super::AnyMessage for Message<N>
 where
     RawMessage<N>: super::AnyMessage,
 {
-    const REG: u8 = RawMessage::<N>::REG;
+    const SIZE: super::ElementSize = RawMessage::<N>::SIZE;
 }
 
 impl<const N: usize> super::Raw for Message<N> {
@@ -71,12 +108,9 @@ where
                     force_error_state_indicator: false,
                 }
             } else {
-                tx::FrameType::Classic(if self.is_remote_frame() {
-                    tx::ClassicFrameType::Remote {
-                        desired_len: dlc_to_len(self.dlc(), self.fd_format()),
-                    }
-                } else {
-                    tx::ClassicFrameType::Data(self.data())
+                tx::FrameType::Classic(match self.remote_request_len() {
+                    Some(desired_len) => tx::ClassicFrameType::Remote { desired_len },
+                    None => tx::ClassicFrameType::Data(self.data()),
                 })
             },
             store_tx_event: None,
@@ -84,23 +118,125 @@ where
     }
 
     fn timestamp(&self) -> u16 {
-        self.0.header[1] as u16
+        self.rx_timestamp()
     }
 
     fn filter_index(&self) -> Option<u8> {
-        if self.accepted_non_matching_frame() {
+        self.rx_filter_index()
+    }
+
+    fn accepted_non_matching_frame(&self) -> bool {
+        self.0.header[1] & (1 << 31) != 0 // ANMF
+    }
+}
+
+/// Fields of the RX header, for messages of any data size
+impl<const N: usize> Message<N> {
+    pub(super) fn rx_timestamp(&self) -> u16 {
+        self.0.header[1] as u16
+    }
+
+    pub(super) fn rx_filter_index(&self) -> Option<u8> {
+        if self.0.header[1] & (1 << 31) != 0 {
+            // ANMF
             None
         } else {
             Some(((self.0.header[1] >> 24) & 0x7f) as u8)
         }
     }
+}
 
-    fn accepted_non_matching_frame(&self) -> bool {
-        self.0.header[1] & (1 << 31) != 0 // ANMF
+impl<const N: usize> Message<N> {
+    /// Converts the message into one to be sent from elements of `M` bytes,
+    /// e.g. to forward frames between elements of different sizes
+    ///
+    /// ID, frame format, data length code and bit rate switching are kept, as
+    /// is the error state indicator, so that the frame indicates error passive
+    /// if its sender did. No TX event is stored for the frame. Unlike
+    /// [`AnyMessage::as_tx_builder`], this does not build the message anew.
+    ///
+    /// Fails with [`TooMuchData`] if the data does not fit in `M` bytes or if
+    /// the message [is truncated](AnyMessage::is_truncated).
+    pub fn to_tx<const M: usize>(&self) -> Result<tx::Message<M>, TooMuchData> {
+        // ESI, XTD, RTR and ID are laid out like in T0, while of R1 only FDF,
+        // BRS and DLC carry over to T1
+        let [r0, r1] = self.0.header;
+        self.0.resize([r0, r1 & 0x003f_0000]).map(tx::Message)
     }
 }
 
 /// RX message in the peripheral's representation
 #[repr(transparent)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct Message<const N: usize>(pub(super) RawMessage<N>);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(header_1: u32) -> Message<8> {
+        Message(RawMessage {
+            header: [0, header_1],
+            data: [0; 8],
+        })
+    }
+
+    #[test]
+    fn filter_matched_frame() {
+        // FIDX = 5, DLC = 8, RXTS = 0x1234
+        let m = message(5 << 24 | 8 << 16 | 0x1234);
+        assert!(!m.accepted_non_matching_frame());
+        assert_eq!(m.filter_index(), Some(5));
+        assert_eq!(m.acceptance_source(), AcceptanceSource::Filter(5));
+        assert_eq!(m.timestamp(), 0x1234);
+        assert_eq!(m.dlc(), 8);
+    }
+
+    #[test]
+    fn highest_filter_index() {
+        // FIDX = 127 together with FDF and BRS set
+        let m = message(0x7f << 24 | 1 << 21 | 1 << 20 | 0xf << 16);
+        assert_eq!(m.filter_index(), Some(127));
+        assert!(m.fd_format());
+        assert!(m.bit_rate_switching());
+        assert_eq!(m.decoded_dlc(), 64);
+    }
+
+    #[test]
+    fn non_matching_frame() {
+        // ANMF is set; FIDX content is invalid and must be ignored
+        let m = message(1 << 31 | 0x2a << 24 | 0xffff);
+        assert!(m.accepted_non_matching_frame());
+        assert_eq!(m.filter_index(), None);
+        assert_eq!(m.acceptance_source(), AcceptanceSource::NonMatching);
+        assert_eq!(m.timestamp(), 0xffff);
+    }
+
+    #[test]
+    fn reserved_bits_are_ignored() {
+        // Reserved bits 22 and 23 must not leak into any field
+        let m = message(3 << 24 | 3 << 22);
+        assert_eq!(m.filter_index(), Some(3));
+        assert!(!m.fd_format());
+        assert!(!m.bit_rate_switching());
+        assert_eq!(m.dlc(), 0);
+    }
+
+    #[test]
+    fn remote_frame_bridges_to_tx() {
+        for dlc in 0..=8u32 {
+            // RTR set, standard ID 0x7df
+            let m = Message::<8>(RawMessage {
+                header: [1 << 29 | 0x7df << 18, dlc << 16],
+                data: [0xaa; 8],
+            });
+            assert_eq!(m.data(), &[]);
+            assert_eq!(m.remote_request_len(), Some(dlc as usize));
+            let tx = m.as_tx_builder().build::<8>().unwrap();
+            assert_eq!(tx.0.header[0], 1 << 29 | 0x7df << 18);
+            assert_eq!((tx.0.header[1] >> 16) & 0xf, dlc);
+            assert!(tx.is_remote_frame());
+            assert_eq!(tx.remote_request_len(), Some(dlc as usize));
+        }
+    }
+}
@@ -13,7 +13,7 @@ impl<const N: usize> super::AnyMessage for Message<N>
 where
     RawMessage<N>: super::AnyMessage,
 {
-    const REG: u8 = RawMessage::<N>::REG;
+    const SIZE: super::ElementSize = RawMessage::<N>::SIZE;
 }
 
 impl<const N: usize> Raw for Message<N> {
@@ -60,6 +60,45 @@ where
 #[derive(Copy, Clone, Debug)]
 pub struct Message<const N: usize>(pub(super) RawMessage<N>);
 
+impl<const N: usize> Message<N> {
+    /// Classic data frame without payload, e.g. for heartbeats and triggers
+    pub fn empty(id: impl Into<Id>) -> Self {
+        Self(RawMessage {
+            header: [id_field(id.into()), 0],
+            data: [0; N],
+        })
+    }
+
+    /// CAN FD data frame without payload
+    ///
+    /// Bit rate switching only affects the control and CRC fields here.
+    pub fn empty_fd(id: impl Into<Id>, bit_rate_switching: bool) -> Self {
+        Self(RawMessage {
+            header: [
+                id_field(id.into()),
+                (bit_rate_switching as u32) << 20 | 1 << 21,
+            ],
+            data: [0; N],
+        })
+    }
+
+    /// Copies the message into one for elements of `M` bytes, keeping all of
+    /// the header including whether a TX event is stored
+    ///
+    /// Fails with [`TooMuchData`] if the data does not fit in `M` bytes.
+    pub fn resize<const M: usize>(&self) -> Result<Message<M>, TooMuchData> {
+        self.0.resize(self.0.header).map(Message)
+    }
+}
+
+/// Encodes the ID and XTD fields of the first header word
+fn id_field(id: Id) -> u32 {
+    match id {
+        Id::Standard(id) => (id.as_raw() as u32) << 18,
+        Id::Extended(id) => id.as_raw() | 1 << 30,
+    }
+}
+
 /// Selects the type of the Classic CAN frame.
 pub enum ClassicFrameType<'a> {
     /// 0-8 byte message payload
@@ -108,6 +147,70 @@ pub struct MessageBuilder<'a> {
 }
 
 impl<'a> MessageBuilder<'a> {
+    /// Classic CAN data frame with up to 8 bytes of `data`
+    pub fn classic(id: impl Into<Id>, data: &'a [u8]) -> Self {
+        Self {
+            id: id.into(),
+            frame_type: FrameType::Classic(ClassicFrameType::Data(data)),
+            store_tx_event: None,
+        }
+    }
+
+    /// Classic CAN remote frame requesting `desired_len` bytes
+    pub fn remote(id: impl Into<Id>, desired_len: usize) -> Self {
+        Self {
+            id: id.into(),
+            frame_type: FrameType::Classic(ClassicFrameType::Remote { desired_len }),
+            store_tx_event: None,
+        }
+    }
+
+    /// CAN FD frame with up to 64 bytes of `payload`, without bit rate
+    /// switching
+    pub fn fd(id: impl Into<Id>, payload: &'a [u8]) -> Self {
+        Self {
+            id: id.into(),
+            frame_type: FrameType::FlexibleDatarate {
+                payload,
+                bit_rate_switching: false,
+                force_error_state_indicator: false,
+            },
+            store_tx_event: None,
+        }
+    }
+
+    /// Enables bit rate switching of a CAN FD frame. Classic CAN frames are
+    /// left unchanged.
+    pub fn with_brs(mut self) -> Self {
+        if let FrameType::FlexibleDatarate {
+            bit_rate_switching, ..
+        } = &mut self.frame_type
+        {
+            *bit_rate_switching = true;
+        }
+        self
+    }
+
+    /// Forces the error state indicator of a CAN FD frame to error passive.
+    /// Classic CAN frames are left unchanged.
+    pub fn with_forced_esi(mut self) -> Self {
+        if let FrameType::FlexibleDatarate {
+            force_error_state_indicator,
+            ..
+        } = &mut self.frame_type
+        {
+            *force_error_state_indicator = true;
+        }
+        self
+    }
+
+    /// Stores an event identified by `marker` in the TX event queue once the
+    /// frame is transmitted
+    pub fn with_tx_event(mut self, marker: u8) -> Self {
+        self.store_tx_event = Some(marker);
+        self
+    }
+
     /// Create the message in the format required by the peripheral.
     pub fn build<const N: usize>(self) -> Result<Message<N>, TooMuchData> {
         let mut data = [0; N];
@@ -120,11 +223,6 @@ impl<'a> MessageBuilder<'a> {
             Ok(())
         };
 
-        let id_field = match self.id {
-            Id::Standard(id) => (id.as_raw() as u32) << 18,
-            Id::Extended(id) => id.as_raw(),
-        };
-        let xtd = matches!(self.id, Id::Extended(_));
         let (fdf, brs, esi, rtr, len) = match self.frame_type {
             FrameType::Classic(payload) => {
                 let (rtr, len) = match payload {
@@ -149,7 +247,7 @@ impl<'a> MessageBuilder<'a> {
         let efc = self.store_tx_event.is_some();
         let mm = self.store_tx_event.unwrap_or(0);
 
-        let t0 = id_field | (rtr as u32) << 29 | (xtd as u32) << 30 | (esi as u32) << 31;
+        let t0 = id_field(self.id) | (rtr as u32) << 29 | (esi as u32) << 31;
         let t1 = (((dlc & 0xf) as u32) << 16)
             | ((brs as u32) << 20)
             | ((fdf as u32) << 21)
@@ -161,3 +259,388 @@ impl<'a> MessageBuilder<'a> {
         }))
     }
 }
+
+/// Frame format of the segments produced by [`segment`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SegmentFormat {
+    /// Classic CAN frames carrying up to 8 bytes
+    Classic,
+    /// CAN FD frames carrying up to 64 bytes
+    FlexibleDatarate {
+        /// Parts of the frames are transmitted at a higher bit rate
+        bit_rate_switching: bool,
+    },
+}
+
+/// Sequence counter prefixed to the data of each segment
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SequenceCounter {
+    /// Value of the counter in the first segment, incremented by one for
+    /// every following segment
+    pub first: u8,
+    /// Width of the counter in bits, from 1 to 8. The counter does not wrap
+    /// around, payloads needing more segments than the counter can number
+    /// are rejected.
+    pub bits: u8,
+}
+
+impl SequenceCounter {
+    /// Number of segments the counter can number
+    fn segments(self) -> usize {
+        match self.bits {
+            1..=8 => (1_usize << self.bits).saturating_sub(self.first.into()),
+            _ => 0,
+        }
+    }
+}
+
+/// Padding of the last segment
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Padding {
+    /// The last segment is as short as the data length codes of the frame
+    /// format allow, padded with zeros
+    Minimal,
+    /// The last segment has the length of the other segments, padded with
+    /// the given byte
+    Full(u8),
+}
+
+/// Configures how [`segment`] splits a payload into frames
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SegmentConfig {
+    /// Number of payload bytes per segment, excluding the sequence counter.
+    /// If `None`, segments fill the whole frame, limited by the frame format
+    /// and the message size `N`. Together with the sequence counter, it has
+    /// to be a length a data length code can encode, e.g. 12 but not 10 for
+    /// CAN FD.
+    pub chunk_size: Option<usize>,
+    /// If `Some`, the first data byte of every segment is a sequence counter
+    pub counter: Option<SequenceCounter>,
+    /// Frame format of the segments
+    pub format: SegmentFormat,
+    /// Padding of the last segment
+    pub padding: Padding,
+}
+
+impl SegmentConfig {
+    /// CAN FD segments filling the whole frame, without sequence counter
+    pub const fn new() -> Self {
+        Self {
+            chunk_size: None,
+            counter: None,
+            format: SegmentFormat::FlexibleDatarate {
+                bit_rate_switching: false,
+            },
+            padding: Padding::Minimal,
+        }
+    }
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits `payload` into consecutive frames with identifier `id`, e.g. for
+/// transfers too large for a single frame.
+///
+/// This is a plain segmentation without flow control or other protocol
+/// state; the receiver reassembles the payload by concatenating the segments
+/// in order. An empty payload results in a single segment without payload
+/// bytes. If the segments of the configuration cannot be encoded in frames
+/// of size `N`, or `payload` needs more segments than the sequence counter can
+/// number, the iterator yields a single [`TooMuchData`] error.
+pub fn segment<const N: usize>(payload: &[u8], id: Id, config: SegmentConfig) -> Segments<'_, N> {
+    let prefix = usize::from(config.counter.is_some());
+    let frame_len = match config.format {
+        SegmentFormat::Classic => 8,
+        SegmentFormat::FlexibleDatarate { .. } => 64,
+    }
+    .min(N);
+    let chunk_size = config
+        .chunk_size
+        .unwrap_or_else(|| frame_len.saturating_sub(prefix));
+    let segments = payload.len().div_ceil(chunk_size.max(1)).max(1);
+    let fd_format = config.format != SegmentFormat::Classic;
+    let encodable = |len| {
+        len_to_dlc(len, fd_format)
+            .map(|dlc| dlc_to_len(dlc, fd_format))
+            .ok()
+            == Some(len)
+    };
+    let valid = chunk_size > 0
+        && chunk_size <= frame_len.saturating_sub(prefix)
+        && encodable(chunk_size + prefix)
+        && config
+            .counter
+            .is_none_or(|counter| segments <= counter.segments());
+    Segments {
+        remaining: payload,
+        id,
+        config,
+        chunk_size,
+        sequence: 0,
+        state: if valid {
+            SegmentsState::Running
+        } else {
+            SegmentsState::Invalid
+        },
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SegmentsState {
+    Running,
+    Invalid,
+    Done,
+}
+
+/// An iterator over the frames of a segmented payload.
+///
+/// This `struct` is created by [`segment`].
+#[derive(Clone, Debug)]
+pub struct Segments<'a, const N: usize> {
+    remaining: &'a [u8],
+    id: Id,
+    config: SegmentConfig,
+    chunk_size: usize,
+    sequence: u8,
+    state: SegmentsState,
+}
+
+impl<const N: usize> Iterator for Segments<'_, N> {
+    type Item = Result<Message<N>, TooMuchData>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.state {
+            SegmentsState::Running => {}
+            SegmentsState::Invalid => {
+                self.state = SegmentsState::Done;
+                return Some(Err(TooMuchData));
+            }
+            SegmentsState::Done => return None,
+        }
+        let (chunk, rest) = self
+            .remaining
+            .split_at_checked(self.chunk_size)
+            .unwrap_or((self.remaining, &[]));
+        self.remaining = rest;
+        if rest.is_empty() {
+            self.state = SegmentsState::Done;
+        }
+
+        let counter = self
+            .config
+            .counter
+            .map(|counter| counter.first.wrapping_add(self.sequence));
+        self.sequence = self.sequence.wrapping_add(1);
+        let (padding, padding_len) = match self.config.padding {
+            Padding::Minimal => (0, 0),
+            Padding::Full(byte) => (byte, self.chunk_size - chunk.len()),
+        };
+        let bytes = counter
+            .into_iter()
+            .chain(chunk.iter().copied())
+            .chain(core::iter::repeat_n(padding, padding_len));
+        let mut data = [0; 64];
+        let mut len = 0;
+        for (slot, byte) in data.iter_mut().zip(bytes) {
+            *slot = byte;
+            len += 1;
+        }
+
+        let payload = data.get(..len).unwrap_or_default();
+        Some(
+            MessageBuilder {
+                id: self.id,
+                frame_type: match self.config.format {
+                    SegmentFormat::Classic => FrameType::Classic(ClassicFrameType::Data(payload)),
+                    SegmentFormat::FlexibleDatarate { bit_rate_switching } => {
+                        FrameType::FlexibleDatarate {
+                            payload,
+                            bit_rate_switching,
+                            force_error_state_indicator: false,
+                        }
+                    }
+                },
+                store_tx_event: None,
+            }
+            .build(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn id() -> Id {
+        StandardId::new(0x7e0).unwrap().into()
+    }
+
+    fn payload(len: usize) -> [u8; 1024] {
+        core::array::from_fn(|i| if i < len { (i * 7 + 1) as u8 } else { 0 })
+    }
+
+    /// Concatenates the segments, checking the sequence counter, and returns
+    /// the number of segments
+    fn reassemble<const N: usize>(
+        payload: &[u8],
+        config: SegmentConfig,
+        out: &mut [u8; 1024],
+    ) -> usize {
+        let mut len = 0;
+        let mut count = 0;
+        for (n, message) in segment::<N>(payload, id(), config).enumerate() {
+            let message = message.unwrap();
+            assert_eq!(message.id(), id());
+            assert_eq!(message.fd_format(), config.format != SegmentFormat::Classic);
+            let data = match config.counter {
+                Some(counter) => {
+                    assert_eq!(message.data()[0], counter.first + n as u8);
+                    &message.data()[1..]
+                }
+                None => message.data(),
+            };
+            out[len..len + data.len()].copy_from_slice(data);
+            len += data.len();
+            count += 1;
+        }
+        // Padding of the last segment
+        assert!(out[payload.len()..len].iter().all(|&byte| byte == 0));
+        assert_eq!(&out[..payload.len()], payload);
+        count
+    }
+
+    #[test]
+    fn payloads_are_reassembled() {
+        let counter = SequenceCounter { first: 0, bits: 8 };
+        let classic = SegmentConfig {
+            format: SegmentFormat::Classic,
+            counter: Some(counter),
+            ..SegmentConfig::new()
+        };
+        let fd = SegmentConfig {
+            format: SegmentFormat::FlexibleDatarate {
+                bit_rate_switching: true,
+            },
+            ..SegmentConfig::new()
+        };
+        let mut out = [0; 1024];
+        // Payload length and segments for 7 and 64 bytes per segment
+        for (len, classic_segments, fd_segments) in [
+            (0, 1, 1),
+            (1, 1, 1),
+            (7, 1, 1),
+            (8, 2, 1),
+            (14, 2, 1),
+            (15, 3, 1),
+            (63, 9, 1),
+            (64, 10, 1),
+            (65, 10, 2),
+            (128, 19, 2),
+            (129, 19, 3),
+            (1000, 143, 16),
+        ] {
+            let payload = payload(len);
+            assert_eq!(
+                reassemble::<64>(&payload[..len], classic, &mut out),
+                classic_segments
+            );
+            assert_eq!(reassemble::<64>(&payload[..len], fd, &mut out), fd_segments);
+        }
+    }
+
+    #[test]
+    fn segments_fit_the_message_size() {
+        let mut out = [0; 1024];
+        let payload = payload(100);
+        let config = SegmentConfig {
+            counter: Some(SequenceCounter { first: 1, bits: 8 }),
+            ..SegmentConfig::new()
+        };
+        // 19 payload bytes per segment
+        assert_eq!(reassemble::<20>(&payload[..100], config, &mut out), 6);
+        let config = SegmentConfig {
+            chunk_size: Some(24),
+            ..SegmentConfig::new()
+        };
+        assert_eq!(reassemble::<32>(&payload[..100], config, &mut out), 5);
+        assert!(matches!(
+            segment::<16>(&payload, id(), config).next(),
+            Some(Err(TooMuchData))
+        ));
+        // No data length code for 30 bytes
+        let config = SegmentConfig {
+            chunk_size: Some(30),
+            ..config
+        };
+        assert!(matches!(
+            segment::<32>(&payload, id(), config).next(),
+            Some(Err(TooMuchData))
+        ));
+    }
+
+    #[test]
+    fn last_segment_is_padded() {
+        let payload = payload(20);
+        let config = SegmentConfig {
+            chunk_size: Some(8),
+            ..SegmentConfig::new()
+        };
+        let last = |config| {
+            segment::<64>(&payload[..20], id(), config)
+                .last()
+                .unwrap()
+                .unwrap()
+        };
+        // 4 bytes need no padding
+        assert_eq!(last(config).data(), &payload[16..20]);
+        let full = last(SegmentConfig {
+            padding: Padding::Full(0xcc),
+            ..config
+        });
+        assert_eq!(&full.data()[..4], &payload[16..20]);
+        assert_eq!(&full.data()[4..], &[0xcc; 4]);
+    }
+
+    #[test]
+    fn counter_width_limits_the_payload() {
+        let counter = SequenceCounter { first: 2, bits: 4 };
+        let config = SegmentConfig {
+            counter: Some(counter),
+            format: SegmentFormat::Classic,
+            ..SegmentConfig::new()
+        };
+        let mut out = [0; 1024];
+        // 14 segments numbered 2 to 15 with 7 bytes each
+        let payload = payload(99);
+        assert_eq!(reassemble::<8>(&payload[..98], config, &mut out), 14);
+        let mut segments = segment::<8>(&payload[..99], id(), config);
+        assert!(matches!(segments.next(), Some(Err(TooMuchData))));
+        assert!(segments.next().is_none());
+
+        let config = SegmentConfig {
+            counter: Some(SequenceCounter { first: 0, bits: 9 }),
+            ..config
+        };
+        assert!(matches!(
+            segment::<8>(&[], id(), config).next(),
+            Some(Err(TooMuchData))
+        ));
+    }
+
+    #[test]
+    fn segments_must_carry_payload() {
+        let config = SegmentConfig {
+            counter: Some(SequenceCounter { first: 0, bits: 8 }),
+            chunk_size: Some(0),
+            ..SegmentConfig::new()
+        };
+        assert!(matches!(
+            segment::<8>(&[1], id(), config).next(),
+            Some(Err(TooMuchData))
+        ));
+    }
+}
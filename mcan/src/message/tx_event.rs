@@ -45,25 +45,67 @@ impl TxEvent {
         (self.0.header[1] >> 24) as u8
     }
 
+    /// Value of the timestamp counter when the frame was transmitted
+    ///
+    /// Captured at the start of frame like RX timestamps. Absolute timestamps
+    /// can be obtained with [`DynTxEventFifo::pop_extended`].
+    ///
+    /// [`DynTxEventFifo::pop_extended`]: crate::tx_event_fifo::DynTxEventFifo::pop_extended
+    pub fn timestamp(&self) -> u16 {
+        self.0.header[1] as u16
+    }
+
     /// Parse the event type field. Indicates whether cancellation was requested
     /// at the time transmission succeeded.
     pub fn event_type(&self) -> TxEventType {
         TxEventType::from((self.0.header[1] >> 22) & 3)
     }
+
+    /// `true` if the frame was transmitted with the error state indicator
+    /// recessive, meaning the transmitter was error passive or ESI was forced
+    /// by [`FrameType::FlexibleDatarate::force_error_state_indicator`].
+    ///
+    /// Same as [`Raw::is_transmitter_error_passive`].
+    ///
+    /// [`FrameType::FlexibleDatarate::force_error_state_indicator`]: crate::message::tx::FrameType::FlexibleDatarate::force_error_state_indicator
+    pub fn esi(&self) -> bool {
+        self.is_transmitter_error_passive()
+    }
+
+    /// `true` if the frame was transmitted with bit rate switching
+    ///
+    /// Same as [`Raw::bit_rate_switching`].
+    pub fn brs(&self) -> bool {
+        self.bit_rate_switching()
+    }
+
+    /// `true` if the frame was transmitted in the CAN FD format
+    ///
+    /// Same as [`Raw::fd_format`].
+    pub fn is_fd(&self) -> bool {
+        self.fd_format()
+    }
 }
 
 /// Indicates whether cancellation was requested at the time transmission
 /// succeeded
+///
+/// Events are only stored for successful transmissions. Transmissions that
+/// are cancelled, or abandoned after a lost arbitration or an error while
+/// automatic retransmission is disabled (CCCR.DAR), do not generate events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TxEventType {
     /// Unrecognized field value
-    Reserved,
+    Reserved(u8),
     /// Transmission was successful
-    TxEvent = 1,
+    TxEvent,
     /// Transmission was successful AND cancellation was requested
     ///
     /// This can happen if transmission had already started when the
-    /// cancellation request was made.
-    TxInSpiteOfCancellation = 2,
+    /// cancellation request was made. The peripheral also reports every
+    /// transmission with automatic retransmission disabled (CCCR.DAR) this
+    /// way.
+    TxInSpiteOfCancellation,
 }
 
 impl From<u32> for TxEventType {
@@ -71,7 +113,64 @@ impl From<u32> for TxEventType {
         match value {
             1 => Self::TxEvent,
             2 => Self::TxInSpiteOfCancellation,
-            _ => Self::Reserved,
+            _ => Self::Reserved(value as u8),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(header: [u32; 2]) -> TxEvent {
+        TxEvent(RawMessage { header, data: [] })
+    }
+
+    #[test]
+    fn classic_event() {
+        // Standard ID 0x123, DLC 8, marker 0x42, event type 1, timestamp 0xbeef
+        let e = event([0x123 << 18, 0x42 << 24 | 1 << 22 | 8 << 16 | 0xbeef]);
+        assert!(!e.esi());
+        assert!(!e.brs());
+        assert!(!e.is_fd());
+        assert_eq!(e.id(), Id::Standard(StandardId::new(0x123).unwrap()));
+        assert_eq!(e.message_marker(), 0x42);
+        assert_eq!(e.timestamp(), 0xbeef);
+        assert_eq!(e.event_type(), TxEventType::TxEvent);
+        assert_eq!(e.decoded_dlc(), 8);
+    }
+
+    #[test]
+    fn fd_event_with_esi_and_brs() {
+        // Extended ID, ESI, FDF, BRS, DLC 15, event type 2
+        let e = event([
+            1 << 31 | 1 << 30 | 0x1abc_def0,
+            2 << 22 | 1 << 21 | 1 << 20 | 15 << 16,
+        ]);
+        assert!(e.esi());
+        assert!(e.brs());
+        assert!(e.is_fd());
+        assert_eq!(e.id(), Id::Extended(ExtendedId::new(0x1abc_def0).unwrap()));
+        assert_eq!(e.event_type(), TxEventType::TxInSpiteOfCancellation);
+        assert_eq!(e.decoded_dlc(), 64);
+        assert!(e.data().is_empty());
+        assert_eq!(e.timestamp(), 0);
+    }
+
+    #[test]
+    fn fd_event_without_brs() {
+        let e = event([0, 1 << 21]);
+        assert!(!e.esi());
+        assert!(!e.brs());
+        assert!(e.is_fd());
+    }
+
+    #[test]
+    fn event_types() {
+        let event_type = |et: u32| event([0, et << 22 | 0x3f << 16]).event_type();
+        assert_eq!(event_type(0), TxEventType::Reserved(0));
+        assert_eq!(event_type(1), TxEventType::TxEvent);
+        assert_eq!(event_type(2), TxEventType::TxInSpiteOfCancellation);
+        assert_eq!(event_type(3), TxEventType::Reserved(3));
+    }
+}
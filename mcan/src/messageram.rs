@@ -3,16 +3,42 @@
 //! More information can be found in the [`crate`] documentation.
 //!
 //! [`crate`]: crate#message-ram-configuration
+
+use crate::array::GenericArray;
+pub use crate::array::LimitedArrayLength;
+use crate::config::Mode;
 use crate::filter::{FilterExtendedId, FilterStandardId};
-use crate::message::{rx, tx, TxEvent};
+use crate::message::{rx, tx, AnyMessage, ElementSize, TxEvent};
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
-use generic_array::{
-    typenum::{consts::*, IsLessOrEqual, LeEq, Same},
-    ArrayLength, GenericArray,
-};
+use generic_array::typenum::{consts::*, Unsigned};
 use vcell::VolatileCell;
 
 /// Element capacities
+///
+/// Each capacity is programmed into a register field of the peripheral, which
+/// bounds it along with the limits of the peripheral declared by
+/// [`mcan_core::CanId`]:
+///
+/// | Associated type        | Register field            | Maximum            |
+/// |------------------------|---------------------------|--------------------|
+/// | `StandardFilters`      | SIDFC.LSS (8 bits)        | 128                |
+/// | `ExtendedFilters`      | XIDFC.LSE (7 bits)        | 64                 |
+/// | `DedicatedRxBuffers`   | NDAT1, NDAT2 (1 bit each) | 64                 |
+/// | `RxFifo0`              | RXF0C.F0S (7 bits)        | 64                 |
+/// | `RxFifo1`              | RXF1C.F1S (7 bits)        | 64                 |
+/// | `TxBuffers`            | TXBC.NDTB + TXBC.TFQS     | 32                 |
+/// | `DedicatedTxBuffers`   | TXBC.NDTB (6 bits)        | `TxBuffers`        |
+/// | `TxEventFifo`          | TXEFC.EFS (6 bits)        | 32                 |
+///
+/// The transmit buffers are split into NDTB dedicated buffers and a queue of
+/// TFQS buffers, whose sum must not exceed 32. This is checked at compile time
+/// when constructing [`CanConfigurable`](crate::bus::CanConfigurable).
+///
+/// `TxEventFifo` is not bounded by `TxBuffers`, as every transmission adds an
+/// event and buffers are reused while earlier events are still waiting to be
+/// read.
 pub trait Capacities {
     /// Maximum number of Standard ID filters
     type StandardFilters: LimitedArrayLength<VolatileCell<FilterStandardId>, U128>;
@@ -41,13 +67,197 @@ pub trait Capacities {
     type TxEventFifo: LimitedArrayLength<VolatileCell<TxEvent>, U32>;
 }
 
-/// [`generic_array::ArrayLength`] with an upper bound.
-pub trait LimitedArrayLength<T, MaxLength>: ArrayLength<T> {}
-impl<T, N, MaxLength> LimitedArrayLength<T, MaxLength> for N
-where
-    N: ArrayLength<T> + IsLessOrEqual<MaxLength>,
-    LeEq<N, MaxLength>: Same<True>,
-{
+/// Element count selected by [`Capacities`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Capacity {
+    /// [`Capacities::StandardFilters`]
+    StandardFilters,
+    /// [`Capacities::ExtendedFilters`]
+    ExtendedFilters,
+    /// [`Capacities::DedicatedRxBuffers`]
+    DedicatedRxBuffers,
+    /// [`Capacities::RxFifo0`]
+    RxFifo0,
+    /// [`Capacities::RxFifo1`]
+    RxFifo1,
+    /// [`Capacities::TxBuffers`]
+    TxBuffers,
+    /// [`Capacities::TxEventFifo`]
+    TxEventFifo,
+}
+
+/// A [`Capacity`] exceeds what the peripheral supports according to its
+/// [`mcan_core::CanId`] implementation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CapacityExceededError {
+    /// The offending capacity
+    pub capacity: Capacity,
+    /// Number of elements selected by [`Capacities`]
+    pub requested: usize,
+    /// Number of elements supported by the peripheral
+    pub supported: usize,
+}
+
+/// Maximum number of transmit buffers of any peripheral
+const TX_BUFFER_LIMIT: usize = 32;
+
+/// Split of the transmit buffers of `C` into dedicated buffers and the queue
+pub(crate) struct TxBufferSplit<C>(PhantomData<C>);
+
+impl<C: Capacities> TxBufferSplit<C> {
+    /// Values of TXBC.NDTB and TXBC.TFQS
+    ///
+    /// Evaluating this fails to compile if the capacities of `C` do not fit
+    /// TXBC.
+    pub(crate) const FIELDS: (u8, u8) =
+        match tx_buffer_split(C::TxBuffers::USIZE, C::DedicatedTxBuffers::USIZE) {
+            Ok(fields) => fields,
+            Err(message) => panic!("{}", message),
+        };
+}
+
+/// Splits `tx_buffers` transmit buffers into `dedicated` buffers and the
+/// queue, or describes the violated constraint
+///
+/// The trait bounds of [`Capacities`] already enforce these constraints; this
+/// makes them explicit for TXBC.
+const fn tx_buffer_split(tx_buffers: usize, dedicated: usize) -> Result<(u8, u8), &'static str> {
+    if tx_buffers > TX_BUFFER_LIMIT {
+        Err("`Capacities::TxBuffers` exceeds the 32 transmit buffers of the peripheral")
+    } else if dedicated > tx_buffers {
+        Err("`Capacities::DedicatedTxBuffers` exceeds `Capacities::TxBuffers`")
+    } else {
+        Ok((dedicated as u8, (tx_buffers - dedicated) as u8))
+    }
+}
+
+/// Checks the capacities of `C` against the limits of `Id`
+pub(crate) fn check_capacities<Id: mcan_core::CanId, C: Capacities>(
+) -> Result<(), CapacityExceededError> {
+    [
+        (
+            Capacity::StandardFilters,
+            C::StandardFilters::USIZE,
+            Id::MAX_STANDARD_FILTERS,
+        ),
+        (
+            Capacity::ExtendedFilters,
+            C::ExtendedFilters::USIZE,
+            Id::MAX_EXTENDED_FILTERS,
+        ),
+        (
+            Capacity::DedicatedRxBuffers,
+            C::DedicatedRxBuffers::USIZE,
+            Id::MAX_DEDICATED_RX_BUFFERS,
+        ),
+        (Capacity::RxFifo0, C::RxFifo0::USIZE, Id::MAX_RX_FIFO_0),
+        (Capacity::RxFifo1, C::RxFifo1::USIZE, Id::MAX_RX_FIFO_1),
+        (Capacity::TxBuffers, C::TxBuffers::USIZE, Id::MAX_TX_BUFFERS),
+        (
+            Capacity::TxEventFifo,
+            C::TxEventFifo::USIZE,
+            Id::MAX_TX_EVENT_FIFO,
+        ),
+    ]
+    .into_iter()
+    .find(|&(_, requested, supported)| requested > supported)
+    .map_or(Ok(()), |(capacity, requested, supported)| {
+        Err(CapacityExceededError {
+            capacity,
+            requested,
+            supported,
+        })
+    })
+}
+
+/// Queue of message elements in the Message RAM
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ElementQueue {
+    /// Dedicated receive buffers
+    RxDedicatedBuffers,
+    /// Receive FIFO 0
+    RxFifo0,
+    /// Receive FIFO 1
+    RxFifo1,
+    /// Transmit buffers
+    TxBuffers,
+}
+
+/// Element sizes of the queues selected by [`Capacities`]. Queues without any
+/// elements are `None`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ElementSizes {
+    /// Element size of the dedicated receive buffers
+    pub rx_dedicated_buffers: Option<ElementSize>,
+    /// Element size of receive FIFO 0
+    pub rx_fifo_0: Option<ElementSize>,
+    /// Element size of receive FIFO 1
+    pub rx_fifo_1: Option<ElementSize>,
+    /// Element size of the transmit buffers
+    pub tx_buffers: Option<ElementSize>,
+}
+
+/// Element size that does not fit the operating mode, see
+/// [`ElementSizes::warnings`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ElementSizeWarning {
+    /// Classic CAN frames never use more than 8 bytes of the elements of the
+    /// queue, so the rest of the Message RAM they occupy is wasted.
+    Oversized(ElementQueue),
+    /// CAN FD frames with more than 8 bytes of data are truncated when stored
+    /// in the queue.
+    Truncating(ElementQueue),
+}
+
+impl ElementSizes {
+    /// Checks the element sizes against the operating `mode`
+    pub fn warnings(&self, mode: &Mode) -> impl Iterator<Item = ElementSizeWarning> {
+        let fd = matches!(mode, Mode::Fd { .. });
+        [
+            (ElementQueue::RxDedicatedBuffers, self.rx_dedicated_buffers),
+            (ElementQueue::RxFifo0, self.rx_fifo_0),
+            (ElementQueue::RxFifo1, self.rx_fifo_1),
+            (ElementQueue::TxBuffers, self.tx_buffers),
+        ]
+        .into_iter()
+        .filter_map(move |(queue, size)| match size? {
+            ElementSize::B8 if fd && queue != ElementQueue::TxBuffers => {
+                Some(ElementSizeWarning::Truncating(queue))
+            }
+            ElementSize::B8 => None,
+            _ if !fd => Some(ElementSizeWarning::Oversized(queue)),
+            _ => None,
+        })
+    }
+}
+
+/// Properties of a [`Capacities`] implementation that are available at
+/// runtime
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CapacitiesSummary {
+    element_sizes: ElementSizes,
+}
+
+impl CapacitiesSummary {
+    /// Summarizes `C`
+    pub fn of<C: Capacities>() -> Self {
+        fn size<M: AnyMessage, N: Unsigned>() -> Option<ElementSize> {
+            (N::USIZE > 0).then_some(M::SIZE)
+        }
+        Self {
+            element_sizes: ElementSizes {
+                rx_dedicated_buffers: size::<C::RxBufferMessage, C::DedicatedRxBuffers>(),
+                rx_fifo_0: size::<C::RxFifo0Message, C::RxFifo0>(),
+                rx_fifo_1: size::<C::RxFifo1Message, C::RxFifo1>(),
+                tx_buffers: size::<C::TxMessage, C::TxBuffers>(),
+            },
+        }
+    }
+
+    /// Element sizes of the queues
+    pub fn element_sizes(&self) -> ElementSizes {
+        self.element_sizes
+    }
 }
 
 #[repr(C)]
@@ -62,18 +272,138 @@ pub(super) struct SharedMemoryInner<C: Capacities> {
     pub(super) tx_buffers: GenericArray<VolatileCell<C::TxMessage>, C::TxBuffers>,
 }
 
+/// Whether the [`SharedMemory`] for `C` fits into `len` bytes of Message RAM
+///
+/// Integrations with less Message RAM than the 64K addressable by MCAN wrap
+/// addresses beyond it around, so that a `SharedMemory` exceeding it corrupts
+/// its own first sections. If the target HAL provides
+/// [`Dependencies::eligible_message_ram_len`], this is checked when
+/// constructing [`CanConfigurable`]. Otherwise the capacities can be checked
+/// against the size known from the data sheet at compile time:
+///
+/// ```no_run
+/// # use mcan::generic_array::typenum::consts::*;
+/// # use mcan::message::{tx, rx};
+/// # struct Capacities;
+/// # impl mcan::messageram::Capacities for Capacities {
+/// #     type StandardFilters = U1;
+/// #     type ExtendedFilters = U1;
+/// #     type RxBufferMessage = rx::Message<8>;
+/// #     type DedicatedRxBuffers = U1;
+/// #     type RxFifo0Message = rx::Message<8>;
+/// #     type RxFifo0 = U1;
+/// #     type RxFifo1Message = rx::Message<8>;
+/// #     type RxFifo1 = U1;
+/// #     type TxMessage = tx::Message<8>;
+/// #     type TxBuffers = U1;
+/// #     type DedicatedTxBuffers = U0;
+/// #     type TxEventFifo = U1;
+/// # }
+/// // 4K of Message RAM per instance
+/// const _: () = assert!(mcan::messageram::fits_within::<Capacities>(4096));
+/// ```
+///
+/// The check is only sufficient if the `SharedMemory` is placed at the start
+/// of the Message RAM.
+///
+/// [`Dependencies::eligible_message_ram_len`]: mcan_core::Dependencies::eligible_message_ram_len
+/// [`CanConfigurable`]: crate::bus::CanConfigurable
+pub const fn fits_within<C: Capacities>(len: usize) -> bool {
+    core::mem::size_of::<SharedMemoryInner<C>>() <= len
+}
+
 /// Memory shared between the peripheral and core. Provide a struct `C` that
 /// implements [`Capacities`] to select the sizes of the buffers, then construct
 /// this using `SharedMemory::<C>::new()`.
 pub struct SharedMemory<C: Capacities>(MaybeUninit<SharedMemoryInner<C>>);
 
+impl<C: Capacities> Default for SharedMemory<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<C: Capacities> SharedMemory<C> {
+    /// Number of bytes of Message RAM taken up by the memory, e.g. to size
+    /// the linker section it is placed in
+    pub const SIZE_BYTES: usize = core::mem::size_of::<SharedMemoryInner<C>>();
+
+    /// Offsets and lengths of the regions of the memory
+    pub const fn layout() -> MemoryLayout {
+        /// Region of `N` elements of `T` following `after`
+        const fn next<T, N: Unsigned>(region: MemoryRegion, after: &RegionInfo) -> RegionInfo {
+            let element_len = core::mem::size_of::<T>();
+            RegionInfo {
+                region,
+                offset: after.offset + after.len,
+                len: N::USIZE * element_len,
+                element_len,
+            }
+        }
+        let start = RegionInfo {
+            region: MemoryRegion::StandardFilters,
+            offset: 0,
+            len: 0,
+            element_len: 0,
+        };
+        let standard_filters =
+            next::<FilterStandardId, C::StandardFilters>(MemoryRegion::StandardFilters, &start);
+        let extended_filters = next::<FilterExtendedId, C::ExtendedFilters>(
+            MemoryRegion::ExtendedFilters,
+            &standard_filters,
+        );
+        let rx_fifo_0 =
+            next::<C::RxFifo0Message, C::RxFifo0>(MemoryRegion::RxFifo0, &extended_filters);
+        let rx_fifo_1 = next::<C::RxFifo1Message, C::RxFifo1>(MemoryRegion::RxFifo1, &rx_fifo_0);
+        let rx_dedicated_buffers = next::<C::RxBufferMessage, C::DedicatedRxBuffers>(
+            MemoryRegion::RxDedicatedBuffers,
+            &rx_fifo_1,
+        );
+        let tx_event_fifo =
+            next::<TxEvent, C::TxEventFifo>(MemoryRegion::TxEventFifo, &rx_dedicated_buffers);
+        let tx_buffers =
+            next::<C::TxMessage, C::TxBuffers>(MemoryRegion::TxBuffers, &tx_event_fifo);
+        MemoryLayout {
+            standard_filters,
+            extended_filters,
+            rx_fifo_0,
+            rx_fifo_1,
+            rx_dedicated_buffers,
+            tx_event_fifo,
+            tx_buffers,
+        }
+    }
+
+    /// Region of the memory that `address` falls into, e.g. to map an
+    /// address back to a queue and element while debugging
+    /// [`Interrupt::MessageRamAccessFailure`]
+    ///
+    /// `None` if the address is outside of the memory.
+    ///
+    /// [`Interrupt::MessageRamAccessFailure`]: crate::interrupt::Interrupt::MessageRamAccessFailure
+    pub fn region_for(&self, address: usize) -> Option<RegionInfo> {
+        let offset = address.checked_sub(self as *const Self as usize)?;
+        Self::layout()
+            .regions()
+            .into_iter()
+            .find(|region| region.contains(offset))
+    }
+
     pub(super) fn init(&mut self) -> &mut SharedMemoryInner<C> {
         self.0 = MaybeUninit::zeroed();
         // Safety: All bits 0 is a valid value for all the contained arrays.
         unsafe { self.0.assume_init_mut() }
     }
 
+    /// # Safety
+    /// The memory was initialized by [`Self::init`], possibly by an earlier
+    /// program such as a bootloader, and has not been modified since except
+    /// by the peripheral.
+    #[cfg(feature = "handoff")]
+    pub(super) unsafe fn assume_init_mut(&mut self) -> &mut SharedMemoryInner<C> {
+        self.0.assume_init_mut()
+    }
+
     /// All initialization is handled by the type that uses the memory, so this
     /// type can safely be assigned to a `link_section` that is not
     /// initialized by the system to control its position in memory.
@@ -81,13 +411,408 @@ impl<C: Capacities> SharedMemory<C> {
         Self(MaybeUninit::uninit())
     }
 
-    /// The peripheral uses 16-bit addressing for its memory configuration,
-    /// offset from the start of system RAM. If `SharedMemory` is allocated
-    /// outside the addressable region, it cannot be used.
-    pub(crate) fn is_addressable(&self, eligible_message_ram_start: *const ()) -> bool {
-        let eligible_message_ram_start = eligible_message_ram_start as usize;
-        let start = self as *const _ as usize;
-        let end_exclusive = start + core::mem::size_of::<Self>();
-        eligible_message_ram_start <= start && end_exclusive - eligible_message_ram_start <= 1 << 16
+    /// Checks that the peripheral can address all of `SharedMemory`, and
+    /// returns the offset of its end from `eligible_message_ram_start`, i.e.
+    /// the number of bytes of Message RAM it requires
+    ///
+    /// The peripheral uses 16-bit addressing of 32-bit words for its memory
+    /// configuration, offset from `eligible_message_ram_start`.
+    pub(crate) fn check_placement(
+        &self,
+        eligible_message_ram_start: *const (),
+    ) -> Result<usize, MemoryError> {
+        let start = (self as *const Self as usize)
+            .checked_sub(eligible_message_ram_start as usize)
+            .filter(|&start| start < MESSAGE_RAM_WINDOW)
+            .ok_or(MemoryError::NotAddressable)?;
+        if start % 4 != 0 {
+            return Err(MemoryError::Misaligned);
+        }
+        let size = start + core::mem::size_of::<Self>();
+        if size > MESSAGE_RAM_WINDOW {
+            return Err(MemoryError::TooLarge {
+                size,
+                max: MESSAGE_RAM_WINDOW,
+            });
+        }
+        Ok(size)
+    }
+}
+
+/// Region of the [`SharedMemory`], in the order they are placed in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// Standard ID filters
+    StandardFilters,
+    /// Extended ID filters
+    ExtendedFilters,
+    /// Receive FIFO 0
+    RxFifo0,
+    /// Receive FIFO 1
+    RxFifo1,
+    /// Dedicated receive buffers
+    RxDedicatedBuffers,
+    /// Transmit event FIFO
+    TxEventFifo,
+    /// Transmit buffers
+    TxBuffers,
+}
+
+/// Placement of a [`MemoryRegion`] within the [`SharedMemory`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegionInfo {
+    /// The region
+    pub region: MemoryRegion,
+    /// Offset in bytes from the start of the memory
+    pub offset: usize,
+    /// Length in bytes, zero for regions without elements
+    pub len: usize,
+    /// Length in bytes of each element of the region
+    pub element_len: usize,
+}
+
+impl RegionInfo {
+    /// Whether `offset` from the start of the memory falls into the region
+    pub const fn contains(&self, offset: usize) -> bool {
+        self.offset <= offset && offset < self.offset + self.len
+    }
+
+    /// Index of the element that `offset` from the start of the memory falls
+    /// into, `None` if it is outside of the region
+    pub const fn element_index(&self, offset: usize) -> Option<usize> {
+        if self.contains(offset) {
+            Some((offset - self.offset) / self.element_len)
+        } else {
+            None
+        }
+    }
+}
+
+/// Regions of the [`SharedMemory`] for a [`Capacities`], see
+/// [`SharedMemory::layout`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// Standard ID filters
+    pub standard_filters: RegionInfo,
+    /// Extended ID filters
+    pub extended_filters: RegionInfo,
+    /// Receive FIFO 0
+    pub rx_fifo_0: RegionInfo,
+    /// Receive FIFO 1
+    pub rx_fifo_1: RegionInfo,
+    /// Dedicated receive buffers
+    pub rx_dedicated_buffers: RegionInfo,
+    /// Transmit event FIFO
+    pub tx_event_fifo: RegionInfo,
+    /// Transmit buffers
+    pub tx_buffers: RegionInfo,
+}
+
+impl MemoryLayout {
+    /// All regions, in the order they are placed in
+    pub const fn regions(&self) -> [RegionInfo; 7] {
+        [
+            self.standard_filters,
+            self.extended_filters,
+            self.rx_fifo_0,
+            self.rx_fifo_1,
+            self.rx_dedicated_buffers,
+            self.tx_event_fifo,
+            self.tx_buffers,
+        ]
+    }
+}
+
+/// Bytes addressable by the 16-bit start addresses of the peripheral
+const MESSAGE_RAM_WINDOW: usize = 1 << 16;
+
+/// [`SharedMemory`] cannot be addressed by the peripheral, see
+/// [`Dependencies::eligible_message_ram_start`]
+///
+/// [`Dependencies::eligible_message_ram_start`]: mcan_core::Dependencies::eligible_message_ram_start
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryError {
+    /// The memory starts before the eligible region, or beyond the 64 KiB
+    /// the peripheral addresses from its start
+    NotAddressable,
+    /// The memory starts within the 64 KiB window, but ends beyond it
+    TooLarge {
+        /// Bytes from the start of the eligible region to the end of the
+        /// memory
+        size: usize,
+        /// Size of the window
+        max: usize,
+    },
+    /// The memory does not start at a 32-bit word from the start of the
+    /// eligible region, which the start addresses of the peripheral require
+    Misaligned,
+}
+
+/// [`SharedMemory`] that can be declared as a non-`mut` `static`.
+///
+/// Taking references to a `static mut` is discouraged, and obtaining two of
+/// them to the same Message RAM is undefined behavior. This cell can be
+/// declared as a plain `static` in the Message RAM section instead. Its
+/// content is handed out either through a [`SharedMemoryGrant`] or through
+/// [`Self::steal`].
+///
+/// ```no_run
+/// # use mcan::generic_array::typenum::consts::*;
+/// # use mcan::message::{tx, rx};
+/// # struct Capacities;
+/// # impl mcan::messageram::Capacities for Capacities {
+/// #     type StandardFilters = U1;
+/// #     type ExtendedFilters = U1;
+/// #     type RxBufferMessage = rx::Message<8>;
+/// #     type DedicatedRxBuffers = U1;
+/// #     type RxFifo0Message = rx::Message<8>;
+/// #     type RxFifo0 = U1;
+/// #     type RxFifo1Message = rx::Message<8>;
+/// #     type RxFifo1 = U1;
+/// #     type TxMessage = tx::Message<8>;
+/// #     type TxBuffers = U1;
+/// #     type DedicatedTxBuffers = U0;
+/// #     type TxEventFifo = U1;
+/// # }
+/// use mcan::messageram::{SharedMemoryCell, SharedMemoryGrant};
+///
+/// #[link_section = ".can"]
+/// static MESSAGE_RAM: SharedMemoryCell<Capacities> = SharedMemoryCell::new();
+/// // Safety: This is the only grant for `MESSAGE_RAM` and it is never stolen.
+/// static MESSAGE_RAM_GRANT: SharedMemoryGrant<Capacities> =
+///     unsafe { SharedMemoryGrant::new(&MESSAGE_RAM) };
+///
+/// let memory = MESSAGE_RAM_GRANT.take().unwrap();
+/// assert!(MESSAGE_RAM_GRANT.take().is_none());
+/// ```
+pub struct SharedMemoryCell<C: Capacities>(UnsafeCell<SharedMemory<C>>);
+
+// Safety: The content is only accessible through `&mut` references handed out
+// once by `SharedMemoryCell::steal` or `SharedMemoryGrant::take`.
+unsafe impl<C: Capacities> Sync for SharedMemoryCell<C> {}
+
+impl<C: Capacities> SharedMemoryCell<C> {
+    /// Like [`SharedMemory::new`], this does not initialize the memory and can
+    /// be placed in a `link_section` that is not initialized by the system.
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new(SharedMemory::new()))
+    }
+
+    /// Returns a mutable reference to the contained [`SharedMemory`].
+    ///
+    /// This is primarily meant for targets without atomic swap operations,
+    /// where [`SharedMemoryGrant::take`] is unavailable.
+    ///
+    /// # Safety
+    /// This must be called at most once for the lifetime of the program, and
+    /// not at all if the cell is handed out by a [`SharedMemoryGrant`].
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn steal(&'static self) -> &'static mut SharedMemory<C> {
+        &mut *self.0.get()
+    }
+}
+
+impl<C: Capacities> Default for SharedMemoryCell<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hands out the [`SharedMemory`] of a [`SharedMemoryCell`] at most once.
+///
+/// The grant keeps track of whether the memory was taken, so unlike the cell,
+/// it has to be placed in memory that is initialized at startup. More
+/// information can be found in the [`SharedMemoryCell`] documentation.
+pub struct SharedMemoryGrant<C: Capacities + 'static> {
+    taken: core::sync::atomic::AtomicBool,
+    memory: &'static SharedMemoryCell<C>,
+}
+
+impl<C: Capacities + 'static> SharedMemoryGrant<C> {
+    /// Create a grant for `memory`
+    ///
+    /// # Safety
+    /// There must be no other grant for `memory` and
+    /// [`SharedMemoryCell::steal`] must not be called on it.
+    pub const unsafe fn new(memory: &'static SharedMemoryCell<C>) -> Self {
+        Self {
+            taken: core::sync::atomic::AtomicBool::new(false),
+            memory,
+        }
+    }
+
+    /// Returns the memory on the first call and `None` on all later calls.
+    #[cfg(target_has_atomic = "8")]
+    pub fn take(&self) -> Option<&'static mut SharedMemory<C>> {
+        use core::sync::atomic::Ordering;
+        if self.taken.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            // Safety: The flag ensures this is only reached once and the
+            // constructor contract ensures that no other references exist.
+            Some(unsafe { &mut *self.memory.0.get() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::Caps;
+
+    static MEMORY: SharedMemoryCell<Caps> = SharedMemoryCell::new();
+    static GRANT: SharedMemoryGrant<Caps> = unsafe { SharedMemoryGrant::new(&MEMORY) };
+
+    #[test]
+    fn tx_buffer_split_fits_txbc() {
+        for tx_buffers in 0..=TX_BUFFER_LIMIT {
+            for dedicated in 0..=tx_buffers {
+                let (ndtb, tfqs) = tx_buffer_split(tx_buffers, dedicated).unwrap();
+                assert_eq!(ndtb as usize, dedicated);
+                assert_eq!((ndtb + tfqs) as usize, tx_buffers);
+            }
+        }
+        assert_eq!(TxBufferSplit::<Caps>::FIELDS, (2, 2));
+    }
+
+    #[test]
+    fn fits_within_boundary() {
+        // Filters 4 * 4 + 4 * 8, RX 3 * 4 * 16, TX events 4 * 8, TX 4 * 16
+        const SIZE: usize = 336;
+        const _: () = assert!(fits_within::<Caps>(SIZE));
+        assert_eq!(core::mem::size_of::<SharedMemory<Caps>>(), SIZE);
+        assert!(fits_within::<Caps>(SIZE + 1));
+        assert!(!fits_within::<Caps>(SIZE - 1));
+        assert!(!fits_within::<Caps>(0));
+    }
+
+    #[test]
+    fn layout_matches_the_placement() {
+        const _: () = assert!(SharedMemory::<Caps>::SIZE_BYTES == 336);
+        let layout = SharedMemory::<Caps>::layout();
+        let regions = layout.regions();
+        assert_eq!(
+            regions.map(|region| (region.offset, region.len, region.element_len)),
+            [
+                (0, 16, 4),
+                (16, 32, 8),
+                (48, 64, 16),
+                (112, 64, 16),
+                (176, 64, 16),
+                (240, 32, 8),
+                (272, 64, 16),
+            ]
+        );
+
+        let memory = SharedMemory::<Caps>::new();
+        // Safety: Only the addresses of the fields are taken.
+        let inner = unsafe { &*memory.0.as_ptr() };
+        let start = &memory as *const _ as usize;
+        let offset = |field: *const ()| field as usize - start;
+        assert_eq!(
+            [
+                offset(inner.filters_standard.as_ptr().cast()),
+                offset(inner.filters_extended.as_ptr().cast()),
+                offset(inner.rx_fifo_0.as_ptr().cast()),
+                offset(inner.rx_fifo_1.as_ptr().cast()),
+                offset(inner.rx_dedicated_buffers.as_ptr().cast()),
+                offset(inner.tx_event_fifo.as_ptr().cast()),
+                offset(inner.tx_buffers.as_ptr().cast()),
+            ],
+            regions.map(|region| region.offset)
+        );
+
+        let region = memory.region_for(start + 0xb4).unwrap();
+        assert_eq!(region, layout.rx_dedicated_buffers);
+        assert_eq!(region.element_index(0xb4), Some(0));
+        assert_eq!(region.element_index(0xef), Some(3));
+        assert_eq!(region.element_index(0xf0), None);
+        assert_eq!(
+            memory.region_for(start + 0x14f).map(|region| region.region),
+            Some(MemoryRegion::TxBuffers)
+        );
+        assert_eq!(memory.region_for(start + 0x150), None);
+        assert_eq!(memory.region_for(start - 1), None);
+        assert_eq!(
+            memory.region_for(start).map(|region| region.region),
+            Some(MemoryRegion::StandardFilters)
+        );
+    }
+
+    #[test]
+    fn tx_buffer_split_violations() {
+        assert_eq!(
+            tx_buffer_split(33, 0),
+            Err("`Capacities::TxBuffers` exceeds the 32 transmit buffers of the peripheral")
+        );
+        assert_eq!(
+            tx_buffer_split(4, 5),
+            Err("`Capacities::DedicatedTxBuffers` exceeds `Capacities::TxBuffers`")
+        );
+    }
+
+    #[test]
+    fn grant_is_taken_once() {
+        let memory = GRANT.take().unwrap();
+        assert_eq!(memory as *const _, MEMORY.0.get() as *const _);
+        assert!(GRANT.take().is_none());
+        assert!(GRANT.take().is_none());
+    }
+
+    struct LargeCaps;
+
+    impl Capacities for LargeCaps {
+        type StandardFilters = U1;
+        type ExtendedFilters = U1;
+        type RxBufferMessage = rx::Message<64>;
+        type DedicatedRxBuffers = U0;
+        type RxFifo0Message = rx::Message<64>;
+        type RxFifo0 = U2;
+        type RxFifo1Message = rx::Message<8>;
+        type RxFifo1 = U2;
+        type TxMessage = tx::Message<12>;
+        type TxBuffers = U2;
+        type DedicatedTxBuffers = U0;
+        type TxEventFifo = U1;
+    }
+
+    fn fd() -> Mode {
+        Mode::Fd {
+            allow_bit_rate_switching: true,
+            data_phase_timing: crate::config::BitTiming::new(fugit::RateExtU32::MHz(2)),
+        }
+    }
+
+    #[test]
+    fn element_sizes_are_summarized() {
+        let sizes = CapacitiesSummary::of::<LargeCaps>().element_sizes();
+        assert_eq!(
+            sizes,
+            ElementSizes {
+                rx_dedicated_buffers: None,
+                rx_fifo_0: Some(ElementSize::B64),
+                rx_fifo_1: Some(ElementSize::B8),
+                tx_buffers: Some(ElementSize::B12),
+            }
+        );
+    }
+
+    #[test]
+    fn element_size_warnings() {
+        use ElementQueue::*;
+        use ElementSizeWarning::*;
+        let small = CapacitiesSummary::of::<Caps>().element_sizes();
+        assert_eq!(small.warnings(&Mode::Classic).count(), 0);
+        assert!(small.warnings(&fd()).eq([
+            Truncating(RxDedicatedBuffers),
+            Truncating(RxFifo0),
+            Truncating(RxFifo1)
+        ]));
+
+        let large = CapacitiesSummary::of::<LargeCaps>().element_sizes();
+        assert!(large
+            .warnings(&Mode::Classic)
+            .eq([Oversized(RxFifo0), Oversized(TxBuffers)]));
+        assert!(large.warnings(&fd()).eq([Truncating(RxFifo1)]));
     }
 }
@@ -0,0 +1,195 @@
+//! Host-side stand-ins for the peripheral used by unit tests
+//!
+//! The register block of a [`mock_can!`] peripheral is a [`Registers`]
+//! static, the memory-backed register block of [`crate::test`], so writes are
+//! read back verbatim. This is sufficient for the mode transitions in
+//! [`crate::reg::Can`] to complete and for tests to inspect what the
+//! abstractions write.
+
+extern crate std;
+
+use crate::message::{rx, tx};
+use crate::messageram::Capacities;
+use core::cell::UnsafeCell;
+use embedded_can::StandardId;
+use generic_array::typenum::consts::*;
+use std::sync::{Mutex, PoisonError};
+use std::vec::Vec;
+
+/// Register offsets and written values, in the order of the writes
+type Writes = Vec<(usize, u32)>;
+
+/// Start addresses of the register blocks whose writes are being recorded,
+/// with the writes so far
+static RECORDINGS: Mutex<Vec<(usize, Writes)>> = Mutex::new(Vec::new());
+
+/// Records the value just written to `register` if its register block is
+/// being recorded
+fn record_write(register: *const u32) {
+    let address = register as usize;
+    let mut recordings = RECORDINGS.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some((start, writes)) = recordings
+        .iter_mut()
+        .find(|(start, _)| (*start..*start + 256).contains(&address))
+    else {
+        return;
+    };
+    // Safety: The register has just been written and belongs to a recorded
+    // register block.
+    let value = unsafe { core::ptr::read_volatile(register) };
+    writes.push((address - *start, value));
+}
+
+/// Stand-in for `vcell::VolatileCell` that records the writes to the
+/// register blocks being recorded
+///
+/// The unit tests substitute it for the one of `vcell`, see `lib.rs`, so that
+/// every write of the register abstractions passes through
+/// [`VolatileCell::set`] and the order of writes can be checked in addition to
+/// their result.
+#[repr(transparent)]
+pub struct VolatileCell<T> {
+    value: UnsafeCell<T>,
+}
+
+impl<T> VolatileCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self) -> T
+    where
+        T: Copy,
+    {
+        // Safety: The cell is valid for reads.
+        unsafe { core::ptr::read_volatile(self.value.get()) }
+    }
+
+    #[inline(always)]
+    pub fn set(&self, value: T)
+    where
+        T: Copy,
+    {
+        // Safety: The cell is valid for writes.
+        unsafe { core::ptr::write_volatile(self.value.get(), value) }
+        if core::mem::size_of::<T>() == 4 {
+            record_write(self.as_ptr().cast());
+        }
+    }
+
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+}
+
+/// Register block backed by memory
+pub(crate) use crate::test::MockRegisters as Registers;
+
+/// Inspection and recording of the registers for the unit tests
+impl Registers {
+    /// Returns the values of all registers
+    pub(crate) fn snapshot(&self) -> [u32; 64] {
+        core::array::from_fn(|index| self.read(index * 4))
+    }
+
+    /// Starts recording the writes of the abstractions to the registers,
+    /// returned by [`Self::take_recording`]
+    ///
+    /// Writes through [`Self::write`] are not recorded.
+    pub(crate) fn start_recording(&self) {
+        let mut recordings = RECORDINGS.lock().unwrap_or_else(PoisonError::into_inner);
+        recordings.push((self.start(), Vec::new()));
+    }
+
+    /// Stops recording and returns the recorded writes as pairs of register
+    /// offset and value, in the order of the writes
+    pub(crate) fn take_recording(&self) -> Writes {
+        let mut recordings = RECORDINGS.lock().unwrap_or_else(PoisonError::into_inner);
+        let start = self.start();
+        recordings
+            .iter()
+            .position(|(recorded, _)| *recorded == start)
+            .map(|index| recordings.swap_remove(index).1)
+            .unwrap_or_default()
+    }
+
+    fn start(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+/// Declares a peripheral identity `$name` backed by a [`Registers`] static
+/// accessible through `$name::regs()`.
+///
+/// Limits and the revision of the peripheral can be overridden by listing
+/// constants of [`mcan_core::CanId`] in braces. The backing memory fits a
+/// `REGISTER_BLOCK_OFFSET` of up to 0x100, with the registers before the
+/// offset accessible through `$name::wrapper_regs()`.
+macro_rules! mock_can {
+    ($name:ident) => {
+        $crate::mock::mock_can!($name {});
+    };
+    ($name:ident { $(const $constant:ident: $type:ty = $value:expr;)* }) => {
+        pub(crate) enum $name {}
+
+        impl $name {
+            pub(crate) fn regs() -> &'static $crate::mock::Registers {
+                use $crate::reg::AccessRegisterBlock as _;
+                assert!(<$name as mcan_core::CanId>::REGISTER_BLOCK_OFFSET <= 0x100);
+                // Safety: The offset is within the `Registers` statics that
+                // `ADDRESS` points to.
+                unsafe { &*($name::register_block() as *const $crate::mock::Registers) }
+            }
+
+            #[allow(dead_code)]
+            pub(crate) fn wrapper_regs() -> &'static $crate::mock::Registers {
+                // Safety: `ADDRESS` points to a `Registers` static.
+                unsafe {
+                    &*(<$name as mcan_core::CanId>::ADDRESS as *const $crate::mock::Registers)
+                }
+            }
+        }
+
+        unsafe impl mcan_core::CanId for $name {
+            const ADDRESS: *const () = {
+                static REGS: [$crate::mock::Registers; 2] =
+                    [$crate::mock::Registers::new(), $crate::mock::Registers::new()];
+                core::ptr::addr_of!(REGS) as *const ()
+            };
+            $(const $constant: $type = $value;)*
+        }
+    };
+}
+pub(crate) use mock_can;
+
+/// Dependencies that accept any Message RAM placement
+pub(crate) use crate::test::MockDependencies as Dependencies;
+
+/// Classic data frame with the standard `id` and no data, e.g. to fill
+/// emulated receive and transmit buffers
+pub(crate) fn message<M: From<tx::Message<8>>>(id: u16) -> M {
+    let id = StandardId::new(id).unwrap();
+    tx::MessageBuilder::classic(id, &[]).build().unwrap().into()
+}
+
+/// Small Message RAM layout
+pub(crate) struct Caps;
+
+impl Capacities for Caps {
+    type StandardFilters = U4;
+    type ExtendedFilters = U4;
+    type RxBufferMessage = rx::Message<8>;
+    type DedicatedRxBuffers = U4;
+    type RxFifo0Message = rx::Message<8>;
+    type RxFifo0 = U4;
+    type RxFifo1Message = rx::Message<8>;
+    type RxFifo1 = U4;
+    type TxMessage = tx::Message<8>;
+    type TxBuffers = U4;
+    type DedicatedTxBuffers = U2;
+    type TxEventFifo = U4;
+}
@@ -1,6 +1,10 @@
 //! Low-level access to peripheral registers
 
 #![allow(non_camel_case_types)]
+// The generated writer accessors elide the lifetime of the returned proxies.
+// The lint only exists since Rust 1.89.
+#![allow(unknown_lints)]
+#![allow(mismatched_lifetime_syntaxes)]
 pub mod generic;
 
 /// Blanket implementation trait that provides convenience method for recasting
@@ -10,12 +14,17 @@ pub mod generic;
 /// know the concrete low-level access type definition.
 pub trait AccessRegisterBlock {
     /// Returns a raw pointer to the peripheral registers
+    ///
+    /// All register accesses of the crate go through this pointer, which
+    /// applies [`CanId::REGISTER_BLOCK_OFFSET`](mcan_core::CanId::REGISTER_BLOCK_OFFSET).
     fn register_block() -> *const RegisterBlock;
 }
 
 impl<T: mcan_core::CanId> AccessRegisterBlock for T {
     fn register_block() -> *const RegisterBlock {
-        T::ADDRESS as *const _
+        (T::ADDRESS as *const u8)
+            .wrapping_add(T::REGISTER_BLOCK_OFFSET)
+            .cast()
     }
 }
 
@@ -77,6 +86,105 @@ impl<Id: mcan_core::CanId> core::ops::Deref for Can<Id> {
     }
 }
 
+/// Registers that no component of the crate claims, which can be accessed
+/// without `unsafe` through [`Aux::borrow_register`]
+///
+/// Unlike [`Can::registers`](crate::bus::Can::registers), this does not
+/// alias the registers owned by the components. The allow-list is:
+///
+/// | Register | Access     | Reason                                                    |
+/// |----------|------------|-----------------------------------------------------------|
+/// | CREL     | read       | Read-only core release                                    |
+/// | ENDN     | read       | Read-only endianness test value                           |
+/// | CUST     | read/write | Reserved for vendor extensions, never accessed by the crate |
+/// | TEST     | read       | Written for loopback and pin tests, reads have no effects |
+/// | RWD      | read       | Writes are protected, reads have no effects               |
+/// | TDCR     | read       | Written for delay compensation, reads have no effects     |
+/// | HPMS     | read       | Read-only status                                          |
+///
+/// Other registers are written by the components, or cleared by reading
+/// like PSR and ECR, and are not available:
+///
+/// ```compile_fail,E0277
+/// # fn example<Id: mcan::core::CanId, D: mcan::core::Dependencies<Id>>(
+/// #     aux: &mcan::bus::Aux<'_, Id, D>,
+/// # ) {
+/// let _ = aux.borrow_register::<mcan::reg::cccr::CCCR_SPEC>();
+/// # }
+/// ```
+///
+/// Read-only registers cannot be written:
+///
+/// ```compile_fail,E0599
+/// # fn example<Id: mcan::core::CanId, D: mcan::core::Dependencies<Id>>(
+/// #     aux: &mcan::bus::Aux<'_, Id, D>,
+/// # ) {
+/// aux.borrow_register::<mcan::reg::test::TEST_SPEC>().write_bits(0);
+/// # }
+/// ```
+///
+/// [`Aux::borrow_register`]: crate::bus::Aux::borrow_register
+pub trait UnownedRegister: crate::Readable + private::Sealed {}
+
+/// [`UnownedRegister`]s that can be written as well
+pub trait UnownedWritable: UnownedRegister + crate::Writable<Ux = u32> {}
+
+mod private {
+    pub trait Sealed: crate::RegisterSpec + Sized {
+        fn register(block: &super::RegisterBlock) -> &crate::Reg<Self>;
+    }
+}
+
+macro_rules! unowned {
+    ($($field:ident: $spec:ty),* $(,)?) => {
+        $(
+            impl private::Sealed for $spec {
+                fn register(block: &RegisterBlock) -> &crate::Reg<Self> {
+                    &block.$field
+                }
+            }
+
+            impl UnownedRegister for $spec {}
+        )*
+    };
+}
+
+unowned!(
+    crel: crel::CREL_SPEC,
+    endn: endn::ENDN_SPEC,
+    cust: cust::CUST_SPEC,
+    test: test::TEST_SPEC,
+    rwd: rwd::RWD_SPEC,
+    tdcr: tdcr::TDCR_SPEC,
+    hpms: hpms::HPMS_SPEC,
+);
+
+impl UnownedWritable for cust::CUST_SPEC {}
+
+/// Short-lived access to an [`UnownedRegister`]
+pub struct UnownedRef<'a, REG: crate::RegisterSpec>(&'a crate::Reg<REG>);
+
+impl<'a, REG: UnownedRegister> UnownedRef<'a, REG> {
+    pub(crate) fn new(block: &'a RegisterBlock) -> Self {
+        Self(REG::register(block))
+    }
+
+    /// Reads the register
+    pub fn read(&self) -> crate::R<REG> {
+        self.0.read()
+    }
+}
+
+impl<REG: UnownedWritable> UnownedRef<'_, REG> {
+    /// Writes `bits` to the register
+    pub fn write_bits(&self, bits: u32) {
+        self.0.modify(|_, w| {
+            w.bits = bits;
+            w
+        });
+    }
+}
+
 #[doc = r"Register block"]
 #[repr(C)]
 pub struct RegisterBlock {
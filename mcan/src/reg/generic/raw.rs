@@ -3,6 +3,17 @@ pub struct R<REG: RegisterSpec> {
     pub(crate) bits: REG::Ux,
     pub(super) _reg: marker::PhantomData<REG>,
 }
+impl<REG: RegisterSpec> R<REG> {
+    #[doc = " Creates a new instance of the reader."]
+    #[allow(unused)]
+    #[inline(always)]
+    pub(crate) const fn new(bits: REG::Ux) -> Self {
+        Self {
+            bits,
+            _reg: marker::PhantomData,
+        }
+    }
+}
 pub struct W<REG: RegisterSpec> {
     #[doc = "Writable bits"]
     pub(crate) bits: REG::Ux,
@@ -0,0 +1,228 @@
+//! Index arithmetic of the ring buffers in Message RAM
+//!
+//! The RX FIFOs, the TX event FIFO and the transmit queue are ring buffers
+//! whose state the peripheral reports as a get index along with either a fill
+//! level or a put index and a full flag. [`RingIndices`] captures this state
+//! and answers where the occupied and free elements are, so that the
+//! components do not have to deal with wraparound themselves.
+
+use core::ops::Range;
+
+/// Get and put index of a ring buffer with `capacity` elements
+///
+/// `get` is a position in memory, while `put` runs ahead of it by the number
+/// of occupied elements without wrapping around, so that a full ring is
+/// distinct from an empty one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RingIndices {
+    get: usize,
+    put: usize,
+    capacity: usize,
+}
+
+impl RingIndices {
+    /// Ring with `fill_level` occupied elements starting at position `get`
+    ///
+    /// Out of range values as reported by an unconfigured peripheral are
+    /// wrapped or clamped to the capacity.
+    pub(crate) fn from_fill_level(get: usize, fill_level: usize, capacity: usize) -> Self {
+        let get = get.checked_rem(capacity).unwrap_or(0);
+        Self {
+            get,
+            put: get + fill_level.min(capacity),
+            capacity,
+        }
+    }
+
+    /// Ring with the occupied elements between positions `get` and `put`
+    ///
+    /// The positions are equal both when the ring is empty and when it is
+    /// full, which is told apart by `full`.
+    pub(crate) fn from_positions(get: usize, put: usize, full: bool, capacity: usize) -> Self {
+        let fill_level = if full || capacity == 0 {
+            capacity
+        } else {
+            (put % capacity + capacity - get % capacity) % capacity
+        };
+        Self::from_fill_level(get, fill_level, capacity)
+    }
+
+    /// Number of occupied elements
+    pub(crate) fn occupied(&self) -> usize {
+        self.put - self.get
+    }
+
+    /// Number of free elements
+    pub(crate) fn free(&self) -> usize {
+        self.capacity - self.occupied()
+    }
+
+    /// Position of the `n`th occupied element, counting from the get index
+    pub(crate) fn nth_occupied(&self, n: usize) -> Option<usize> {
+        (n < self.occupied()).then(|| self.wrap(self.get + n))
+    }
+
+    /// Position of the element at the put index, if it is free
+    pub(crate) fn next_free(&self) -> Option<usize> {
+        (self.free() > 0).then(|| self.wrap(self.put))
+    }
+
+    /// Release up to `n` occupied elements at the get index and return how
+    /// many were released
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn advance(&mut self, n: usize) -> usize {
+        let n = n.min(self.occupied());
+        self.get += n;
+        if self.get >= self.capacity {
+            self.get -= self.capacity;
+            self.put -= self.capacity;
+        }
+        n
+    }
+
+    /// Positions of the occupied elements in order, split at the end of the
+    /// ring
+    ///
+    /// The second range is empty unless the occupied elements wrap around.
+    pub(crate) fn segments(&self) -> [Range<usize>; 2] {
+        let end = self.put.min(self.capacity);
+        [self.get..end, 0..self.put - end]
+    }
+
+    fn wrap(&self, index: usize) -> usize {
+        if index >= self.capacity {
+            index - self.capacity
+        } else {
+            index
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    const MAX_CAPACITY: usize = 8;
+
+    /// Every consistent state of rings up to `MAX_CAPACITY` elements, along
+    /// with the positions of their occupied elements
+    fn rings() -> impl Iterator<Item = (RingIndices, Vec<usize>)> {
+        (0..=MAX_CAPACITY).flat_map(|capacity| {
+            (0..capacity.max(1)).flat_map(move |get| {
+                (0..=capacity).map(move |fill_level| {
+                    let positions = (0..fill_level).map(|n| (get + n) % capacity).collect();
+                    (
+                        RingIndices::from_fill_level(get, fill_level, capacity),
+                        positions,
+                    )
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn counts() {
+        for (ring, positions) in rings() {
+            assert_eq!(ring.occupied(), positions.len(), "{ring:?}");
+            assert_eq!(ring.free(), ring.capacity - positions.len(), "{ring:?}");
+        }
+    }
+
+    #[test]
+    fn nth_occupied() {
+        for (ring, positions) in rings() {
+            for n in 0..=MAX_CAPACITY + 1 {
+                assert_eq!(ring.nth_occupied(n), positions.get(n).copied(), "{ring:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn next_free() {
+        for (ring, positions) in rings() {
+            let expected = (positions.len() < ring.capacity)
+                .then(|| (ring.get + positions.len()) % ring.capacity);
+            assert_eq!(ring.next_free(), expected, "{ring:?}");
+        }
+    }
+
+    #[test]
+    fn segments() {
+        for (ring, positions) in rings() {
+            let [first, second] = ring.segments();
+            assert!(first.end <= ring.capacity, "{ring:?}");
+            assert!(second.is_empty() || second.start == 0, "{ring:?}");
+            assert!(second.is_empty() || first.end == ring.capacity, "{ring:?}");
+            assert_eq!(
+                first.chain(second).collect::<Vec<_>>(),
+                positions,
+                "{ring:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn advance() {
+        for (ring, positions) in rings() {
+            for n in 0..=MAX_CAPACITY + 1 {
+                let mut advanced = ring;
+                let released = advanced.advance(n);
+                assert_eq!(released, n.min(positions.len()), "{ring:?} by {n}");
+                assert_eq!(
+                    (0..advanced.occupied())
+                        .map(|n| advanced.nth_occupied(n).unwrap())
+                        .collect::<Vec<_>>(),
+                    positions[released..],
+                    "{ring:?} by {n}",
+                );
+                assert_eq!(
+                    advanced.next_free(),
+                    ring.next_free().or((released > 0).then_some(ring.get)),
+                    "{ring:?} by {n}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn from_positions() {
+        for (ring, positions) in rings() {
+            if ring.capacity == 0 {
+                continue;
+            }
+            let put = (ring.get + positions.len()) % ring.capacity;
+            let full = positions.len() == ring.capacity;
+            assert_eq!(
+                RingIndices::from_positions(ring.get, put, full, ring.capacity),
+                ring
+            );
+        }
+    }
+
+    #[test]
+    fn capacity_one() {
+        let mut ring = RingIndices::from_positions(0, 0, true, 1);
+        assert_eq!((ring.occupied(), ring.free()), (1, 0));
+        assert_eq!(ring.nth_occupied(0), Some(0));
+        assert_eq!(ring.next_free(), None);
+        assert_eq!(ring.segments(), [0..1, 0..0]);
+        assert_eq!(ring.advance(2), 1);
+        assert_eq!(ring, RingIndices::from_positions(0, 0, false, 1));
+        assert_eq!(ring.next_free(), Some(0));
+    }
+
+    #[test]
+    fn out_of_range_status() {
+        let ring = RingIndices::from_fill_level(5, 9, 4);
+        assert_eq!((ring.occupied(), ring.nth_occupied(0)), (4, Some(1)));
+        // Full takes precedence over differing positions
+        assert_eq!(RingIndices::from_positions(1, 3, true, 4).occupied(), 4);
+        let empty = RingIndices::from_positions(3, 1, false, 0);
+        assert_eq!(empty, RingIndices::from_fill_level(0, 0, 0));
+        assert_eq!((empty.nth_occupied(0), empty.next_free()), (None, None));
+        assert_eq!(empty.segments(), [0..0, 0..0]);
+    }
+}
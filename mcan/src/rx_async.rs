@@ -0,0 +1,187 @@
+//! Cancellation-safe asynchronous reception
+//!
+//! [`RxFifo::receive_async`] resolves to the oldest message of a FIFO once
+//! there is one. The message is only taken out of the FIFO in the poll that
+//! returns it, so dropping the future at any point, e.g. in a `select` or on
+//! a timeout, never loses a message.
+//!
+//! The future is woken through a [`RxWaker`], which the interrupt handler has
+//! to notify on one of the interrupts of the FIFO, e.g.
+//! [`Interrupt::RxFifo0NewMessage`]. [`Wakers`] bundles the wakers of a
+//! peripheral and picks the ones to wake from the flags of either interrupt
+//! line. If none of the interrupts of the FIFO is enabled, the future only
+//! resolves if a message is already available when it is polled.
+//!
+//! ```no_run
+//! # use mcan::interrupt::{InterruptSet, Wakers};
+//! # use mcan::message::rx;
+//! # use mcan::rx_fifo::{Fifo0, RxFifo};
+//! static WAKERS: Wakers = Wakers::new();
+//!
+//! // In the interrupt handlers of both lines, after clearing the flags
+//! # let flags = InterruptSet::empty();
+//! WAKERS.wake_on(flags);
+//!
+//! // In a task
+//! # async fn task<P: mcan::core::CanId>(rx_fifo_0: &mut RxFifo<'_, Fifo0, P, rx::Message<8>>) {
+//! let message = rx_fifo_0.receive_async(&WAKERS.rx_fifo_0).await;
+//! # }
+//! ```
+//!
+//! [`Interrupt::RxFifo0NewMessage`]: crate::interrupt::Interrupt::RxFifo0NewMessage
+//! [`Wakers`]: crate::interrupt::Wakers
+
+use crate::message::rx;
+use crate::rx_fifo::{DynRxFifo as _, GetRxFifoRegs, RxFifo};
+use atomic_waker::AtomicWaker;
+use core::future::poll_fn;
+use core::task::Poll;
+
+/// Wakes a task waiting in [`RxFifo::receive_async`]
+///
+/// Meant to be placed in a `static` shared between the task and the interrupt
+/// handler, with one waker per FIFO.
+pub struct RxWaker(AtomicWaker);
+
+impl RxWaker {
+    /// Create an instance with no registered task
+    pub const fn new() -> Self {
+        Self(AtomicWaker::new())
+    }
+
+    /// Wake the waiting task, if any
+    ///
+    /// Should be called on one of the interrupts of the FIFO, see
+    /// [`RxFifo::INTERRUPTS`].
+    pub fn wake(&self) {
+        self.0.wake();
+    }
+}
+
+impl Default for RxWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, F, P: mcan_core::CanId, M: rx::AnyMessage> RxFifo<'a, F, P, M>
+where
+    Self: GetRxFifoRegs,
+{
+    /// Wait for a message and take it out of the FIFO
+    ///
+    /// See the [module documentation](crate::rx_async) for why this is
+    /// cancellation-safe.
+    pub async fn receive_async(&mut self, waker: &RxWaker) -> M {
+        poll_fn(|cx| {
+            if let Ok(message) = self.receive() {
+                return Poll::Ready(message);
+            }
+            waker.0.register(cx.waker());
+            // A message may have arrived before the waker was registered, in
+            // which case the corresponding wake-up has been missed.
+            match self.receive() {
+                Ok(message) => Poll::Ready(message),
+                Err(_) => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::interrupt::{Interrupt, InterruptSet, Wakers};
+    use crate::message::Raw as _;
+    use crate::mock::{self, mock_can};
+    use crate::rx_fifo::{Fifo0, Fifo1};
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Context;
+    use embedded_can::StandardId;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use vcell::VolatileCell;
+
+    mock_can!(Can0);
+
+    const RXF0S: usize = 0xa4;
+    const RXF0A: usize = 0xa8;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn receive_waits_for_a_message_and_is_woken() {
+        let regs = Can0::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can0, rx::Message<8>>::new(&mut memory, None) };
+        let wakers = Wakers::new();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = pin!(fifo.receive_async(&wakers.rx_fifo_0));
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+
+        // Flags of the other FIFO and of transmission do not wake it
+        wakers.wake_on(InterruptSet::from_slice(&[
+            Interrupt::RxFifo1NewMessage,
+            Interrupt::TxFifoEmpty,
+        ]));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+        // Woken without a message, it stays pending
+        wakers.wake_on(InterruptSet::empty().with(Interrupt::RxFifo0NewMessage));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(regs.read(RXF0A), 0);
+
+        // Get index 2, fill level 1
+        regs.write(RXF0S, 2 << 8 | 1);
+        wakers.wake_on(InterruptSet::empty().with(Interrupt::RxFifo0WatermarkReached));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        let Poll::Ready(received) = future.as_mut().poll(&mut cx) else {
+            panic!("a message is available");
+        };
+        assert_eq!(received.id(), StandardId::new(2).unwrap().into());
+        assert_eq!(regs.read(RXF0A), 2);
+    }
+
+    #[test]
+    fn dropping_the_future_takes_no_message() {
+        mock_can!(Can1);
+        const RXF1S: usize = 0xb4;
+        const RXF1A: usize = 0xb8;
+        let regs = Can1::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo1, Can1, rx::Message<8>>::new(&mut memory, None) };
+        let rx_waker = RxWaker::new();
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+        let mut cx = Context::from_waker(&waker);
+
+        {
+            let future = pin!(fifo.receive_async(&rx_waker));
+            assert!(future.poll(&mut cx).is_pending());
+        }
+        regs.write(RXF1A, 0xdead);
+        regs.write(RXF1S, 3 << 8 | 1);
+        assert_eq!(regs.read(RXF1A), 0xdead);
+        let future = pin!(fifo.receive_async(&rx_waker));
+        assert!(future.poll(&mut cx).is_ready());
+        assert_eq!(regs.read(RXF1A), 3);
+    }
+}
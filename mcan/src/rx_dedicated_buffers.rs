@@ -3,9 +3,72 @@
 //! Messages can be placed in dedicated buffers by [`Filter::StoreBuffer`] or
 //! [`ExtFilter::StoreBuffer`].
 //!
+//! A buffer can also be bound to a single ID with
+//! [`CanConfigurable::bind_dedicated_buffer`], which sets up the filter and
+//! returns a [`BoundRxBuffer`] that receives from exactly this buffer. The
+//! buffer index is part of its type, so handles for different signals cannot
+//! be mixed up once they have been moved to their tasks.
+//!
+//! ```no_run
+//! # use mcan::generic_array::typenum::consts::*;
+//! # use mcan::message::{rx, tx};
+//! # use mcan::messageram::SharedMemory;
+//! # use fugit::RateExtU32 as _;
+//! # struct Capacities;
+//! # impl mcan::messageram::Capacities for Capacities {
+//! #     type StandardFilters = U8;
+//! #     type ExtendedFilters = U8;
+//! #     type RxBufferMessage = rx::Message<8>;
+//! #     type DedicatedRxBuffers = U2;
+//! #     type RxFifo0Message = rx::Message<8>;
+//! #     type RxFifo0 = U8;
+//! #     type RxFifo1Message = rx::Message<8>;
+//! #     type RxFifo1 = U8;
+//! #     type TxMessage = tx::Message<8>;
+//! #     type TxBuffers = U8;
+//! #     type DedicatedTxBuffers = U0;
+//! #     type TxEventFifo = U8;
+//! # }
+//! # struct Can0;
+//! # unsafe impl mcan::core::CanId for Can0 {
+//! #     const ADDRESS: *const () = 0xDEAD0000 as *const _;
+//! # }
+//! # struct Dependencies;
+//! # unsafe impl<ID: mcan::core::CanId> mcan::core::Dependencies<ID> for Dependencies {
+//! #     fn eligible_message_ram_start(&self) -> *const () { unreachable!() }
+//! #     fn host_clock(&self) -> fugit::HertzU32 { unreachable!() }
+//! #     fn can_clock(&self) -> fugit::HertzU32 { unreachable!() }
+//! # }
+//! # fn example(memory: &'static mut SharedMemory<Capacities>) {
+//! use mcan::embedded_can::{ExtendedId, StandardId};
+//!
+//! let mut can =
+//!     mcan::bus::CanConfigurable::<Can0, _, _>::new(500.kHz(), Dependencies, memory).unwrap();
+//! // Fails if frames with 8 bytes of data would not fit into the buffer
+//! let mut temperature = can
+//!     .bind_dedicated_buffer::<0>(StandardId::new(0x123).unwrap(), Some(8))
+//!     .unwrap();
+//! let mut pressure = can
+//!     .bind_dedicated_buffer::<1>(ExtendedId::new(0x1abc_0001).unwrap(), None)
+//!     .unwrap();
+//! // Binding a buffer twice fails
+//! assert!(can
+//!     .bind_dedicated_buffer::<1>(StandardId::new(0x124).unwrap(), None)
+//!     .is_err());
+//! let can = can.finalize_recoverable().unwrap();
+//!
+//! // `temperature` and `pressure` can be moved to the tasks handling them
+//! if let Ok(frame) = temperature.receive() {
+//!     // ...
+//! }
+//! # }
+//! ```
+//!
 //! [`Filter::StoreBuffer`]: crate::filter::Filter::StoreBuffer
 //! [`ExtFilter::StoreBuffer`]: crate::filter::ExtFilter::StoreBuffer
+//! [`CanConfigurable::bind_dedicated_buffer`]: crate::bus::CanConfigurable::bind_dedicated_buffer
 
+use crate::interrupt::{Interrupt, InterruptSet};
 use crate::message::rx;
 use crate::reg;
 use core::convert::Infallible;
@@ -13,18 +76,73 @@ use core::marker::PhantomData;
 use reg::AccessRegisterBlock as _;
 use vcell::VolatileCell;
 
-/// Index is out of bounds
+/// Index is out of bounds, or refers to a buffer bound to a
+/// [`BoundRxBuffer`]
 #[derive(Debug)]
 pub struct OutOfBounds;
 
+/// Error returned by
+/// [`CanConfigurable::bind_dedicated_buffer`](crate::bus::CanConfigurable::bind_dedicated_buffer)
+/// and
+/// [`CanConfigurable::assign_dedicated_buffer`](crate::bus::CanConfigurable::assign_dedicated_buffer)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BindError {
+    /// The buffer index exceeds the number of dedicated receive buffers
+    OutOfBounds,
+    /// The buffer is already bound
+    AlreadyBound,
+    /// There is no room for the filter routing the ID to the buffer
+    FilterListFull,
+    /// The frames with the ID may carry more data than the buffer holds
+    ElementTooSmall(ElementTooSmall),
+}
+
+/// Frames routed to the dedicated buffers may carry more data than their
+/// elements, sized by [`Capacities::RxBufferMessage`], hold
+///
+/// The peripheral would store such frames truncated, see
+/// [`rx::AnyMessage::is_truncated`].
+///
+/// [`Capacities::RxBufferMessage`]: crate::messageram::Capacities::RxBufferMessage
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ElementTooSmall {
+    /// Largest data length declared for the frames
+    pub declared_max_len: usize,
+    /// Data bytes held by an element of the dedicated buffers
+    pub element_capacity: usize,
+}
+
+/// Error returned when pushing filters that store frames in dedicated buffers
+/// with
+/// [`CanConfigurable::push_store_buffer_filter`](crate::bus::CanConfigurable::push_store_buffer_filter)
+/// or
+/// [`CanConfigurable::push_store_buffer_ext_filter`](crate::bus::CanConfigurable::push_store_buffer_ext_filter)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StoreBufferError<F> {
+    /// The filter list is full, the filter is handed back
+    FilterListFull(F),
+    /// The offset of the filter exceeds the number of dedicated receive
+    /// buffers, the filter is handed back
+    OutOfBounds(F),
+    /// The frames matching the filter may carry more data than the buffers
+    /// hold
+    ElementTooSmall(ElementTooSmall),
+}
+
 /// Dedicated receive buffers on peripheral `P`
 pub struct RxDedicatedBuffer<'a, P, M: rx::AnyMessage> {
-    memory: &'a mut [VolatileCell<M>],
+    memory: &'a [VolatileCell<M>],
+    /// Buffers handed out as [`BoundRxBuffer`]s, which are skipped here
+    bound: u64,
     _markers: PhantomData<P>,
 }
 
+// Safety: The memory is shared only with `BoundRxBuffer`s, which access
+// disjoint elements. This is as `Send` as exclusive ownership of the memory.
+unsafe impl<'a, P: Send, M: rx::AnyMessage + Send> Send for RxDedicatedBuffer<'a, P, M> {}
+
 /// Trait which erases generic parametrization for [`RxDedicatedBuffer`] type
-pub trait DynRxDedicatedBuffer {
+pub trait DynRxDedicatedBuffer: crate::sealed::Sealed {
     /// CAN identity type
     type Id;
 
@@ -35,10 +153,71 @@ pub trait DynRxDedicatedBuffer {
     fn receive(&mut self, index: usize) -> nb::Result<Self::Message, OutOfBounds>;
 
     /// Returns a received frame from any dedicated buffer if available
+    ///
+    /// Of several received frames, the one with the highest priority ID is
+    /// returned, which takes reading all buffers with new data.
     fn receive_any(&mut self) -> nb::Result<Self::Message, Infallible>;
+
+    /// Returns a received frame from the buffer with the lowest index that
+    /// has new data, which only reads that buffer
+    fn receive_lowest_index(&mut self) -> nb::Result<Self::Message, Infallible>;
+
+    /// Returns a received frame from the selected buffer if available,
+    /// without acknowledging it, so that it is returned again by the next
+    /// call
+    fn peek(&self, index: usize) -> nb::Result<Self::Message, OutOfBounds>;
+
+    /// New data flags of NDAT1 and NDAT2, bit `n` for buffer `n`
+    ///
+    /// Only the flags of buffers received from here are included, not those
+    /// of buffers bound to a [`BoundRxBuffer`] or beyond the number of
+    /// buffers.
+    fn new_data_flags(&self) -> u64;
+
+    /// Iterates over the buffers that have new data when this is called, in
+    /// order of their index, yielding the index along with the frame
+    ///
+    /// Each frame is acknowledged as it is yielded; frames that are not
+    /// yielded stay unread.
+    fn iter_new_data(&mut self) -> NewData<'_, Self>
+    where
+        Self: Sized,
+    {
+        NewData {
+            pending: self.new_data_flags(),
+            buffers: self,
+        }
+    }
+}
+
+/// Iterator over the buffers with new data, see
+/// [`DynRxDedicatedBuffer::iter_new_data`]
+pub struct NewData<'r, R> {
+    buffers: &'r mut R,
+    /// Flags of the buffers not yielded yet
+    pending: u64,
+}
+
+impl<R: DynRxDedicatedBuffer> Iterator for NewData<'_, R> {
+    type Item = (usize, R::Message);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending != 0 {
+            let index = self.pending.trailing_zeros() as usize;
+            self.pending &= self.pending - 1;
+            if let Ok(message) = self.buffers.receive(index) {
+                return Some((index, message));
+            }
+        }
+        None
+    }
 }
 
 impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> RxDedicatedBuffer<'a, P, M> {
+    /// Interrupts raised by the dedicated buffers
+    pub const INTERRUPTS: InterruptSet =
+        InterruptSet::from_slice(&[Interrupt::MessageStoredToDedicatedRxBuffer]);
+
     /// # Safety
     /// The caller must be the owner or the peripheral referenced by `P`. The
     /// constructed type assumes ownership of some of the registers from the
@@ -49,33 +228,13 @@ impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> RxDedicatedBuffer<'a, P, M> {
     pub(crate) unsafe fn new(memory: &'a mut [VolatileCell<M>]) -> Self {
         Self {
             memory,
+            bound: 0,
             _markers: PhantomData,
         }
     }
 
-    /// Raw access to the registers.
-    unsafe fn regs(&self) -> &reg::RegisterBlock {
-        &(*P::register_block())
-    }
-
-    fn ndat1(&self) -> &reg::NDAT1 {
-        // Safety: `Self` owns the register.
-        unsafe { &self.regs().ndat1 }
-    }
-
-    fn ndat2(&self) -> &reg::NDAT2 {
-        // Safety: `Self` owns the register.
-        unsafe { &self.regs().ndat2 }
-    }
-
     fn has_new_data(&self, index: usize) -> bool {
-        if index < 32 {
-            self.ndat1().read().bits() & (1 << index) != 0
-        } else if index < 64 {
-            self.ndat2().read().bits() & (1 << (index - 32)) != 0
-        } else {
-            false
-        }
+        has_new_data::<P>(index)
     }
 
     fn has_new_data_checked(&self, index: usize) -> Result<bool, OutOfBounds> {
@@ -87,30 +246,98 @@ impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> RxDedicatedBuffer<'a, P, M> {
     }
 
     fn mark_buffer_read(&self, index: usize) {
-        if index < 32 {
-            unsafe {
-                self.ndat1().write(|w| w.bits(1 << index));
-            }
-        } else if index < 64 {
-            unsafe {
-                self.ndat2().write(|w| w.bits(1 << index));
-            }
+        mark_buffer_read::<P>(index);
+    }
+
+    /// Passes the message in buffer `index` to `f` without copying it out of
+    /// the Message RAM, and marks the buffer as read once `f` returns
+    ///
+    /// Like [`DynRxDedicatedBuffer::receive`], this blocks while the new data
+    /// flag of the buffer is clear. The peripheral does not write the buffer
+    /// while the flag is set, so the message does not change while `f` reads
+    /// it.
+    pub fn receive_with<R>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&M) -> R,
+    ) -> nb::Result<R, OutOfBounds> {
+        if index >= self.memory.len() || self.is_bound(index) {
+            return Err(nb::Error::Other(OutOfBounds));
         }
+        if !self.has_new_data_checked(index)? {
+            return Err(nb::Error::WouldBlock);
+        }
+        let element = self
+            .memory
+            .get(index)
+            .ok_or(nb::Error::Other(OutOfBounds))?;
+        // Safety: The peripheral does not write the buffer until the new data
+        // flag is cleared below.
+        let result = f(unsafe { &*element.as_ptr() });
+        self.mark_buffer_read(index);
+        Ok(result)
     }
 
-    fn peek(&self, index: usize) -> nb::Result<M, OutOfBounds> {
-        if self.has_new_data_checked(index)? {
-            Ok(self
-                .memory
-                .get(index)
-                .ok_or(nb::Error::Other(OutOfBounds))?
-                .get())
+    /// Number of data bytes each dedicated buffer holds
+    ///
+    /// Frames with more data are stored truncated, see
+    /// [`rx::AnyMessage::is_truncated`].
+    pub fn element_capacity(&self) -> usize {
+        M::SIZE.bytes()
+    }
+
+    /// Checks that frames with up to `declared_max_len` bytes of data fit
+    /// into the buffers. Nothing is checked without a declared length.
+    pub(crate) fn check_declared_len(
+        &self,
+        declared_max_len: Option<usize>,
+    ) -> Result<(), ElementTooSmall> {
+        let element_capacity = self.element_capacity();
+        match declared_max_len {
+            Some(declared_max_len) if declared_max_len > element_capacity => Err(ElementTooSmall {
+                declared_max_len,
+                element_capacity,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_bound(&self, index: usize) -> bool {
+        index < 64 && self.bound & (1 << index) != 0
+    }
+
+    /// Checks that buffer `index` exists and is not bound yet
+    pub(crate) fn check_bindable(&self, index: usize) -> Result<(), BindError> {
+        if index >= self.memory.len() {
+            Err(BindError::OutOfBounds)
+        } else if self.is_bound(index) {
+            Err(BindError::AlreadyBound)
         } else {
-            Err(nb::Error::WouldBlock)
+            Ok(())
         }
     }
+
+    /// Hands out buffer `N` as a [`BoundRxBuffer`] and stops receiving from it
+    /// here
+    pub(crate) fn bind<const N: usize>(&mut self) -> Result<BoundRxBuffer<'a, P, M, N>, BindError> {
+        self.check_bindable(N)?;
+        let cell = self.memory.get(N).ok_or(BindError::OutOfBounds)?;
+        self.bound |= 1 << N;
+        Ok(BoundRxBuffer {
+            cell,
+            _markers: PhantomData,
+        })
+    }
+
+    /// Buffers received from here, bit `n` for buffer `n`
+    fn handled(&self) -> u64 {
+        let len = u32::try_from(self.memory.len()).unwrap_or(u32::MAX);
+        u64::MAX.checked_shr(64u32.saturating_sub(len)).unwrap_or(0) & !self.bound
+    }
 }
 
+impl<P, M: rx::AnyMessage> crate::sealed::Sealed for RxDedicatedBuffer<'_, P, M> {}
+
 impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> DynRxDedicatedBuffer
     for RxDedicatedBuffer<'a, P, M>
 {
@@ -123,11 +350,40 @@ impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> DynRxDedicatedBuffer
         Ok(message)
     }
 
+    fn peek(&self, index: usize) -> nb::Result<Self::Message, OutOfBounds> {
+        if index >= self.memory.len() || self.is_bound(index) {
+            return Err(nb::Error::Other(OutOfBounds));
+        }
+        if self.has_new_data_checked(index)? {
+            Ok(self
+                .memory
+                .get(index)
+                .ok_or(nb::Error::Other(OutOfBounds))?
+                .get())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    fn new_data_flags(&self) -> u64 {
+        let (ndat1, ndat2) = ndat::<P>();
+        (u64::from(ndat2.read().bits()) << 32 | u64::from(ndat1.read().bits())) & self.handled()
+    }
+
+    fn receive_lowest_index(&mut self) -> nb::Result<Self::Message, Infallible> {
+        let flags = self.new_data_flags();
+        if flags == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.receive(flags.trailing_zeros() as usize)
+            .map_err(|_| nb::Error::WouldBlock)
+    }
+
     fn receive_any(&mut self) -> nb::Result<Self::Message, Infallible> {
         self.memory
             .iter()
             .enumerate()
-            .filter(|&(i, _)| self.has_new_data(i))
+            .filter(|&(i, _)| !self.is_bound(i) && self.has_new_data(i))
             .map(|(i, m)| (i, m.get()))
             .min_by_key(|(_, m)| m.id())
             .map(|(i, m)| {
@@ -145,3 +401,291 @@ impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> Iterator for RxDedicatedBuffer<
         self.receive_any().ok()
     }
 }
+
+/// Dedicated receive buffer `N` on peripheral `P`, bound to a single ID by
+/// [`CanConfigurable::bind_dedicated_buffer`]
+///
+/// [`CanConfigurable::bind_dedicated_buffer`]: crate::bus::CanConfigurable::bind_dedicated_buffer
+pub struct BoundRxBuffer<'a, P, M, const N: usize> {
+    cell: &'a VolatileCell<M>,
+    _markers: PhantomData<P>,
+}
+
+// Safety: Only this handle accesses the element of buffer `N`.
+unsafe impl<'a, P: Send, M: Send, const N: usize> Send for BoundRxBuffer<'a, P, M, N> {}
+
+impl<'a, P: mcan_core::CanId, M: rx::AnyMessage, const N: usize> BoundRxBuffer<'a, P, M, N> {
+    /// Index of the buffer
+    pub const INDEX: usize = N;
+
+    /// Returns a received frame from the buffer if available
+    pub fn receive(&mut self) -> nb::Result<M, Infallible> {
+        if !has_new_data::<P>(N) {
+            return Err(nb::Error::WouldBlock);
+        }
+        let message = self.cell.get();
+        mark_buffer_read::<P>(N);
+        Ok(message)
+    }
+}
+
+/// NDAT1 and NDAT2 of peripheral `P`
+fn ndat<P: mcan_core::CanId>() -> (&'static reg::NDAT1, &'static reg::NDAT2) {
+    // Safety: The new data flags are only accessed bitwise by the owners of the
+    // corresponding buffers, and writing 0 bits has no effect.
+    let regs = unsafe { &*P::register_block() };
+    (&regs.ndat1, &regs.ndat2)
+}
+
+fn has_new_data<P: mcan_core::CanId>(index: usize) -> bool {
+    let (ndat1, ndat2) = ndat::<P>();
+    if index < 32 {
+        ndat1.read().bits() & (1 << index) != 0
+    } else if index < 64 {
+        ndat2.read().bits() & (1 << (index - 32)) != 0
+    } else {
+        false
+    }
+}
+
+fn mark_buffer_read<P: mcan_core::CanId>(index: usize) {
+    let (ndat1, ndat2) = ndat::<P>();
+    // Safety: Writing 1 clears the flag of the buffer, leaving the others
+    // unchanged.
+    if index < 32 {
+        unsafe {
+            ndat1.write(|w| w.bits(1 << index));
+        }
+    } else if index < 64 {
+        unsafe {
+            ndat2.write(|w| w.bits(1 << (index - 32)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::{rx::AnyMessage as _, tx, Raw as _};
+    use crate::mock::{self, mock_can};
+    use embedded_can::{Id, StandardId};
+
+    const NDAT1: usize = 0x98;
+    const NDAT2: usize = 0x9c;
+
+    fn id(message: &rx::Message<8>) -> Id {
+        message.id()
+    }
+
+    #[test]
+    fn bound_buffer_receives_only_its_index() {
+        mock_can!(Can0);
+        let regs = Can0::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut buffers = unsafe { RxDedicatedBuffer::<Can0, rx::Message<8>>::new(&mut memory) };
+        assert_eq!(buffers.check_bindable(4), Err(BindError::OutOfBounds));
+        assert!(matches!(buffers.bind::<4>(), Err(BindError::OutOfBounds)));
+        let mut bound = buffers.bind::<2>().unwrap();
+        assert_eq!(BoundRxBuffer::<Can0, rx::Message<8>, 2>::INDEX, 2);
+        assert_eq!(buffers.check_bindable(2), Err(BindError::AlreadyBound));
+        assert!(matches!(buffers.bind::<2>(), Err(BindError::AlreadyBound)));
+
+        regs.write(NDAT1, 0b0100);
+        let received = bound.receive().unwrap();
+        assert_eq!(id(&received), StandardId::new(2).unwrap().into());
+        assert_eq!(regs.read(NDAT1), 0b0100);
+
+        // The unbound receiver leaves the bound buffer alone
+        regs.write(NDAT1, 0b0110);
+        assert!(matches!(
+            buffers.receive(2),
+            Err(nb::Error::Other(OutOfBounds))
+        ));
+        let received = buffers.receive_any().unwrap();
+        assert_eq!(id(&received), StandardId::new(1).unwrap().into());
+        assert_eq!(regs.read(NDAT1), 0b0010);
+
+        regs.write(NDAT1, 0);
+        assert!(matches!(bound.receive(), Err(nb::Error::WouldBlock)));
+    }
+
+    #[test]
+    fn fd_frames_larger_than_the_elements_are_flagged_as_truncated() {
+        mock_can!(Can2);
+        let regs = Can2::regs();
+        let frame: rx::Message<64> = tx::MessageBuilder {
+            id: StandardId::new(0x7).unwrap().into(),
+            frame_type: tx::FrameType::FlexibleDatarate {
+                payload: &[0xaa; 12],
+                bit_rate_switching: false,
+                force_error_state_indicator: false,
+            },
+            store_tx_event: None,
+        }
+        .build::<64>()
+        .unwrap()
+        .into();
+        assert!(!frame.is_truncated());
+        // The peripheral stores as much of the frame as fits into the element
+        // Safety: Both are `repr(C)` with the header first, and the 8-byte
+        // message is a prefix of the 64-byte one.
+        let stored: rx::Message<8> = unsafe { core::ptr::read(&frame as *const _ as *const _) };
+        let mut memory = [
+            VolatileCell::new(mock::message(0)),
+            VolatileCell::new(stored),
+        ];
+        // Safety: The registers belong to this test only.
+        let mut buffers = unsafe { RxDedicatedBuffer::<Can2, rx::Message<8>>::new(&mut memory) };
+        assert_eq!(buffers.element_capacity(), 8);
+
+        regs.write(NDAT1, 0b01);
+        let received = buffers.receive(0).unwrap();
+        assert!(!received.is_truncated());
+        regs.write(NDAT1, 0b10);
+        let received = buffers.receive(1).unwrap();
+        assert!(received.is_truncated());
+        assert_eq!(received.decoded_dlc(), 12);
+        assert_eq!(received.data(), [0xaa; 8]);
+    }
+
+    #[test]
+    fn buffers_above_32_clear_their_ndat2_bit() {
+        mock_can!(Can1);
+        let regs = Can1::regs();
+        let mut memory: [_; 40] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut buffers = unsafe { RxDedicatedBuffer::<Can1, rx::Message<8>>::new(&mut memory) };
+        regs.write(NDAT2, 1 << 3);
+        let received = buffers.receive(35).unwrap();
+        assert_eq!(id(&received), StandardId::new(35).unwrap().into());
+        assert_eq!(regs.read(NDAT2), 1 << 3);
+    }
+
+    #[test]
+    fn buffers_are_read_in_place_before_the_flag_is_cleared() {
+        mock_can!(Can4);
+        let regs = Can4::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut buffers = unsafe { RxDedicatedBuffer::<Can4, rx::Message<8>>::new(&mut memory) };
+        assert!(matches!(
+            buffers.receive_with(4, |_| unreachable!()),
+            Err(nb::Error::Other(OutOfBounds))
+        ));
+        regs.write(NDAT1, 0);
+        assert!(matches!(
+            buffers.receive_with(1, |_| unreachable!()),
+            Err(nb::Error::WouldBlock)
+        ));
+
+        regs.write(NDAT1, 1 << 3 | 1 << 1);
+        let received = buffers.receive_with(1, |message| {
+            assert_eq!(regs.read(NDAT1), 1 << 3 | 1 << 1);
+            id(message)
+        });
+        assert_eq!(received.unwrap(), StandardId::new(1).unwrap().into());
+        assert_eq!(regs.read(NDAT1), 1 << 1);
+    }
+
+    #[test]
+    fn new_data_is_peeked_and_received_by_lowest_index() {
+        mock_can!(Can3);
+        let regs = Can3::regs();
+        let mut memory: [_; 40] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut buffers = unsafe { RxDedicatedBuffer::<Can3, rx::Message<8>>::new(&mut memory) };
+        let _bound = buffers.bind::<1>().unwrap();
+
+        // Buffer 45 is beyond the 40 buffers
+        regs.write(NDAT1, 1 << 5 | 1 << 1);
+        regs.write(NDAT2, 1 << 13 | 1 << 3);
+        assert_eq!(buffers.new_data_flags(), 1 << 35 | 1 << 5);
+        let peeked = buffers.peek(5).unwrap();
+        assert_eq!(id(&peeked), StandardId::new(5).unwrap().into());
+        assert_eq!(regs.read(NDAT1), 1 << 5 | 1 << 1);
+        for index in [1, 45, 64] {
+            assert!(matches!(
+                buffers.peek(index),
+                Err(nb::Error::Other(OutOfBounds))
+            ));
+        }
+        assert!(matches!(
+            buffers.receive(45),
+            Err(nb::Error::Other(OutOfBounds))
+        ));
+        assert!(matches!(buffers.peek(6), Err(nb::Error::WouldBlock)));
+
+        let received = buffers.receive_lowest_index().unwrap();
+        assert_eq!(id(&received), StandardId::new(5).unwrap().into());
+        // Written 1 clears the flag of buffer 5
+        assert_eq!(regs.read(NDAT1), 1 << 5);
+
+        regs.write(NDAT1, 0);
+        let received = buffers.receive_lowest_index().unwrap();
+        assert_eq!(id(&received), StandardId::new(35).unwrap().into());
+        regs.write(NDAT2, 1 << 13);
+        assert!(matches!(
+            buffers.receive_lowest_index(),
+            Err(nb::Error::WouldBlock)
+        ));
+    }
+
+    /// Buffers whose frames are their indices, with new data flags that are
+    /// cleared when received
+    struct Flags(u64);
+
+    impl crate::sealed::Sealed for Flags {}
+
+    impl DynRxDedicatedBuffer for Flags {
+        type Id = ();
+        type Message = usize;
+
+        fn receive(&mut self, index: usize) -> nb::Result<usize, OutOfBounds> {
+            let message = self.peek(index)?;
+            self.0 &= !(1 << index);
+            Ok(message)
+        }
+
+        fn receive_any(&mut self) -> nb::Result<usize, Infallible> {
+            unreachable!("not used by the iterator")
+        }
+
+        fn receive_lowest_index(&mut self) -> nb::Result<usize, Infallible> {
+            unreachable!("not used by the iterator")
+        }
+
+        fn peek(&self, index: usize) -> nb::Result<usize, OutOfBounds> {
+            match self.0.checked_shr(index as u32) {
+                Some(flags) if flags & 1 != 0 => Ok(index),
+                _ => Err(nb::Error::WouldBlock),
+            }
+        }
+
+        fn new_data_flags(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn new_data_is_iterated_in_index_order() {
+        extern crate std;
+        use std::vec::Vec;
+
+        let mut buffers = Flags(1 << 63 | 1 << 32 | 1 << 7 | 1 << 2);
+        assert_eq!(buffers.iter_new_data().next(), Some((2, 2)));
+        // Frames not yielded stay unread
+        assert_eq!(buffers.new_data_flags(), 1 << 63 | 1 << 32 | 1 << 7);
+
+        // Frames received meanwhile are skipped
+        let new_data = buffers.iter_new_data();
+        new_data.buffers.0 &= !(1 << 32);
+        assert_eq!(new_data.collect::<Vec<_>>(), [(7, 7), (63, 63)]);
+        assert_eq!(buffers.new_data_flags(), 0);
+        assert_eq!(buffers.iter_new_data().next(), None);
+    }
+}
@@ -0,0 +1,562 @@
+//! Fan-out of received messages to independent consumers
+//!
+//! When the filters cannot route frames of several software modules to
+//! separate queues (e.g. because their identifier ranges overlap), all of
+//! them have to be served from the same [`RxFifo`]. Sharing the FIFO behind a
+//! lock couples the latency of the consumers. [`RxDemux`] instead lets a
+//! single owner of the FIFO (typically the interrupt handler) drain the
+//! hardware queue into one software ring per consumer. A routing closure
+//! selects the rings a frame is copied into, so a frame can be delivered to
+//! several consumers at once.
+//!
+//! Every ring has exactly one producer ([`DemuxProducer`]) and one consumer
+//! ([`DemuxConsumer`]), which synchronize through atomic loads and stores only.
+//! No read-modify-write operations are used, so the demultiplexer works on
+//! cores without compare-and-swap support as well.
+//!
+//! With [`OverflowPolicy::DropOldest`], a ring works like a seqlock: the
+//! producer may overwrite the slot that the consumer is copying, in which case
+//! the consumer notices afterwards and discards the torn copy. Copying a slot
+//! while it is written is a data race under the Rust memory model nonetheless,
+//! as it is for any seqlock written in Rust today. The copy is done with
+//! volatile reads, so that the compiler cannot rely on the slot being
+//! unchanged, which works in practice but is not guaranteed by the language.
+//! Rings with [`OverflowPolicy::Reject`] never overwrite a slot before it was
+//! read and are free of this race.
+//!
+//! ```no_run
+//! # use mcan::message::rx;
+//! # use mcan::rx_demux::{ConsumerMask, OverflowPolicy, RxDemux};
+//! # use mcan::prelude::*;
+//! # use mcan::rx_fifo::DynRxFifo;
+//! # use mcan::embedded_can::Id;
+//! # fn example(fifo: &mut impl DynRxFifo<Message = rx::Message<8>>) {
+//! const UDS: usize = 0;
+//! const TELEMETRY: usize = 1;
+//!
+//! let mut demux: RxDemux<rx::Message<8>, 2, 16> =
+//!     RxDemux::new([OverflowPolicy::Reject, OverflowPolicy::DropOldest]);
+//! let (mut producer, [mut uds, mut telemetry]) = demux.split(|m: &rx::Message<8>| {
+//!     match m.id() {
+//!         Id::Standard(id) if id.as_raw() == 0x7e0 => ConsumerMask::single(UDS),
+//!         Id::Standard(_) => ConsumerMask::single(TELEMETRY),
+//!         Id::Extended(_) => ConsumerMask::single(UDS).with(TELEMETRY),
+//!     }
+//! });
+//!
+//! // In the interrupt handler
+//! producer.drain(fifo);
+//!
+//! // In the respective tasks
+//! while let Some(message) = uds.pop() {}
+//! while let Some(message) = telemetry.pop() {}
+//! # }
+//! ```
+//!
+//! [`RxFifo`]: crate::rx_fifo::RxFifo
+
+use crate::rx_fifo::DynRxFifo;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{fence, AtomicU32, AtomicUsize, Ordering};
+
+/// Set of consumers of a [`RxDemux`] a message is routed to
+///
+/// Bit `n` selects the consumer with index `n`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsumerMask(u32);
+
+impl ConsumerMask {
+    /// Routes the message to no consumer, dropping it
+    pub const NONE: Self = Self(0);
+
+    /// Creates a mask from its raw representation
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Selects only the consumer `index`
+    pub const fn single(index: usize) -> Self {
+        Self::NONE.with(index)
+    }
+
+    /// Selects the consumer `index` in addition to the ones already selected
+    pub const fn with(self, index: usize) -> Self {
+        Self(self.0 | 1 << index)
+    }
+
+    /// Selects all of the first `count` consumers
+    pub const fn first(count: usize) -> Self {
+        if count >= 32 {
+            Self(u32::MAX)
+        } else {
+            Self((1 << count) - 1)
+        }
+    }
+
+    /// Returns `true` if the consumer `index` is selected
+    pub const fn contains(self, index: usize) -> bool {
+        index < 32 && self.0 & 1 << index != 0
+    }
+
+    /// Returns `true` if no consumer is selected
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Raw representation of the mask
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+/// Behavior of a consumer ring that is full when a new message is routed
+/// to it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Keep the queued messages and discard the new one. Counted by
+    /// [`DemuxConsumer::rejected`].
+    Reject,
+    /// Overwrite the oldest queued message with the new one. Counted by
+    /// [`DemuxConsumer::dropped`].
+    DropOldest,
+}
+
+/// Single-producer single-consumer ring of a consumer
+struct Ring<M, const DEPTH: usize> {
+    slots: [UnsafeCell<MaybeUninit<M>>; DEPTH],
+    /// Number of writes the producer has started
+    started: AtomicUsize,
+    /// Number of writes the producer has completed
+    written: AtomicUsize,
+    /// Number of messages the consumer has read or skipped
+    read: AtomicUsize,
+    /// Written by the producer only
+    rejected: AtomicU32,
+    /// Written by the consumer only
+    dropped: AtomicU32,
+}
+
+// Safety: The slots are only accessed by the unique producer and consumer
+// handles following the protocol implemented in `push` and `pop`.
+unsafe impl<M: Send, const DEPTH: usize> Sync for Ring<M, DEPTH> {}
+
+impl<M: Copy, const DEPTH: usize> Ring<M, DEPTH> {
+    // Only used as initializers of fresh rings.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const SLOT: UnsafeCell<MaybeUninit<M>> = UnsafeCell::new(MaybeUninit::uninit());
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NEW: Self = Self {
+        slots: [Self::SLOT; DEPTH],
+        started: AtomicUsize::new(0),
+        written: AtomicUsize::new(0),
+        read: AtomicUsize::new(0),
+        rejected: AtomicU32::new(0),
+        dropped: AtomicU32::new(0),
+    };
+
+    /// Must only be called by the producer.
+    fn push(&self, message: M, policy: OverflowPolicy) -> bool {
+        let index = self.written.load(Ordering::Relaxed);
+        if policy == OverflowPolicy::Reject
+            && index.wrapping_sub(self.read.load(Ordering::Acquire)) >= DEPTH
+        {
+            let rejected = self.rejected.load(Ordering::Relaxed);
+            self.rejected
+                .store(rejected.wrapping_add(1), Ordering::Relaxed);
+            return false;
+        }
+        let Some(slot) = self.slot(index) else {
+            return false;
+        };
+        let next = index.wrapping_add(1);
+        self.started.store(next, Ordering::Relaxed);
+        fence(Ordering::Release);
+        // Safety: Only the producer writes to the slots. A consumer that is
+        // concurrently copying this slot detects the overwrite through
+        // `started` and discards the copy.
+        unsafe {
+            core::ptr::write_volatile(slot.get(), MaybeUninit::new(message));
+        }
+        self.written.store(next, Ordering::Release);
+        true
+    }
+
+    /// Slot of the message with sequence number `index`, which always exists
+    /// as `DEPTH` is not zero
+    fn slot(&self, index: usize) -> Option<&UnsafeCell<MaybeUninit<M>>> {
+        self.slots.get(index.checked_rem(DEPTH)?)
+    }
+
+    /// Must only be called by the consumer.
+    fn pop(&self) -> Option<M> {
+        let mut index = self.read.load(Ordering::Relaxed);
+        loop {
+            let written = self.written.load(Ordering::Acquire);
+            let len = written.wrapping_sub(index);
+            if len == 0 {
+                return None;
+            }
+            if len > DEPTH {
+                // The producer has overwritten the oldest messages.
+                self.skip((len - DEPTH) as u32);
+                index = written.wrapping_sub(DEPTH);
+            }
+            let slot = self.slot(index)?;
+            // Safety: The slot has been written to since `written` passed
+            // `index`. The copy is only used if the producer did not start
+            // overwriting it in the meantime. If it did, the copy races with
+            // the write, see the module documentation.
+            let message = unsafe { core::ptr::read_volatile(slot.get()) };
+            fence(Ordering::Acquire);
+            let started = self.started.load(Ordering::Relaxed);
+            if started.wrapping_sub(index) <= DEPTH {
+                self.read.store(index.wrapping_add(1), Ordering::Release);
+                // Safety: The slot was completely written before `written` was
+                // published and not touched while it was copied.
+                return Some(unsafe { message.assume_init() });
+            }
+            // The slot was overwritten while being copied; count it as dropped
+            // and retry with the next one.
+            self.skip(1);
+            index = index.wrapping_add(1);
+            self.read.store(index, Ordering::Release);
+        }
+    }
+
+    /// Must only be called by the consumer.
+    fn skip(&self, count: u32) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        self.dropped
+            .store(dropped.wrapping_add(count), Ordering::Relaxed);
+    }
+
+    fn len(&self) -> usize {
+        let written = self.written.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        written.wrapping_sub(read).min(DEPTH)
+    }
+}
+
+/// Demultiplexer distributing received messages of type `M` to `CONSUMERS`
+/// rings of `DEPTH` messages each
+///
+/// `CONSUMERS` must not exceed 32 and `DEPTH` must not be zero.
+pub struct RxDemux<M, const CONSUMERS: usize, const DEPTH: usize> {
+    rings: [Ring<M, DEPTH>; CONSUMERS],
+    policies: [OverflowPolicy; CONSUMERS],
+}
+
+impl<M: Copy, const CONSUMERS: usize, const DEPTH: usize> RxDemux<M, CONSUMERS, DEPTH> {
+    const VALID: () = assert!(
+        CONSUMERS <= 32 && DEPTH > 0,
+        "RxDemux supports up to 32 consumers with non-empty rings"
+    );
+
+    /// Creates a demultiplexer with the given overflow policy per consumer
+    pub const fn new(policies: [OverflowPolicy; CONSUMERS]) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        Self {
+            rings: [Ring::NEW; CONSUMERS],
+            policies,
+        }
+    }
+
+    /// Splits the demultiplexer into the producer, which routes messages
+    /// using `router`, and one handle per consumer
+    ///
+    /// Messages that have not been consumed are retained across splits.
+    pub fn split<R: Fn(&M) -> ConsumerMask>(
+        &mut self,
+        router: R,
+    ) -> (
+        DemuxProducer<'_, M, R, CONSUMERS, DEPTH>,
+        [DemuxConsumer<'_, M, DEPTH>; CONSUMERS],
+    ) {
+        let this = &*self;
+        let consumers = this.rings.each_ref().map(|ring| DemuxConsumer { ring });
+        (
+            DemuxProducer {
+                rings: &this.rings,
+                policies: &this.policies,
+                router,
+            },
+            consumers,
+        )
+    }
+}
+
+/// Routes messages into the consumer rings of a [`RxDemux`]
+pub struct DemuxProducer<'a, M, R, const CONSUMERS: usize, const DEPTH: usize> {
+    rings: &'a [Ring<M, DEPTH>; CONSUMERS],
+    policies: &'a [OverflowPolicy; CONSUMERS],
+    router: R,
+}
+
+impl<'a, M: Copy, R: Fn(&M) -> ConsumerMask, const CONSUMERS: usize, const DEPTH: usize>
+    DemuxProducer<'a, M, R, CONSUMERS, DEPTH>
+{
+    /// Queues `message` for the consumers selected by the router
+    ///
+    /// Returns the consumers that received the message. Consumers with the
+    /// [`OverflowPolicy::Reject`] policy and a full ring are missing from the
+    /// returned mask.
+    pub fn push(&mut self, message: M) -> ConsumerMask {
+        let selected = (self.router)(&message);
+        let mut delivered = ConsumerMask::NONE;
+        for (index, (ring, &policy)) in self.rings.iter().zip(self.policies).enumerate() {
+            if selected.contains(index) && ring.push(message, policy) {
+                delivered = delivered.with(index);
+            }
+        }
+        delivered
+    }
+
+    /// Moves all messages from `fifo` into the consumer rings
+    ///
+    /// Returns the number of messages taken from `fifo`.
+    pub fn drain<F: DynRxFifo<Message = M>>(&mut self, fifo: &mut F) -> usize {
+        let mut count = 0;
+        while let Ok(message) = fifo.receive() {
+            self.push(message);
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Receives the messages routed to a single consumer of a [`RxDemux`]
+pub struct DemuxConsumer<'a, M, const DEPTH: usize> {
+    ring: &'a Ring<M, DEPTH>,
+}
+
+impl<'a, M: Copy, const DEPTH: usize> DemuxConsumer<'a, M, DEPTH> {
+    /// Returns the oldest queued message, if any
+    pub fn pop(&mut self) -> Option<M> {
+        self.ring.pop()
+    }
+
+    /// Returns the number of queued messages
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns `true` if no message is queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of messages the ring can hold
+    pub fn capacity(&self) -> usize {
+        DEPTH
+    }
+
+    /// Number of messages discarded because the ring was full, under
+    /// [`OverflowPolicy::Reject`]. Wraps on overflow.
+    pub fn rejected(&self) -> u32 {
+        self.ring.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Number of queued messages overwritten before being read, under
+    /// [`OverflowPolicy::DropOldest`]. Wraps on overflow.
+    ///
+    /// Overwritten messages are accounted for when the consumer catches up
+    /// in [`pop`](Self::pop).
+    pub fn dropped(&self) -> u32 {
+        self.ring.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<'a, M: Copy, const DEPTH: usize> Iterator for DemuxConsumer<'a, M, DEPTH> {
+    type Item = M;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::message::{rx, Raw};
+    use crate::mock;
+    use crate::rx_fifo::OffsetOutOfRange;
+    use core::convert::Infallible;
+    use embedded_can::Id;
+
+    fn raw_id(message: rx::Message<8>) -> u16 {
+        match message.id() {
+            Id::Standard(id) => id.as_raw(),
+            Id::Extended(_) => unreachable!(),
+        }
+    }
+
+    fn by_id(message: &rx::Message<8>) -> ConsumerMask {
+        match raw_id(*message) {
+            0x100..=0x1ff => ConsumerMask::single(0),
+            0x200..=0x2ff => ConsumerMask::single(1),
+            0x300..=0x3ff => ConsumerMask::single(0).with(1),
+            _ => ConsumerMask::NONE,
+        }
+    }
+
+    struct Fifo(std::vec::Vec<rx::Message<8>>);
+
+    impl crate::sealed::Sealed for Fifo {}
+
+    impl DynRxFifo for Fifo {
+        type RxFifoId = ();
+        type CanId = ();
+        type Message = rx::Message<8>;
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn capacity(&self) -> usize {
+            usize::MAX
+        }
+
+        fn is_full(&self) -> bool {
+            false
+        }
+
+        fn watermark(&self) -> usize {
+            0
+        }
+
+        fn watermark_reached(&self) -> bool {
+            false
+        }
+
+        fn message_lost(&self) -> bool {
+            false
+        }
+
+        fn receive(&mut self) -> nb::Result<Self::Message, Infallible> {
+            if self.0.is_empty() {
+                Err(nb::Error::WouldBlock)
+            } else {
+                Ok(self.0.remove(0))
+            }
+        }
+
+        fn peek(&self) -> Option<Self::Message> {
+            unreachable!("not used by the demultiplexer")
+        }
+
+        fn peek_at(&self, _: usize) -> Result<Self::Message, OffsetOutOfRange> {
+            unreachable!("not used by the demultiplexer")
+        }
+
+        fn receive_with_offset(&mut self, _: usize) -> Result<Self::Message, OffsetOutOfRange> {
+            unreachable!("not used by the demultiplexer")
+        }
+
+        fn read_count(&self) -> u32 {
+            unreachable!("not used by the demultiplexer")
+        }
+    }
+
+    #[test]
+    fn routes_and_duplicates() {
+        let mut demux: RxDemux<_, 2, 4> = RxDemux::new([OverflowPolicy::Reject; 2]);
+        let (mut producer, [mut a, mut b]) = demux.split(by_id);
+        let mut fifo = Fifo([0x100, 0x200, 0x300, 0x400].map(mock::message).to_vec());
+        assert_eq!(producer.drain(&mut fifo), 4);
+        assert!(fifo.is_empty());
+        assert_eq!(a.len(), 2);
+        assert_eq!(b.len(), 2);
+        assert_eq!(
+            a.by_ref().map(raw_id).collect::<std::vec::Vec<_>>(),
+            [0x100, 0x300]
+        );
+        assert_eq!(
+            b.by_ref().map(raw_id).collect::<std::vec::Vec<_>>(),
+            [0x200, 0x300]
+        );
+        assert!(a.is_empty() && b.is_empty());
+    }
+
+    #[test]
+    fn reject_keeps_oldest() {
+        let mut demux: RxDemux<_, 1, 2> = RxDemux::new([OverflowPolicy::Reject]);
+        let (mut producer, [mut consumer]) = demux.split(|_: &_| ConsumerMask::single(0));
+        assert_eq!(producer.push(mock::message(1)), ConsumerMask::single(0));
+        assert_eq!(producer.push(mock::message(2)), ConsumerMask::single(0));
+        assert_eq!(producer.push(mock::message(3)), ConsumerMask::NONE);
+        assert_eq!(consumer.rejected(), 1);
+        assert_eq!(consumer.pop().map(raw_id), Some(1));
+        assert_eq!(producer.push(mock::message(4)), ConsumerMask::single(0));
+        assert_eq!(
+            consumer.by_ref().map(raw_id).collect::<std::vec::Vec<_>>(),
+            [2, 4]
+        );
+        assert_eq!(consumer.dropped(), 0);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_newest() {
+        let mut demux: RxDemux<_, 1, 2> = RxDemux::new([OverflowPolicy::DropOldest]);
+        let (mut producer, [mut consumer]) = demux.split(|_: &_| ConsumerMask::single(0));
+        for id in 1..=5 {
+            assert_eq!(producer.push(mock::message(id)), ConsumerMask::single(0));
+        }
+        assert_eq!(consumer.len(), 2);
+        assert_eq!(
+            consumer.by_ref().map(raw_id).collect::<std::vec::Vec<_>>(),
+            [4, 5]
+        );
+        assert_eq!(consumer.dropped(), 3);
+        assert_eq!(consumer.rejected(), 0);
+    }
+
+    #[test]
+    fn messages_survive_resplit() {
+        let mut demux: RxDemux<_, 2, 2> = RxDemux::new([OverflowPolicy::Reject; 2]);
+        let (mut producer, _) = demux.split(|_: &_| ConsumerMask::first(2));
+        producer.push(mock::message(7));
+        let (_, [mut a, mut b]) = demux.split(|_: &_| ConsumerMask::NONE);
+        assert_eq!(a.pop().map(raw_id), Some(7));
+        assert_eq!(b.pop().map(raw_id), Some(7));
+    }
+
+    #[test]
+    fn concurrent_consumers_see_all_messages_in_order() {
+        const COUNT: u16 = 2000;
+        let mut demux: RxDemux<_, 2, 8> = RxDemux::new([OverflowPolicy::Reject; 2]);
+        let (mut producer, [a, b]) = demux.split(|_: &_| ConsumerMask::first(2));
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for id in 0..COUNT {
+                    // Wait for space instead of losing messages.
+                    while producer.rings.iter().any(|ring| ring.len() == 8) {
+                        std::thread::yield_now();
+                    }
+                    assert_eq!(producer.push(mock::message(id)), ConsumerMask::first(2));
+                }
+            });
+            for mut consumer in [a, b] {
+                scope.spawn(move || {
+                    let mut expected = 0;
+                    while expected < COUNT {
+                        if let Some(message) = consumer.pop() {
+                            assert_eq!(raw_id(message), expected);
+                            expected += 1;
+                        } else {
+                            std::thread::yield_now();
+                        }
+                    }
+                    assert_eq!(consumer.dropped(), 0);
+                });
+            }
+        });
+    }
+}
@@ -2,23 +2,54 @@
 //!
 //! Messages can be placed in the queues by filter [`Action`]s.
 //!
+//! # External drain
+//!
+//! Instead of [`DynRxFifo::receive`], which copies one message at a time, the
+//! FIFO contents can be copied out by other means such as DMA, or read in
+//! place with [`RxFifo::receive_with`].
+//! [`RxFifo::drain_descriptor`] describes the occupied elements in Message RAM
+//! without acknowledging them, and [`RxFifo::complete_drain`] acknowledges
+//! them once they have been copied. Until then, the peripheral does not
+//! overwrite them.
+//!
+//! The copy must have finished before [`RxFifo::complete_drain`] is called,
+//! i.e. the CPU must have observed the completion of the transfer (e.g. by
+//! reading the transfer complete flag of the DMA) before the acknowledge is
+//! written, otherwise the peripheral may overwrite elements that are still
+//! being read. The acknowledge is preceded by a memory fence so that it is
+//! not reordered before that observation. Cache maintenance for the
+//! destination of the copy is up to the user.
+//!
+//! Unlike [`DynRxFifo::receive`], the drain does not respect the read offset
+//! of a FIFO in overwrite mode, so it is meant for FIFOs in blocking mode.
+//!
 //! [`Action`]: crate::filter::Action
 
+use crate::bus::{Aux, ConfigurationError};
+use crate::config::RxFifoMode;
+use crate::interrupt::{Interrupt, InterruptSet};
 use crate::message::rx;
+use crate::messageram::Capacity;
 use crate::reg;
+use crate::ring::RingIndices;
 use core::convert::Infallible;
 use core::marker::PhantomData;
+use core::sync::atomic::{fence, Ordering};
 use reg::AccessRegisterBlock as _;
 use vcell::VolatileCell;
 
 /// Receive FIFO `F` on peripheral `P`.
 pub struct RxFifo<'a, F, P, M: rx::AnyMessage> {
     memory: &'a mut [VolatileCell<M>],
+    /// Messages read out, reported by [`DynRxFifo::read_count`]
+    read: u32,
+    /// Read offset in overwrite mode, `None` in blocking mode
+    pub(crate) read_offset: Option<u8>,
     _markers: PhantomData<(F, P)>,
 }
 
 /// Trait which erases generic parametrization for [`RxFifo`] type
-pub trait DynRxFifo {
+pub trait DynRxFifo: crate::sealed::Sealed {
     /// RX FIFO identity type
     type RxFifoId;
 
@@ -37,9 +68,87 @@ pub trait DynRxFifo {
     /// Returns the number of elements the queue can hold
     fn capacity(&self) -> usize;
 
+    /// Returns `true` if the queue is full, the polled counterpart of
+    /// [`Interrupt::RxFifo0Full`] and [`Interrupt::RxFifo1Full`]
+    ///
+    /// [`Interrupt::RxFifo0Full`]: crate::interrupt::Interrupt::RxFifo0Full
+    /// [`Interrupt::RxFifo1Full`]: crate::interrupt::Interrupt::RxFifo1Full
+    fn is_full(&self) -> bool;
+
+    /// Returns the watermark programmed for the queue, 0 if it is disabled
+    ///
+    /// This is the configured [`RxFifoConfig::watermark`] after clamping to
+    /// the capacity, see [`CanConfig::strict_validation`].
+    ///
+    /// [`RxFifoConfig::watermark`]: crate::config::RxFifoConfig::watermark
+    /// [`CanConfig::strict_validation`]: crate::config::CanConfig::strict_validation
+    fn watermark(&self) -> usize;
+
+    /// Returns `true` if the fill level has reached the configured watermark,
+    /// the polled counterpart of [`Interrupt::RxFifo0WatermarkReached`] and
+    /// [`Interrupt::RxFifo1WatermarkReached`]. Always `false` if the
+    /// watermark is disabled.
+    ///
+    /// [`Interrupt::RxFifo0WatermarkReached`]: crate::interrupt::Interrupt::RxFifo0WatermarkReached
+    /// [`Interrupt::RxFifo1WatermarkReached`]: crate::interrupt::Interrupt::RxFifo1WatermarkReached
+    fn watermark_reached(&self) -> bool;
+
+    /// Returns `true` if a message was lost because the queue was full, the
+    /// polled counterpart of [`Interrupt::RxFifo0MessageLost`] and
+    /// [`Interrupt::RxFifo1MessageLost`].
+    ///
+    /// This mirrors the interrupt flag and stays set until the flag is
+    /// cleared, e.g. with [`OwnedInterruptSet::clear_interrupts`]. The flag
+    /// is set regardless of whether the interrupt is enabled.
+    ///
+    /// [`Interrupt::RxFifo0MessageLost`]: crate::interrupt::Interrupt::RxFifo0MessageLost
+    /// [`Interrupt::RxFifo1MessageLost`]: crate::interrupt::Interrupt::RxFifo1MessageLost
+    /// [`OwnedInterruptSet::clear_interrupts`]: crate::interrupt::OwnedInterruptSet::clear_interrupts
+    fn message_lost(&self) -> bool;
+
     /// Returns a received frame if available. Note that the FIFO also
     /// implements [`Iterator`] to receive messages until the queue is empty.
+    ///
+    /// In overwrite mode, the [read offset] is the number of messages that
+    /// may arrive while a message is read. If that many could make the
+    /// peripheral overwrite the oldest messages, these are skipped and
+    /// discarded along with the message read.
+    ///
+    /// [read offset]: crate::config::RxFifoConfig::overwrite_read_offset
     fn receive(&mut self) -> nb::Result<Self::Message, Infallible>;
+
+    /// Returns a copy of the oldest message without acknowledging it, or
+    /// `None` if the queue is empty
+    ///
+    /// In overwrite mode, the peripheral may overwrite the message while it
+    /// is copied if the queue is full; see [`Self::receive_with_offset`].
+    fn peek(&self) -> Option<Self::Message>;
+
+    /// Returns a copy of the message `offset` elements after the oldest one
+    /// without acknowledging it
+    ///
+    /// The position wraps around the end of the Message RAM of the queue like
+    /// the get index does. Offsets beyond the messages in the queue fail.
+    fn peek_at(&self, offset: usize) -> Result<Self::Message, OffsetOutOfRange>;
+
+    /// Returns the message `offset` elements after the oldest one and
+    /// acknowledges it, discarding the `offset` messages before it
+    ///
+    /// This is the read with an explicit offset that the M_CAN user manual
+    /// recommends in overwrite mode, where the oldest messages may be
+    /// overwritten while they are read. [`Self::receive`] does the same with
+    /// an offset derived from the configured read offset and the fill level.
+    /// Offsets beyond the messages in the queue fail without acknowledging
+    /// anything.
+    fn receive_with_offset(&mut self, offset: usize) -> Result<Self::Message, OffsetOutOfRange>;
+
+    /// Number of messages read out of the FIFO through this handle, by
+    /// [`Self::receive`] or [`RxFifo::complete_drain`], wrapping around at
+    /// [`u32::MAX`]. Messages skipped in overwrite mode are counted as read.
+    ///
+    /// Along with [`Self::len`], this tells how many messages were stored in
+    /// the FIFO between two calls, even if they were read out in between.
+    fn read_count(&self) -> u32;
 }
 
 /// Value of the type-level FIFO selection enum representing FIFO 0.
@@ -54,6 +163,81 @@ pub trait GetRxFifoRegs {
     unsafe fn registers(&self) -> &reg::RxFifoRegs;
 }
 
+impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> RxFifo<'a, Fifo0, P, M> {
+    /// Interrupts raised by this FIFO
+    pub const INTERRUPTS: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::RxFifo0NewMessage,
+        Interrupt::RxFifo0WatermarkReached,
+        Interrupt::RxFifo0Full,
+        Interrupt::RxFifo0MessageLost,
+    ]);
+
+    /// Switches the FIFO between blocking and overwrite mode during
+    /// operation, e.g. to accept losing the oldest messages only while
+    /// nothing is being read out
+    ///
+    /// The mode can only be changed in configuration mode, so the peripheral
+    /// briefly enters it. Frames on the bus at this point are lost. Entering
+    /// configuration mode also resets the transmission occurred flags of the
+    /// TX buffers, the TX queue, the fill levels of the RX FIFOs and the TX
+    /// event FIFO, discarding the messages and events in them, and cancels
+    /// transmissions requested from another context, e.g. an interrupt
+    /// handler, after checking for them.
+    ///
+    /// The configuration of `aux` and the read algorithm of this handle are
+    /// updated along with the peripheral. `overwrite_read_offset` is required
+    /// in overwrite mode, as in
+    /// [`RxFifoConfig::overwrite_read_offset`](crate::config::RxFifoConfig::overwrite_read_offset).
+    ///
+    /// Fails without changing anything with
+    /// [`ConfigurationError::InvalidOverwriteReadOffset`], with
+    /// [`ConfigurationError::NotOperational`] if the peripheral is not in
+    /// normal operation, or with [`ConfigurationError::TransmissionsPending`]
+    /// if transmissions are requested or pending, since they would be
+    /// dropped.
+    pub fn set_mode<D: mcan_core::Dependencies<P>>(
+        &mut self,
+        aux: &mut Aux<'_, P, D>,
+        mode: RxFifoMode,
+        overwrite_read_offset: Option<u8>,
+    ) -> Result<(), ConfigurationError> {
+        self.read_offset = aux.set_rx_fifo_mode(
+            Capacity::RxFifo0,
+            self.memory.len(),
+            mode,
+            overwrite_read_offset,
+        )?;
+        Ok(())
+    }
+}
+
+impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> RxFifo<'a, Fifo1, P, M> {
+    /// Interrupts raised by this FIFO
+    pub const INTERRUPTS: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::RxFifo1NewMessage,
+        Interrupt::RxFifo1WatermarkReached,
+        Interrupt::RxFifo1Full,
+        Interrupt::RxFifo1MessageLost,
+    ]);
+
+    /// Switches the FIFO between blocking and overwrite mode during
+    /// operation, see [`RxFifo::set_mode`] of FIFO 0
+    pub fn set_mode<D: mcan_core::Dependencies<P>>(
+        &mut self,
+        aux: &mut Aux<'_, P, D>,
+        mode: RxFifoMode,
+        overwrite_read_offset: Option<u8>,
+    ) -> Result<(), ConfigurationError> {
+        self.read_offset = aux.set_rx_fifo_mode(
+            Capacity::RxFifo1,
+            self.memory.len(),
+            mode,
+            overwrite_read_offset,
+        )?;
+        Ok(())
+    }
+}
+
 impl<'a, P: mcan_core::CanId, M: rx::AnyMessage> GetRxFifoRegs for RxFifo<'a, Fifo0, P, M> {
     unsafe fn registers(&self) -> &reg::RxFifoRegs {
         &(*P::register_block()).rxf0
@@ -77,9 +261,14 @@ where
     /// - RXFC
     /// - RXFS
     /// - RXFA
-    pub(crate) unsafe fn new(memory: &'a mut [VolatileCell<M>]) -> Self {
+    ///
+    /// `read_offset` is the offset configured for overwrite mode, or `None`
+    /// in blocking mode.
+    pub(crate) unsafe fn new(memory: &'a mut [VolatileCell<M>], read_offset: Option<u8>) -> Self {
         Self {
             memory,
+            read: 0,
+            read_offset,
             _markers: PhantomData,
         }
     }
@@ -88,8 +277,225 @@ where
         // Safety: The RxFifo owns the registers.
         unsafe { self.registers() }
     }
+
+    fn ring(&self) -> RingIndices {
+        let status = self.regs().s.read();
+        RingIndices::from_fill_level(
+            status.fgi().bits() as usize,
+            status.ffl().bits() as usize,
+            self.memory.len(),
+        )
+    }
+
+    /// Reads the message at `position` of the occupied elements of `ring` and
+    /// acknowledges it, along with the ones before it
+    fn read_at(&mut self, ring: RingIndices, position: usize) -> Option<M> {
+        let index = ring.nth_occupied(position)?;
+        let message = self.memory.get(index)?.get();
+        self.acknowledge(index, position);
+        Some(message)
+    }
+
+    /// Acknowledges the element at `index`, which is at `position` of the
+    /// occupied elements, along with the ones before it
+    fn acknowledge(&mut self, index: usize, position: usize) {
+        // Safety: The written index must be valid since it was retrieved from the
+        // peripheral, and the configuration was not changed.
+        unsafe {
+            self.regs().a.write(|w| w.fai().bits(index as u8));
+        }
+        self.read = self.read.wrapping_add(position as u32 + 1);
+    }
+
+    /// Position of the occupied elements of `ring` that [`DynRxFifo::receive`]
+    /// reads
+    fn receive_position(&self, ring: RingIndices) -> usize {
+        // In overwrite mode, the peripheral overwrites the oldest messages
+        // once the FIFO is full. Reading at least as many messages after
+        // them as may arrive meanwhile stays clear of these.
+        self.read_offset.map_or(0, |offset| {
+            (ring.occupied() + usize::from(offset)).saturating_sub(self.memory.len())
+        })
+    }
+
+    /// Passes the message that [`DynRxFifo::receive`] would return to `f`
+    /// without copying it out of the Message RAM, and acknowledges it once
+    /// `f` returns
+    ///
+    /// This saves the copy of [`DynRxFifo::receive`], e.g. when decoding 64
+    /// byte payloads in place. In blocking mode, the peripheral does not
+    /// write the element before it is acknowledged. In overwrite mode, the
+    /// message is taken after the oldest ones like by
+    /// [`DynRxFifo::receive`], and `f` has to return before the [read
+    /// offset] number of messages arrive, otherwise the peripheral may
+    /// overwrite the message while `f` reads it.
+    ///
+    /// [read offset]: crate::config::RxFifoConfig::overwrite_read_offset
+    pub fn receive_with<R>(&mut self, f: impl FnOnce(&M) -> R) -> nb::Result<R, Infallible> {
+        let ring = self.ring();
+        let position = self.receive_position(ring);
+        let Some(index) = ring.nth_occupied(position) else {
+            return Err(nb::Error::WouldBlock);
+        };
+        let Some(element) = self.memory.get(index) else {
+            return Err(nb::Error::WouldBlock);
+        };
+        // Safety: The element is not written by the peripheral until it is
+        // acknowledged below, within the constraints documented above.
+        let result = f(unsafe { &*element.as_ptr() });
+        self.acknowledge(index, position);
+        Ok(result)
+    }
+
+    /// Reads the fill level, indices and flags of the FIFO at once, e.g. for
+    /// debugging a FIFO that stopped receiving
+    pub fn status(&self) -> RxFifoStatus {
+        RxFifoStatus::from(self.regs().s.read())
+    }
+
+    /// Describes the occupied elements for copying them out externally,
+    /// without acknowledging them. `None` if the FIFO is empty.
+    ///
+    /// See the [module documentation](self#external-drain).
+    pub fn drain_descriptor(&mut self) -> Option<DrainDescriptor> {
+        let ring = self.ring();
+        if ring.occupied() == 0 {
+            return None;
+        }
+        let segment = |positions: core::ops::Range<usize>| {
+            Some(DrainSegment {
+                address: self.memory.get(positions.start)?.as_ptr() as *const u8,
+                count: positions.len(),
+            })
+            .filter(|_| !positions.is_empty())
+        };
+        let [first, second] = ring.segments();
+        Some(DrainDescriptor {
+            get_index: first.start,
+            count: ring.occupied(),
+            element_size: core::mem::size_of::<M>(),
+            segments: [segment(first), segment(second)],
+        })
+    }
+
+    /// Acknowledges the elements described by `descriptor` after they have
+    /// been copied out
+    ///
+    /// Fails if the FIFO has been read in some other way since `descriptor`
+    /// was created, in which case nothing is acknowledged.
+    pub fn complete_drain(&mut self, descriptor: DrainDescriptor) -> Result<(), StaleDrainError> {
+        let ring = self.ring();
+        let last_index = ring
+            .nth_occupied(descriptor.count - 1)
+            .filter(|_| ring.nth_occupied(0) == Some(descriptor.get_index))
+            .ok_or(StaleDrainError)?;
+        // The acknowledge must not become visible before the copy has been
+        // observed to be complete.
+        fence(Ordering::SeqCst);
+        // Acknowledging an element acknowledges all elements before it as well.
+        // Safety: The written index is valid since it is within the occupied part
+        // of the FIFO reported by the peripheral.
+        unsafe {
+            self.regs().a.write(|w| w.fai().bits(last_index as u8));
+        }
+        self.read = self.read.wrapping_add(descriptor.count as u32);
+        Ok(())
+    }
+}
+
+/// Snapshot of the status register of a [`RxFifo`]
+///
+/// Created by [`RxFifo::status`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RxFifoStatus {
+    /// Number of messages in the FIFO, see [`DynRxFifo::len`]
+    pub fill_level: usize,
+    /// Index of the oldest message, read next
+    pub get_index: usize,
+    /// Index the next received message is stored at
+    pub put_index: usize,
+    /// The FIFO is full, see [`DynRxFifo::is_full`]
+    pub full: bool,
+    /// A message was lost since the interrupt flag was last cleared, see
+    /// [`DynRxFifo::message_lost`]
+    pub message_lost: bool,
+}
+
+impl From<reg::rxfs::R> for RxFifoStatus {
+    fn from(status: reg::rxfs::R) -> Self {
+        Self {
+            fill_level: status.ffl().bits() as usize,
+            get_index: status.fgi().bits() as usize,
+            put_index: status.fpi().bits() as usize,
+            full: status.ff().bit(),
+            message_lost: status.rfl().bit(),
+        }
+    }
+}
+
+/// Location of the occupied elements of a [`RxFifo`] in Message RAM
+///
+/// Created by [`RxFifo::drain_descriptor`].
+#[derive(Debug)]
+pub struct DrainDescriptor {
+    get_index: usize,
+    count: usize,
+    element_size: usize,
+    segments: [Option<DrainSegment>; 2],
+}
+
+impl DrainDescriptor {
+    /// Index of the first element
+    pub fn get_index(&self) -> usize {
+        self.get_index
+    }
+
+    /// Total number of elements
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Size of each element in bytes
+    pub fn element_size(&self) -> usize {
+        self.element_size
+    }
+
+    /// The contiguous parts of the occupied elements in the order they were
+    /// received
+    ///
+    /// There are two segments if the occupied elements wrap around the end of
+    /// the FIFO, otherwise one.
+    pub fn segments(&self) -> impl Iterator<Item = &DrainSegment> {
+        self.segments.iter().flatten()
+    }
+}
+
+/// Contiguous elements in Message RAM, see [`DrainDescriptor::segments`]
+#[derive(Debug)]
+pub struct DrainSegment {
+    /// Address of the first element
+    pub address: *const u8,
+    /// Number of elements
+    pub count: usize,
+}
+
+/// The [`DrainDescriptor`] no longer describes the elements of the FIFO
+#[derive(Debug)]
+pub struct StaleDrainError;
+
+/// The offset passed to [`DynRxFifo::peek_at`] or
+/// [`DynRxFifo::receive_with_offset`] is not less than the number of messages
+/// in the FIFO
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OffsetOutOfRange {
+    /// The requested offset
+    pub offset: usize,
+    /// Number of messages in the FIFO when reading
+    pub len: usize,
 }
 
+impl<F, P, M: rx::AnyMessage> crate::sealed::Sealed for RxFifo<'_, F, P, M> {}
+
 impl<'a, F, P: mcan_core::CanId, M: rx::AnyMessage> DynRxFifo for RxFifo<'a, F, P, M>
 where
     Self: GetRxFifoRegs,
@@ -110,21 +516,53 @@ where
         self.memory.len()
     }
 
+    fn is_full(&self) -> bool {
+        self.regs().s.read().ff().bit()
+    }
+
+    fn watermark(&self) -> usize {
+        self.regs().c.read().fwm().bits() as usize
+    }
+
+    fn watermark_reached(&self) -> bool {
+        let watermark = self.watermark();
+        watermark != 0 && self.len() >= watermark
+    }
+
+    fn message_lost(&self) -> bool {
+        self.regs().s.read().rfl().bit()
+    }
+
     fn receive(&mut self) -> nb::Result<Self::Message, Infallible> {
-        let status = self.regs().s.read();
-        let len = status.ffl().bits();
-        if len == 0 {
-            return Err(nb::Error::WouldBlock);
-        }
-        let get_index = status.fgi().bits() as usize;
-        let message = self.memory[get_index].get();
-        // Mark the message as read.
-        // Safety: The written index must be valid since it was retrieved from the
-        // peripheral, and the configuration was not changed.
-        unsafe {
-            self.regs().a.write(|w| w.fai().bits(get_index as u8));
-        }
-        Ok(message)
+        let ring = self.ring();
+        let position = self.receive_position(ring);
+        self.read_at(ring, position).ok_or(nb::Error::WouldBlock)
+    }
+
+    fn peek(&self) -> Option<Self::Message> {
+        self.peek_at(0).ok()
+    }
+
+    fn peek_at(&self, offset: usize) -> Result<Self::Message, OffsetOutOfRange> {
+        let ring = self.ring();
+        ring.nth_occupied(offset)
+            .and_then(|index| Some(self.memory.get(index)?.get()))
+            .ok_or(OffsetOutOfRange {
+                offset,
+                len: ring.occupied(),
+            })
+    }
+
+    fn receive_with_offset(&mut self, offset: usize) -> Result<Self::Message, OffsetOutOfRange> {
+        let ring = self.ring();
+        self.read_at(ring, offset).ok_or(OffsetOutOfRange {
+            offset,
+            len: ring.occupied(),
+        })
+    }
+
+    fn read_count(&self) -> u32 {
+        self.read
     }
 }
 
@@ -138,3 +576,266 @@ where
         self.receive().ok()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::Raw as _;
+    use crate::mock::{self, mock_can};
+    use embedded_can::StandardId;
+
+    mock_can!(Can0);
+
+    const RXF0S: usize = 0xa4;
+    const RXF0A: usize = 0xa8;
+
+    fn status(get_index: u32, fill_level: u32) -> u32 {
+        get_index << 8 | fill_level
+    }
+
+    #[test]
+    fn external_drain_acknowledges_described_elements() {
+        let regs = Can0::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        let base = memory.as_ptr() as usize;
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can0, rx::Message<8>>::new(&mut memory, None) };
+
+        regs.write(RXF0A, 0xdead);
+        regs.write(RXF0S, status(0, 0));
+        assert!(fifo.drain_descriptor().is_none());
+
+        regs.write(RXF0S, status(3, 3));
+        let descriptor = fifo.drain_descriptor().unwrap();
+        let size = core::mem::size_of::<rx::Message<8>>();
+        assert_eq!(descriptor.element_size(), size);
+        assert_eq!(descriptor.count(), 3);
+        let segments: [_; 2] = core::array::from_fn(|n| {
+            descriptor
+                .segments()
+                .nth(n)
+                .map(|s| (s.address as usize, s.count))
+        });
+        assert_eq!(segments, [Some((base + 3 * size, 1)), Some((base, 2))]);
+        // Nothing is acknowledged yet
+        assert_eq!(regs.read(RXF0A), 0xdead);
+
+        fifo.complete_drain(descriptor).unwrap();
+        assert_eq!(regs.read(RXF0A), 1);
+    }
+
+    #[test]
+    fn stale_drain_is_not_acknowledged() {
+        mock_can!(Can1);
+        let regs = Can1::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can1, rx::Message<8>>::new(&mut memory, None) };
+        regs.write(RXF0S, status(1, 2));
+        let descriptor = fifo.drain_descriptor().unwrap();
+        // Read by `receive` in the meantime
+        regs.write(RXF0S, status(2, 1));
+        regs.write(RXF0A, 0xdead);
+        assert!(fifo.complete_drain(descriptor).is_err());
+        assert_eq!(regs.read(RXF0A), 0xdead);
+    }
+
+    #[test]
+    fn status_is_pollable() {
+        mock_can!(Can2);
+        const RXF1C: usize = 0xb0;
+        const RXF1S: usize = 0xb4;
+        const FULL: u32 = 1 << 24;
+        const MESSAGE_LOST: u32 = 1 << 25;
+        let regs = Can2::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let fifo = unsafe { RxFifo::<Fifo1, Can2, rx::Message<8>>::new(&mut memory, None) };
+
+        regs.write(RXF1S, status(0, 2));
+        assert!(!fifo.is_full() && !fifo.message_lost());
+        // Disabled watermark
+        assert!(!fifo.watermark_reached());
+        regs.write(RXF1C, 3 << 24);
+        assert_eq!(fifo.watermark(), 3);
+        assert!(!fifo.watermark_reached());
+        regs.write(RXF1S, status(0, 3));
+        assert!(fifo.watermark_reached());
+
+        regs.write(RXF1S, FULL | MESSAGE_LOST | status(0, 4));
+        assert!(fifo.is_full() && fifo.message_lost());
+    }
+
+    #[test]
+    fn status_register_is_decoded() {
+        mock_can!(Can7);
+        const RXF1S: usize = 0xb4;
+        let regs = Can7::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let fifo = unsafe { RxFifo::<Fifo1, Can7, rx::Message<8>>::new(&mut memory, None) };
+
+        // Put index 1, get index 2, fill level 3; reserved bits set
+        regs.write(RXF1S, 0x00c1_c283);
+        assert_eq!(
+            fifo.status(),
+            RxFifoStatus {
+                fill_level: 3,
+                get_index: 2,
+                put_index: 1,
+                full: false,
+                message_lost: false,
+            }
+        );
+        // Full and lost, with all index bits set
+        regs.write(RXF1S, 0x033f_3f7f);
+        assert_eq!(
+            fifo.status(),
+            RxFifoStatus {
+                fill_level: 127,
+                get_index: 63,
+                put_index: 63,
+                full: true,
+                message_lost: true,
+            }
+        );
+        regs.write(RXF1S, 1 << 25);
+        let status = fifo.status();
+        assert!(status.message_lost && !status.full && status.fill_level == 0);
+    }
+
+    #[test]
+    fn out_of_range_status_does_not_panic() {
+        mock_can!(Can3);
+        let regs = Can3::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can3, rx::Message<8>>::new(&mut memory, None) };
+
+        // Get index and fill level beyond the 4 elements, e.g. after
+        // misconfiguring RXF0C
+        regs.write(RXF0S, status(63, 127));
+        let descriptor = fifo.drain_descriptor().unwrap();
+        assert_eq!(descriptor.count(), 4);
+        assert_eq!(descriptor.segments().map(|s| s.count).sum::<usize>(), 4);
+        assert!(fifo.receive().is_ok());
+    }
+
+    #[test]
+    fn blocking_mode_reads_the_oldest_message() {
+        mock_can!(Can4);
+        let regs = Can4::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can4, rx::Message<8>>::new(&mut memory, None) };
+
+        regs.write(RXF0S, status(1, 4));
+        assert_eq!(
+            fifo.receive().unwrap().id(),
+            StandardId::new(1).unwrap().into()
+        );
+        assert_eq!(regs.read(RXF0A), 1);
+        assert_eq!(fifo.read_count(), 1);
+    }
+
+    #[test]
+    fn overwrite_mode_skips_messages_that_may_be_overwritten() {
+        mock_can!(Can5);
+        let regs = Can5::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can5, rx::Message<8>>::new(&mut memory, Some(2)) };
+        let id = |raw| StandardId::new(raw).unwrap().into();
+
+        // Two more messages fit, so the oldest cannot be overwritten
+        regs.write(RXF0S, status(3, 2));
+        assert_eq!(fifo.receive().unwrap().id(), id(3));
+        assert_eq!(regs.read(RXF0A), 3);
+        assert_eq!(fifo.read_count(), 1);
+
+        // One more message fits, so the oldest is skipped
+        regs.write(RXF0S, status(3, 3));
+        assert_eq!(fifo.receive().unwrap().id(), id(0));
+        assert_eq!(regs.read(RXF0A), 0);
+        assert_eq!(fifo.read_count(), 3);
+
+        // Full
+        regs.write(RXF0S, status(1, 4));
+        assert_eq!(fifo.receive().unwrap().id(), id(3));
+        assert_eq!(regs.read(RXF0A), 3);
+        assert_eq!(fifo.read_count(), 6);
+
+        regs.write(RXF0S, status(0, 0));
+        assert!(fifo.receive().is_err());
+    }
+
+    #[test]
+    fn messages_are_peeked_and_read_at_an_offset() {
+        mock_can!(Can6);
+        let regs = Can6::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can6, rx::Message<8>>::new(&mut memory, None) };
+        let id = |raw| StandardId::new(raw).unwrap().into();
+
+        regs.write(RXF0S, status(0, 0));
+        assert!(fifo.peek().is_none());
+        assert_eq!(
+            fifo.receive_with_offset(0).err(),
+            Some(OffsetOutOfRange { offset: 0, len: 0 })
+        );
+
+        regs.write(RXF0A, 0xdead);
+        regs.write(RXF0S, status(2, 3));
+        assert_eq!(fifo.peek().unwrap().id(), id(2));
+        // Wrapping around the end of the FIFO
+        assert_eq!(fifo.peek_at(2).unwrap().id(), id(0));
+        assert_eq!(
+            fifo.peek_at(3).err(),
+            Some(OffsetOutOfRange { offset: 3, len: 3 })
+        );
+        assert!(fifo.receive_with_offset(3).is_err());
+        assert_eq!(regs.read(RXF0A), 0xdead);
+        assert_eq!(fifo.read_count(), 0);
+
+        assert_eq!(fifo.receive_with_offset(1).unwrap().id(), id(3));
+        assert_eq!(regs.read(RXF0A), 3);
+        assert_eq!(fifo.read_count(), 2);
+    }
+
+    #[test]
+    fn messages_are_read_in_place_before_the_acknowledge() {
+        mock_can!(Can8);
+        let regs = Can8::regs();
+        let mut memory: [_; 4] =
+            core::array::from_fn(|index| VolatileCell::new(mock::message(index as u16)));
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { RxFifo::<Fifo0, Can8, rx::Message<8>>::new(&mut memory, Some(2)) };
+        let id = |raw| StandardId::new(raw).unwrap().into();
+
+        regs.write(RXF0A, 0xdead);
+        regs.write(RXF0S, status(0, 0));
+        assert!(matches!(
+            fifo.receive_with(|_| unreachable!()),
+            Err(nb::Error::WouldBlock)
+        ));
+
+        // One more message fits, so the oldest is skipped like by `receive`
+        regs.write(RXF0S, status(3, 3));
+        let received = fifo.receive_with(|message| {
+            assert_eq!(regs.read(RXF0A), 0xdead);
+            message.id()
+        });
+        assert_eq!(received.unwrap(), id(0));
+        assert_eq!(regs.read(RXF0A), 0);
+        assert_eq!(fifo.read_count(), 2);
+    }
+}
@@ -0,0 +1,327 @@
+//! Restoring the arrival order of messages received through different queues
+//!
+//! Messages stored in FIFO 0, FIFO 1 and the dedicated receive buffers are read
+//! independently, which loses their relative order. [`RxMerger`] restores it
+//! from the RX timestamps of the messages, taking the wraparound of the 16-bit
+//! timestamp counter into account.
+//!
+//! The timestamp counter has to be enabled in [`Timestamp`] for this to work,
+//! see [`Timestamp::is_enabled`]. Otherwise all timestamps are zero and
+//! messages are released in the order they were pushed.
+//!
+//! A buffered message is released by [`RxMerger::pop_in_order`] once it is
+//! known that no older message can arrive, i.e. when every source has a
+//! message buffered, or when the window is full. To avoid waiting for sources
+//! that stay idle, [`RxMerger::pop_in_order_at`] additionally releases
+//! messages that were received more than the configured timeout ago.
+//!
+//! [`Timestamp`]: crate::config::Timestamp
+//! [`Timestamp::is_enabled`]: crate::config::Timestamp::is_enabled
+
+use crate::message::rx;
+
+/// Queue a received message was read from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RxSource {
+    /// RX FIFO 0
+    Fifo0,
+    /// RX FIFO 1
+    Fifo1,
+    /// Dedicated receive buffers
+    DedicatedBuffer,
+}
+
+impl RxSource {
+    const ALL: [Self; 3] = [Self::Fifo0, Self::Fifo1, Self::DedicatedBuffer];
+}
+
+/// Returns `true` if timestamp `a` was taken before `b`
+///
+/// Timestamps less than half of the counter range apart are compared as if the
+/// counter did not wrap around in between.
+fn is_before(a: u16, b: u16) -> bool {
+    (b.wrapping_sub(a) as i16) > 0
+}
+
+#[derive(Copy, Clone)]
+struct Entry<M> {
+    /// Order in which the messages were pushed; breaks timestamp ties
+    sequence: u32,
+    source: RxSource,
+    message: M,
+}
+
+/// Merges messages from the RX queues in timestamp order, buffering up to
+/// `DEPTH` messages
+///
+/// Messages of each source have to be pushed in the order they were read from
+/// the peripheral.
+pub struct RxMerger<M, const DEPTH: usize> {
+    window: [Option<Entry<M>>; DEPTH],
+    sequence: u32,
+    timeout: u16,
+}
+
+impl<M: rx::AnyMessage, const DEPTH: usize> Default for RxMerger<M, DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: rx::AnyMessage, const DEPTH: usize> RxMerger<M, DEPTH> {
+    /// Creates an empty merger
+    ///
+    /// The timeout defaults to half of the timestamp counter range, the
+    /// largest age that can be told apart from a message of the future.
+    pub const fn new() -> Self {
+        Self {
+            window: [None; DEPTH],
+            sequence: 0,
+            timeout: i16::MAX as u16,
+        }
+    }
+
+    /// Sets the number of timestamp counter ticks after which a message is
+    /// released by [`Self::pop_in_order_at`] regardless of the other sources.
+    ///
+    /// The timeout is limited to half of the timestamp counter range.
+    pub const fn with_timeout(mut self, ticks: u16) -> Self {
+        self.timeout = if ticks > i16::MAX as u16 {
+            i16::MAX as u16
+        } else {
+            ticks
+        };
+        self
+    }
+
+    /// Buffers `message` read from `source`
+    ///
+    /// If the window is full, the message is handed back. Messages have to be
+    /// popped first in that case.
+    pub fn push(&mut self, source: RxSource, message: M) -> Result<(), M> {
+        let Some(slot) = self.window.iter_mut().find(|slot| slot.is_none()) else {
+            return Err(message);
+        };
+        *slot = Some(Entry {
+            sequence: self.sequence,
+            source,
+            message,
+        });
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Releases the oldest buffered message if no older message can arrive
+    /// anymore
+    ///
+    /// This is the case if every source has a message buffered, since each
+    /// source delivers its messages in order, or if the window is full.
+    pub fn pop_in_order(&mut self) -> Option<(RxSource, M)> {
+        let ready = self.is_full()
+            || RxSource::ALL
+                .iter()
+                .all(|&source| self.entries().any(|entry| entry.source == source));
+        if ready {
+            self.pop_oldest()
+        } else {
+            None
+        }
+    }
+
+    /// Like [`Self::pop_in_order`], but additionally releases the oldest
+    /// message if it was received at least the timeout before `now`
+    ///
+    /// `now` is the current value of the timestamp counter, as returned by
+    /// [`DynAux::timestamp`].
+    ///
+    /// [`DynAux::timestamp`]: crate::bus::DynAux::timestamp
+    pub fn pop_in_order_at(&mut self, now: u16) -> Option<(RxSource, M)> {
+        if let Some(popped) = self.pop_in_order() {
+            return Some(popped);
+        }
+        let index = self.oldest()?;
+        let timestamp = self.window.get(index)?.as_ref()?.message.timestamp();
+        if now.wrapping_sub(timestamp) >= self.timeout {
+            self.take(index)
+        } else {
+            None
+        }
+    }
+
+    /// Releases the oldest buffered message unconditionally, e.g. to flush
+    /// the window
+    pub fn pop_oldest(&mut self) -> Option<(RxSource, M)> {
+        let index = self.oldest()?;
+        self.take(index)
+    }
+
+    /// Returns the number of buffered messages
+    pub fn len(&self) -> usize {
+        self.entries().count()
+    }
+
+    /// Returns `true` if no message is buffered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if no more messages can be pushed
+    pub fn is_full(&self) -> bool {
+        self.len() == DEPTH
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry<M>> {
+        self.window.iter().flatten()
+    }
+
+    /// Index of the buffered message with the oldest timestamp
+    fn oldest(&self) -> Option<usize> {
+        let mut oldest: Option<(usize, &Entry<M>)> = None;
+        for (index, entry) in self.window.iter().enumerate() {
+            let Some(entry) = entry else { continue };
+            let older = match oldest {
+                None => true,
+                Some((_, current)) => {
+                    let (a, b) = (entry.message.timestamp(), current.message.timestamp());
+                    is_before(a, b)
+                        || a == b && is_before_sequence(entry.sequence, current.sequence)
+                }
+            };
+            if older {
+                oldest = Some((index, entry));
+            }
+        }
+        oldest.map(|(index, _)| index)
+    }
+
+    fn take(&mut self, index: usize) -> Option<(RxSource, M)> {
+        self.window
+            .get_mut(index)?
+            .take()
+            .map(|entry| (entry.source, entry.message))
+    }
+}
+
+fn is_before_sequence(a: u32, b: u32) -> bool {
+    (b.wrapping_sub(a) as i32) > 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::rx::AnyMessage as _;
+    use crate::message::Raw;
+    use crate::mock;
+
+    /// A message whose ID reflects the order of arrival
+    fn message(id: u16, timestamp: u16) -> rx::Message<8> {
+        let mut m: rx::Message<8> = mock::message(id);
+        m.set_timestamp(timestamp);
+        m
+    }
+
+    fn order(merger: &mut RxMerger<rx::Message<8>, 8>) -> [u16; 3] {
+        let mut ids = [0; 3];
+        for id in &mut ids {
+            let (_, m) = merger.pop_in_order().unwrap();
+            *id = match m.id() {
+                embedded_can::Id::Standard(id) => id.as_raw(),
+                embedded_can::Id::Extended(_) => unreachable!(),
+            };
+        }
+        ids
+    }
+
+    #[test]
+    fn half_range_comparison() {
+        assert!(is_before(0, 1));
+        assert!(!is_before(1, 0));
+        assert!(!is_before(5, 5));
+        assert!(is_before(0xfff0, 0x0010));
+        assert!(!is_before(0x0010, 0xfff0));
+        assert!(is_before(0, 0x7fff));
+        assert!(!is_before(0, 0x8000));
+    }
+
+    #[test]
+    fn releases_once_all_sources_have_messages() {
+        let mut merger = RxMerger::<_, 8>::new();
+        merger.push(RxSource::Fifo1, message(2, 20)).unwrap();
+        merger.push(RxSource::Fifo0, message(3, 30)).unwrap();
+        assert!(merger.pop_in_order().is_none());
+        merger
+            .push(RxSource::DedicatedBuffer, message(1, 10))
+            .unwrap();
+        let (source, _) = merger.pop_in_order().unwrap();
+        assert_eq!(source, RxSource::DedicatedBuffer);
+        // The dedicated buffers might still deliver older messages
+        assert!(merger.pop_in_order().is_none());
+        assert_eq!(merger.len(), 2);
+    }
+
+    #[test]
+    fn orders_across_wraparound() {
+        let mut merger = RxMerger::<_, 8>::new();
+        merger.push(RxSource::Fifo0, message(3, 0x0005)).unwrap();
+        merger.push(RxSource::Fifo1, message(1, 0xfffe)).unwrap();
+        merger
+            .push(RxSource::DedicatedBuffer, message(2, 0xffff))
+            .unwrap();
+        merger.push(RxSource::Fifo0, message(4, 0x0006)).unwrap();
+        merger.push(RxSource::Fifo1, message(5, 0x0100)).unwrap();
+        merger
+            .push(RxSource::DedicatedBuffer, message(6, 0x0100))
+            .unwrap();
+        assert_eq!(order(&mut merger), [1, 2, 3]);
+    }
+
+    #[test]
+    fn equal_timestamps_keep_push_order() {
+        let mut merger = RxMerger::<_, 8>::new();
+        merger.push(RxSource::Fifo1, message(1, 7)).unwrap();
+        merger.push(RxSource::Fifo0, message(2, 7)).unwrap();
+        merger
+            .push(RxSource::DedicatedBuffer, message(3, 7))
+            .unwrap();
+        merger.push(RxSource::Fifo1, message(4, 8)).unwrap();
+        merger.push(RxSource::Fifo0, message(5, 8)).unwrap();
+        merger
+            .push(RxSource::DedicatedBuffer, message(6, 8))
+            .unwrap();
+        assert_eq!(order(&mut merger), [1, 2, 3]);
+    }
+
+    #[test]
+    fn idle_source_times_out() {
+        let mut merger = RxMerger::<_, 4>::new().with_timeout(100);
+        merger.push(RxSource::Fifo0, message(1, 0xffc0)).unwrap();
+        merger.push(RxSource::Fifo1, message(2, 0xffd0)).unwrap();
+        assert!(merger.pop_in_order_at(0x0010).is_none());
+        let (source, _) = merger.pop_in_order_at(0x0024).unwrap();
+        assert_eq!(source, RxSource::Fifo0);
+        assert!(merger.pop_in_order_at(0x0024).is_none());
+        assert!(merger.pop_in_order_at(0x0034).is_some());
+        assert!(merger.is_empty());
+    }
+
+    #[test]
+    fn full_window_releases_and_rejects() {
+        let mut merger = RxMerger::<_, 2>::new();
+        merger.push(RxSource::Fifo0, message(1, 2)).unwrap();
+        merger.push(RxSource::Fifo0, message(2, 1)).unwrap();
+        assert!(merger.push(RxSource::Fifo0, message(3, 3)).is_err());
+        assert!(merger.is_full());
+        assert_eq!(merger.pop_in_order().unwrap().1.timestamp(), 1);
+        assert!(merger.pop_in_order().is_none());
+        assert_eq!(merger.pop_oldest().unwrap().1.timestamp(), 2);
+        assert!(merger.pop_oldest().is_none());
+    }
+
+    #[test]
+    fn timeout_is_capped_at_half_range() {
+        let mut merger = RxMerger::<_, 2>::new().with_timeout(u16::MAX);
+        merger.push(RxSource::Fifo0, message(1, 0)).unwrap();
+        assert!(merger.pop_in_order_at(0x7ffe).is_none());
+        assert!(merger.pop_in_order_at(0x7fff).is_some());
+    }
+}
@@ -0,0 +1,544 @@
+//! Sequence counter and CRC sealing of frames for safety protocols
+//!
+//! Safety layers on top of CAN commonly require every frame of a stream to
+//! carry a sequence counter and a checksum in its last payload bytes. A
+//! [`FrameSealer`] appends such a trailer to outgoing frames with
+//! [`FrameSealer::seal`] and checks it on incoming frames with
+//! [`FrameSealer::verify`].
+//!
+//! The trailer consists of the counter followed by the CRC, both big-endian.
+//! The CRC covers the frame ID and every data byte preceding it, including
+//! the counter. For CAN FD frames whose sealed length does not match a valid
+//! data length, zero padding is inserted between the payload and the trailer
+//! so that the trailer always occupies the last bytes of the data field.
+//!
+//! A `FrameSealer` keeps the state of a single stream, so one instance should
+//! be used per sealed ID.
+//!
+//! ```
+//! use mcan::embedded_can::StandardId;
+//! use mcan::message::tx;
+//! use mcan::safety::{CounterWidth, CrcAlgorithm, FrameSealer};
+//!
+//! let mut sealer = FrameSealer::new(CrcAlgorithm::Crc8Sae, CounterWidth::U8, 16);
+//! let mut scratch = [0; 8];
+//! let mut builder = tx::MessageBuilder {
+//!     id: StandardId::new(0x100).unwrap().into(),
+//!     frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Data(&[1, 2, 3])),
+//!     store_tx_event: None,
+//! };
+//! sealer.seal(&mut builder, &mut scratch).unwrap();
+//! let message: tx::Message<8> = builder.build().unwrap();
+//! ```
+
+use crate::message::{dlc_to_len, len_to_dlc, rx, tx};
+use embedded_can::Id;
+
+/// CRC algorithm used to protect sealed frames
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CrcAlgorithm {
+    /// CRC-8 as specified in SAE J1850 (polynomial `0x1D`, initial value and
+    /// final XOR `0xFF`)
+    Crc8Sae,
+    /// CRC-16-CCITT (polynomial `0x1021`, initial value `0xFFFF`, no final
+    /// XOR)
+    Crc16Ccitt,
+}
+
+impl CrcAlgorithm {
+    /// Number of bytes the CRC occupies in the frame
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Crc8Sae => 1,
+            Self::Crc16Ccitt => 2,
+        }
+    }
+
+    /// Computes the CRC over the concatenation of `chunks`
+    fn compute<'a>(&self, chunks: impl IntoIterator<Item = &'a [u8]>) -> u16 {
+        let bytes = chunks.into_iter().flatten();
+        match self {
+            Self::Crc8Sae => {
+                let crc = bytes.fold(0xff_u8, |mut crc, &byte| {
+                    crc ^= byte;
+                    for _ in 0..8 {
+                        crc = if crc & 0x80 != 0 {
+                            (crc << 1) ^ 0x1d
+                        } else {
+                            crc << 1
+                        };
+                    }
+                    crc
+                });
+                u16::from(crc ^ 0xff)
+            }
+            Self::Crc16Ccitt => crc16_ccitt(0xffff, bytes),
+        }
+    }
+}
+
+/// Continues the CRC-16-CCITT `crc` over `bytes`, with polynomial `0x1021`
+///
+/// A new CRC starts from the initial value `0xFFFF`.
+pub(crate) fn crc16_ccitt<'a>(crc: u16, bytes: impl IntoIterator<Item = &'a u8>) -> u16 {
+    bytes.into_iter().fold(crc, |mut crc, &byte| {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Width of the sequence counter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CounterWidth {
+    /// 8-bit counter, wrapping after 255
+    U8,
+    /// 16-bit counter, wrapping after 65535
+    U16,
+}
+
+impl CounterWidth {
+    /// Number of bytes the counter occupies in the frame
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+        }
+    }
+
+    fn mask(&self) -> u16 {
+        match self {
+            Self::U8 => 0xff,
+            Self::U16 => 0xffff,
+        }
+    }
+}
+
+/// Errors that may occur when sealing a frame
+#[derive(Debug, PartialEq, Eq)]
+pub enum SealError {
+    /// Remote frames carry no data and cannot be sealed
+    RemoteFrame,
+    /// The payload together with the trailer does not fit in the frame format
+    /// or in the scratch buffer
+    TooMuchData,
+}
+
+/// Errors that may occur when verifying a frame
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The frame is too short to contain a trailer
+    TooShort,
+    /// The CRC in the frame does not match its contents
+    CrcMismatch,
+    /// The counter is equal to that of the previously accepted frame
+    Duplicate {
+        /// Received counter value
+        counter: u16,
+    },
+    /// The counter is outside the accepted window after the previously
+    /// accepted frame, which is the case for stale and replayed frames
+    OutOfWindow {
+        /// Received counter value
+        counter: u16,
+        /// Counter value of the previously accepted frame
+        last: u16,
+    },
+}
+
+/// Sequence information of a successfully verified frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SeqInfo {
+    /// Received counter value
+    pub counter: u16,
+    /// Number of frames skipped since the previously accepted frame. Always
+    /// zero for the first verified frame.
+    pub missed: u16,
+}
+
+/// Seals outgoing and verifies incoming frames of a single stream
+///
+/// More information can be found in the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct FrameSealer {
+    crc: CrcAlgorithm,
+    counter_width: CounterWidth,
+    window: u16,
+    next_counter: u16,
+    last_received: Option<u16>,
+}
+
+impl FrameSealer {
+    /// Create an instance
+    ///
+    /// A received frame is accepted if its counter is at most `window` steps
+    /// ahead of the previously accepted one. `window` is limited to half of
+    /// the counter range, so that frames from the past are never mistaken for
+    /// frames from the future.
+    pub fn new(crc: CrcAlgorithm, counter_width: CounterWidth, window: u16) -> Self {
+        Self {
+            crc,
+            counter_width,
+            window: window.clamp(1, counter_width.mask() / 2),
+            next_counter: 0,
+            last_received: None,
+        }
+    }
+
+    /// Number of bytes appended to every sealed frame
+    pub fn trailer_len(&self) -> usize {
+        self.counter_width.size() + self.crc.size()
+    }
+
+    /// Appends the sequence counter and CRC to the payload of `builder`.
+    ///
+    /// The sealed payload is assembled in `scratch`, which `builder` refers to
+    /// afterwards. `scratch` should not be larger than the data field of the
+    /// transmit elements the message will be built into. The counter is only
+    /// advanced if sealing succeeds.
+    pub fn seal<'a>(
+        &mut self,
+        builder: &mut tx::MessageBuilder<'a>,
+        scratch: &'a mut [u8],
+    ) -> Result<(), SealError> {
+        let (payload, fd_format) = match builder.frame_type {
+            tx::FrameType::Classic(tx::ClassicFrameType::Data(payload)) => (payload, false),
+            tx::FrameType::Classic(tx::ClassicFrameType::Remote { .. }) => {
+                return Err(SealError::RemoteFrame)
+            }
+            tx::FrameType::FlexibleDatarate { payload, .. } => (payload, true),
+        };
+        let len = self.sealed_len(payload.len(), fd_format)?;
+        let scratch = scratch.get_mut(..len).ok_or(SealError::TooMuchData)?;
+
+        let trailer_start = len - self.trailer_len();
+        let crc_start = trailer_start + self.counter_width.size();
+        // The fields are always within `scratch`, as `sealed_len` leaves room
+        // for the payload and the trailer.
+        scratch
+            .get_mut(..payload.len())
+            .ok_or(SealError::TooMuchData)?
+            .copy_from_slice(payload);
+        scratch
+            .get_mut(payload.len()..trailer_start)
+            .ok_or(SealError::TooMuchData)?
+            .fill(0);
+        let counter_field = scratch
+            .get_mut(trailer_start..crc_start)
+            .ok_or(SealError::TooMuchData)?;
+        write_be(counter_field, self.next_counter);
+        let data = scratch.get(..crc_start).ok_or(SealError::TooMuchData)?;
+        let crc = self.crc.compute([id_bytes(builder.id).as_slice(), data]);
+        let crc_field = scratch.get_mut(crc_start..).ok_or(SealError::TooMuchData)?;
+        write_be(crc_field, crc);
+
+        self.next_counter = self.next_counter.wrapping_add(1) & self.counter_width.mask();
+        let scratch = &*scratch;
+        match &mut builder.frame_type {
+            tx::FrameType::Classic(tx::ClassicFrameType::Data(payload))
+            | tx::FrameType::FlexibleDatarate { payload, .. } => *payload = scratch,
+            tx::FrameType::Classic(tx::ClassicFrameType::Remote { .. }) => {}
+        }
+        Ok(())
+    }
+
+    /// Checks the CRC and sequence counter of a received frame.
+    ///
+    /// The counter is only recorded if the frame is accepted.
+    pub fn verify(&mut self, message: &impl rx::AnyMessage) -> Result<SeqInfo, VerifyError> {
+        let data = message.data();
+        let crc_start = data
+            .len()
+            .checked_sub(self.crc.size())
+            .filter(|&start| start >= self.counter_width.size())
+            .ok_or(VerifyError::TooShort)?;
+        let trailer_start = crc_start - self.counter_width.size();
+        let crc_field = data.get(crc_start..).ok_or(VerifyError::TooShort)?;
+        let data = data.get(..crc_start).ok_or(VerifyError::TooShort)?;
+        let counter_field = data.get(trailer_start..).ok_or(VerifyError::TooShort)?;
+        let crc = self.crc.compute([id_bytes(message.id()).as_slice(), data]);
+        if crc != read_be(crc_field) {
+            return Err(VerifyError::CrcMismatch);
+        }
+
+        let counter = read_be(counter_field);
+        let missed = match self.last_received {
+            None => 0,
+            Some(last) => {
+                let delta = counter.wrapping_sub(last) & self.counter_width.mask();
+                if delta == 0 {
+                    return Err(VerifyError::Duplicate { counter });
+                } else if delta > self.window {
+                    return Err(VerifyError::OutOfWindow { counter, last });
+                }
+                delta - 1
+            }
+        };
+        self.last_received = Some(counter);
+        Ok(SeqInfo { counter, missed })
+    }
+
+    /// Length of the data field after sealing a payload of `len` bytes
+    fn sealed_len(&self, len: usize, fd_format: bool) -> Result<usize, SealError> {
+        let len = len + self.trailer_len();
+        if len > 64 {
+            return Err(SealError::TooMuchData);
+        }
+        let dlc = len_to_dlc(len, fd_format).map_err(|_| SealError::TooMuchData)?;
+        Ok(dlc_to_len(dlc, fd_format))
+    }
+}
+
+/// Representation of `id` that is covered by the CRC. Bit 31 distinguishes
+/// extended from standard IDs.
+fn id_bytes(id: Id) -> [u8; 4] {
+    match id {
+        Id::Standard(id) => u32::from(id.as_raw()),
+        Id::Extended(id) => id.as_raw() | 1 << 31,
+    }
+    .to_be_bytes()
+}
+
+fn write_be(dst: &mut [u8], value: u16) {
+    match dst {
+        [b] => *b = value as u8,
+        [hi, lo] => [*hi, *lo] = value.to_be_bytes(),
+        _ => {}
+    }
+}
+
+fn read_be(src: &[u8]) -> u16 {
+    src.iter().fold(0, |acc, &b| acc << 8 | u16::from(b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::Raw;
+    use embedded_can::{ExtendedId, StandardId};
+
+    const CHECK: &[u8] = b"123456789";
+
+    fn builder(payload: &[u8], fd: bool) -> tx::MessageBuilder<'_> {
+        tx::MessageBuilder {
+            id: StandardId::new(0x123).unwrap().into(),
+            frame_type: if fd {
+                tx::FrameType::FlexibleDatarate {
+                    payload,
+                    bit_rate_switching: false,
+                    force_error_state_indicator: false,
+                }
+            } else {
+                tx::FrameType::Classic(tx::ClassicFrameType::Data(payload))
+            },
+            store_tx_event: None,
+        }
+    }
+
+    fn sealed(
+        sealer: &mut FrameSealer,
+        payload: &[u8],
+        fd: bool,
+    ) -> Result<rx::Message<64>, SealError> {
+        let mut scratch = [0; 64];
+        let mut builder = builder(payload, fd);
+        sealer.seal(&mut builder, &mut scratch)?;
+        Ok(builder.build::<64>().unwrap().into())
+    }
+
+    fn sealer(crc: CrcAlgorithm, counter_width: CounterWidth) -> FrameSealer {
+        FrameSealer::new(crc, counter_width, 4)
+    }
+
+    #[test]
+    fn crc_check_values() {
+        assert_eq!(CrcAlgorithm::Crc8Sae.compute([CHECK]), 0x4b);
+        assert_eq!(CrcAlgorithm::Crc16Ccitt.compute([CHECK]), 0x29b1);
+        assert_eq!(
+            CrcAlgorithm::Crc16Ccitt.compute([&CHECK[..4], &CHECK[4..]]),
+            0x29b1
+        );
+    }
+
+    #[test]
+    fn classic_frame_layout() {
+        let mut tx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U16);
+        let message = sealed(&mut tx, &[0xaa, 0xbb], false).unwrap();
+        let data = message.data();
+        assert_eq!(data.len(), 6);
+        assert_eq!(&data[..4], &[0xaa, 0xbb, 0x00, 0x00]);
+        let crc = CrcAlgorithm::Crc16Ccitt.compute([&[0, 0, 0x01, 0x23][..], &data[..4]]);
+        assert_eq!(read_be(&data[4..]), crc);
+    }
+
+    #[test]
+    fn round_trip() {
+        for crc in [CrcAlgorithm::Crc8Sae, CrcAlgorithm::Crc16Ccitt] {
+            for width in [CounterWidth::U8, CounterWidth::U16] {
+                let mut tx = sealer(crc, width);
+                let mut rx = sealer(crc, width);
+                for counter in 0..3 {
+                    let message = sealed(&mut tx, &[1, 2, 3, 4], false).unwrap();
+                    assert_eq!(rx.verify(&message), Ok(SeqInfo { counter, missed: 0 }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn classic_frame_overflow() {
+        let mut tx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U8);
+        assert!(sealed(&mut tx, &[0; 5], false).is_ok());
+        assert_eq!(
+            sealed(&mut tx, &[0; 6], false).unwrap_err(),
+            SealError::TooMuchData
+        );
+    }
+
+    #[test]
+    fn failed_seal_does_not_advance_counter() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        assert!(sealed(&mut tx, &[0; 7], false).is_err());
+        let message = sealed(&mut tx, &[], false).unwrap();
+        assert_eq!(rx.verify(&message).unwrap().counter, 0);
+    }
+
+    #[test]
+    fn scratch_limits_sealed_length() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut scratch = [0; 4];
+        let mut b = builder(&[1, 2], false);
+        assert!(tx.seal(&mut b, &mut scratch).is_ok());
+        let mut b = builder(&[1, 2, 3], false);
+        assert_eq!(tx.seal(&mut b, &mut scratch), Err(SealError::TooMuchData));
+    }
+
+    #[test]
+    fn remote_frames_are_rejected() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut b = tx::MessageBuilder {
+            id: StandardId::ZERO.into(),
+            frame_type: tx::FrameType::Classic(tx::ClassicFrameType::Remote { desired_len: 8 }),
+            store_tx_event: None,
+        };
+        assert_eq!(tx.seal(&mut b, &mut [0; 8]), Err(SealError::RemoteFrame));
+    }
+
+    #[test]
+    fn fd_frames_are_padded_to_valid_length() {
+        let mut tx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U8);
+        // 10 bytes of payload and 3 bytes of trailer are padded to 16 bytes
+        let message = sealed(&mut tx, &[0xff; 10], true).unwrap();
+        let data = message.data();
+        assert_eq!(data.len(), 16);
+        assert_eq!(&data[..10], &[0xff; 10]);
+        assert_eq!(&data[10..13], &[0; 3]);
+        assert_eq!(data[13], 0);
+        assert_eq!(rx.verify(&message).unwrap().counter, 0);
+    }
+
+    #[test]
+    fn fd_frames_with_exact_length_are_not_padded() {
+        let mut tx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U16);
+        let message = sealed(&mut tx, &[0; 8], true).unwrap();
+        assert_eq!(message.data().len(), 12);
+        let message = sealed(&mut tx, &[0; 60], true).unwrap();
+        assert_eq!(message.data().len(), 64);
+        assert_eq!(
+            sealed(&mut tx, &[0; 61], true).unwrap_err(),
+            SealError::TooMuchData
+        );
+    }
+
+    #[test]
+    fn corrupted_frames_are_rejected() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut scratch = [0; 8];
+        let mut b = builder(&[1, 2, 3], false);
+        tx.seal(&mut b, &mut scratch).unwrap();
+        scratch[1] ^= 0x10;
+        let message: rx::Message<64> = builder(&scratch[..5], false).build::<64>().unwrap().into();
+        assert_eq!(rx.verify(&message), Err(VerifyError::CrcMismatch));
+    }
+
+    #[test]
+    fn crc_covers_id() {
+        let mut tx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U8);
+        let mut scratch = [0; 8];
+        let mut b = builder(&[1], false);
+        tx.seal(&mut b, &mut scratch).unwrap();
+        b.id = ExtendedId::new(0x123).unwrap().into();
+        let message: rx::Message<64> = b.build::<64>().unwrap().into();
+        assert_eq!(rx.verify(&message), Err(VerifyError::CrcMismatch));
+    }
+
+    #[test]
+    fn short_frames_are_rejected() {
+        let mut rx = sealer(CrcAlgorithm::Crc16Ccitt, CounterWidth::U16);
+        let message: rx::Message<64> = builder(&[0; 3], false).build::<64>().unwrap().into();
+        assert_eq!(rx.verify(&message), Err(VerifyError::TooShort));
+    }
+
+    #[test]
+    fn duplicates_and_stale_frames_are_rejected() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let first = sealed(&mut tx, &[], false).unwrap();
+        let second = sealed(&mut tx, &[], false).unwrap();
+        assert!(rx.verify(&first).is_ok());
+        assert!(rx.verify(&second).is_ok());
+        assert_eq!(
+            rx.verify(&second),
+            Err(VerifyError::Duplicate { counter: 1 })
+        );
+        assert_eq!(
+            rx.verify(&first),
+            Err(VerifyError::OutOfWindow {
+                counter: 0,
+                last: 1
+            })
+        );
+    }
+
+    #[test]
+    fn missed_frames_are_counted_within_window() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let messages: [_; 7] = core::array::from_fn(|_| sealed(&mut tx, &[], false).unwrap());
+        assert!(rx.verify(&messages[0]).is_ok());
+        assert_eq!(
+            rx.verify(&messages[4]),
+            Ok(SeqInfo {
+                counter: 4,
+                missed: 3
+            })
+        );
+        // Window of 4 is exceeded by a jump of 5
+        let mut rx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        assert!(rx.verify(&messages[0]).is_ok());
+        assert!(matches!(
+            rx.verify(&messages[5]),
+            Err(VerifyError::OutOfWindow { .. })
+        ));
+    }
+
+    #[test]
+    fn counter_wraps_around() {
+        let mut tx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        let mut rx = sealer(CrcAlgorithm::Crc8Sae, CounterWidth::U8);
+        for i in 0..=256_u16 {
+            let message = sealed(&mut tx, &[], false).unwrap();
+            assert_eq!(rx.verify(&message).unwrap().counter, i & 0xff);
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Host-side stand-ins for the peripheral (`test-util` feature)
+//!
+//! These allow testing code that uses the abstractions, e.g. a [`Tx`] or an
+//! [`RxFifo`], under `cargo test` without hardware:
+//! - [`MockCan`] is a peripheral identity whose register block is a
+//!   [`MockRegisters`] static of plain memory instead of the peripheral
+//! - [`MockDependencies`] accepts the [`SharedMemory`] wherever it is placed
+//!   and reports configurable clocks
+//!
+//! Writes to the registers are read back verbatim, which is sufficient for
+//! the INIT and CCE handshakes of [`CanConfigurable::new`] and
+//! [`CanConfigurable::finalize_recoverable`] to complete. Registers that the
+//! peripheral updates on its own, such as the fill levels and indices of the
+//! FIFOs, keep the value last written, so tests emulate the peripheral by
+//! setting them, e.g. with [`MockRegisters::set_rx_fifo_0_status`]. Messages
+//! to be received are written to the [`SharedMemory`] by the test like the
+//! peripheral would.
+//!
+//! ```
+//! use fugit::RateExtU32 as _;
+//! use mcan::bus::CanConfigurable;
+//! use mcan::messageram::SharedMemory;
+//! use mcan::prelude::*;
+//! use mcan::test::{MockCan, MockDependencies};
+//! # use mcan::generic_array::typenum::consts::*;
+//! # use mcan::message::{rx, tx};
+//! # struct Capacities;
+//! # impl mcan::messageram::Capacities for Capacities {
+//! #     type StandardFilters = U1;
+//! #     type ExtendedFilters = U1;
+//! #     type RxBufferMessage = rx::Message<8>;
+//! #     type DedicatedRxBuffers = U0;
+//! #     type RxFifo0Message = rx::Message<8>;
+//! #     type RxFifo0 = U4;
+//! #     type RxFifo1Message = rx::Message<8>;
+//! #     type RxFifo1 = U1;
+//! #     type TxMessage = tx::Message<8>;
+//! #     type TxBuffers = U4;
+//! #     type DedicatedTxBuffers = U0;
+//! #     type TxEventFifo = U1;
+//! # }
+//!
+//! let mut memory = SharedMemory::<Capacities>::new();
+//! let dependencies = MockDependencies::new(&memory);
+//! let can = CanConfigurable::<MockCan<0>, _, _>::new(500.kHz(), dependencies, &mut memory);
+//! let mut can = can.unwrap().finalize_recoverable().unwrap();
+//!
+//! // The Message RAM is zeroed on construction, so the FIFO holds an empty
+//! // frame with the standard ID 0 once its fill level is set.
+//! let registers = MockCan::<0>::registers();
+//! assert!(can.rx_fifo_0.receive().is_err());
+//! registers.set_rx_fifo_0_status(2, 1);
+//! assert!(can.rx_fifo_0.receive().is_ok());
+//! // Acknowledged through RXF0A
+//! assert_eq!(registers.read(0xa8), 2);
+//! ```
+//!
+//! [`Tx`]: crate::tx_buffers::Tx
+//! [`RxFifo`]: crate::rx_fifo::RxFifo
+//! [`CanConfigurable::new`]: crate::bus::CanConfigurable::new
+//! [`CanConfigurable::finalize_recoverable`]: crate::bus::CanConfigurable::finalize_recoverable
+
+use crate::messageram::{Capacities, SharedMemory};
+use core::sync::atomic::{AtomicU32, Ordering};
+use fugit::{HertzU32, RateExtU32};
+
+/// Number of [`MockCan`] identities
+pub const MOCK_CAN_COUNT: usize = 16;
+
+/// Register blocks of the [`MockCan`] identities
+static REGISTERS: [MockRegisters; MOCK_CAN_COUNT] = [MockRegisters::ZERO; MOCK_CAN_COUNT];
+
+const NDAT1: usize = 0x98;
+const NDAT2: usize = 0x9c;
+const RXF0S: usize = 0xa4;
+const RXF1S: usize = 0xb4;
+const TXFQS: usize = 0xc4;
+const TXEFS: usize = 0xf4;
+
+/// Register block backed by memory, so that writes are read back verbatim
+#[repr(C, align(4))]
+pub struct MockRegisters([AtomicU32; 64]);
+
+impl MockRegisters {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: Self = Self::new();
+
+    /// Create a register block with all registers zero
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const REGISTER: AtomicU32 = AtomicU32::new(0);
+        Self([REGISTER; 64])
+    }
+
+    /// Reads the register at byte `offset` from the start of the register
+    /// block
+    ///
+    /// # Panics
+    /// If `offset` is beyond the register block.
+    pub fn read(&self, offset: usize) -> u32 {
+        self.register(offset).load(Ordering::SeqCst)
+    }
+
+    /// Writes the register at byte `offset` from the start of the register
+    /// block
+    ///
+    /// # Panics
+    /// If `offset` is beyond the register block.
+    pub fn write(&self, offset: usize, value: u32) {
+        self.register(offset).store(value, Ordering::SeqCst);
+    }
+
+    /// Sets all registers to zero, e.g. to reuse the registers of a
+    /// [`MockCan`] in another test
+    pub fn reset(&self) {
+        self.0
+            .iter()
+            .for_each(|register| register.store(0, Ordering::SeqCst));
+    }
+
+    /// Emulates the peripheral storing messages in RX FIFO 0: `fill_level`
+    /// messages starting at `get_index` (RXF0S)
+    pub fn set_rx_fifo_0_status(&self, get_index: u8, fill_level: u8) {
+        self.write(RXF0S, fifo_status(get_index, fill_level));
+    }
+
+    /// Emulates the peripheral storing messages in RX FIFO 1: `fill_level`
+    /// messages starting at `get_index` (RXF1S)
+    pub fn set_rx_fifo_1_status(&self, get_index: u8, fill_level: u8) {
+        self.write(RXF1S, fifo_status(get_index, fill_level));
+    }
+
+    /// Emulates the peripheral storing TX events: `fill_level` events
+    /// starting at `get_index` (TXEFS)
+    pub fn set_tx_event_fifo_status(&self, get_index: u8, fill_level: u8) {
+        self.write(TXEFS, fifo_status(get_index, fill_level));
+    }
+
+    /// Emulates the peripheral taking messages from the transmit queue: the
+    /// oldest pending request at `get_index`, the next free buffer at
+    /// `put_index` and whether the queue is full (TXFQS)
+    ///
+    /// The indices count from the first dedicated transmit buffer, like the
+    /// ones of the peripheral.
+    pub fn set_tx_queue_status(&self, get_index: u8, put_index: u8, full: bool) {
+        let get_index = u32::from(get_index & 0x1f);
+        let put_index = u32::from(put_index & 0x1f);
+        self.write(
+            TXFQS,
+            u32::from(full) << 21 | put_index << 16 | get_index << 8,
+        );
+    }
+
+    /// Emulates the peripheral storing messages in dedicated receive buffers,
+    /// with bit `n` of `flags` set for buffer `n` (NDAT1 and NDAT2)
+    pub fn set_new_data(&self, flags: u64) {
+        self.write(NDAT1, flags as u32);
+        self.write(NDAT2, (flags >> 32) as u32);
+    }
+
+    fn register(&self, offset: usize) -> &AtomicU32 {
+        #[allow(clippy::indexing_slicing)]
+        &self.0[offset / 4]
+    }
+}
+
+impl Default for MockRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// RXFnS and TXEFS value with `fill_level` elements starting at `get_index`
+fn fifo_status(get_index: u8, fill_level: u8) -> u32 {
+    u32::from(get_index & 0x3f) << 8 | u32::from(fill_level & 0x7f)
+}
+
+/// Peripheral identity backed by the [`MockRegisters`] static number `N`
+///
+/// The identities do not share registers, but every use of the same `N` does,
+/// also across tests running in parallel. Each test should use its own `N`,
+/// below [`MOCK_CAN_COUNT`], or tests using the same one should not run at
+/// the same time and [`MockRegisters::reset`] the registers.
+pub enum MockCan<const N: usize> {}
+
+impl<const N: usize> MockCan<N> {
+    /// The registers of the peripheral, e.g. to emulate the peripheral or to
+    /// check the writes of the abstractions
+    pub fn registers() -> &'static MockRegisters {
+        // Safety: `ADDRESS` points to a `MockRegisters` static.
+        unsafe { &*(<Self as mcan_core::CanId>::ADDRESS as *const MockRegisters) }
+    }
+}
+
+// Safety: Every identity has its own register block, and the memory of the
+// `MockRegisters` can be accessed like registers.
+unsafe impl<const N: usize> mcan_core::CanId for MockCan<N> {
+    // An `N` beyond the statics fails to compile
+    #[allow(clippy::indexing_slicing)]
+    const ADDRESS: *const () = core::ptr::addr_of!(REGISTERS[N]) as *const ();
+}
+
+/// Dependencies that accept any Message RAM placement, clocked with 8 MHz
+/// unless configured otherwise
+pub struct MockDependencies {
+    message_ram_start: *const (),
+    message_ram_len: Option<usize>,
+    host_clock: HertzU32,
+    can_clock: HertzU32,
+}
+
+impl MockDependencies {
+    /// Create an instance with the eligible Message RAM starting at `memory`
+    pub fn new<C: Capacities>(memory: &SharedMemory<C>) -> Self {
+        Self {
+            message_ram_start: memory as *const _ as *const (),
+            message_ram_len: None,
+            host_clock: 8.MHz(),
+            can_clock: 8.MHz(),
+        }
+    }
+
+    /// Clocks the peripheral with `clock` instead of 8 MHz, raising the host
+    /// clock to match if needed
+    pub fn with_can_clock(self, clock: HertzU32) -> Self {
+        Self {
+            host_clock: self.host_clock.max(clock),
+            can_clock: clock,
+            ..self
+        }
+    }
+
+    /// Runs the host with `clock` instead of 8 MHz
+    pub fn with_host_clock(self, clock: HertzU32) -> Self {
+        Self {
+            host_clock: clock,
+            ..self
+        }
+    }
+
+    /// Moves the start of the eligible region to `start`
+    pub fn with_message_ram_start(self, start: usize) -> Self {
+        Self {
+            message_ram_start: start as *const (),
+            ..self
+        }
+    }
+
+    /// Limits the Message RAM to `len` bytes from the start of the eligible
+    /// region
+    pub fn with_message_ram_len(self, len: usize) -> Self {
+        Self {
+            message_ram_len: Some(len),
+            ..self
+        }
+    }
+}
+
+// Safety: The eligible region is the one the mock peripheral is configured
+// with, which it does not actually access.
+unsafe impl<Id: mcan_core::CanId> mcan_core::Dependencies<Id> for MockDependencies {
+    fn eligible_message_ram_start(&self) -> *const () {
+        self.message_ram_start
+    }
+
+    fn eligible_message_ram_len(&self) -> Option<usize> {
+        self.message_ram_len
+    }
+
+    fn host_clock(&self) -> HertzU32 {
+        self.host_clock
+    }
+
+    fn can_clock(&self) -> HertzU32 {
+        self.can_clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::CanConfigurable;
+    use crate::message::tx;
+    use crate::mock::Caps;
+    use crate::tx_buffers::DynTx as _;
+    use embedded_can::StandardId;
+
+    const TXBAR: usize = 0xd0;
+
+    #[test]
+    fn queued_transmission_uses_the_emulated_put_index() {
+        let registers = MockCan::<1>::registers();
+        let mut memory = SharedMemory::<Caps>::new();
+        let dependencies = MockDependencies::new(&memory).with_can_clock(40.MHz());
+        let can = CanConfigurable::<MockCan<1>, _, _>::new(500.kHz(), dependencies, &mut memory);
+        let mut can = can.unwrap().finalize_recoverable().unwrap();
+
+        registers.set_tx_queue_status(2, 3, false);
+        let message = tx::MessageBuilder::classic(StandardId::new(0x10).unwrap(), &[1, 2])
+            .build()
+            .unwrap();
+        let token = can.tx.transmit_queued_indexed(message).unwrap();
+        assert_eq!(token.index(), 3);
+        assert_eq!(registers.read(TXBAR), 1 << 3);
+
+        registers.set_tx_queue_status(2, 2, true);
+        assert!(can.tx.transmit_queued_indexed(message).is_err());
+
+        registers.reset();
+        assert_eq!(registers.read(TXBAR), 0);
+    }
+}
@@ -0,0 +1,129 @@
+//! Extension of the 16-bit timestamp counter to 64 bits
+//!
+//! RX messages and TX events carry the value of the 16-bit timestamp counter
+//! at the time the frame was on the bus. [`Extender`] turns these into
+//! absolute timestamps that do not wrap around, for protocols that need to
+//! relate frames over longer periods of time.
+//!
+//! The extender only learns about wraparounds of the counter through
+//! [`Extender::update`], which has to be called with the current counter value
+//! ([`DynAux::timestamp`]) at least once every half counter period (32768
+//! ticks), e.g. from a periodic task or on
+//! [`Interrupt::TimestampWraparound`].
+//!
+//! # Accuracy
+//!
+//! A frame is often read out long after it was captured. Its timestamp is
+//! therefore interpreted as the counter value closest to the last update,
+//! i.e. at most 32768 ticks before or 32767 ticks after it. Frames captured
+//! further away from the last update are off by a multiple of 65536 ticks. In
+//! practice this means that frames have to be read out within half a counter
+//! period of their capture, and that the extender must be updated right before
+//! reading out frames that may have been waiting for a while.
+//!
+//! The timestamp counter has to be enabled in [`Timestamp`], see
+//! [`Timestamp::is_enabled`].
+//!
+//! [`DynAux::timestamp`]: crate::bus::DynAux::timestamp
+//! [`Interrupt::TimestampWraparound`]: crate::interrupt::Interrupt::TimestampWraparound
+//! [`Timestamp`]: crate::config::Timestamp
+//! [`Timestamp::is_enabled`]: crate::config::Timestamp::is_enabled
+
+/// Tracks wraparounds of the timestamp counter to extend its values to 64
+/// bits
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Extender {
+    /// Extended value of the latest counter value observed
+    latest: u64,
+}
+
+impl Extender {
+    /// Create an instance starting at the counter value `now`
+    pub const fn new(now: u16) -> Self {
+        Self { latest: now as u64 }
+    }
+
+    /// Advance to the current counter value `now` and return its extended
+    /// value
+    ///
+    /// Has to be called at least once every half counter period.
+    pub fn update(&mut self, now: u16) -> u64 {
+        let elapsed = now.wrapping_sub(self.latest as u16);
+        self.latest += u64::from(elapsed);
+        self.latest
+    }
+
+    /// Extend the counter value `captured`
+    ///
+    /// See the [module documentation](self#accuracy) for the range in which
+    /// this is correct. A timestamp newer than the last update advances the
+    /// extender, as the counter must have passed it already. Timestamps from
+    /// before the extender was created saturate at zero.
+    pub fn extend(&mut self, captured: u16) -> u64 {
+        let offset = captured.wrapping_sub(self.latest as u16) as i16;
+        let extended = self.latest.saturating_add_signed(offset.into());
+        if offset > 0 {
+            self.latest = extended;
+        }
+        extended
+    }
+
+    /// Extended value of the latest counter value observed
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn update_accumulates_wraparounds() {
+        let mut extender = Extender::new(0xfff0);
+        assert_eq!(extender.update(0xfff0), 0xfff0);
+        assert_eq!(extender.update(0x0010), 0x1_0010);
+        assert_eq!(extender.update(0x8000), 0x1_8000);
+        assert_eq!(extender.update(0x0000), 0x2_0000);
+        assert_eq!(extender.latest(), 0x2_0000);
+    }
+
+    #[test]
+    fn timestamps_before_the_last_update() {
+        let mut extender = Extender::new(0);
+        extender.update(0x8000);
+        extender.update(0x0010);
+        assert_eq!(extender.latest(), 0x1_0010);
+        // Captured before the wraparound
+        assert_eq!(extender.extend(0xff00), 0xff00);
+        // Half a period back is the limit
+        assert_eq!(extender.extend(0x8011), 0x8011);
+        assert_eq!(extender.extend(0x0000), 0x1_0000);
+        assert_eq!(extender.latest(), 0x1_0010);
+    }
+
+    #[test]
+    fn timestamps_after_the_last_update_advance() {
+        let mut extender = Extender::new(0xff00);
+        assert_eq!(extender.extend(0x0100), 0x1_0100);
+        assert_eq!(extender.latest(), 0x1_0100);
+        // Older ones are still placed before
+        assert_eq!(extender.extend(0xff80), 0xff80);
+        assert_eq!(extender.latest(), 0x1_0100);
+    }
+
+    #[test]
+    fn timestamps_beyond_half_a_period_are_misplaced() {
+        let mut extender = Extender::new(0);
+        extender.update(0x8000);
+        extender.update(0x0000);
+        // Captured 0x8001 ticks before the last update, taken as 0x7fff after
+        assert_eq!(extender.extend(0x7fff), 0x1_7fff);
+    }
+
+    #[test]
+    fn timestamps_before_creation_saturate() {
+        let mut extender = Extender::new(0x0010);
+        assert_eq!(extender.extend(0xfff0), 0);
+    }
+}
@@ -0,0 +1,345 @@
+//! Cancellation-safe asynchronous transmission
+//!
+//! A transmission through the queue consists of waiting for free space,
+//! writing the message into the buffer at the put index and requesting its
+//! transmission by setting the corresponding TXBAR bit. A future that does all
+//! of this in one go can be dropped in between, e.g. by a `select` or a
+//! timeout, and it is hard to tell from the outside which steps have already
+//! happened.
+//!
+//! Here, only the waiting part is asynchronous. [`Tx::reserve_slot`] resolves
+//! to a [`TxSlotReservation`] once the queue has space. The reservation
+//! borrows the [`Tx`] mutably, so nothing else can enqueue a message and move
+//! the put index until it is gone. [`TxSlotReservation::send`] then writes the
+//! message and sets TXBAR synchronously. This makes every await point
+//! cancellation-safe:
+//!
+//! - Dropping the [`ReserveSlot`] future only leaves a stale waker in the
+//!   [`TxWaker`], which is replaced by the next registration.
+//! - Dropping an unused [`TxSlotReservation`] is a no-op, as nothing has been
+//!   written to Message RAM or the registers yet.
+//! - Once [`TxSlotReservation::send`] is called, the message is queued before
+//!   control returns to the executor.
+//!
+//! The future is woken through a [`TxWaker`], which the interrupt handler has
+//! to notify on [`Interrupt::TxFifoEmpty`] or
+//! [`Interrupt::TransmissionCompleted`], both of which mean that space may
+//! have become available in the queue. [`Wakers`] does so from the interrupt
+//! flags. If none of these interrupts is enabled, the future only resolves if
+//! the queue has space when it is polled.
+//!
+//! [`Tx::transmit_queued_async`] combines the reservation and sending for
+//! callers that do not need to tell the steps apart: if it is dropped before
+//! it completes, the message has not been queued.
+//!
+//! ```no_run
+//! # use mcan::messageram::Capacities;
+//! # use mcan::tx_async::TxWaker;
+//! # use mcan::tx_buffers::Tx;
+//! static TX_WAKER: TxWaker = TxWaker::new();
+//!
+//! // In the interrupt handler, after clearing the flags
+//! TX_WAKER.wake();
+//!
+//! // In a task
+//! # async fn task<P: mcan::core::CanId, C: Capacities>(tx: &mut Tx<'_, P, C>, message: C::TxMessage) {
+//! let reservation = tx.reserve_slot(&TX_WAKER).await;
+//! let token = reservation.send(message);
+//! # }
+//! ```
+//!
+//! [`Interrupt::TxFifoEmpty`]: crate::interrupt::Interrupt::TxFifoEmpty
+//! [`Interrupt::TransmissionCompleted`]: crate::interrupt::Interrupt::TransmissionCompleted
+//! [`Wakers`]: crate::interrupt::Wakers
+
+use crate::messageram::Capacities;
+use crate::tx_buffers::{Error, Tx, TxToken};
+use atomic_waker::AtomicWaker;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Wakes a task waiting in [`Tx::reserve_slot`]
+///
+/// Meant to be placed in a `static` shared between the task and the interrupt
+/// handler.
+pub struct TxWaker(AtomicWaker);
+
+impl TxWaker {
+    /// Create an instance with no registered task
+    pub const fn new() -> Self {
+        Self(AtomicWaker::new())
+    }
+
+    /// Wake the waiting task, if any
+    ///
+    /// Should be called on [`Interrupt::TxFifoEmpty`] or
+    /// [`Interrupt::TransmissionCompleted`].
+    ///
+    /// [`Interrupt::TxFifoEmpty`]: crate::interrupt::Interrupt::TxFifoEmpty
+    /// [`Interrupt::TransmissionCompleted`]: crate::interrupt::Interrupt::TransmissionCompleted
+    pub fn wake(&self) {
+        self.0.wake();
+    }
+}
+
+impl Default for TxWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, P: mcan_core::CanId, C: Capacities> Tx<'a, P, C> {
+    /// Wait for space in the transmit queue
+    ///
+    /// See the [module documentation](crate::tx_async) for why this is
+    /// cancellation-safe.
+    pub fn reserve_slot<'t>(&'t mut self, waker: &'t TxWaker) -> ReserveSlot<'t, 'a, P, C> {
+        ReserveSlot {
+            tx: Some(self),
+            waker,
+        }
+    }
+
+    /// Wait for space in the transmit queue and put a frame in it, see
+    /// [`Self::reserve_slot`] and [`TxSlotReservation::send`]
+    pub async fn transmit_queued_async(
+        &mut self,
+        waker: &TxWaker,
+        message: C::TxMessage,
+    ) -> Result<TxToken, Error> {
+        self.reserve_slot(waker).await.send(message)
+    }
+}
+
+/// Future returned by [`Tx::reserve_slot`]
+///
+/// Polling it again after it completed returns [`Poll::Pending`].
+pub struct ReserveSlot<'t, 'a, P, C: Capacities> {
+    /// `None` once the reservation has been handed out
+    tx: Option<&'t mut Tx<'a, P, C>>,
+    waker: &'t TxWaker,
+}
+
+impl<'t, 'a, P: mcan_core::CanId, C: Capacities> Future for ReserveSlot<'t, 'a, P, C> {
+    type Output = TxSlotReservation<'t, 'a, P, C>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        // Polled after completion
+        let Some(tx) = this.tx.take() else {
+            return Poll::Pending;
+        };
+        if let Some(index) = tx.find_put_index() {
+            return Poll::Ready(TxSlotReservation { tx, index });
+        }
+        this.waker.0.register(cx.waker());
+        // Space may have become available before the waker was registered, in
+        // which case the corresponding wake-up has been missed.
+        if let Some(index) = tx.find_put_index() {
+            Poll::Ready(TxSlotReservation { tx, index })
+        } else {
+            this.tx = Some(tx);
+            Poll::Pending
+        }
+    }
+}
+
+/// Exclusive right to the buffer at the put index of the transmit queue
+///
+/// Dropping the reservation without calling [`Self::send`] has no effect.
+pub struct TxSlotReservation<'t, 'a, P, C: Capacities> {
+    tx: &'t mut Tx<'a, P, C>,
+    index: usize,
+}
+
+impl<'t, 'a, P: mcan_core::CanId, C: Capacities> TxSlotReservation<'t, 'a, P, C> {
+    /// Index of the reserved buffer
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Puts a frame in the reserved buffer and requests its transmission
+    ///
+    /// Returns a [`TxToken`] like
+    /// [`DynTx::transmit_queued_indexed`](crate::tx_buffers::DynTx::transmit_queued_indexed).
+    /// On error, nothing is written and the reservation is released.
+    pub fn send(self, message: C::TxMessage) -> Result<TxToken, Error> {
+        self.tx.write(self.index, message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::config::Mode;
+    use crate::interrupt::{Interrupt, InterruptSet};
+    use crate::message::{tx, Raw};
+    use crate::mock::{self, mock_can, Caps};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use vcell::VolatileCell;
+
+    mock_can!(Can0);
+    mock_can!(Can1);
+    mock_can!(Can2);
+    mock_can!(Can3);
+
+    const TXFQS: usize = 0xc4;
+    const TXBAR: usize = 0xd0;
+    const TFQF: u32 = 1 << 21;
+
+    fn put_index(index: u32) -> u32 {
+        index << 16
+    }
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    type Memory =
+        crate::array::GenericArray<VolatileCell<tx::Message<8>>, generic_array::typenum::U4>;
+
+    /// Buffers initially holding messages with ID 0x7ff
+    fn memory() -> Memory {
+        [(); 4]
+            .map(|_| VolatileCell::new(mock::message::<tx::Message<8>>(0x7ff)))
+            .into()
+    }
+
+    fn poll<F: Future + Unpin>(future: &mut F, waker: &Waker) -> Poll<F::Output> {
+        Pin::new(future).poll(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn reservation_waits_for_space_and_is_woken() {
+        let regs = Can0::regs();
+        regs.write(TXFQS, TFQF | put_index(2));
+        let mut memory = memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can0, Caps>::new(&mut memory, Mode::Classic) };
+        let tx_waker = TxWaker::new();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+
+        let mut future = tx.reserve_slot(&tx_waker);
+        assert!(poll(&mut future, &waker).is_pending());
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+        tx_waker.wake();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        // A wake-up without space keeps the future pending
+        assert!(poll(&mut future, &waker).is_pending());
+
+        regs.write(TXFQS, put_index(3));
+        tx_waker.wake();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        let Poll::Ready(reservation) = poll(&mut future, &waker) else {
+            panic!("queue has space");
+        };
+        assert_eq!(reservation.index(), 3);
+        assert_eq!(regs.read(TXBAR), 0);
+
+        let token = reservation
+            .send(mock::message::<tx::Message<8>>(0x123))
+            .unwrap();
+        assert_eq!(token.index(), 3);
+        assert_eq!(regs.read(TXBAR), 1 << 3);
+        assert_eq!(
+            memory[3].get().id(),
+            mock::message::<tx::Message<8>>(0x123).id()
+        );
+    }
+
+    #[test]
+    fn dropping_unused_reservation_writes_nothing() {
+        let regs = Can1::regs();
+        regs.write(TXFQS, put_index(2));
+        let mut memory = memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can1, Caps>::new(&mut memory, Mode::Classic) };
+        let tx_waker = TxWaker::new();
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+
+        {
+            let mut future = tx.reserve_slot(&tx_waker);
+            let Poll::Ready(reservation) = poll(&mut future, &waker) else {
+                panic!("queue has space");
+            };
+            assert_eq!(reservation.index(), 2);
+        }
+        assert_eq!(regs.read(TXBAR), 0);
+        assert_eq!(
+            memory[2].get().id(),
+            mock::message::<tx::Message<8>>(0x7ff).id()
+        );
+    }
+
+    #[test]
+    fn dropping_pending_future_leaves_queue_usable() {
+        let regs = Can2::regs();
+        regs.write(TXFQS, TFQF);
+        let mut memory = memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can2, Caps>::new(&mut memory, Mode::Classic) };
+        let tx_waker = TxWaker::new();
+        let stale = Arc::new(CountingWaker::default());
+        let fresh = Arc::new(CountingWaker::default());
+
+        {
+            let mut future = tx.reserve_slot(&tx_waker);
+            assert!(poll(&mut future, &Waker::from(stale.clone())).is_pending());
+        }
+
+        let mut future = tx.reserve_slot(&tx_waker);
+        assert!(poll(&mut future, &Waker::from(fresh.clone())).is_pending());
+        tx_waker.wake();
+        assert_eq!(stale.0.load(Ordering::SeqCst), 0);
+        assert_eq!(fresh.0.load(Ordering::SeqCst), 1);
+
+        regs.write(TXFQS, put_index(2));
+        assert!(poll(&mut future, &Waker::from(fresh)).is_ready());
+        assert_eq!(regs.read(TXBAR), 0);
+    }
+
+    #[test]
+    fn transmission_waits_for_space_and_queues_the_message() {
+        let regs = Can3::regs();
+        regs.write(TXFQS, TFQF);
+        let mut memory = memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can3, Caps>::new(&mut memory, Mode::Classic) };
+        let wakers = crate::interrupt::Wakers::new();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let token = {
+            let mut future = core::pin::pin!(
+                tx.transmit_queued_async(&wakers.tx, mock::message::<tx::Message<8>>(0x123))
+            );
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            regs.write(TXFQS, put_index(3));
+            wakers.wake_on(InterruptSet::empty().with(Interrupt::TransmissionCompleted));
+            assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+            let Poll::Ready(token) = future.as_mut().poll(&mut cx) else {
+                panic!("queue has space");
+            };
+            token
+        };
+        assert_eq!(token.unwrap().index(), 3);
+        assert_eq!(regs.read(TXBAR), 1 << 3);
+        assert_eq!(
+            memory[3].get().id(),
+            mock::message::<tx::Message<8>>(0x123).id()
+        );
+    }
+}
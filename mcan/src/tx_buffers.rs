@@ -5,13 +5,33 @@
 //! it up to the user to find unused spots for new transmit requests. The queue
 //! automatically selects where new messages are placed. The ordering of the
 //! queue is configurable; see [`crate::config::TxQueueMode`].
+//!
+//! # Completion ordering
+//!
+//! Only in [`TxQueueMode::Fifo`] are queued messages transmitted in the order
+//! they were enqueued. In [`TxQueueMode::Priority`], and for dedicated
+//! buffers in general, the peripheral picks the pending buffer with the
+//! highest priority (lowest ID), so transmission completed flags can be set
+//! in any order. Matching completed buffers positionally against a
+//! software-side queue of sent messages is therefore wrong. Use
+//! [`DynTx::take_completions`] instead, which reports the ID of every newly
+//! completed message alongside its buffer index.
+//!
+//! [`TxQueueMode::Fifo`]: crate::config::TxQueueMode::Fifo
+//! [`TxQueueMode::Priority`]: crate::config::TxQueueMode::Priority
 
+use crate::array::GenericArray;
 use crate::config::Mode;
+use crate::interrupt::{Interrupt, InterruptSet};
+use crate::message::{tx, TooMuchData};
 use crate::messageram::Capacities;
 use crate::reg;
+use crate::ring::RingIndices;
 use core::convert::Infallible;
 use core::marker::PhantomData;
-use generic_array::{typenum::Unsigned, GenericArray};
+use core::sync::atomic::{AtomicU32, Ordering};
+use embedded_can::{Id, StandardId};
+use generic_array::typenum::Unsigned;
 use reg::AccessRegisterBlock as _;
 use vcell::VolatileCell;
 
@@ -30,17 +50,92 @@ pub enum Error {
     /// In order to be able to send CAN FD messages change its mode of operation
     /// to [`Mode::Fd { bit_rate_switching: true }`].
     BitRateSwitchingDisabled,
+    /// The buffer referred to by a [`TxToken`] has been reused for another
+    /// message since the token was issued
+    SlotReused,
+    /// The payload of a [`tx::MessageBuilder`] does not fit the message size
+    /// or its frame format
+    TooMuchData,
+}
+
+impl From<TooMuchData> for Error {
+    fn from(_: TooMuchData) -> Self {
+        Self::TooMuchData
+    }
+}
+
+/// Why a message cannot be put in a transmit buffer at the moment
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TxBlockedReason {
+    /// All buffers of the queue are pending. Space becomes available as soon
+    /// as one of them is transmitted or cancelled.
+    QueueFull,
+    /// The dedicated buffer still holds a message pending transmission. It
+    /// becomes available once that message is transmitted or cancelled.
+    BufferPending,
+    /// The peripheral is in initialization mode, e.g. because it went bus off,
+    /// so pending messages are not transmitted until it is operational again.
+    NotOperational,
+}
+
+/// Change of the full state of the transmit queue, see
+/// [`DynTx::poll_queue_full`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QueueFullTransition {
+    /// All buffers of the queue are pending
+    BecameFull,
+    /// A buffer of the queue was released, so a message can be queued again
+    BecameAvailable,
+}
+
+/// Error returned when a message cannot be put in a transmit buffer
+///
+/// Mirrors [`nb::Error`], with the reason for blocking attached. Use
+/// [`From`] to convert it to a [`nb::Error`] for use with [`nb::block`].
+#[derive(Debug)]
+pub enum TransmitError {
+    /// The message could be accepted later
+    WouldBlock(TxBlockedReason),
+    /// The message cannot be accepted
+    Other(Error),
+}
+
+impl From<Error> for TransmitError {
+    fn from(error: Error) -> Self {
+        Self::Other(error)
+    }
+}
+
+impl From<TransmitError> for nb::Error<Error> {
+    fn from(error: TransmitError) -> Self {
+        match error {
+            TransmitError::WouldBlock(_) => nb::Error::WouldBlock,
+            TransmitError::Other(error) => nb::Error::Other(error),
+        }
+    }
 }
 
 /// Transmit queue and dedicated buffers
 pub struct Tx<'a, P, C: Capacities> {
     memory: &'a mut GenericArray<VolatileCell<C::TxMessage>, C::TxBuffers>,
     pub(crate) mode: Mode,
+    /// Transmission completed flags already reported by
+    /// [`DynTx::take_completions`]
+    reported_completions: u32,
+    /// Dedicated buffers cancelled by [`DynTx::transmit_dedicated_replace`]
+    replacing: u32,
+    /// Cancellation reported on by [`DynTx::cancel_and_report`]
+    cancelling: Option<Cancellation>,
+    generations: Generations,
+    /// Full state of the queue last reported by [`DynTx::poll_queue_full`]
+    queue_full: bool,
+    /// Messages put in the queue, reported by [`DynTx::queued_count`]
+    queued: u32,
     _markers: PhantomData<P>,
 }
 
 /// Trait which erases generic parametrization for [`Tx`] type
-pub trait DynTx {
+pub trait DynTx: crate::sealed::Sealed {
     /// CAN identity type
     type Id;
 
@@ -48,14 +143,90 @@ pub trait DynTx {
     type Message;
 
     /// Puts a frame in the specified dedicated transmit buffer to be sent on
-    /// the bus. Fails with [`nb::Error::WouldBlock`] if the transmit buffer
-    /// is full.
-    fn transmit_dedicated(&mut self, index: usize, message: Self::Message)
-        -> nb::Result<(), Error>;
+    /// the bus. Fails with [`TransmitError::WouldBlock`] if the transmit
+    /// buffer is still pending, and with [`Error::OutOfBounds`] if `index` is
+    /// not a dedicated buffer.
+    fn transmit_dedicated(
+        &mut self,
+        index: usize,
+        message: Self::Message,
+    ) -> Result<(), TransmitError>;
+
+    /// Puts a frame in the specified dedicated transmit buffer, replacing the
+    /// frame pending there if any, e.g. to always send the latest data of a
+    /// cyclic message.
+    ///
+    /// A pending frame is cancelled first, which fails with
+    /// [`TxBlockedReason::BufferPending`] until the cancellation finished, so
+    /// the call has to be repeated, e.g. with [`nb::block`] after converting
+    /// the error. The returned [`ReplaceOutcome`] tells whether the frame
+    /// pending at the first call was replaced or transmitted anyway because
+    /// its transmission had already started. Fails with
+    /// [`Error::OutOfBounds`] if `index` is not a dedicated buffer.
+    fn transmit_dedicated_replace(
+        &mut self,
+        index: usize,
+        message: Self::Message,
+    ) -> Result<ReplaceOutcome, TransmitError>;
 
     /// Puts a frame in the queue to be sent on the bus.
-    /// Fails with [`nb::Error::WouldBlock`] if the transmit buffer is full.
-    fn transmit_queued(&mut self, message: Self::Message) -> nb::Result<(), Error>;
+    /// Fails with [`nb::Error::WouldBlock`] if the queue is full.
+    #[deprecated(note = "use `transmit_queued_indexed`, which identifies the buffer used")]
+    fn transmit_queued(&mut self, message: Self::Message) -> nb::Result<(), Error> {
+        self.transmit_queued_indexed(message)?;
+        Ok(())
+    }
+
+    /// Puts a frame in the queue to be sent on the bus.
+    /// Fails with [`TransmitError::WouldBlock`] if the queue is full.
+    ///
+    /// Returns a [`TxToken`] identifying the buffer the frame was placed in,
+    /// which can later be used with [`Self::status_of`] and
+    /// [`Self::cancel_token`].
+    fn transmit_queued_indexed(&mut self, message: Self::Message)
+        -> Result<TxToken, TransmitError>;
+
+    /// Builds a frame and puts it in the queue, see
+    /// [`Self::transmit_queued_indexed`]
+    ///
+    /// Fails with [`Error::TooMuchData`] if the payload does not fit the
+    /// message size. Like every transmission, frames that the configured
+    /// [`Mode`] does not allow are rejected with [`Error::FdDisabled`] or
+    /// [`Error::BitRateSwitchingDisabled`] before checking for free buffers.
+    fn transmit_queued_checked(
+        &mut self,
+        builder: tx::MessageBuilder<'_>,
+    ) -> Result<TxToken, TransmitError>
+    where
+        Self::Message: tx::AnyMessage,
+    {
+        let message = <Self::Message as tx::AnyMessage>::new(builder).map_err(Error::from)?;
+        self.transmit_queued_indexed(message)
+    }
+
+    /// Builds a frame and puts it in the specified dedicated transmit buffer,
+    /// see [`Self::transmit_dedicated`] and [`Self::transmit_queued_checked`]
+    fn transmit_dedicated_checked(
+        &mut self,
+        index: usize,
+        builder: tx::MessageBuilder<'_>,
+    ) -> Result<(), TransmitError>
+    where
+        Self::Message: tx::AnyMessage,
+    {
+        let message = <Self::Message as tx::AnyMessage>::new(builder).map_err(Error::from)?;
+        self.transmit_dedicated(index, message)
+    }
+
+    /// Returns the state of the transmission identified by `token`.
+    fn status_of(&self, token: TxToken) -> TxSlotStatus;
+
+    /// Request cancellation of the transmission identified by `token`. See
+    /// [`Self::cancel_multi`].
+    ///
+    /// Fails with [`Error::SlotReused`] instead of cancelling an unrelated
+    /// message if the buffer has since been reused.
+    fn cancel_token(&mut self, token: TxToken) -> nb::Result<(), Error>;
 
     /// Allow [`Interrupt::TransmissionCancellationFinished`] to be triggered by
     /// `to_be_enabled`. Interrupts for other buffers remain unchanged.
@@ -87,6 +258,63 @@ pub trait DynTx {
     /// [`Interrupt::TransmissionCompleted`]: crate::interrupt::Interrupt::TransmissionCompleted
     fn disable_transmission_completed_interrupt(&mut self, to_be_disabled: TxBufferSet);
 
+    /// Returns `true` if no message in the queue is pending transmission, the
+    /// polled counterpart of [`Interrupt::TxFifoEmpty`]. Dedicated buffers
+    /// are not taken into account.
+    ///
+    /// [`Interrupt::TxFifoEmpty`]: crate::interrupt::Interrupt::TxFifoEmpty
+    fn is_queue_empty(&self) -> bool;
+
+    /// Returns `true` if all buffers of the queue are pending, so that
+    /// [`Self::transmit_queued_indexed`] would block with
+    /// [`TxBlockedReason::QueueFull`]. Always `true` if all buffers are
+    /// dedicated.
+    fn is_queue_full(&self) -> bool;
+
+    /// Returns the number of messages that can be put in the queue before it
+    /// is full. Dedicated buffers are not taken into account.
+    ///
+    /// In FIFO mode, this counts the buffers from the put index up to the get
+    /// index, so buffers freed out of order by cancellation only count once
+    /// the get index has passed them.
+    fn queue_free_level(&self) -> usize;
+
+    /// Returns the index of the buffer the next queued message is put in, or
+    /// `None` if the queue is full or all buffers are dedicated
+    ///
+    /// The index counts from the first dedicated buffer, like the indices of
+    /// [`TxBufferSet`].
+    fn queue_put_index(&self) -> Option<usize>;
+
+    /// Reports a change of [`Self::is_queue_full`] since the previous call,
+    /// e.g. to resume queueing without enabling
+    /// [`Interrupt::TxFifoEmpty`], which only triggers once the whole queue
+    /// is sent. It can be called from the handler of
+    /// [`Interrupt::TransmissionCompleted`] or from a main loop.
+    ///
+    /// Each transition is reported exactly once; `None` is returned if the
+    /// state did not change. The state is sampled on every call, so a queue
+    /// that became available and full again between two calls reports
+    /// nothing, and the other way around. As only this handle fills the
+    /// queue, it can only have become full again through messages queued in
+    /// between, which found the space they were waiting for.
+    ///
+    /// [`Interrupt::TxFifoEmpty`]: crate::interrupt::Interrupt::TxFifoEmpty
+    /// [`Interrupt::TransmissionCompleted`]: crate::interrupt::Interrupt::TransmissionCompleted
+    fn poll_queue_full(&mut self) -> Option<QueueFullTransition>;
+
+    /// Returns the number of messages in the queue pending transmission.
+    /// Dedicated buffers are not taken into account.
+    fn queue_len(&self) -> usize;
+
+    /// Number of messages put in the queue through this handle, wrapping
+    /// around at [`u32::MAX`]
+    ///
+    /// Along with [`Self::queue_len`], this tells how many messages left the
+    /// queue between two calls, transmitted or cancelled, even if the queue
+    /// was refilled in between.
+    fn queued_count(&self) -> u32;
+
     /// Returns the set of `TxBuffer`s that the peripheral indicates have been
     /// cancelled. The flags are only cleared when a new transmission is
     /// requested for the buffer.
@@ -107,6 +335,20 @@ pub trait DynTx {
     /// when a new transmission is requested for the buffer.
     fn iter_transmission_completed_flags(&self) -> Iter;
 
+    /// Returns the set of `TxBuffer`s with a transmission requested but not
+    /// yet finished, whether dedicated or part of the queue
+    ///
+    /// Only buffers that exist are included, so the set can be passed to
+    /// [`Self::cancel_multi`], e.g. to cancel what is still pending after a
+    /// deadline.
+    fn pending_buffers(&self) -> TxBufferSet;
+
+    /// Returns `true` if a transmission is requested from the buffer `index`
+    /// and not yet finished, see [`Self::pending_buffers`]
+    ///
+    /// Fails with [`Error::OutOfBounds`] if there is no buffer `index`.
+    fn is_pending(&self, index: usize) -> Result<bool, Error>;
+
     /// Request cancellation of `to_be_canceled`. Returns
     /// [`nb::Error::WouldBlock`] until the cancellation is finished. If a
     /// buffer that has started transmission is canceled, it may still finish
@@ -118,9 +360,67 @@ pub trait DynTx {
 
     /// Request cancellation of a transmit buffer. See [`Self::cancel_multi`].
     fn cancel(&mut self, index: usize) -> nb::Result<(), Infallible>;
+
+    /// Request cancellation of `to_be_canceled` and report which of the
+    /// buffers were cancelled and which were transmitted anyway. Returns
+    /// [`nb::Error::WouldBlock`] while any of them is still pending.
+    ///
+    /// The flags of a buffer are only cleared when it is reused, so the ones
+    /// set before the first call are left out of the outcome. Buffers that
+    /// were not pending are in neither set.
+    ///
+    /// Meant to be called again with the same set until the outcome is
+    /// returned. Calling it with another set, or transmitting from one of the
+    /// buffers in between, requests the cancellation anew.
+    fn cancel_and_report(
+        &mut self,
+        to_be_canceled: TxBufferSet,
+    ) -> nb::Result<CancellationOutcome, Infallible>;
+
+    /// Request cancellation of every pending buffer holding a message of
+    /// lower priority than `id`, e.g. to make room in a full queue for an
+    /// urgent message. Returns the buffers whose cancellation was requested.
+    ///
+    /// Priority follows the arbitration rules of [`Id`]'s `Ord`. Meant for
+    /// [`TxQueueMode::Priority`], where the peripheral sends the highest
+    /// priority first. In [`TxQueueMode::Fifo`], lower priority messages
+    /// ahead of `id` in the queue still block it until they are cancelled.
+    ///
+    /// Buffers that finish transmission while the messages are compared are
+    /// left out. A buffer whose transmission already started may still be
+    /// transmitted, so once a buffer of the set is no longer pending, it
+    /// only needs to be requeued if its cancellation flag is set and its
+    /// transmission completed flag is not. Its message can be read back with
+    /// [`Self::buffered_message`] until the buffer is reused.
+    ///
+    /// [`TxQueueMode::Priority`]: crate::config::TxQueueMode::Priority
+    /// [`TxQueueMode::Fifo`]: crate::config::TxQueueMode::Fifo
+    fn cancel_lower_priority_than(&mut self, id: Id) -> TxBufferSet;
+
+    /// Returns the message last put in buffer `index`
+    ///
+    /// Fails with [`Error::OutOfBounds`] if there is no buffer `index`.
+    fn buffered_message(&self, index: usize) -> Result<Self::Message, Error>;
+
+    /// Returns the buffers that finished transmission since the last call,
+    /// each paired with the ID of the message that was sent from it.
+    ///
+    /// Unlike [`Self::get_transmission_completed_flags`], a completed
+    /// transmission is only reported once. The IDs are read back from Message
+    /// RAM, so completions can be correlated with sent messages regardless of
+    /// the order in which the peripheral transmitted them. See the
+    /// [module documentation](self#completion-ordering) for why this matters.
+    fn take_completions(&mut self) -> CompletionSet;
 }
 
 impl<'a, P: mcan_core::CanId, C: Capacities> Tx<'a, P, C> {
+    /// Interrupts raised by the transmit buffers and the queue
+    pub const INTERRUPTS: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::TransmissionCompleted,
+        Interrupt::TransmissionCancellationFinished,
+        Interrupt::TxFifoEmpty,
+    ]);
+
     /// # Safety
     /// The caller must be the owner or the peripheral referenced by `P`. The
     /// constructed type assumes ownership of some of the registers from the
@@ -141,6 +441,12 @@ impl<'a, P: mcan_core::CanId, C: Capacities> Tx<'a, P, C> {
         Self {
             memory,
             mode,
+            reported_completions: 0,
+            replacing: 0,
+            cancelling: None,
+            generations: Generations::new(),
+            queue_full: false,
+            queued: 0,
             _markers: PhantomData,
         }
     }
@@ -199,36 +505,74 @@ impl<'a, P: mcan_core::CanId, C: Capacities> Tx<'a, P, C> {
     }
 
     fn is_buffer_in_use(&self, index: usize) -> bool {
-        // It is unclear from the datasheet when BRP is updated. It is hopefully done
-        // before clearing BAR, so that we don't get any false "not in use" from this.
-        let add_requests = self.txbar().read().bits();
-        let pending = self.txbrp().read().bits();
-        (add_requests | pending) & (1 << index) != 0
+        self.pending_buffers().0 & (1 << index) != 0
     }
 
     /// Puts a frame in the specified transmit buffer to be sent on the bus.
-    /// Fails with [`nb::Error::WouldBlock`] if the transmit buffer is full.
-    fn transmit(&mut self, index: usize, message: C::TxMessage) -> nb::Result<(), Error> {
+    /// Fails with [`TxBlockedReason::BufferPending`] if the transmit buffer is
+    /// in use.
+    fn transmit(&mut self, index: usize, message: C::TxMessage) -> Result<TxToken, TransmitError> {
         if self.is_buffer_in_use(index) {
-            return Err(nb::Error::WouldBlock);
+            return Err(self.blocked(TxBlockedReason::BufferPending));
         }
+        Ok(self.write(index, message)?)
+    }
+
+    /// Error for a transmission blocked by `reason`, unless the peripheral is
+    /// not operational in the first place.
+    fn blocked(&self, reason: TxBlockedReason) -> TransmitError {
+        // Safety: Reading CCCR has no side effects.
+        let initializing = unsafe { self.regs().cccr.read().init().bit() };
+        TransmitError::WouldBlock(if initializing {
+            TxBlockedReason::NotOperational
+        } else {
+            reason
+        })
+    }
+
+    /// Puts a frame in the specified transmit buffer and requests its
+    /// transmission, assuming that the buffer is not in use.
+    pub(crate) fn write(&mut self, index: usize, message: C::TxMessage) -> Result<TxToken, Error> {
         self.validate_message(&message)?;
         self.memory
             .get_mut(index)
             .ok_or(Error::OutOfBounds)?
             .set(message);
+        // The completed flag of the buffer is cleared by the new request, so a
+        // later completion has to be reported again.
+        self.reported_completions &= !(1 << index);
+        self.replacing &= !(1 << index);
+        if self
+            .cancelling
+            .is_some_and(|cancellation| cancellation.requested & 1 << index != 0)
+        {
+            self.cancelling = None;
+        }
+        let token = self.generations.issue(index);
+        if index >= C::DedicatedTxBuffers::USIZE {
+            self.queued = self.queued.wrapping_add(1);
+        }
         self.add_request(index);
-        Ok(())
+        Ok(token)
     }
 
     /// Returns the put index if available. `None` if the queue is full.
-    fn find_put_index(&self) -> Option<usize> {
+    pub(crate) fn find_put_index(&self) -> Option<usize> {
+        let position = self.queue_ring().next_free()?;
+        Some(C::DedicatedTxBuffers::USIZE + position)
+    }
+
+    /// Get and put positions of the queue, relative to its first buffer
+    fn queue_ring(&self) -> RingIndices {
+        // The indices count from the first dedicated buffer.
+        let queue_start = C::DedicatedTxBuffers::USIZE;
         let status = self.txfqs().read();
-        if status.tfqf().bit() {
-            None
-        } else {
-            Some(status.tfqpi().bits() as usize)
-        }
+        RingIndices::from_positions(
+            (status.tfgi().bits() as usize).saturating_sub(queue_start),
+            (status.tfqpi().bits() as usize).saturating_sub(queue_start),
+            status.tfqf().bit(),
+            C::TxBuffers::USIZE - queue_start,
+        )
     }
 
     fn poll_canceled(&self, to_be_canceled: TxBufferSet) -> nb::Result<(), Infallible> {
@@ -260,6 +604,8 @@ impl<'a, P: mcan_core::CanId, C: Capacities> Tx<'a, P, C> {
     }
 }
 
+impl<P, C: Capacities> crate::sealed::Sealed for Tx<'_, P, C> {}
+
 impl<'a, P: mcan_core::CanId, C: Capacities> DynTx for Tx<'a, P, C> {
     type Id = P;
     type Message = C::TxMessage;
@@ -268,18 +614,74 @@ impl<'a, P: mcan_core::CanId, C: Capacities> DynTx for Tx<'a, P, C> {
         &mut self,
         index: usize,
         message: Self::Message,
-    ) -> nb::Result<(), Error> {
-        if index > C::DedicatedTxBuffers::USIZE {
+    ) -> Result<(), TransmitError> {
+        if index >= C::DedicatedTxBuffers::USIZE {
             Err(Error::OutOfBounds)?;
         }
-        self.transmit(index, message)
+        // Frames the mode does not allow would never be accepted, so they
+        // are rejected instead of blocking on a pending buffer
+        self.validate_message(&message)?;
+        self.transmit(index, message).map(|_| ())
     }
 
-    fn transmit_queued(&mut self, message: Self::Message) -> nb::Result<(), Error> {
-        let index = self.find_put_index().ok_or(nb::Error::WouldBlock)?;
+    fn transmit_dedicated_replace(
+        &mut self,
+        index: usize,
+        message: Self::Message,
+    ) -> Result<ReplaceOutcome, TransmitError> {
+        if index >= C::DedicatedTxBuffers::USIZE {
+            Err(Error::OutOfBounds)?;
+        }
+        self.validate_message(&message)?;
+        let mask = 1 << index;
+        let pending = self.pending_buffers().0;
+        if pending & mask != 0 && self.replacing & mask == 0 {
+            // Safety: There are no reserved bit patterns.
+            unsafe {
+                self.txbcr().write(|w| w.bits(mask));
+            }
+            self.replacing |= mask;
+        }
+        let outcome = ReplaceOutcome::new(
+            self.replacing & mask != 0,
+            pending & mask != 0,
+            self.get_transmission_completed_flags().0 & mask != 0,
+        )
+        .ok_or_else(|| self.blocked(TxBlockedReason::BufferPending))?;
+        self.write(index, message)?;
+        Ok(outcome)
+    }
+
+    fn transmit_queued_indexed(
+        &mut self,
+        message: Self::Message,
+    ) -> Result<TxToken, TransmitError> {
+        self.validate_message(&message)?;
+        let index = self
+            .find_put_index()
+            .ok_or_else(|| self.blocked(TxBlockedReason::QueueFull))?;
         self.transmit(index, message)
     }
 
+    fn status_of(&self, token: TxToken) -> TxSlotStatus {
+        let pending = self.txbar().read().bits() | self.txbrp().read().bits();
+        TxSlotStatus::new(
+            token,
+            self.generations.is_current(token),
+            pending,
+            self.get_transmission_completed_flags().0,
+            self.get_cancellation_flags().0,
+        )
+    }
+
+    fn cancel_token(&mut self, token: TxToken) -> nb::Result<(), Error> {
+        if !self.generations.is_current(token) {
+            return Err(nb::Error::Other(Error::SlotReused));
+        }
+        self.cancel(token.index())
+            .map_err(|nb::Error::WouldBlock| nb::Error::WouldBlock)
+    }
+
     fn enable_cancellation_interrupt(&mut self, to_be_enabled: TxBufferSet) {
         // Safety: There are no reserved bit patterns.
         unsafe {
@@ -312,6 +714,53 @@ impl<'a, P: mcan_core::CanId, C: Capacities> DynTx for Tx<'a, P, C> {
         }
     }
 
+    fn is_queue_empty(&self) -> bool {
+        let pending = self.txbar().read().bits() | self.txbrp().read().bits();
+        pending >> C::DedicatedTxBuffers::USIZE == 0
+    }
+
+    fn is_queue_full(&self) -> bool {
+        C::TxBuffers::USIZE == C::DedicatedTxBuffers::USIZE || self.txfqs().read().tfqf().bit()
+    }
+
+    fn queue_free_level(&self) -> usize {
+        // Safety: TXBC is only read.
+        let queue_mode = unsafe { self.regs().txbc.read().tfqm().bit() };
+        if queue_mode {
+            // The peripheral reports no free level in queue mode, but any
+            // buffer that is not pending can be used.
+            (C::TxBuffers::USIZE - C::DedicatedTxBuffers::USIZE).saturating_sub(self.queue_len())
+        } else {
+            self.queue_ring().free()
+        }
+    }
+
+    fn queue_put_index(&self) -> Option<usize> {
+        self.find_put_index()
+    }
+
+    fn poll_queue_full(&mut self) -> Option<QueueFullTransition> {
+        let full = self.is_queue_full();
+        if full == self.queue_full {
+            return None;
+        }
+        self.queue_full = full;
+        Some(if full {
+            QueueFullTransition::BecameFull
+        } else {
+            QueueFullTransition::BecameAvailable
+        })
+    }
+
+    fn queue_len(&self) -> usize {
+        let pending = self.txbar().read().bits() | self.txbrp().read().bits();
+        (pending >> C::DedicatedTxBuffers::USIZE).count_ones() as usize
+    }
+
+    fn queued_count(&self) -> u32 {
+        self.queued
+    }
+
     fn get_cancellation_flags(&self) -> TxBufferSet {
         TxBufferSet(self.txbcf().read().bits())
     }
@@ -328,6 +777,24 @@ impl<'a, P: mcan_core::CanId, C: Capacities> DynTx for Tx<'a, P, C> {
         self.get_transmission_completed_flags().iter()
     }
 
+    fn pending_buffers(&self) -> TxBufferSet {
+        // It is unclear from the datasheet when BRP is updated. It is hopefully done
+        // before clearing BAR, so that we don't get any false "not in use" from this.
+        let add_requests = self.txbar().read().bits();
+        let pending = self.txbrp().read().bits();
+        let buffers = u32::MAX
+            .checked_shr(32 - C::TxBuffers::USIZE as u32)
+            .unwrap_or(0);
+        TxBufferSet((add_requests | pending) & buffers)
+    }
+
+    fn is_pending(&self, index: usize) -> Result<bool, Error> {
+        if index >= C::TxBuffers::USIZE {
+            return Err(Error::OutOfBounds);
+        }
+        Ok(self.is_buffer_in_use(index))
+    }
+
     fn cancel_multi(&mut self, to_be_canceled: TxBufferSet) -> nb::Result<(), Infallible> {
         self.poll_canceled(to_be_canceled).or_else(|_| {
             // Safety: There are no reserved bit patterns.
@@ -341,11 +808,326 @@ impl<'a, P: mcan_core::CanId, C: Capacities> DynTx for Tx<'a, P, C> {
     fn cancel(&mut self, index: usize) -> nb::Result<(), Infallible> {
         self.cancel_multi([index].into_iter().collect())
     }
+
+    fn cancel_and_report(
+        &mut self,
+        to_be_canceled: TxBufferSet,
+    ) -> nb::Result<CancellationOutcome, Infallible> {
+        let cancellation = match self.cancelling {
+            Some(cancellation) if cancellation.requested == to_be_canceled.0 => cancellation,
+            _ => {
+                let cancellation = Cancellation {
+                    requested: to_be_canceled.0,
+                    transmitted: self.get_transmission_completed_flags().0,
+                    cancelled: self.get_cancellation_flags().0,
+                };
+                // Safety: There are no reserved bit patterns.
+                unsafe {
+                    self.txbcr().write(|w| w.bits(cancellation.requested));
+                }
+                self.cancelling = Some(cancellation);
+                cancellation
+            }
+        };
+        if self.pending_buffers().0 & cancellation.requested != 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.cancelling = None;
+        Ok(cancellation.outcome(
+            self.get_transmission_completed_flags().0,
+            self.get_cancellation_flags().0,
+        ))
+    }
+
+    fn cancel_lower_priority_than(&mut self, id: Id) -> TxBufferSet {
+        use crate::message::Raw;
+        let lower: TxBufferSet = self
+            .pending_buffers()
+            .iter()
+            .filter(|&index| {
+                self.memory
+                    .get(index)
+                    .is_some_and(|message| message.get().id() > id)
+            })
+            .collect();
+        if lower.0 == 0 {
+            return lower;
+        }
+        // Safety: There are no reserved bit patterns.
+        unsafe {
+            self.txbcr().write(|w| w.bits(lower.0));
+        }
+        // Requests for buffers that finished in the meantime have no effect.
+        // Those cancelled right away are no longer pending, but flagged.
+        let requested = self.pending_buffers().0 | self.get_cancellation_flags().0;
+        TxBufferSet(lower.0 & requested)
+    }
+
+    fn buffered_message(&self, index: usize) -> Result<Self::Message, Error> {
+        self.memory
+            .get(index)
+            .map(VolatileCell::get)
+            .ok_or(Error::OutOfBounds)
+    }
+
+    fn take_completions(&mut self) -> CompletionSet {
+        let completed = self.get_transmission_completed_flags().0;
+        let (set, reported) =
+            CompletionSet::new(self.reported_completions, completed, &self.memory[..]);
+        self.reported_completions = reported;
+        set
+    }
+}
+
+/// Transmit buffers that finished transmission, together with the IDs of the
+/// messages they held.
+///
+/// This `struct` is created by [`DynTx::take_completions`].
+#[derive(Copy, Clone, Debug)]
+pub struct CompletionSet {
+    buffers: TxBufferSet,
+    ids: [Id; 32],
+}
+
+impl CompletionSet {
+    /// Computes the flags in `completed` that are not in `reported` and reads
+    /// back the IDs of the corresponding messages from `memory`. Also returns
+    /// the new set of reported flags.
+    fn new<M: crate::message::Raw + Copy>(
+        reported: u32,
+        completed: u32,
+        memory: &[VolatileCell<M>],
+    ) -> (Self, u32) {
+        // Flags that are no longer set have been cleared by a new transmission
+        // request and must not mask future completions.
+        let reported = reported & completed;
+        let mut set = Self {
+            buffers: TxBufferSet(completed & !reported),
+            ids: [Id::Standard(StandardId::ZERO); 32],
+        };
+        for (index, id) in set.ids.iter_mut().enumerate() {
+            if set.buffers.0 & (1 << index) != 0 {
+                match memory.get(index) {
+                    Some(message) => *id = message.get().id(),
+                    None => set.buffers.0 &= !(1 << index),
+                }
+            }
+        }
+        (set, completed)
+    }
+
+    /// Buffers that finished transmission
+    pub fn buffers(&self) -> TxBufferSet {
+        self.buffers
+    }
+
+    /// Returns `true` if no buffers finished transmission
+    pub fn is_empty(&self) -> bool {
+        self.buffers.0 == 0
+    }
+
+    /// ID of the message sent from buffer `index`, if it is part of the set
+    pub fn id(&self, index: usize) -> Option<Id> {
+        self.buffers
+            .iter()
+            .find(|&i| i == index)
+            .and_then(|i| self.ids.get(i).copied())
+    }
+
+    /// An iterator over buffer indexes and message IDs, in buffer index order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Id)> + '_ {
+        self.buffers
+            .iter()
+            .filter_map(|i| Some((i, *self.ids.get(i)?)))
+    }
+}
+
+/// Identifies a single transmission request made through
+/// [`DynTx::transmit_queued_indexed`].
+///
+/// Buffers are reused for new messages once their transmission finished. The
+/// token remembers which use of the buffer it refers to, so that it cannot be
+/// used to act on a later message placed in the same buffer. This holds
+/// across instances of [`Tx`] as well, e.g. after the peripheral was released
+/// and constructed again.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TxToken {
+    index: u8,
+    epoch: u32,
+    generation: u32,
+}
+
+impl TxToken {
+    /// Index of the transmit buffer the message was placed in
+    pub fn index(&self) -> usize {
+        self.index.into()
+    }
+}
+
+/// State of a transmission identified by a [`TxToken`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TxSlotStatus {
+    /// The message is waiting for or undergoing transmission
+    Pending,
+    /// The message was successfully transmitted
+    Transmitted,
+    /// The transmission was cancelled or aborted due to an error
+    Cancelled,
+    /// The buffer has been reused for another message, so the state of the
+    /// identified transmission is no longer known
+    Reused,
+    /// The buffer holds no request, which is the case after the peripheral has
+    /// been reconfigured
+    Idle,
+}
+
+impl TxSlotStatus {
+    fn new(token: TxToken, current: bool, pending: u32, transmitted: u32, cancelled: u32) -> Self {
+        let mask = 1 << token.index;
+        if !current {
+            Self::Reused
+        } else if pending & mask != 0 {
+            Self::Pending
+        } else if transmitted & mask != 0 {
+            Self::Transmitted
+        } else if cancelled & mask != 0 {
+            Self::Cancelled
+        } else {
+            Self::Idle
+        }
+    }
+}
+
+/// What happened to the frame pending in a dedicated buffer, see
+/// [`DynTx::transmit_dedicated_replace`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplaceOutcome {
+    /// No frame was pending
+    Idle,
+    /// The pending frame was cancelled before it was transmitted
+    Replaced,
+    /// The pending frame was transmitted before the cancellation took effect
+    Transmitted,
+}
+
+impl ReplaceOutcome {
+    /// Outcome once the buffer is no longer `pending`, given whether its
+    /// cancellation was `requested` and whether the completed flag of the
+    /// buffer is set. `None` while the cancellation is in progress.
+    ///
+    /// A transmission that completes while it is being cancelled sets both
+    /// the completed and the cancellation flag, so only the former tells it
+    /// apart.
+    fn new(requested: bool, pending: bool, transmitted: bool) -> Option<Self> {
+        Some(match (requested, pending, transmitted) {
+            (_, true, _) => return None,
+            (false, false, _) => Self::Idle,
+            (true, false, false) => Self::Replaced,
+            (true, false, true) => Self::Transmitted,
+        })
+    }
+}
+
+/// What happened to the buffers of a cancellation, see
+/// [`DynTx::cancel_and_report`]
+#[derive(Copy, Clone, Debug)]
+pub struct CancellationOutcome {
+    /// Buffers whose transmission completed before the cancellation took
+    /// effect
+    pub transmitted_anyway: TxBufferSet,
+    /// Buffers whose transmission was cancelled, either before it started or
+    /// by aborting it due to an error
+    pub cancelled: TxBufferSet,
+}
+
+/// Cancellation of the `requested` buffers, with the transmission completed
+/// and cancellation flags as they were before it was requested
+#[derive(Copy, Clone)]
+struct Cancellation {
+    requested: u32,
+    transmitted: u32,
+    cancelled: u32,
+}
+
+impl Cancellation {
+    /// Outcome given the flags once none of the requested buffers is pending
+    ///
+    /// A transmission that completes while it is being cancelled sets both
+    /// flags, so only buffers without the completed flag count as cancelled.
+    fn outcome(&self, transmitted: u32, cancelled: u32) -> CancellationOutcome {
+        CancellationOutcome {
+            transmitted_anyway: TxBufferSet(transmitted & !self.transmitted & self.requested),
+            cancelled: TxBufferSet(cancelled & !self.cancelled & !transmitted & self.requested),
+        }
+    }
+}
+
+/// Epoch of the last [`Tx`] created
+static LAST_EPOCH: AtomicU32 = AtomicU32::new(0);
+
+/// Advances [`LAST_EPOCH`] and returns the new epoch
+///
+/// Targets without read-modify-write atomics advance it with a load and a
+/// store. Instances of [`Tx`] are only created while a peripheral is
+/// constructed, which on these single-core targets is not expected to be
+/// preempted by the construction of another one.
+fn next_epoch() -> u32 {
+    #[cfg(target_has_atomic = "32")]
+    let epoch = LAST_EPOCH.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+    #[cfg(not(target_has_atomic = "32"))]
+    let epoch = {
+        let epoch = LAST_EPOCH.load(Ordering::Relaxed).wrapping_add(1);
+        LAST_EPOCH.store(epoch, Ordering::Relaxed);
+        epoch
+    };
+    epoch
+}
+
+/// Number of transmission requests made for each transmit buffer of one
+/// instance of [`Tx`], identified by its `epoch`
+#[derive(Default)]
+struct Generations {
+    epoch: u32,
+    counts: [u32; 32],
+}
+
+impl Generations {
+    /// Counters for a new instance, whose tokens are never current for the
+    /// instances created before
+    fn new() -> Self {
+        Self {
+            epoch: next_epoch(),
+            counts: [0; 32],
+        }
+    }
+
+    /// Records a new transmission request for buffer `index` and returns the
+    /// token identifying it.
+    ///
+    /// Tokens for indices beyond the 32 buffers of the peripheral are never
+    /// current.
+    fn issue(&mut self, index: usize) -> TxToken {
+        let generation = self.counts.get_mut(index).map_or(0, |generation| {
+            *generation = generation.wrapping_add(1);
+            *generation
+        });
+        TxToken {
+            index: index as u8,
+            epoch: self.epoch,
+            generation,
+        }
+    }
+
+    /// Returns `true` if `token` was issued by this instance and no
+    /// transmission requests have been made for its buffer since.
+    fn is_current(&self, token: TxToken) -> bool {
+        token.epoch == self.epoch && self.counts.get(token.index()) == Some(&token.generation)
+    }
 }
 
 /// A set of transmit buffers, which may be dedicated buffers or part of the
 /// queue.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct TxBufferSet(pub u32);
 impl FromIterator<usize> for TxBufferSet {
     fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
@@ -384,14 +1166,578 @@ impl Iterator for Iter {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let i = self.index;
-        self.index = self.index.saturating_add(1);
-        if i > 31 {
-            None
-        } else if self.flags.0 & (1 << i) != 0 {
-            Some(i as usize)
-        } else {
-            self.next()
+        let remaining = self.flags.0.checked_shr(self.index.into()).unwrap_or(0);
+        if remaining == 0 {
+            self.index = 32;
+            return None;
+        }
+        let i = self.index + remaining.trailing_zeros() as u8;
+        self.index = i + 1;
+        Some(i as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::message::tx;
+    use crate::mock::{self, mock_can, Caps};
+    use embedded_can::ExtendedId;
+
+    fn memory() -> [VolatileCell<tx::Message<8>>; 3] {
+        [
+            VolatileCell::new(mock::message(0x10)),
+            VolatileCell::new(
+                tx::MessageBuilder::classic(ExtendedId::new(0x1234_5678).unwrap(), &[])
+                    .build()
+                    .unwrap(),
+            ),
+            VolatileCell::new(mock::message(0x7ff)),
+        ]
+    }
+
+    #[test]
+    fn completions_are_reported_once() {
+        let memory = memory();
+        let (set, reported) = CompletionSet::new(0, 0b101, &memory);
+        assert_eq!(set.buffers().0, 0b101);
+        assert_eq!(reported, 0b101);
+        let (set, reported) = CompletionSet::new(reported, 0b111, &memory);
+        assert_eq!(set.buffers().0, 0b010);
+        assert_eq!(reported, 0b111);
+        let (set, _) = CompletionSet::new(reported, 0b111, &memory);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn cleared_flags_are_reported_again() {
+        let memory = memory();
+        let (_, reported) = CompletionSet::new(0, 0b001, &memory);
+        // Buffer 0 was reused, which cleared its flag
+        let (set, reported) = CompletionSet::new(reported, 0b000, &memory);
+        assert!(set.is_empty());
+        let (set, _) = CompletionSet::new(reported, 0b001, &memory);
+        assert_eq!(set.buffers().0, 0b001);
+    }
+
+    #[test]
+    fn completions_carry_ids_from_memory() {
+        let memory = memory();
+        let (set, _) = CompletionSet::new(0, 0b110, &memory);
+        let mut iter = set.iter();
+        assert_eq!(
+            iter.next(),
+            Some((1, Id::Extended(ExtendedId::new(0x1234_5678).unwrap())))
+        );
+        assert_eq!(
+            iter.next(),
+            Some((2, Id::Standard(StandardId::new(0x7ff).unwrap())))
+        );
+        assert_eq!(iter.next(), None);
+        assert_eq!(set.id(0), None);
+        assert_eq!(
+            set.id(2),
+            Some(Id::Standard(StandardId::new(0x7ff).unwrap()))
+        );
+    }
+
+    #[test]
+    fn tokens_become_stale_on_reuse() {
+        let mut generations = Generations::default();
+        let first = generations.issue(3);
+        let other = generations.issue(4);
+        assert_eq!(first.index(), 3);
+        assert!(generations.is_current(first));
+        let second = generations.issue(3);
+        assert_eq!(second.index(), 3);
+        assert!(!generations.is_current(first));
+        assert!(generations.is_current(second));
+        assert!(generations.is_current(other));
+    }
+
+    #[test]
+    fn tokens_of_earlier_instances_are_stale() {
+        let mut earlier = Generations::new();
+        let token = earlier.issue(1);
+        let mut later = Generations::new();
+        assert_eq!(later.issue(1).index(), token.index());
+        assert!(earlier.is_current(token));
+        assert!(!later.is_current(token));
+    }
+
+    #[test]
+    fn slot_status_of_current_token() {
+        let token = Generations::default().issue(2);
+        let status = |pending, transmitted, cancelled| {
+            TxSlotStatus::new(token, true, pending, transmitted, cancelled)
+        };
+        assert_eq!(status(0b100, 0, 0), TxSlotStatus::Pending);
+        assert_eq!(status(0b011, 0b100, 0), TxSlotStatus::Transmitted);
+        // Cancellation requested while transmission had already started
+        assert_eq!(status(0, 0b100, 0b100), TxSlotStatus::Transmitted);
+        assert_eq!(status(0, 0b011, 0b100), TxSlotStatus::Cancelled);
+        assert_eq!(status(0, 0, 0), TxSlotStatus::Idle);
+    }
+
+    #[test]
+    fn slot_status_of_reused_slot() {
+        let mut generations = Generations::default();
+        let token = generations.issue(2);
+        generations.issue(2);
+        assert_eq!(
+            TxSlotStatus::new(token, generations.is_current(token), 0, 0b100, 0),
+            TxSlotStatus::Reused
+        );
+    }
+
+    #[test]
+    fn tokens_beyond_the_buffers_are_never_current() {
+        let mut generations = Generations::default();
+        let token = generations.issue(40);
+        assert_eq!(token.index(), 40);
+        assert!(!generations.is_current(token));
+    }
+
+    #[test]
+    fn buffer_sets_iterate_over_all_bits() {
+        assert!(TxBufferSet::all().iter().eq(0..32));
+        assert!(TxBufferSet(1 << 31 | 1).iter().eq([0, 31]));
+        let mut iter = TxBufferSet(1 << 31).iter();
+        assert_eq!(iter.next(), Some(31));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(TxBufferSet(0).iter().next(), None);
+    }
+
+    #[test]
+    fn completions_outside_memory_are_ignored() {
+        let memory = memory();
+        let (set, _) = CompletionSet::new(0, 0b1001, &memory);
+        assert_eq!(set.buffers().0, 0b0001);
+    }
+
+    mock_can!(Can0);
+    mock_can!(Can1);
+    mock_can!(Can2);
+
+    const CCCR: usize = 0x18;
+    const TXFQS: usize = 0xc4;
+    const TXBRP: usize = 0xcc;
+    const TXBAR: usize = 0xd0;
+    const INIT: u32 = 1;
+    const TFQF: u32 = 1 << 21;
+
+    type Memory = GenericArray<VolatileCell<tx::Message<8>>, generic_array::typenum::U4>;
+
+    fn tx_memory() -> Memory {
+        [(); 4]
+            .map(|_| VolatileCell::new(mock::message(0x7ff)))
+            .into()
+    }
+
+    fn data() -> tx::Message<8> {
+        VolatileCell::new(mock::message(0x123)).get()
+    }
+
+    fn blocked_reason<T: core::fmt::Debug>(result: Result<T, TransmitError>) -> TxBlockedReason {
+        match result {
+            Err(TransmitError::WouldBlock(reason)) => reason,
+            other => panic!("not blocked: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn full_queue_blocks() {
+        let regs = Can0::regs();
+        regs.write(TXFQS, TFQF | 2 << 16 | 2 << 8);
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can0, Caps>::new(&mut memory, Mode::Classic) };
+        assert_eq!(
+            blocked_reason(tx.transmit_queued_indexed(data())),
+            TxBlockedReason::QueueFull
+        );
+        regs.write(CCCR, INIT);
+        assert_eq!(
+            blocked_reason(tx.transmit_queued_indexed(data())),
+            TxBlockedReason::NotOperational
+        );
+        assert_eq!(regs.read(TXBAR), 0);
+    }
+
+    #[test]
+    fn queue_full_transitions_are_reported_once() {
+        mock_can!(Can3);
+        let regs = Can3::regs();
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can3, Caps>::new(&mut memory, Mode::Classic) };
+        let mut poll = |txfqs| {
+            regs.write(TXFQS, txfqs);
+            tx.poll_queue_full()
+        };
+        let (available, full) = (2 << 16 | 2 << 8, TFQF | 2 << 16 | 2 << 8);
+
+        assert_eq!(poll(available), None);
+        assert_eq!(poll(full), Some(QueueFullTransition::BecameFull));
+        assert_eq!(poll(full), None);
+        assert_eq!(poll(available), Some(QueueFullTransition::BecameAvailable));
+        assert_eq!(poll(available), None);
+        // Available and full again between two polls
+        assert_eq!(poll(full), Some(QueueFullTransition::BecameFull));
+        regs.write(TXFQS, available);
+        assert_eq!(poll(full), None);
+        assert_eq!(poll(available), Some(QueueFullTransition::BecameAvailable));
+        assert!(!tx.is_queue_full());
+    }
+
+    #[test]
+    fn queue_free_level_and_put_index_are_reported() {
+        mock_can!(Can4);
+        const TXBC: usize = 0xc0;
+        const TFQM: u32 = 1 << 30;
+        let regs = Can4::regs();
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let tx = unsafe { Tx::<Can4, Caps>::new(&mut memory, Mode::Classic) };
+
+        // FIFO mode with the get index at buffer 3, the put index at buffer 2
+        regs.write(TXFQS, 2 << 16 | 3 << 8);
+        assert_eq!((tx.queue_free_level(), tx.queue_put_index()), (1, Some(2)));
+        regs.write(TXFQS, TFQF | 3 << 16 | 3 << 8);
+        assert_eq!((tx.queue_free_level(), tx.queue_put_index()), (0, None));
+        regs.write(TXFQS, 2 << 16 | 2 << 8);
+        assert_eq!((tx.queue_free_level(), tx.queue_put_index()), (2, Some(2)));
+        // Dedicated buffers are not counted
+        regs.write(TXBRP, 0b0011);
+        assert_eq!(tx.queue_free_level(), 2);
+
+        // Queue mode, where only the put index is reported
+        regs.write(TXBC, TFQM);
+        regs.write(TXBRP, 0b1001);
+        regs.write(TXFQS, 2 << 16);
+        assert_eq!((tx.queue_free_level(), tx.queue_put_index()), (1, Some(2)));
+    }
+
+    #[test]
+    fn pending_buffers_can_be_cancelled() {
+        mock_can!(Can6);
+        const TXBCF: usize = 0xdc;
+        let regs = Can6::regs();
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can6, Caps>::new(&mut memory, Mode::Classic) };
+
+        // Bits beyond the 4 buffers are not reported
+        regs.write(TXBRP, 0xf0 | 0b0001);
+        regs.write(TXBAR, 0b0100);
+        assert_eq!(tx.pending_buffers().0, 0b0101);
+        assert_eq!(
+            [0, 1, 2, 3].map(|index| tx.is_pending(index).unwrap()),
+            [true, false, true, false]
+        );
+        assert!(matches!(tx.is_pending(4), Err(Error::OutOfBounds)));
+
+        regs.write(TXBCF, 0b0101);
+        assert!(tx.cancel_multi(tx.pending_buffers()).is_ok());
+    }
+
+    #[test]
+    fn lower_priority_buffers_are_cancelled() {
+        mock_can!(Can8);
+        const TXBCR: usize = 0xd4;
+        let regs = Can8::regs();
+        let mut memory = tx_memory();
+        memory[0] = VolatileCell::new(mock::message(0x100));
+        memory[2] = VolatileCell::new(mock::message(0x050));
+        // Base ID 0x48d
+        memory[3] = VolatileCell::new(
+            tx::MessageBuilder::classic(ExtendedId::new(0x1234_5678).unwrap(), &[])
+                .build()
+                .unwrap(),
+        );
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can8, Caps>::new(&mut memory, Mode::Classic) };
+
+        regs.write(TXBRP, 0b1011);
+        let urgent = StandardId::new(0x100).unwrap().into();
+        assert_eq!(tx.cancel_lower_priority_than(urgent).0, 0b1010);
+        assert_eq!(regs.read(TXBCR), 0b1010);
+        // Extended IDs are compared by their base ID first
+        let extended = ExtendedId::new(0x1234_5678).unwrap().into();
+        assert_eq!(tx.cancel_lower_priority_than(extended).0, 0b0010);
+        // Nothing to cancel
+        regs.write(TXBCR, 0);
+        assert_eq!(tx.cancel_lower_priority_than(ExtendedId::MAX.into()).0, 0);
+        assert_eq!(regs.read(TXBCR), 0);
+
+        let requeued = tx.buffered_message(3).unwrap();
+        assert_eq!(crate::message::Raw::id(&requeued), extended);
+        assert!(matches!(tx.buffered_message(4), Err(Error::OutOfBounds)));
+    }
+
+    #[test]
+    fn replace_outcome_follows_the_flags() {
+        assert_eq!(
+            ReplaceOutcome::new(false, false, false),
+            Some(ReplaceOutcome::Idle)
+        );
+        assert_eq!(
+            ReplaceOutcome::new(false, false, true),
+            Some(ReplaceOutcome::Idle)
+        );
+        assert_eq!(ReplaceOutcome::new(true, true, false), None);
+        assert_eq!(ReplaceOutcome::new(true, true, true), None);
+        assert_eq!(
+            ReplaceOutcome::new(true, false, false),
+            Some(ReplaceOutcome::Replaced)
+        );
+        assert_eq!(
+            ReplaceOutcome::new(true, false, true),
+            Some(ReplaceOutcome::Transmitted)
+        );
+    }
+
+    #[test]
+    fn pending_dedicated_frames_are_replaced() {
+        mock_can!(Can9);
+        const TXBCR: usize = 0xd4;
+        const TXBTO: usize = 0xd8;
+        let regs = Can9::regs();
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can9, Caps>::new(&mut memory, Mode::Classic) };
+        let fresh = |id| tx::MessageBuilder::classic(StandardId::new(id).unwrap(), &[]).build();
+
+        assert_eq!(
+            tx.transmit_dedicated_replace(1, fresh(0x1).unwrap())
+                .unwrap(),
+            ReplaceOutcome::Idle
+        );
+        assert_eq!(regs.read(TXBAR), 1 << 1);
+
+        // Cancelled while pending, and not requested again when retried
+        regs.write(TXBAR, 0);
+        regs.write(TXBRP, 1 << 1);
+        for requested in [1 << 1, 0] {
+            assert_eq!(
+                blocked_reason(tx.transmit_dedicated_replace(1, fresh(0x2).unwrap())),
+                TxBlockedReason::BufferPending
+            );
+            assert_eq!(regs.read(TXBCR), requested);
+            regs.write(TXBCR, 0);
+        }
+        assert_eq!(regs.read(TXBAR), 0);
+        regs.write(TXBRP, 0);
+        assert_eq!(
+            tx.transmit_dedicated_replace(1, fresh(0x2).unwrap())
+                .unwrap(),
+            ReplaceOutcome::Replaced
+        );
+        assert_eq!(regs.read(TXBAR), 1 << 1);
+
+        // Transmitted before the cancellation took effect
+        regs.write(TXBAR, 0);
+        regs.write(TXBRP, 1 << 1);
+        assert!(tx
+            .transmit_dedicated_replace(1, fresh(0x3).unwrap())
+            .is_err());
+        regs.write(TXBRP, 0);
+        regs.write(TXBTO, 1 << 1);
+        assert_eq!(
+            tx.transmit_dedicated_replace(1, fresh(0x3).unwrap())
+                .unwrap(),
+            ReplaceOutcome::Transmitted
+        );
+        let sent = crate::message::Raw::id(&tx.buffered_message(1).unwrap());
+        assert_eq!(sent, StandardId::new(0x3).unwrap().into());
+
+        assert!(matches!(
+            tx.transmit_dedicated_replace(2, data()),
+            Err(TransmitError::Other(Error::OutOfBounds))
+        ));
+    }
+
+    #[test]
+    fn cancellation_outcome_leaves_out_earlier_flags() {
+        let cancellation = Cancellation {
+            requested: 0b1111,
+            transmitted: 0b0001,
+            cancelled: 0b1000,
+        };
+        let outcome = cancellation.outcome(0b0101, 0b1110);
+        assert_eq!(outcome.transmitted_anyway.0, 0b0100);
+        // Buffer 2 was transmitted while being cancelled
+        assert_eq!(outcome.cancelled.0, 0b0010);
+        // Flags of buffers beyond the request are ignored
+        let outcome = cancellation.outcome(0b1_0001, 0b10_1000);
+        assert_eq!(outcome.transmitted_anyway.0, 0);
+        assert_eq!(outcome.cancelled.0, 0);
+        // Nothing settled differently
+        let outcome = cancellation.outcome(0b0001, 0b1000);
+        assert_eq!(outcome.transmitted_anyway.0 | outcome.cancelled.0, 0);
+    }
+
+    #[test]
+    fn cancellation_is_reported_once_settled() {
+        mock_can!(Can10);
+        const TXBCR: usize = 0xd4;
+        const TXBTO: usize = 0xd8;
+        const TXBCF: usize = 0xdc;
+        let regs = Can10::regs();
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can10, Caps>::new(&mut memory, Mode::Classic) };
+
+        // Flags of earlier transmissions from buffers 0 and 3
+        regs.write(TXBTO, 0b0001);
+        regs.write(TXBCF, 0b1000);
+        regs.write(TXBRP, 0b0110);
+        for requested in [0b1111, 0] {
+            assert!(matches!(
+                tx.cancel_and_report(TxBufferSet(0b1111)),
+                Err(nb::Error::WouldBlock)
+            ));
+            assert_eq!(regs.read(TXBCR), requested);
+            regs.write(TXBCR, 0);
+        }
+        regs.write(TXBRP, 0);
+        regs.write(TXBTO, 0b0101);
+        regs.write(TXBCF, 0b1110);
+        let outcome = tx.cancel_and_report(TxBufferSet(0b1111)).unwrap();
+        assert_eq!(outcome.transmitted_anyway.0, 0b0100);
+        assert_eq!(outcome.cancelled.0, 0b0010);
+
+        // A new round compares against the current flags
+        let outcome = tx.cancel_and_report(TxBufferSet(0b1111)).unwrap();
+        assert_eq!(outcome.transmitted_anyway.0 | outcome.cancelled.0, 0);
+        assert_eq!(regs.read(TXBCR), 0b1111);
+    }
+
+    #[test]
+    fn queue_without_buffers_is_full() {
+        use crate::message::rx;
+        use generic_array::typenum::{U0, U2};
+
+        struct Dedicated;
+        impl Capacities for Dedicated {
+            type StandardFilters = U0;
+            type ExtendedFilters = U0;
+            type RxBufferMessage = rx::Message<8>;
+            type DedicatedRxBuffers = U0;
+            type RxFifo0Message = rx::Message<8>;
+            type RxFifo0 = U0;
+            type RxFifo1Message = rx::Message<8>;
+            type RxFifo1 = U0;
+            type TxMessage = tx::Message<8>;
+            type TxBuffers = U2;
+            type DedicatedTxBuffers = U2;
+            type TxEventFifo = U0;
+        }
+
+        mock_can!(Can5);
+        // Values of an unconfigured queue
+        Can5::regs().write(TXFQS, 7 << 16 | 5 << 8);
+        let mut memory: GenericArray<_, U2> = [(); 2]
+            .map(|_| VolatileCell::new(mock::message(0x7ff)))
+            .into();
+        // Safety: The registers belong to this test only.
+        let tx = unsafe { Tx::<Can5, Dedicated>::new(&mut memory, Mode::Classic) };
+        assert!(tx.is_queue_full());
+        assert_eq!((tx.queue_free_level(), tx.queue_put_index()), (0, None));
+    }
+
+    #[test]
+    fn pending_dedicated_buffer_blocks() {
+        let regs = Can1::regs();
+        regs.write(TXBRP, 1 << 1);
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can1, Caps>::new(&mut memory, Mode::Classic) };
+        assert_eq!(
+            blocked_reason(tx.transmit_dedicated(1, data())),
+            TxBlockedReason::BufferPending
+        );
+        regs.write(CCCR, INIT);
+        assert_eq!(
+            blocked_reason(tx.transmit_dedicated(1, data())),
+            TxBlockedReason::NotOperational
+        );
+        assert_eq!(regs.read(TXBAR), 0);
+        tx.transmit_dedicated(0, data()).unwrap();
+        assert_eq!(regs.read(TXBAR), 1 << 0);
+    }
+
+    #[test]
+    fn checked_transmissions_are_built_and_validated() {
+        mock_can!(Can7);
+        let regs = Can7::regs();
+        regs.write(TXFQS, TFQF | 2 << 16 | 2 << 8);
+        let mut memory = tx_memory();
+        let payload = [0; 12];
+        let id = StandardId::new(0x123).unwrap();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe {
+            Tx::<Can7, Caps>::new(
+                &mut memory,
+                Mode::Fd {
+                    allow_bit_rate_switching: false,
+                    data_phase_timing: crate::config::BitTiming::new(fugit::RateExtU32::MHz(2)),
+                },
+            )
+        };
+        fn rejected<T: core::fmt::Debug>(result: Result<T, TransmitError>) -> Error {
+            match result {
+                Err(TransmitError::Other(error)) => error,
+                other => panic!("not rejected: {other:?}"),
+            }
+        }
+        // Rejected although the queue is full
+        assert!(matches!(
+            rejected(tx.transmit_queued_checked(tx::MessageBuilder::fd(id, &[]).with_brs())),
+            Error::BitRateSwitchingDisabled
+        ));
+        assert!(matches!(
+            rejected(tx.transmit_queued_checked(tx::MessageBuilder::fd(id, &payload))),
+            Error::TooMuchData
+        ));
+        assert!(matches!(
+            rejected(tx.transmit_queued_checked(tx::MessageBuilder::classic(id, &payload))),
+            Error::TooMuchData
+        ));
+        assert_eq!(
+            blocked_reason(tx.transmit_queued_checked(tx::MessageBuilder::classic(id, &[1]))),
+            TxBlockedReason::QueueFull
+        );
+
+        tx.mode = Mode::Classic;
+        assert!(matches!(
+            rejected(tx.transmit_dedicated_checked(0, tx::MessageBuilder::fd(id, &[]))),
+            Error::FdDisabled
+        ));
+        tx.transmit_dedicated_checked(1, tx::MessageBuilder::classic(id, &[1, 2]).with_tx_event(7))
+            .unwrap();
+        assert_eq!(regs.read(TXBAR), 1 << 1);
+        let sent = memory[1].get();
+        assert_eq!(crate::message::Raw::data(&sent), &[1, 2]);
+    }
+
+    #[test]
+    fn queue_buffers_are_not_dedicated() {
+        let regs = Can2::regs();
+        let mut memory = tx_memory();
+        // Safety: The registers belong to this test only.
+        let mut tx = unsafe { Tx::<Can2, Caps>::new(&mut memory, Mode::Classic) };
+        for index in [2, 4] {
+            assert!(matches!(
+                tx.transmit_dedicated(index, data()),
+                Err(TransmitError::Other(Error::OutOfBounds))
+            ));
         }
+        assert_eq!(regs.read(TXBAR), 0);
+        assert!(matches!(
+            nb::Error::from(TransmitError::WouldBlock(TxBlockedReason::QueueFull)),
+            nb::Error::<Error>::WouldBlock
+        ));
     }
 }
@@ -2,9 +2,49 @@
 //!
 //! Events are only generated for messages with [`store_tx_event`] set.
 //!
+//! # Message markers
+//!
+//! Events identify their frame by the 8 bit message marker only.
+//! [`MarkerTracker`] hands out markers from a fixed pool along with a value
+//! of the application, e.g. the request a frame belongs to, and resolves the
+//! events back to these values.
+//!
+//! ```no_run
+//! # use mcan::embedded_can::StandardId;
+//! # use mcan::message::tx;
+//! # use mcan::tx_buffers::DynTx;
+//! # use mcan::tx_event_fifo::DynTxEventFifo;
+//! # fn run(
+//! #     mut tx: impl DynTx<Message = tx::Message<8>>,
+//! #     mut tx_event_fifo: impl DynTxEventFifo,
+//! # ) {
+//! use mcan::tx_event_fifo::MarkerTracker;
+//!
+//! #[derive(Copy, Clone)]
+//! struct Request(u16);
+//!
+//! let mut markers = MarkerTracker::<Request, 32>::new();
+//! let id = StandardId::new(0x100).unwrap();
+//! if let Ok(marker) = markers.allocate(Request(7)) {
+//!     let frame = tx::MessageBuilder::classic(id, &[1, 2]).with_tx_event(marker);
+//!     if tx.transmit_queued_checked(frame).is_err() {
+//!         markers.release(marker);
+//!     }
+//! }
+//! while let Some(event) = tx_event_fifo.pop() {
+//!     if let Some(Request(request)) = markers.resolve(&event) {
+//!         // `request` was transmitted
+//!     }
+//! }
+//! # }
+//! ```
+//!
 //! [`store_tx_event`]: crate::message::tx::MessageBuilder::store_tx_event
+use crate::interrupt::{Interrupt, InterruptSet};
 use crate::message::TxEvent;
 use crate::reg;
+use crate::ring::RingIndices;
+use crate::timestamp::Extender;
 use core::marker::PhantomData;
 use reg::AccessRegisterBlock as _;
 use vcell::VolatileCell;
@@ -16,7 +56,7 @@ pub struct TxEventFifo<'a, P> {
 }
 
 /// Trait which erases generic parametrization for [`TxEventFifo`] type
-pub trait DynTxEventFifo {
+pub trait DynTxEventFifo: crate::sealed::Sealed {
     /// CAN identity type
     type Id;
 
@@ -26,11 +66,58 @@ pub trait DynTxEventFifo {
     fn is_empty(&self) -> bool;
     /// Returns the number of elements the queue can hold
     fn capacity(&self) -> usize;
-    /// Takes the first event from the queue
+    /// Returns `true` if the queue is full, the polled counterpart of
+    /// [`Interrupt::TxEventFifoFull`]
+    ///
+    /// [`Interrupt::TxEventFifoFull`]: crate::interrupt::Interrupt::TxEventFifoFull
+    fn is_full(&self) -> bool;
+    /// Returns the watermark programmed for the queue, 0 if it is disabled
+    ///
+    /// This is the configured [`TxConfig::tx_event_fifo_watermark`] after
+    /// clamping to the capacity, see [`CanConfig::strict_validation`].
+    ///
+    /// [`TxConfig::tx_event_fifo_watermark`]: crate::config::TxConfig::tx_event_fifo_watermark
+    /// [`CanConfig::strict_validation`]: crate::config::CanConfig::strict_validation
+    fn watermark(&self) -> usize;
+    /// Returns `true` if the fill level has reached the configured watermark,
+    /// the polled counterpart of [`Interrupt::TxEventFifoWatermarkReached`].
+    /// Always `false` if the watermark is disabled.
+    ///
+    /// [`Interrupt::TxEventFifoWatermarkReached`]: crate::interrupt::Interrupt::TxEventFifoWatermarkReached
+    fn watermark_reached(&self) -> bool;
+    /// Returns `true` if an event was lost because the queue was full, the
+    /// polled counterpart of [`Interrupt::TxEventFifoElementLost`]
+    ///
+    /// This mirrors the interrupt flag and stays set until the flag is
+    /// cleared, e.g. with [`OwnedInterruptSet::clear_interrupts`]. The flag
+    /// is set regardless of whether the interrupt is enabled.
+    ///
+    /// [`Interrupt::TxEventFifoElementLost`]: crate::interrupt::Interrupt::TxEventFifoElementLost
+    /// [`OwnedInterruptSet::clear_interrupts`]: crate::interrupt::OwnedInterruptSet::clear_interrupts
+    fn element_lost(&self) -> bool;
+    /// Takes the first event from the queue. Note that the queue also
+    /// implements [`Iterator`] to take events until it is empty.
     fn pop(&mut self) -> Option<TxEvent>;
+    /// Takes the first event from the queue along with its timestamp extended
+    /// by `extender`
+    ///
+    /// `extender` should have been updated recently, see the
+    /// [`timestamp`](crate::timestamp#accuracy) module for details.
+    fn pop_extended(&mut self, extender: &mut Extender) -> Option<(TxEvent, u64)> {
+        let event = self.pop()?;
+        Some((event, extender.extend(event.timestamp())))
+    }
 }
 
 impl<'a, P: mcan_core::CanId> TxEventFifo<'a, P> {
+    /// Interrupts raised by this FIFO
+    pub const INTERRUPTS: InterruptSet = InterruptSet::from_slice(&[
+        Interrupt::TxEventFifoNewEntry,
+        Interrupt::TxEventFifoWatermarkReached,
+        Interrupt::TxEventFifoFull,
+        Interrupt::TxEventFifoElementLost,
+    ]);
+
     /// # Safety
     /// The caller must be the owner or the peripheral referenced by `P`. The
     /// constructed type assumes ownership of some of the registers from the
@@ -59,8 +146,16 @@ impl<'a, P: mcan_core::CanId> TxEventFifo<'a, P> {
         // Safety: `Self` owns the register.
         unsafe { &self.regs().txefa }
     }
+
+    /// Reads the fill level, indices and flags of the queue at once, e.g.
+    /// to tell how many events were pending when one was lost
+    pub fn status(&self) -> TxEventFifoStatus {
+        TxEventFifoStatus::from(self.txefs().read())
+    }
 }
 
+impl<P> crate::sealed::Sealed for TxEventFifo<'_, P> {}
+
 impl<'a, P: mcan_core::CanId> DynTxEventFifo for TxEventFifo<'a, P> {
     type Id = P;
 
@@ -76,19 +171,273 @@ impl<'a, P: mcan_core::CanId> DynTxEventFifo for TxEventFifo<'a, P> {
         self.memory.len()
     }
 
+    fn is_full(&self) -> bool {
+        self.txefs().read().eff().bit()
+    }
+
+    fn watermark(&self) -> usize {
+        // Safety: Reading TXEFC has no side effects and it is only written in
+        // configuration mode.
+        unsafe { self.regs().txefc.read().efwm().bits() as usize }
+    }
+
+    fn watermark_reached(&self) -> bool {
+        let watermark = self.watermark();
+        watermark != 0 && self.len() >= watermark
+    }
+
+    fn element_lost(&self) -> bool {
+        self.txefs().read().tefl().bit()
+    }
+
     fn pop(&mut self) -> Option<TxEvent> {
         let status = self.txefs().read();
-        if status.effl().bits() == 0 {
-            None
-        } else {
-            let get_index = status.efgi().bits();
-            let event = self.memory.get(get_index as usize)?.get();
-            // Safety: The get index must be valid since it was retrieved from the
-            // peripheral and the configuration has not changed.
-            unsafe {
-                self.txefa().write(|w| w.efai().bits(get_index));
+        let get_index = RingIndices::from_fill_level(
+            status.efgi().bits() as usize,
+            status.effl().bits() as usize,
+            self.memory.len(),
+        )
+        .nth_occupied(0)?;
+        let event = self.memory.get(get_index)?.get();
+        // Safety: The get index must be valid since it was retrieved from the
+        // peripheral and the configuration has not changed.
+        unsafe {
+            self.txefa().write(|w| w.efai().bits(get_index as u8));
+        }
+        Some(event)
+    }
+}
+
+impl<'a, P: mcan_core::CanId> Iterator for TxEventFifo<'a, P> {
+    type Item = TxEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pop()
+    }
+}
+
+/// Allocates message markers from a pool of `N` and associates them with
+/// values of type `T`, see the [module documentation](self#message-markers)
+///
+/// `N` must be between 1 and 256. Markers are handed out round robin, so a
+/// released marker is only reused after the others, making it less likely
+/// that a late event is resolved to a newer message.
+pub struct MarkerTracker<T, const N: usize> {
+    slots: [Option<T>; N],
+    next: usize,
+    allocated: usize,
+}
+
+/// All markers of a [`MarkerTracker`] are allocated
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MarkersExhausted;
+
+impl<T: Copy, const N: usize> MarkerTracker<T, N> {
+    const VALID: () = assert!(N > 0 && N <= 256, "MarkerTracker supports 1 to 256 markers");
+
+    /// Creates a tracker without allocated markers
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        Self {
+            slots: [None; N],
+            next: 0,
+            allocated: 0,
+        }
+    }
+
+    /// Allocates a marker associated with `value`
+    pub fn allocate(&mut self, value: T) -> Result<u8, MarkersExhausted> {
+        let marker = (0..N)
+            .map(|offset| (self.next + offset) % N)
+            .find(|&marker| matches!(self.slots.get(marker), Some(None)))
+            .ok_or(MarkersExhausted)?;
+        if let Some(slot) = self.slots.get_mut(marker) {
+            *slot = Some(value);
+        }
+        self.next = (marker + 1) % N;
+        self.allocated += 1;
+        Ok(marker as u8)
+    }
+
+    /// Releases the marker of `event`, returning its value
+    ///
+    /// `None` if the marker is not allocated, e.g. because the event was
+    /// already resolved or belongs to a frame not tracked here.
+    pub fn resolve(&mut self, event: &TxEvent) -> Option<T> {
+        self.release(event.message_marker())
+    }
+
+    /// Releases `marker` without an event, e.g. when its frame could not be
+    /// queued or was cancelled, returning its value
+    pub fn release(&mut self, marker: u8) -> Option<T> {
+        let value = self.slots.get_mut(usize::from(marker))?.take()?;
+        self.allocated -= 1;
+        Some(value)
+    }
+
+    /// Value associated with `marker`, if it is allocated
+    pub fn get(&self, marker: u8) -> Option<T> {
+        self.slots.get(usize::from(marker)).copied().flatten()
+    }
+
+    /// Number of allocated markers
+    pub fn len(&self) -> usize {
+        self.allocated
+    }
+
+    /// Returns `true` if no marker is allocated
+    pub fn is_empty(&self) -> bool {
+        self.allocated == 0
+    }
+
+    /// Number of markers in the pool
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T: Copy, const N: usize> Default for MarkerTracker<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Snapshot of the status register of a [`TxEventFifo`]
+///
+/// Created by [`TxEventFifo::status`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TxEventFifoStatus {
+    /// Number of events in the queue, see [`DynTxEventFifo::len`]
+    pub fill_level: usize,
+    /// Index of the oldest event, taken next
+    pub get_index: usize,
+    /// Index the next event is stored at
+    pub put_index: usize,
+    /// The queue is full, see [`DynTxEventFifo::is_full`]
+    pub full: bool,
+    /// An event was lost since the interrupt flag was last cleared, see
+    /// [`DynTxEventFifo::element_lost`]
+    pub element_lost: bool,
+}
+
+impl From<reg::txefs::R> for TxEventFifoStatus {
+    fn from(status: reg::txefs::R) -> Self {
+        Self {
+            fill_level: status.effl().bits() as usize,
+            get_index: status.efgi().bits() as usize,
+            put_index: status.efpi().bits() as usize,
+            full: status.eff().bit(),
+            element_lost: status.tefl().bit(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::mock_can;
+
+    const TXEFS: usize = 0xf4;
+    const TXEFA: usize = 0xf8;
+
+    fn memory() -> [VolatileCell<TxEvent>; 4] {
+        core::array::from_fn(|index| VolatileCell::new(TxEvent::with_marker(index as u8)))
+    }
+
+    #[test]
+    fn status_register_is_decoded() {
+        mock_can!(Can0);
+        let regs = Can0::regs();
+        let mut memory = memory();
+        // Safety: The registers belong to this test only.
+        let fifo = unsafe { TxEventFifo::<Can0>::new(&mut memory) };
+
+        // Put index 1, get index 2, fill level 3; reserved bits set
+        regs.write(TXEFS, 0xfce1_e2c3);
+        assert_eq!(
+            fifo.status(),
+            TxEventFifoStatus {
+                fill_level: 3,
+                get_index: 2,
+                put_index: 1,
+                full: false,
+                element_lost: false,
+            }
+        );
+        assert!(!fifo.is_full() && !fifo.element_lost());
+
+        // Full and lost, with all index bits set
+        regs.write(TXEFS, 0x031f_1f3f);
+        assert_eq!(
+            fifo.status(),
+            TxEventFifoStatus {
+                fill_level: 63,
+                get_index: 31,
+                put_index: 31,
+                full: true,
+                element_lost: true,
             }
-            Some(event)
+        );
+        assert!(fifo.is_full() && fifo.element_lost());
+
+        // Lost after the application drained the full queue
+        regs.write(TXEFS, 1 << 25 | 3 << 16 | 3 << 8);
+        let status = fifo.status();
+        assert!(status.element_lost && !status.full && status.fill_level == 0);
+        assert!(fifo.element_lost() && fifo.is_empty());
+    }
+
+    #[test]
+    fn iteration_takes_the_events_in_order() {
+        mock_can!(Can1);
+        let regs = Can1::regs();
+        let mut memory = memory();
+        // Safety: The registers belong to this test only.
+        let mut fifo = unsafe { TxEventFifo::<Can1>::new(&mut memory) };
+
+        // Get index 3, fill level 2, wrapping around the end of the queue
+        regs.write(TXEFS, 3 << 8 | 2);
+        let event = fifo.next().unwrap();
+        assert_eq!(event.message_marker(), 3);
+        assert_eq!(regs.read(TXEFA), 3);
+        // The mock does not advance the get index on acknowledgement
+        regs.write(TXEFS, 1);
+        assert_eq!(fifo.next().map(|event| event.message_marker()), Some(0));
+        assert_eq!(regs.read(TXEFA), 0);
+        regs.write(TXEFS, 0);
+        assert!(fifo.next().is_none());
+    }
+
+    #[test]
+    fn markers_are_allocated_and_resolved_once() {
+        let mut markers = MarkerTracker::<u16, 3>::new();
+        assert_eq!(markers.capacity(), 3);
+        assert_eq!(markers.allocate(10), Ok(0));
+        assert_eq!(markers.allocate(11), Ok(1));
+        assert_eq!(markers.allocate(12), Ok(2));
+        assert_eq!(markers.allocate(13), Err(MarkersExhausted));
+        assert_eq!(markers.len(), 3);
+
+        assert_eq!(markers.resolve(&TxEvent::with_marker(1)), Some(11));
+        assert_eq!(markers.resolve(&TxEvent::with_marker(1)), None);
+        // Outside of the pool
+        assert_eq!(markers.resolve(&TxEvent::with_marker(3)), None);
+        assert_eq!((markers.get(1), markers.get(2)), (None, Some(12)));
+
+        assert_eq!(markers.release(0), Some(10));
+        assert_eq!(markers.release(0), None);
+        // Round robin, continuing after the last allocation
+        assert_eq!(markers.allocate(14), Ok(0));
+        assert_eq!(markers.allocate(15), Ok(1));
+        assert_eq!(markers.len(), 3);
+
+        let mut markers = MarkerTracker::<(), 256>::default();
+        assert!(markers.is_empty());
+        for marker in 0..=255 {
+            assert_eq!(markers.allocate(()), Ok(marker));
         }
+        assert_eq!(markers.allocate(()), Err(MarkersExhausted));
+        assert_eq!(markers.resolve(&TxEvent::with_marker(255)), Some(()));
     }
 }
@@ -0,0 +1,253 @@
+//! Development tasks for the workspace, run as `cargo xtask <task>`
+//!
+//! - `bench`: host-side timings of hot paths, see `mcan/benches/operations.rs`
+//! - `size`: `.text` size of the `mcan` code in the `dual_bus` example
+//! - `report [path]`: both of the above as a markdown report, written to
+//!   `target/mcan-report.md` unless a path is given
+
+use std::env;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+/// Target the code size is measured for
+const SIZE_TARGET: &str = "thumbv7em-none-eabihf";
+
+/// Tools tried in order to list the symbols of the size example
+const NM_TOOLS: [&str; 3] = ["llvm-nm", "rust-nm", "arm-none-eabi-nm"];
+
+/// Number of largest symbols listed in the report
+const LARGEST_SYMBOLS: usize = 10;
+
+type Result<T> = std::result::Result<T, String>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["bench"] => bench().map(|timings| print!("{}", timings_section(&timings))),
+        ["size"] => size().map(|size| print!("{}", size_section(&Ok(size)))),
+        ["report"] => report(&workspace_root().join("target/mcan-report.md")),
+        ["report", path] => report(Path::new(path)),
+        _ => Err("usage: cargo xtask <bench | size | report [path]>".into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is a workspace member")
+        .to_path_buf()
+}
+
+/// Runs cargo with `args` and environment `envs` in the workspace root and
+/// returns its stdout
+fn cargo(args: &[&str], envs: &[(&str, &str)]) -> Result<String> {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let output = Command::new(cargo)
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(workspace_root())
+        .output()
+        .map_err(|error| format!("failed to run cargo: {error}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`cargo {}` failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Median nanoseconds per iteration of each benchmark
+fn bench() -> Result<Vec<(String, f64)>> {
+    let output = cargo(
+        &[
+            "bench",
+            "--package",
+            "mcan",
+            "--features",
+            "test-util",
+            "--bench",
+            "operations",
+        ],
+        &[],
+    )?;
+    let timings = parse_timings(&output);
+    if timings.is_empty() {
+        return Err("the benchmarks reported no results".into());
+    }
+    Ok(timings)
+}
+
+fn parse_timings(output: &str) -> Vec<(String, f64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.strip_prefix("bench: ")?.split_whitespace();
+            let name = fields.next()?;
+            let nanoseconds = fields.next()?.parse().ok()?;
+            Some((name.to_owned(), nanoseconds))
+        })
+        .collect()
+}
+
+/// Code size of the `mcan` symbols in the size example
+struct Size {
+    total: u64,
+    largest: Vec<(String, u64)>,
+}
+
+fn size() -> Result<Size> {
+    // Link-time optimization leaves only the code reachable from the exported
+    // functions of the example, most of it inlined into them.
+    cargo(
+        &[
+            "build",
+            "--package",
+            "mcan",
+            "--release",
+            "--example",
+            "dual_bus",
+            "--target",
+            SIZE_TARGET,
+        ],
+        &[
+            ("CARGO_PROFILE_RELEASE_LTO", "true"),
+            ("CARGO_PROFILE_RELEASE_CODEGEN_UNITS", "1"),
+            ("CARGO_PROFILE_RELEASE_OPT_LEVEL", "s"),
+        ],
+    )?;
+    let library = workspace_root()
+        .join("target")
+        .join(SIZE_TARGET)
+        .join("release/examples/libdual_bus.a");
+    let symbols = NM_TOOLS
+        .iter()
+        .find_map(|tool| {
+            let output = Command::new(tool)
+                .args(["--print-size", "--size-sort", "--demangle", "--radix=d"])
+                .arg(&library)
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .ok_or_else(|| format!("none of {NM_TOOLS:?} could list the symbols"))?;
+    Ok(mcan_text_size(&symbols))
+}
+
+/// Sums up the `.text` symbols of `mcan` and of the example, which `mcan` is
+/// mostly inlined into, in `nm --print-size` output
+fn mcan_text_size(symbols: &str) -> Size {
+    let mut largest: Vec<(String, u64)> = symbols
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ' ');
+            let _address = fields.next()?;
+            let size = fields.next()?.parse().ok()?;
+            let kind = fields.next()?;
+            let name = fields.next()?;
+            (matches!(kind, "t" | "T") && (name.contains("mcan::") || name.starts_with("dual_bus")))
+                .then(|| (name.to_owned(), size))
+        })
+        .collect();
+    largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let total = largest.iter().map(|(_, size)| size).sum();
+    largest.truncate(LARGEST_SYMBOLS);
+    Size { total, largest }
+}
+
+fn report(path: &Path) -> Result<()> {
+    let mut report = String::from("# mcan performance report\n\n");
+    report += &timings_section(&bench()?);
+    report += "\n";
+    report += &size_section(&size());
+    std::fs::write(path, report)
+        .map_err(|error| format!("failed to write {}: {error}", path.display()))?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+fn timings_section(timings: &[(String, f64)]) -> String {
+    let mut section = String::from(
+        "## Host timings\n\n\
+         Median time per operation against the memory-backed register block.\n\n\
+         | Operation | ns |\n|---|---:|\n",
+    );
+    for (name, nanoseconds) in timings {
+        let _ = writeln!(section, "| `{name}` | {nanoseconds:.1} |");
+    }
+    section
+}
+
+/// The size is reported as unavailable rather than failing the report, as the
+/// target or tools may not be installed.
+fn size_section(size: &Result<Size>) -> String {
+    let mut section = format!("## Code size ({SIZE_TARGET})\n\n");
+    match size {
+        Ok(size) => {
+            let _ = writeln!(
+                section,
+                "`.text` of `mcan` and the `dual_bus` example: {} bytes\n",
+                size.total
+            );
+            section += "| Symbol | Bytes |\n|---|---:|\n";
+            for (name, bytes) in &size.largest {
+                let _ = writeln!(section, "| `{name}` | {bytes} |");
+            }
+        }
+        Err(error) => {
+            let _ = writeln!(
+                section,
+                "Not available: {}",
+                error.lines().next().unwrap_or("")
+            );
+        }
+    }
+    section
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timings_are_parsed_from_bench_output() {
+        let output = "warming up\nbench: rx_single 12.5\nbench: broken\nbench: tx_single 40.0\n";
+        assert_eq!(
+            parse_timings(output),
+            [
+                ("rx_single".to_owned(), 12.5),
+                ("tx_single".to_owned(), 40.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn only_text_of_mcan_and_the_example_counts() {
+        let symbols = "\
+00000000 00000010 T dual_bus_isr
+00000016 00000120 t mcan::tx_buffers::Tx<P,C>::write
+00000136 00000008 r mcan::config::NOMINAL_BIT_TIMING_RANGES
+00000144 00000064 T <mcan::rx_fifo::RxFifo<F,P,M> as mcan::rx_fifo::DynRxFifo>::receive
+00000208 00000300 t core::fmt::write
+";
+        let size = mcan_text_size(symbols);
+        assert_eq!(size.total, 194);
+        assert_eq!(
+            size.largest[0],
+            ("mcan::tx_buffers::Tx<P,C>::write".to_owned(), 120)
+        );
+        assert_eq!(size.largest.len(), 3);
+    }
+}